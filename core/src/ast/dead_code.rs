@@ -0,0 +1,123 @@
+// 未使用符号分析：找出从未以被调用方出现过的函数与方法
+//
+// 仅基于名称匹配调用关系，动态分发、反射、回调注册等调用方式无法识别
+
+use crate::ast::symbol::{Symbol, SymbolKind};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// 按约定由运行时或框架调用的函数名
+const ENTRY_POINT_NAMES: &[&str] = &["main", "init", "constructor", "setUp", "tearDown", "setup", "teardown"];
+
+/// 分析结果的已知局限，随响应返回给调用方
+pub const DEAD_CODE_LIMITATIONS: &[&str] = &[
+    "调用关系仅按函数名匹配：任意位置调用了同名函数，所有同名定义都视为已使用",
+    "无法识别动态分发（接口/虚方法/trait 对象）、反射、getattr/eval 等动态调用",
+    "通过装饰器、注解、路由表或事件注册的回调不会出现在调用关系中，需用 ignore_patterns 排除",
+    "公开/导出判断基于源码前缀：Java 的 public/protected、Rust 的 pub、Go 的首字母大写、JS/TS 的 export 与类方法；Python 没有导出概念，模块级函数均参与分析",
+    "结果基于最近一次构建的 AST 索引，索引之后的修改不会反映在结果中",
+];
+
+/// 一个可能未被使用的符号
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadCodeCandidate {
+    pub name: String,
+    pub kind: String,
+    pub file_path: String,
+    pub line: u32,
+    pub column: u32,
+    pub end_line: u32,
+    pub language: String,
+}
+
+impl From<&Symbol> for DeadCodeCandidate {
+    fn from(symbol: &Symbol) -> Self {
+        Self {
+            name: symbol.name.clone(),
+            kind: format!("{:?}", symbol.kind),
+            file_path: symbol.file_path.clone(),
+            line: symbol.start_line,
+            column: symbol.column,
+            end_line: symbol.end_line,
+            language: symbol.language.clone(),
+        }
+    }
+}
+
+/// 找出未以被调用方出现过的函数/方法，排除入口点与公开/导出符号（`include_exported` 为真时保留后者）
+///
+/// `called_names` 为额外的被调用函数名（如已保存的调用关系），与符号中的调用点合并
+pub fn find_dead_code<'a>(
+    symbols: &'a [Symbol],
+    called_names: &HashSet<String>,
+    include_exported: bool,
+) -> Vec<&'a Symbol> {
+    let called: HashSet<&str> = symbols
+        .iter()
+        .filter(|s| s.kind == SymbolKind::MethodCall)
+        .map(|s| s.name.as_str())
+        .chain(called_names.iter().map(String::as_str))
+        .collect();
+
+    let mut candidates: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|s| matches!(s.kind, SymbolKind::Function | SymbolKind::Method))
+        .filter(|s| !called.contains(s.name.as_str()))
+        .filter(|s| !is_entry_point(s))
+        .filter(|s| include_exported || !is_exported(s))
+        .collect();
+    candidates.sort_by(|a, b| {
+        a.file_path
+            .cmp(&b.file_path)
+            .then_with(|| a.start_line.cmp(&b.start_line))
+    });
+    candidates
+}
+
+/// 入口点与由运行时隐式调用的函数
+fn is_entry_point(symbol: &Symbol) -> bool {
+    let name = symbol.name.as_str();
+    if ENTRY_POINT_NAMES.contains(&name) {
+        return true;
+    }
+    match symbol.language.as_str() {
+        // 魔术方法由解释器调用
+        "python" => name.starts_with("__") && name.ends_with("__"),
+        // 覆盖的方法由父类或框架调用
+        "java" => header(symbol).contains("@Override"),
+        "rust" => name == "new" || name == "default",
+        _ => false,
+    }
+}
+
+/// 公开/导出符号可能被项目外部调用
+fn is_exported(symbol: &Symbol) -> bool {
+    let header = header(symbol);
+    match symbol.language.as_str() {
+        "java" => header
+            .split_whitespace()
+            .any(|w| w == "public" || w == "protected"),
+        "rust" => header.trim_start().starts_with("pub"),
+        "go" => symbol.name.starts_with(|c: char| c.is_ascii_uppercase()),
+        "javascript" | "typescript" => {
+            let exported = symbol
+                .metadata
+                .get("exported")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let private_method = header.trim_start().starts_with("private") || symbol.name.starts_with('#');
+            exported || (symbol.kind == SymbolKind::Method && !private_method)
+        }
+        _ => false,
+    }
+}
+
+/// 符号定义的声明部分（函数体之前）
+fn header(symbol: &Symbol) -> &str {
+    let code = symbol.code.as_str();
+    let end = code
+        .find('{')
+        .or_else(|| code.find('\n'))
+        .unwrap_or(code.len());
+    &code[..end]
+}
@@ -1,15 +1,42 @@
 use crate::ast::cache::{CacheData, FileIndex};
 use crate::ast::{ASTParser, CacheManager, QueryEngine, Symbol};
-use ignore::Walk;
+use crate::rules::model::PathFilter;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
 use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use walkdir::WalkDir;
 
 pub struct ASTEngine {
     parser: Arc<Mutex<ASTParser>>,
     cache_manager: Arc<Mutex<CacheManager>>,
-    query_engine: Arc<Mutex<Option<QueryEngine>>>,
+    /// 索引数据，读操作可并发进行，仅加载和更新索引时加写锁
+    query_engine: Arc<RwLock<Option<QueryEngine>>>,
+}
+
+/// 项目根目录下的索引排除文件，语法同 .gitignore
+pub const IGNORE_FILE_NAME: &str = ".deepauditignore";
+
+/// 项目扫描的路径过滤选项，glob 相对项目根目录匹配
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// 非空时仅索引匹配的文件
+    pub include_globs: Vec<String>,
+    pub exclude_globs: Vec<String>,
+}
+
+/// 项目扫描结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanSummary {
+    pub files_processed: usize,
+    /// 被逐个排除的源码文件数
+    pub files_excluded: usize,
+    /// 被整体跳过的目录数，其中的文件不计入 `files_excluded`
+    pub dirs_excluded: usize,
 }
 
 impl ASTEngine {
@@ -17,17 +44,17 @@ impl ASTEngine {
         Self {
             parser: Arc::new(Mutex::new(ASTParser::new())),
             cache_manager: Arc::new(Mutex::new(CacheManager::new(cache_dir))),
-            query_engine: Arc::new(Mutex::new(None)),
+            query_engine: Arc::new(RwLock::new(None)),
         }
     }
 
     pub fn use_repository(&self, repo_path: &str) {
-        if let Ok(mut cache_manager) = self.cache_manager.try_lock() {
+        if let Ok(mut cache_manager) = self.cache_manager.lock() {
             cache_manager.use_repository(repo_path);
 
             // Load existing cache if available
             if let Some(cache_data) = cache_manager.load_cache() {
-                if let Ok(mut query_engine) = self.query_engine.try_lock() {
+                if let Ok(mut query_engine) = self.query_engine.write() {
                     *query_engine = Some(QueryEngine::new(cache_data));
                 }
             } else {
@@ -37,7 +64,7 @@ impl ASTEngine {
                     class_map: std::collections::HashMap::new(),
                     build_time: chrono::Utc::now().to_rfc3339(),
                 };
-                if let Ok(mut query_engine) = self.query_engine.try_lock() {
+                if let Ok(mut query_engine) = self.query_engine.write() {
                     *query_engine = Some(QueryEngine::new(cache_data));
                 }
             }
@@ -46,29 +73,76 @@ impl ASTEngine {
 
     /// 直接从 CacheData 初始化引擎（用于从数据库恢复）
     pub fn load_from_cache_data(&self, cache_data: CacheData) {
-        if let Ok(mut query_engine) = self.query_engine.try_lock() {
+        if let Ok(mut query_engine) = self.query_engine.write() {
             *query_engine = Some(QueryEngine::new(cache_data));
         }
     }
 
     pub fn scan_project(&self, root_path: &str) -> Result<usize, String> {
+        self.scan_project_with_options(root_path, &ScanOptions::default())
+            .map(|summary| summary.files_processed)
+    }
+
+    /// 按过滤选项和项目根目录的 `.deepauditignore` 扫描项目
+    ///
+    /// 不再属于项目的文件（已删除或被排除）会从索引中移除
+    pub fn scan_project_with_options(&self, root_path: &str, options: &ScanOptions) -> Result<ScanSummary, String> {
         let root_path = PathBuf::from(root_path);
         if !root_path.exists() {
             return Err(format!("Path '{}' does not exist", root_path.display()));
         }
 
+        let filter = PathFilter {
+            include: options.include_globs.clone(),
+            exclude: options.exclude_globs.clone(),
+        }
+        .compile()?;
+        let ignore_file = Arc::new(load_ignore_file(&root_path));
+        let filter = Arc::new(filter);
+        let dirs_excluded = Arc::new(AtomicUsize::new(0));
+
+        // 被排除的目录整体跳过，不再遍历其中的文件
+        let walker = {
+            let root = root_path.clone();
+            let ignore_file = Arc::clone(&ignore_file);
+            let filter = Arc::clone(&filter);
+            let dirs_excluded = Arc::clone(&dirs_excluded);
+            WalkBuilder::new(&root_path)
+                .filter_entry(move |entry| {
+                    let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                    if !is_dir || entry.depth() == 0 {
+                        return true;
+                    }
+                    let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                    let excluded = ignore_file.matched(entry.path(), true).is_ignore()
+                        || filter.is_excluded(relative);
+                    if excluded {
+                        dirs_excluded.fetch_add(1, Ordering::Relaxed);
+                    }
+                    !excluded
+                })
+                .build()
+        };
+
         // Collect all files to process
         let mut files_to_process = Vec::new();
+        let mut files_excluded = 0;
 
-        for entry in Walk::new(&root_path) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_file() && self.is_supported_file(path) {
-                    files_to_process.push(path.to_path_buf());
-                }
+        for entry in walker.flatten() {
+            let path = entry.path();
+            if !path.is_file() || !self.is_supported_file(path) {
+                continue;
             }
+            let relative = path.strip_prefix(&root_path).unwrap_or(path);
+            if ignore_file.matched(path, false).is_ignore() || !filter.is_match(relative) {
+                files_excluded += 1;
+                continue;
+            }
+            files_to_process.push(path.to_path_buf());
         }
 
+        self.remove_stale_files(&root_path, &files_to_process);
+
         let total_files = files_to_process.len();
         log::info!(
             "Found {} files to scan in {}",
@@ -99,7 +173,37 @@ impl ASTEngine {
             processed_files.len(),
             total_files
         );
-        Ok(processed_files.len())
+        Ok(ScanSummary {
+            files_processed: processed_files.len(),
+            files_excluded,
+            dirs_excluded: dirs_excluded.load(Ordering::Relaxed),
+        })
+    }
+
+    /// 移除索引中位于项目目录下、但不在本次扫描文件列表中的文件
+    fn remove_stale_files(&self, root_path: &Path, files: &[PathBuf]) {
+        let current: HashSet<String> = files.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        let stale: Vec<PathBuf> = match self.query_engine.read() {
+            Ok(guard) => guard
+                .as_ref()
+                .map(|engine| {
+                    engine
+                        .cache
+                        .index
+                        .keys()
+                        .filter(|k| Path::new(k).starts_with(root_path) && !current.contains(*k))
+                        .map(PathBuf::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(_) => return,
+        };
+        if !stale.is_empty() {
+            log::info!("Removing {} stale files from index", stale.len());
+        }
+        for path in stale {
+            self.remove_file_from_cache(&path);
+        }
     }
 
     pub fn update_file(&self, file_path: &Path) -> Result<(), String> {
@@ -109,23 +213,21 @@ impl ASTEngine {
             return Ok(());
         }
 
-        let cache_manager = self.cache_manager.try_lock()
-            .map_err(|_| "Cache manager lock poisoned")?;
-
         // Check if file needs updating
         let file_path_str = file_path.to_string_lossy().to_string();
-        let needs_update = if let Some(query_engine) = self.query_engine.try_lock()
-            .map_err(|_| "Query engine lock poisoned")?.as_ref() {
-            if let Some(file_index) = query_engine.cache.index.get(&file_path_str) {
-                cache_manager.is_file_changed(file_path, file_index.mtime)?
-            } else {
-                true
-            }
-        } else {
-            true
-        };
-
-        if !needs_update {
+        let cached_mtime = self
+            .query_engine
+            .read()
+            .map_err(|_| "Query engine lock poisoned")?
+            .as_ref()
+            .and_then(|engine| engine.cache.index.get(&file_path_str).map(|f| f.mtime));
+        let mtime = self
+            .cache_manager
+            .lock()
+            .map_err(|_| "Cache manager lock poisoned")?
+            .get_file_mtime(file_path)?;
+
+        if cached_mtime == Some(mtime) {
             return Ok(());
         }
 
@@ -134,16 +236,15 @@ impl ASTEngine {
             .map_err(|e| format!("Failed to read file: {}", e))?;
 
         let symbols = {
-            let mut parser = self.parser.try_lock()
+            let mut parser = self.parser.lock()
                 .map_err(|_| "Parser lock poisoned")?;
             parser.parse_file(file_path, &content)?
         };
 
         // Update cache
-        let mtime = cache_manager.get_file_mtime(file_path)?;
         let file_index = FileIndex { mtime, symbols };
 
-        let mut query_engine = self.query_engine.try_lock()
+        let mut query_engine = self.query_engine.write()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref mut engine) = *query_engine {
             engine.cache.index.insert(file_path_str.clone(), file_index);
@@ -163,9 +264,9 @@ impl ASTEngine {
     }
 
     pub fn save_cache(&self) -> Result<(), String> {
-        let cache_manager = self.cache_manager.try_lock()
+        let cache_manager = self.cache_manager.lock()
             .map_err(|_| "Cache manager lock poisoned")?;
-        if let Some(query_engine) = self.query_engine.try_lock()
+        if let Some(query_engine) = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?.as_ref() {
             cache_manager.save_cache(&query_engine.cache)?;
         }
@@ -173,7 +274,7 @@ impl ASTEngine {
     }
 
     pub fn get_statistics(&self) -> Result<serde_json::Value, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
             Ok(engine.get_statistics())
@@ -183,24 +284,25 @@ impl ASTEngine {
     }
 
     pub fn generate_report(&self, repository_path: &str) -> Result<serde_json::Value, String> {
-        let query_engine = self.query_engine.try_lock()
-            .map_err(|_| "Query engine lock poisoned")?;
-        if let Some(ref engine) = *query_engine {
-            let report = engine.generate_report(repository_path);
+        let report = {
+            let query_engine = self.query_engine.read()
+                .map_err(|_| "Query engine lock poisoned")?;
+            match *query_engine {
+                Some(ref engine) => engine.generate_report(repository_path),
+                None => return Err("No cache loaded".to_string()),
+            }
+        };
 
-            // Save report to cache
-            let cache_manager = self.cache_manager.try_lock()
-                .map_err(|_| "Cache manager lock poisoned")?;
-            cache_manager.save_analysis_report(&report)?;
+        // Save report to cache
+        let cache_manager = self.cache_manager.lock()
+            .map_err(|_| "Cache manager lock poisoned")?;
+        cache_manager.save_analysis_report(&report)?;
 
-            Ok(report)
-        } else {
-            Err("No cache loaded".to_string())
-        }
+        Ok(report)
     }
 
     pub fn search_symbols(&self, query: &str) -> Result<Vec<Symbol>, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
             let results = engine.search_symbols(query);
@@ -211,7 +313,7 @@ impl ASTEngine {
     }
 
     pub fn find_call_sites(&self, callee_name: &str) -> Result<Vec<Symbol>, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
             let results = engine.find_call_sites(callee_name);
@@ -225,18 +327,33 @@ impl ASTEngine {
         &self,
         entry: &str,
         max_depth: usize,
+        cross_file: bool,
+    ) -> Result<serde_json::Value, String> {
+        let query_engine = self.query_engine.read()
+            .map_err(|_| "Query engine lock poisoned")?;
+        if let Some(ref engine) = *query_engine {
+            Ok(engine.get_call_graph(entry, max_depth, cross_file))
+        } else {
+            Err("No cache loaded".to_string())
+        }
+    }
+
+    pub fn get_reverse_call_graph(
+        &self,
+        function: &str,
+        max_depth: usize,
     ) -> Result<serde_json::Value, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
-            Ok(engine.get_call_graph(entry, max_depth))
+            Ok(engine.get_reverse_call_graph(function, max_depth))
         } else {
             Err("No cache loaded".to_string())
         }
     }
 
     pub fn get_file_structure(&self, file_path: &str) -> Result<Vec<Symbol>, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
             let results = engine.get_file_structure(file_path);
@@ -246,8 +363,39 @@ impl ASTEngine {
         }
     }
 
+    pub fn find_enclosing_function(
+        &self,
+        file_path: &str,
+        start_line: u32,
+        end_line: u32,
+    ) -> Result<Option<Symbol>, String> {
+        let query_engine = self.query_engine.read()
+            .map_err(|_| "Query engine lock poisoned")?;
+        if let Some(ref engine) = *query_engine {
+            Ok(engine.find_enclosing_function(file_path, start_line, end_line).cloned())
+        } else {
+            Err("No cache loaded".to_string())
+        }
+    }
+
+    pub fn find_calls_in_range(
+        &self,
+        file_path: &str,
+        start_line: u32,
+        end_line: u32,
+    ) -> Result<Vec<Symbol>, String> {
+        let query_engine = self.query_engine.read()
+            .map_err(|_| "Query engine lock poisoned")?;
+        if let Some(ref engine) = *query_engine {
+            let results = engine.find_calls_in_range(file_path, start_line, end_line);
+            Ok(results.into_iter().cloned().collect())
+        } else {
+            Err("No cache loaded".to_string())
+        }
+    }
+
     pub fn get_class_hierarchy(&self, class_name: &str) -> Result<serde_json::Value, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
             Ok(engine.get_class_hierarchy(class_name))
@@ -257,11 +405,11 @@ impl ASTEngine {
     }
 
     pub fn get_all_symbols(&self) -> Result<Vec<Symbol>, String> {
-        let query_engine = self.query_engine.try_lock()
+        let query_engine = self.query_engine.read()
             .map_err(|_| "Query engine lock poisoned")?;
         if let Some(ref engine) = *query_engine {
             let mut all_symbols = Vec::new();
-            for (_file_path, data) in &engine.cache.index {
+            for data in engine.cache.index.values() {
                 all_symbols.extend(data.symbols.iter().cloned());
             }
             Ok(all_symbols)
@@ -271,7 +419,7 @@ impl ASTEngine {
     }
 
     pub fn get_analysis_report(&self) -> Result<Option<serde_json::Value>, String> {
-        let cache_manager = self.cache_manager.try_lock()
+        let cache_manager = self.cache_manager.lock()
             .map_err(|_| "Cache manager lock poisoned")?;
         Ok(cache_manager.load_analysis_report())
     }
@@ -306,7 +454,7 @@ impl ASTEngine {
 
     fn remove_file_from_cache(&self, file_path: &Path) {
         let file_path_str = file_path.to_string_lossy().to_string();
-        if let Ok(mut query_engine) = self.query_engine.try_lock() {
+        if let Ok(mut query_engine) = self.query_engine.write() {
             if let Some(ref mut engine) = *query_engine {
                 // Remove from index
                 if let Some(file_index) = engine.cache.index.remove(&file_path_str) {
@@ -322,6 +470,22 @@ impl ASTEngine {
     }
 }
 
+/// 读取项目根目录的 `.deepauditignore`，文件不存在时返回空规则
+fn load_ignore_file(root_path: &Path) -> Gitignore {
+    let path = root_path.join(IGNORE_FILE_NAME);
+    if !path.is_file() {
+        return Gitignore::empty();
+    }
+    let mut builder = GitignoreBuilder::new(root_path);
+    if let Some(e) = builder.add(&path) {
+        log::warn!("Invalid entries in {}: {}", path.display(), e);
+    }
+    builder.build().unwrap_or_else(|e| {
+        log::warn!("Failed to load {}: {}", path.display(), e);
+        Gitignore::empty()
+    })
+}
+
 // Security scanner functionality
 pub struct SecurityScanner;
 
@@ -1,11 +1,12 @@
 pub mod cache;
+pub mod dead_code;
 pub mod engine;
 pub mod parser;
 pub mod query;
 pub mod symbol;
 
 pub use cache::{CacheData, CacheManager, FileIndex};
-pub use engine::{ASTEngine, CustomRule, SecurityScanner};
-pub use parser::ASTParser;
-pub use query::QueryEngine;
+pub use engine::{ASTEngine, CustomRule, ScanOptions, ScanSummary, SecurityScanner};
+pub use parser::{language_for_path, ASTParser};
+pub use query::{build_call_graph, build_reverse_call_graph, CallRelation, QueryEngine};
 pub use symbol::{Symbol, SymbolKind};
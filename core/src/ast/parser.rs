@@ -7,6 +7,12 @@ pub struct ASTParser {
     parsers: HashMap<String, Parser>,
 }
 
+impl Default for ASTParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ASTParser {
     pub fn new() -> Self {
         let mut parsers = HashMap::new();
@@ -35,7 +41,7 @@ impl ASTParser {
 
         for (ext, language) in supported_extensions {
             let mut parser = Parser::new();
-            if let Err(_) = parser.set_language(&language) {
+            if parser.set_language(&language).is_err() {
                 log::warn!("Failed to load parser for extension: {}", ext);
                 continue;
             }
@@ -63,14 +69,20 @@ impl ASTParser {
 
         let root_node = tree.root_node();
 
-        match ext.as_str() {
+        let mut symbols = match ext.as_str() {
             ".java" => self.extract_java_symbols(file_path, content, root_node),
             ".py" => self.extract_python_symbols(file_path, content, root_node),
             ".rs" => self.extract_rust_symbols(file_path, content, root_node),
             ".ts" | ".tsx" => self.extract_typescript_symbols(file_path, content, root_node),
             ".js" | ".jsx" => self.extract_javascript_symbols(file_path, content, root_node),
             _ => self.extract_generic_symbols(file_path, content, &ext, root_node),
+        }?;
+
+        let language = language_for_path(file_path).unwrap_or_default();
+        for symbol in &mut symbols {
+            symbol.language = language.to_string();
         }
+        Ok(symbols)
     }
 
     fn extract_java_symbols(
@@ -175,6 +187,7 @@ impl ASTParser {
                             code,
                         )
                         .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node))
                         .with_package(package_name.to_string())
                         .with_modifiers(modifiers)
                         .with_parent_classes(parent_classes)
@@ -213,6 +226,7 @@ impl ASTParser {
                             code,
                         )
                         .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node))
                         .with_package(package_name.to_string())
                         .with_metadata(metadata);
 
@@ -225,13 +239,18 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let mut metadata = HashMap::new();
+                        if let Some(receiver) = node
+                            .child_by_field_name("object")
+                            .and_then(|object| last_identifier(&content[object.byte_range()]))
+                        {
+                            metadata.insert(
+                                "receiver".to_string(),
+                                serde_json::Value::String(receiver),
+                            );
+                        }
                         if let Some(class_name) = class_stack.last() {
                             metadata.insert(
                                 "callerClass".to_string(),
@@ -253,6 +272,7 @@ impl ASTParser {
                             code,
                         )
                         .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node))
                         .with_package(package_name.to_string())
                         .with_metadata(metadata);
 
@@ -321,11 +341,7 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let symbol = Symbol::new(
                             name,
@@ -334,7 +350,8 @@ impl ASTParser {
                             start_line as u32,
                             code,
                         )
-                        .with_end_line(end_line as u32);
+                        .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node));
 
                         symbols.push(symbol);
                     }
@@ -347,11 +364,7 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let kind = if class_stack.is_empty() {
                             SymbolKind::Function
@@ -381,6 +394,7 @@ impl ASTParser {
                             code,
                         )
                         .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node))
                         .with_metadata(metadata);
 
                         symbols.push(symbol);
@@ -393,13 +407,17 @@ impl ASTParser {
                             let start_line = node.start_position().row + 1;
                             let end_line = node.end_position().row + 1;
                             let code = content[node.byte_range()].to_string();
-                            let code = if code.len() > 200 {
-                                format!("{}...", &code[..200])
-                            } else {
-                                code
-                            };
+                            let code = truncate_code(code);
 
                             let mut metadata = HashMap::new();
+                            if let Some(receiver) =
+                                extract_receiver(&content[function_node.byte_range()])
+                            {
+                                metadata.insert(
+                                    "receiver".to_string(),
+                                    serde_json::Value::String(receiver),
+                                );
+                            }
                             if let Some(class_name) = class_stack.last() {
                                 metadata.insert(
                                     "callerClass".to_string(),
@@ -421,6 +439,7 @@ impl ASTParser {
                                 code,
                             )
                             .with_end_line(end_line as u32)
+                            .with_columns(start_column(&node), end_column(&node))
                             .with_metadata(metadata);
 
                             symbols.push(symbol);
@@ -477,11 +496,7 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let symbol = Symbol::new(
                             name,
@@ -490,7 +505,8 @@ impl ASTParser {
                             start_line as u32,
                             code,
                         )
-                        .with_end_line(end_line as u32);
+                        .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node));
 
                         symbols.push(symbol);
                     }
@@ -503,11 +519,7 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let mut metadata = HashMap::new();
                         if let Some(func_name) = func_stack.last() {
@@ -525,6 +537,7 @@ impl ASTParser {
                             code,
                         )
                         .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node))
                         .with_metadata(metadata);
 
                         symbols.push(symbol);
@@ -537,13 +550,17 @@ impl ASTParser {
                             let start_line = node.start_position().row + 1;
                             let end_line = node.end_position().row + 1;
                             let code = content[node.byte_range()].to_string();
-                            let code = if code.len() > 200 {
-                                format!("{}...", &code[..200])
-                            } else {
-                                code
-                            };
+                            let code = truncate_code(code);
 
                             let mut metadata = HashMap::new();
+                            if let Some(receiver) =
+                                extract_receiver(&content[function_node.byte_range()])
+                            {
+                                metadata.insert(
+                                    "receiver".to_string(),
+                                    serde_json::Value::String(receiver),
+                                );
+                            }
                             if let Some(func_name) = func_stack.last() {
                                 metadata.insert(
                                     "callerFunction".to_string(),
@@ -559,6 +576,7 @@ impl ASTParser {
                                 code,
                             )
                             .with_end_line(end_line as u32)
+                            .with_columns(start_column(&node), end_column(&node))
                             .with_metadata(metadata);
 
                             symbols.push(symbol);
@@ -618,11 +636,7 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let symbol = Symbol::new(
                             name,
@@ -631,7 +645,8 @@ impl ASTParser {
                             start_line as u32,
                             code,
                         )
-                        .with_end_line(end_line as u32);
+                        .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node));
 
                         symbols.push(symbol);
                     }
@@ -644,11 +659,7 @@ impl ASTParser {
                         let start_line = node.start_position().row + 1;
                         let end_line = node.end_position().row + 1;
                         let code = content[node.byte_range()].to_string();
-                        let code = if code.len() > 200 {
-                            format!("{}...", &code[..200])
-                        } else {
-                            code
-                        };
+                        let code = truncate_code(code);
 
                         let kind = if class_stack.is_empty() {
                             SymbolKind::Function
@@ -669,6 +680,9 @@ impl ASTParser {
                                 serde_json::Value::String(func_name.clone()),
                             );
                         }
+                        if node.parent().is_some_and(|p| p.kind() == "export_statement") {
+                            metadata.insert("exported".to_string(), serde_json::Value::Bool(true));
+                        }
 
                         let symbol = Symbol::new(
                             name,
@@ -678,6 +692,7 @@ impl ASTParser {
                             code,
                         )
                         .with_end_line(end_line as u32)
+                        .with_columns(start_column(&node), end_column(&node))
                         .with_metadata(metadata);
 
                         symbols.push(symbol);
@@ -690,13 +705,17 @@ impl ASTParser {
                             let start_line = node.start_position().row + 1;
                             let end_line = node.end_position().row + 1;
                             let code = content[node.byte_range()].to_string();
-                            let code = if code.len() > 200 {
-                                format!("{}...", &code[..200])
-                            } else {
-                                code
-                            };
+                            let code = truncate_code(code);
 
                             let mut metadata = HashMap::new();
+                            if let Some(receiver) =
+                                extract_receiver(&content[function_node.byte_range()])
+                            {
+                                metadata.insert(
+                                    "receiver".to_string(),
+                                    serde_json::Value::String(receiver),
+                                );
+                            }
                             if let Some(class_name) = class_stack.last() {
                                 metadata.insert(
                                     "callerClass".to_string(),
@@ -718,6 +737,7 @@ impl ASTParser {
                                 code,
                             )
                             .with_end_line(end_line as u32)
+                            .with_columns(start_column(&node), end_column(&node))
                             .with_metadata(metadata);
 
                             symbols.push(symbol);
@@ -766,6 +786,50 @@ impl ASTParser {
     }
 }
 
+/// 根据文件扩展名判断源码语言
+pub fn language_for_path(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    let language = match ext.as_str() {
+        "js" | "jsx" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "py" => "python",
+        "java" => "java",
+        "rs" => "rust",
+        "go" => "go",
+        "html" | "htm" => "html",
+        "vue" => "vue",
+        "css" => "css",
+        "json" => "json",
+        "c" | "h" => "c",
+        "cpp" | "hpp" | "cc" => "cpp",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// 截断过长的代码片段，按字符边界截断避免切开多字节字符
+fn truncate_code(code: String) -> String {
+    const MAX_CODE_LEN: usize = 200;
+    if code.len() <= MAX_CODE_LEN {
+        return code;
+    }
+    let mut end = MAX_CODE_LEN;
+    while !code.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &code[..end])
+}
+
+/// 节点起始列，从 1 开始
+fn start_column(node: &Node) -> u32 {
+    node.start_position().column as u32 + 1
+}
+
+/// 节点结束列，从 1 开始
+fn end_column(node: &Node) -> u32 {
+    node.end_position().column as u32 + 1
+}
+
 fn extract_java_field(node: &Node, content: &str) -> Result<Field, String> {
     if let Some(name_node) = node.child_by_field_name("declarator") {
         if let Some(name_node) = name_node.child_by_field_name("name") {
@@ -817,6 +881,32 @@ fn extract_method_name(node: &Node, content: &str) -> String {
     }
 }
 
+/// 提取调用表达式的接收者（如 `Foo.bar` 中的 `Foo`），无法识别时返回 None
+fn extract_receiver(callee: &str) -> Option<String> {
+    let callee = callee
+        .trim()
+        .replace("?.", ".")
+        .replace("::", ".")
+        .replace("->", ".");
+    let (receiver, _) = callee.rsplit_once('.')?;
+    last_identifier(receiver)
+}
+
+/// 取表达式最后一段，仅当其为合法标识符时返回
+fn last_identifier(text: &str) -> Option<String> {
+    let text = text
+        .trim()
+        .replace("?.", ".")
+        .replace("::", ".")
+        .replace("->", ".");
+    let last = text.rsplit('.').next()?.trim();
+    let is_identifier = !last.is_empty()
+        && last
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    is_identifier.then(|| last.to_string())
+}
+
 fn extract_last_name(node: &Node, content: &str) -> String {
     let text = content[node.byte_range()].to_string();
     let text = text.trim();
@@ -832,5 +922,5 @@ fn extract_last_name(node: &Node, content: &str) -> String {
         .replace("->", ".");
 
     // Get the last part after splitting by dots
-    text.split('.').last().unwrap_or(&text).to_string()
+    text.split('.').next_back().unwrap_or(&text).to_string()
 }
@@ -47,7 +47,11 @@ impl QueryEngine {
         results
     }
 
-    pub fn get_call_graph(&self, entry: &str, max_depth: usize) -> Value {
+    /// 构建调用图
+    ///
+    /// `cross_file` 为 true 时，会借助 `class_map` 和 `parent_classes` 将调用解析到具体定义，
+    /// 节点附带定义所在的文件和行号，并且只沿解析到的定义体内的调用继续展开
+    pub fn get_call_graph(&self, entry: &str, max_depth: usize, cross_file: bool) -> Value {
         let entry = entry.trim();
         if entry.is_empty() {
             return serde_json::json!({
@@ -59,28 +63,32 @@ impl QueryEngine {
 
         let mut edges = Vec::new();
         let mut nodes = HashMap::new();
-        let mut queue = VecDeque::new();
+        let mut queue: VecDeque<(String, Option<&Symbol>)> = VecDeque::new();
         let mut visited = HashSet::new();
+        // 首次发现每个函数时记录其父节点，用于判断回边（环）
+        let mut parents: HashMap<String, String> = HashMap::new();
 
-        queue.push_back(entry.to_string());
+        let entry_def = if cross_file {
+            self.find_unique_definition(entry)
+        } else {
+            None
+        };
+        queue.push_back((entry.to_string(), entry_def));
         let mut depth = 0;
 
         while !queue.is_empty() && depth < max_depth {
             let mut next_queue = VecDeque::new();
 
-            while let Some(current) = queue.pop_front() {
+            while let Some((current, current_def)) = queue.pop_front() {
                 if visited.contains(&current) {
                     continue;
                 }
                 visited.insert(current.clone());
 
                 // Add node
-                nodes.entry(current.clone()).or_insert_with(|| {
-                    serde_json::json!({
-                        "id": current,
-                        "label": current
-                    })
-                });
+                nodes
+                    .entry(current.clone())
+                    .or_insert_with(|| call_graph_node(&current, current_def));
 
                 // Find calls from current function
                 for file_index in self.cache.index.values() {
@@ -96,32 +104,59 @@ impl QueryEngine {
                             .and_then(|v| v.as_str())
                             .unwrap_or("");
 
-                        let callee = &symbol.name;
+                        if caller != current {
+                            continue;
+                        }
 
-                        if caller == current {
-                            let caller_id = current.clone();
-                            let callee_id = callee.to_string();
-
-                            // Add callee node
-                            nodes.entry(callee_id.clone()).or_insert_with(|| {
-                                serde_json::json!({
-                                    "id": callee_id,
-                                    "label": callee_id
-                                })
-                            });
-
-                            // Add edge
-                            edges.push(serde_json::json!({
-                                "from": caller_id,
-                                "to": callee_id,
-                                "file": symbol.file_path,
-                                "line": symbol.start_line
-                            }));
-
-                            if !visited.contains(callee) {
-                                next_queue.push_back(callee_id);
+                        // 已解析到定义时，只保留定义体内的调用
+                        if let Some(def) = current_def {
+                            if symbol.file_path != def.file_path
+                                || symbol.start_line < def.start_line
+                                || symbol.start_line > def.end_line
+                            {
+                                continue;
                             }
                         }
+
+                        let callee = &symbol.name;
+                        let caller_id = current.clone();
+                        let callee_id = callee.to_string();
+                        let callee_def = if cross_file {
+                            self.resolve_call_target(symbol)
+                        } else {
+                            None
+                        };
+
+                        // Add callee node
+                        nodes
+                            .entry(callee_id.clone())
+                            .or_insert_with(|| call_graph_node(&callee_id, callee_def));
+
+                        // 被调用者是当前节点自身或其祖先时，该边闭合了一个环
+                        let is_cycle = is_ancestor(&parents, callee, &current);
+                        if !is_cycle && callee_id != entry && !parents.contains_key(&callee_id) {
+                            parents.insert(callee_id.clone(), current.clone());
+                        }
+
+                        // Add edge
+                        let mut edge = serde_json::json!({
+                            "from": caller_id,
+                            "to": callee_id,
+                            "file": symbol.file_path,
+                            "line": symbol.start_line
+                        });
+                        if is_cycle {
+                            edge["is_cycle"] = Value::Bool(true);
+                        }
+                        if let Some(def) = callee_def {
+                            edge["callee_file"] = Value::String(def.file_path.clone());
+                            edge["callee_line"] = Value::from(def.start_line);
+                        }
+                        edges.push(edge);
+
+                        if !visited.contains(callee) {
+                            next_queue.push_back((callee_id, callee_def));
+                        }
                     }
                 }
             }
@@ -137,6 +172,128 @@ impl QueryEngine {
         })
     }
 
+    /// 构建反向调用图：从目标函数出发，沿被调用者 → 调用者方向展开
+    pub fn get_reverse_call_graph(&self, function: &str, max_depth: usize) -> Value {
+        let mut relations = Vec::new();
+        for file_index in self.cache.index.values() {
+            for symbol in &file_index.symbols {
+                if !matches!(symbol.kind, crate::ast::symbol::SymbolKind::MethodCall) {
+                    continue;
+                }
+                let caller = symbol
+                    .metadata
+                    .get("callerMethod")
+                    .or_else(|| symbol.metadata.get("callerFunction"))
+                    .and_then(|v| v.as_str());
+                if let Some(caller) = caller {
+                    relations.push(CallRelation {
+                        caller: caller.to_string(),
+                        callee: symbol.name.clone(),
+                        file: symbol.file_path.clone(),
+                        line: symbol.start_line as i64,
+                    });
+                }
+            }
+        }
+
+        build_reverse_call_graph(function, max_depth, relations)
+    }
+
+    /// 将调用点解析到被调用函数/方法的定义
+    ///
+    /// 解析顺序：接收者类（含 this/self/super）及其父类 > 调用者所在类及其父类 > 同文件函数 > 全局唯一定义
+    pub fn resolve_call_target(&self, call: &Symbol) -> Option<&Symbol> {
+        let name = call.name.as_str();
+        let receiver = call.metadata.get("receiver").and_then(|v| v.as_str());
+        let caller_class = call.metadata.get("callerClass").and_then(|v| v.as_str());
+
+        let receiver_class = match receiver {
+            Some("this") | Some("self") => caller_class,
+            Some("super") => caller_class
+                .and_then(|c| self.find_class_symbol(c))
+                .and_then(|c| c.parent_classes.first())
+                .map(|s| s.as_str()),
+            Some(r) if self.cache.class_map.contains_key(r) => Some(r),
+            _ => None,
+        };
+
+        if let Some(class_name) = receiver_class {
+            if let Some(def) = self.find_method_in_class(class_name, name) {
+                return Some(def);
+            }
+        }
+
+        // 无接收者的调用优先视为调用者所在类的方法
+        if receiver.is_none() {
+            if let Some(def) = caller_class.and_then(|c| self.find_method_in_class(c, name)) {
+                return Some(def);
+            }
+
+            let same_file = self.cache.index.get(&call.file_path).and_then(|file_index| {
+                file_index.symbols.iter().find(|s| {
+                    matches!(s.kind, crate::ast::symbol::SymbolKind::Function) && s.name == name
+                })
+            });
+            if same_file.is_some() {
+                return same_file;
+            }
+        }
+
+        self.find_unique_definition(name)
+    }
+
+    /// 在类及其父类中查找方法定义
+    fn find_method_in_class(&self, class_name: &str, method_name: &str) -> Option<&Symbol> {
+        let mut queue = VecDeque::from([class_name.to_string()]);
+        let mut visited = HashSet::new();
+
+        while let Some(class_name) = queue.pop_front() {
+            if !visited.insert(class_name.clone()) {
+                continue;
+            }
+            let Some(file_path) = self.cache.class_map.get(&class_name) else {
+                continue;
+            };
+            if let Some(file_index) = self.cache.index.get(file_path) {
+                let found = file_index.symbols.iter().find(|s| {
+                    matches!(
+                        s.kind,
+                        crate::ast::symbol::SymbolKind::Method | crate::ast::symbol::SymbolKind::Function
+                    ) && s.name == method_name
+                        && owner_class(s) == Some(class_name.as_str())
+                });
+                if found.is_some() {
+                    return found;
+                }
+            }
+            if let Some(class_symbol) = self.find_class_symbol(&class_name) {
+                queue.extend(class_symbol.parent_classes.iter().cloned());
+            }
+        }
+
+        None
+    }
+
+    /// 查找全局唯一的函数/方法定义，存在同名歧义时返回 None
+    fn find_unique_definition(&self, name: &str) -> Option<&Symbol> {
+        let mut found = None;
+        for file_index in self.cache.index.values() {
+            for symbol in &file_index.symbols {
+                if matches!(
+                    symbol.kind,
+                    crate::ast::symbol::SymbolKind::Method | crate::ast::symbol::SymbolKind::Function
+                ) && symbol.name == name
+                {
+                    if found.is_some() {
+                        return None;
+                    }
+                    found = Some(symbol);
+                }
+            }
+        }
+        found
+    }
+
     pub fn get_class_hierarchy(&self, class_name: &str) -> Value {
         // Find the class symbol
         let target_symbol = self.find_class_symbol(class_name);
@@ -191,14 +348,14 @@ impl QueryEngine {
         let mut children = Vec::new();
         for file_path in self.cache.index.keys() {
             for symbol in self.cache.index.get(file_path).unwrap().symbols.iter() {
-                if matches!(symbol.kind, crate::ast::symbol::SymbolKind::Class) {
-                    if symbol.parent_classes.contains(&class_name.to_string()) {
-                        children.push(serde_json::json!({
-                            "name": symbol.name,
-                            "file": file_path,
-                            "line": symbol.start_line
-                        }));
-                    }
+                if matches!(symbol.kind, crate::ast::symbol::SymbolKind::Class)
+                    && symbol.parent_classes.contains(&class_name.to_string())
+                {
+                    children.push(serde_json::json!({
+                        "name": symbol.name,
+                        "file": file_path,
+                        "line": symbol.start_line
+                    }));
                 }
             }
         }
@@ -219,6 +376,48 @@ impl QueryEngine {
         }
     }
 
+    /// 查找包含指定行范围的最内层函数或方法
+    pub fn find_enclosing_function(
+        &self,
+        file_path: &str,
+        start_line: u32,
+        end_line: u32,
+    ) -> Option<&Symbol> {
+        let file_index = self.cache.index.get(file_path)?;
+        file_index
+            .symbols
+            .iter()
+            .filter(|s| {
+                matches!(
+                    s.kind,
+                    crate::ast::symbol::SymbolKind::Function | crate::ast::symbol::SymbolKind::Method
+                ) && s.start_line <= start_line
+                    && s.end_line >= end_line
+            })
+            .min_by_key(|s| s.end_line - s.start_line)
+    }
+
+    /// 获取文件中指定行范围内的调用点
+    pub fn find_calls_in_range(
+        &self,
+        file_path: &str,
+        start_line: u32,
+        end_line: u32,
+    ) -> Vec<&Symbol> {
+        match self.cache.index.get(file_path) {
+            Some(file_index) => file_index
+                .symbols
+                .iter()
+                .filter(|s| {
+                    matches!(s.kind, crate::ast::symbol::SymbolKind::MethodCall)
+                        && s.start_line >= start_line
+                        && s.start_line <= end_line
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
     pub fn get_statistics(&self) -> Value {
         let mut total_nodes = 0;
         let mut type_counts = HashMap::new();
@@ -248,7 +447,7 @@ impl QueryEngine {
     pub fn generate_report(&self, repository_path: &str) -> Value {
         let mut nodes = serde_json::Map::new();
 
-        for (_file_path, data) in &self.cache.index {
+        for data in self.cache.index.values() {
             for symbol in &data.symbols {
                 let symbol_dict = symbol.to_dict();
                 if let Some(id) = symbol_dict.get("id").and_then(|v| v.as_str()) {
@@ -301,3 +500,297 @@ impl QueryEngine {
         }
     }
 }
+
+/// 判断 `candidate` 是否为 `node` 自身或其在遍历树中的祖先
+fn is_ancestor(parents: &HashMap<String, String>, candidate: &str, node: &str) -> bool {
+    let mut current = node;
+    // 父节点链只在首次发现时写入，因此不会成环；长度以节点数为上限
+    for _ in 0..=parents.len() {
+        if current == candidate {
+            return true;
+        }
+        match parents.get(current) {
+            Some(parent) => current = parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// 方法所属的类名
+fn owner_class(symbol: &Symbol) -> Option<&str> {
+    symbol
+        .metadata
+        .get("ownerClass")
+        .or_else(|| symbol.metadata.get("callerClass"))
+        .and_then(|v| v.as_str())
+}
+
+/// 调用图节点，解析到定义时附带位置
+fn call_graph_node(name: &str, definition: Option<&Symbol>) -> Value {
+    match definition {
+        Some(def) => serde_json::json!({
+            "id": name,
+            "label": name,
+            "file": def.file_path,
+            "line": def.start_line
+        }),
+        None => serde_json::json!({
+            "id": name,
+            "label": name
+        }),
+    }
+}
+
+/// 一条调用关系（调用者 -> 被调用者）
+#[derive(Debug, Clone)]
+pub struct CallRelation {
+    pub caller: String,
+    pub callee: String,
+    pub file: String,
+    pub line: i64,
+}
+
+/// 根据调用关系构建反向调用图，输出与正向调用图相同的 `{nodes, edges}` 结构
+///
+/// 每个函数只展开一次，递归或互相递归的调用也能终止
+pub fn build_reverse_call_graph(
+    function: &str,
+    max_depth: usize,
+    relations: impl IntoIterator<Item = CallRelation>,
+) -> Value {
+    let function = function.trim();
+    if function.is_empty() {
+        return serde_json::json!({
+            "entry": function,
+            "nodes": [],
+            "edges": []
+        });
+    }
+
+    let mut callers_of: HashMap<String, Vec<CallRelation>> = HashMap::new();
+    for relation in relations {
+        callers_of
+            .entry(relation.callee.clone())
+            .or_default()
+            .push(relation);
+    }
+
+    let mut nodes = HashMap::new();
+    let mut edges = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([(function.to_string(), 0usize)]);
+
+    nodes.insert(
+        function.to_string(),
+        serde_json::json!({ "id": function, "label": function }),
+    );
+
+    while let Some((current, depth)) = queue.pop_front() {
+        if depth >= max_depth || !visited.insert(current.clone()) {
+            continue;
+        }
+
+        let Some(relations) = callers_of.get(&current) else {
+            continue;
+        };
+
+        let mut seen_edges = HashSet::new();
+        for relation in relations {
+            if !seen_edges.insert((&relation.caller, &relation.file, relation.line)) {
+                continue;
+            }
+
+            nodes.entry(relation.caller.clone()).or_insert_with(|| {
+                serde_json::json!({ "id": relation.caller, "label": relation.caller })
+            });
+            edges.push(serde_json::json!({
+                "from": relation.caller,
+                "to": relation.callee,
+                "file": relation.file,
+                "line": relation.line
+            }));
+
+            if !visited.contains(&relation.caller) {
+                queue.push_back((relation.caller.clone(), depth + 1));
+            }
+        }
+    }
+
+    serde_json::json!({
+        "entry": function,
+        "nodes": nodes.into_values().collect::<Vec<_>>(),
+        "edges": edges
+    })
+}
+
+/// 根据调用关系构建正向调用图，输出与 [`QueryEngine::get_call_graph`] 相同的 `{nodes, edges}` 结构
+///
+/// 用于 AST 缓存未加载时基于已保存的调用关系构建；调用关系不含定义位置，节点只有名称
+pub fn build_call_graph(
+    entry: &str,
+    max_depth: usize,
+    relations: impl IntoIterator<Item = CallRelation>,
+) -> Value {
+    let entry = entry.trim();
+    if entry.is_empty() {
+        return serde_json::json!({
+            "entry": entry,
+            "nodes": [],
+            "edges": []
+        });
+    }
+
+    let mut callees_of: HashMap<String, Vec<CallRelation>> = HashMap::new();
+    for relation in relations {
+        callees_of
+            .entry(relation.caller.clone())
+            .or_default()
+            .push(relation);
+    }
+
+    let mut nodes = HashMap::new();
+    let mut edges = Vec::new();
+    let mut visited = HashSet::new();
+    let mut parents: HashMap<String, String> = HashMap::new();
+    let mut queue = VecDeque::from([(entry.to_string(), 0usize)]);
+
+    while let Some((current, depth)) = queue.pop_front() {
+        if depth >= max_depth || !visited.insert(current.clone()) {
+            continue;
+        }
+        nodes
+            .entry(current.clone())
+            .or_insert_with(|| call_graph_node(&current, None));
+
+        let Some(relations) = callees_of.get(&current) else {
+            continue;
+        };
+
+        let mut seen_edges = HashSet::new();
+        for relation in relations {
+            if !seen_edges.insert((&relation.callee, &relation.file, relation.line)) {
+                continue;
+            }
+
+            nodes
+                .entry(relation.callee.clone())
+                .or_insert_with(|| call_graph_node(&relation.callee, None));
+
+            let is_cycle = is_ancestor(&parents, &relation.callee, &current);
+            if !is_cycle && relation.callee != entry && !parents.contains_key(&relation.callee) {
+                parents.insert(relation.callee.clone(), current.clone());
+            }
+
+            let mut edge = serde_json::json!({
+                "from": relation.caller,
+                "to": relation.callee,
+                "file": relation.file,
+                "line": relation.line
+            });
+            if is_cycle {
+                edge["is_cycle"] = Value::Bool(true);
+            }
+            edges.push(edge);
+
+            if !visited.contains(&relation.callee) {
+                queue.push_back((relation.callee.clone(), depth + 1));
+            }
+        }
+    }
+
+    serde_json::json!({
+        "entry": entry,
+        "nodes": nodes.into_values().collect::<Vec<_>>(),
+        "edges": edges
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ASTEngine;
+
+    fn relation(caller: &str, callee: &str, line: i64) -> CallRelation {
+        CallRelation {
+            caller: caller.to_string(),
+            callee: callee.to_string(),
+            file: "app.py".to_string(),
+            line,
+        }
+    }
+
+    fn node_ids(graph: &Value) -> Vec<String> {
+        let mut ids: Vec<String> = graph["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| node["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// 解析 `app.py` 得到的引擎，临时目录须在使用期间保留
+    fn parsed_engine(source: &str) -> (tempfile::TempDir, ASTEngine) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        std::fs::write(repo.join("app.py"), source).unwrap();
+        let engine = ASTEngine::new(dir.path().join("cache").to_str().unwrap());
+        engine.use_repository(repo.to_str().unwrap());
+        engine.scan_project(repo.to_str().unwrap()).unwrap();
+        (dir, engine)
+    }
+
+    #[test]
+    fn reverse_call_graph_terminates_on_recursion() {
+        let graph = build_reverse_call_graph(
+            "fact",
+            10,
+            vec![relation("fact", "fact", 3), relation("main", "fact", 7), relation("main", "main", 8)],
+        );
+        assert_eq!(node_ids(&graph), ["fact", "main"]);
+        assert_eq!(graph["edges"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn reverse_call_graph_from_parsed_recursive_function() {
+        let (_dir, engine) = parsed_engine(
+            "def fact(n):\n    return 1 if n == 0 else n * fact(n - 1)\n\n\ndef main():\n    return fact(5)\n",
+        );
+        let graph = engine.get_reverse_call_graph("fact", 10).unwrap();
+        assert_eq!(node_ids(&graph), ["fact", "main"]);
+    }
+
+    /// 标记为闭合环的边
+    fn cycle_edges(graph: &Value) -> Vec<(String, String)> {
+        graph["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|edge| edge["is_cycle"] == Value::Bool(true))
+            .map(|edge| (edge["from"].as_str().unwrap().to_string(), edge["to"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn call_graph_flags_cycle_back_edge() {
+        let (_dir, engine) = parsed_engine("def a():\n    b()\n\n\ndef b():\n    c()\n\n\ndef c():\n    a()\n");
+        let graph = engine.get_call_graph("a", 10, false).unwrap();
+        assert_eq!(node_ids(&graph), ["a", "b", "c"]);
+        assert_eq!(graph["edges"].as_array().unwrap().len(), 3);
+        assert_eq!(cycle_edges(&graph), [("c".to_string(), "a".to_string())]);
+
+        let graph = build_call_graph("a", 10, vec![relation("a", "b", 2), relation("b", "c", 6), relation("c", "a", 10)]);
+        assert_eq!(node_ids(&graph), ["a", "b", "c"]);
+        assert_eq!(cycle_edges(&graph), [("c".to_string(), "a".to_string())]);
+    }
+
+    #[test]
+    fn call_graph_terminates_on_self_recursion() {
+        let graph = build_call_graph("fact", 10, vec![relation("fact", "fact", 2)]);
+        assert_eq!(node_ids(&graph), ["fact"]);
+        assert_eq!(cycle_edges(&graph), [("fact".to_string(), "fact".to_string())]);
+    }
+}
@@ -9,6 +9,12 @@ pub struct Symbol {
     pub line: u32,
     pub start_line: u32,
     pub end_line: u32,
+    /// 起始列（从 1 开始，按字节计），0 表示解析器无法提供
+    #[serde(default)]
+    pub column: u32,
+    /// 结束列（从 1 开始，按字节计），0 表示解析器无法提供
+    #[serde(default)]
+    pub end_column: u32,
     pub code: String,
     pub parent_classes: Vec<String>,
     pub package: String,
@@ -16,9 +22,12 @@ pub struct Symbol {
     pub fields: Vec<Field>,
     pub metadata: HashMap<String, serde_json::Value>,
     pub subclasses: Vec<String>, // Populated post-analysis
+    /// 源码语言，由文件扩展名确定，如 "python"、"java"
+    #[serde(default)]
+    pub language: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SymbolKind {
     Class,
     Function,
@@ -28,6 +37,23 @@ pub enum SymbolKind {
     Struct,
 }
 
+impl std::str::FromStr for SymbolKind {
+    type Err = String;
+
+    /// 解析符号类型，同时接受 "method_call" 与 "MethodCall" 两种写法
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.replace('_', "").to_lowercase().as_str() {
+            "class" => Ok(SymbolKind::Class),
+            "function" => Ok(SymbolKind::Function),
+            "method" => Ok(SymbolKind::Method),
+            "methodcall" => Ok(SymbolKind::MethodCall),
+            "interface" => Ok(SymbolKind::Interface),
+            "struct" => Ok(SymbolKind::Struct),
+            _ => Err(format!("Unknown symbol kind: {}", s)),
+        }
+    }
+}
+
 impl Symbol {
     pub fn new(
         name: String,
@@ -44,6 +70,8 @@ impl Symbol {
             line: start_line,
             start_line,
             end_line,
+            column: 0,
+            end_column: 0,
             code,
             parent_classes: Vec::new(),
             package: String::new(),
@@ -51,12 +79,18 @@ impl Symbol {
             fields: Vec::new(),
             metadata: HashMap::new(),
             subclasses: Vec::new(),
+            language: String::new(),
         }
     }
 
     pub fn with_end_line(mut self, end_line: u32) -> Self {
         self.end_line = end_line;
-        self.line = end_line; // Update line for compatibility
+        self
+    }
+
+    pub fn with_columns(mut self, column: u32, end_column: u32) -> Self {
+        self.column = column;
+        self.end_column = end_column;
         self
     }
 
@@ -134,6 +168,8 @@ impl Symbol {
             "package": self.package,
             "startLine": self.start_line,
             "endLine": self.end_line,
+            "column": self.column,
+            "endColumn": self.end_column,
             "code": self.code, // Keep for display
             "modifiers": self.modifiers,
             "fields": self.fields,
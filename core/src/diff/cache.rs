@@ -0,0 +1,127 @@
+// 差异结果缓存：按文件路径、大小、修改时间及影响行差异的配置缓存单个文件的差异，切换显示模式等选项时免于重新读盘计算
+
+use crate::diff::types::{ComparisonConfig, DiffAlgorithm, FileDiff};
+use anyhow::Result;
+use hashlink::LruCache;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 影响单个文件差异行的配置项；显示模式、上下文行数、重命名检测等在缓存之后处理，不参与键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LineDiffSettings {
+    ignore_whitespace: bool,
+    ignore_case: bool,
+    ignore_line_endings: bool,
+    ignore_trailing_newline: bool,
+    min_moved_lines: u32,
+    algorithm: DiffAlgorithm,
+    deadline_ms: Option<u64>,
+    max_file_size: u64,
+    ignore_regions: Vec<(String, String)>,
+}
+
+impl LineDiffSettings {
+    fn new(config: &ComparisonConfig) -> Self {
+        Self {
+            ignore_whitespace: config.ignore_whitespace,
+            ignore_case: config.ignore_case,
+            ignore_line_endings: config.ignore_line_endings,
+            ignore_trailing_newline: config.ignore_trailing_newline,
+            min_moved_lines: config.min_moved_lines,
+            algorithm: config.algorithm,
+            deadline_ms: config.deadline_ms,
+            max_file_size: config.max_file_size,
+            ignore_regions: config.ignore_regions.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    path_a: Option<PathBuf>,
+    path_b: Option<PathBuf>,
+    settings: LineDiffSettings,
+}
+
+/// 文件的大小与修改时间，任一变化即视为缓存失效
+type FileStamp = (u64, Option<SystemTime>);
+
+struct CacheEntry {
+    stamps: (Option<FileStamp>, Option<FileStamp>),
+    diff: FileDiff,
+}
+
+/// 文件差异的 LRU 缓存，可在多次比较之间共享
+pub struct DiffCache {
+    entries: Mutex<LruCache<CacheKey, CacheEntry>>,
+}
+
+impl DiffCache {
+    /// 创建最多保留 `capacity` 个文件差异的缓存，超出时淘汰最久未使用的条目
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity.max(1))),
+        }
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// 当前缓存的文件差异数
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 返回缓存的差异；未命中或文件大小、修改时间已变化时调用 `compute` 计算并缓存
+    ///
+    /// `path_a` / `path_b` 为空表示该侧文件不存在（新增或删除）
+    pub(crate) fn get_or_compute(
+        &self,
+        path_a: Option<&Path>,
+        path_b: Option<&Path>,
+        config: &ComparisonConfig,
+        compute: impl FnOnce() -> Result<FileDiff>,
+    ) -> Result<FileDiff> {
+        let key = CacheKey {
+            path_a: path_a.map(Path::to_path_buf),
+            path_b: path_b.map(Path::to_path_buf),
+            settings: LineDiffSettings::new(config),
+        };
+        // 先取时间戳再计算，计算期间文件被修改时下次比较会重新计算
+        let stamps = (path_a.and_then(file_stamp), path_b.and_then(file_stamp));
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get(&key) {
+                Some(entry) if entry.stamps == stamps => return Ok(entry.diff.clone()),
+                Some(_) => {
+                    entries.remove(&key);
+                }
+                None => {}
+            }
+        }
+
+        let diff = compute()?;
+        self.entries.lock().unwrap().insert(
+            key,
+            CacheEntry {
+                stamps,
+                diff: diff.clone(),
+            },
+        );
+        Ok(diff)
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
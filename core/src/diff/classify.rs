@@ -0,0 +1,172 @@
+// 变更分类：判断修改的文件是否只改动了换行符、空白或注释，便于审阅时跳过纯格式变更
+
+use crate::ast::language_for_path;
+use crate::diff::engine::BINARY_MARKER;
+use crate::diff::types::{ChangeKind, DiffType, FileDiff, FileStatus};
+use std::path::Path;
+
+/// 语言的注释语法
+struct CommentSyntax {
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+    /// 字符串定界符，其中的注释标记不视为注释
+    quotes: &'static [char],
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+};
+
+const PYTHON: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: None,
+    quotes: &['"', '\''],
+};
+
+const CSS: CommentSyntax = CommentSyntax {
+    line: &[],
+    block: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+};
+
+const MARKUP: CommentSyntax = CommentSyntax {
+    line: &[],
+    block: Some(("<!--", "-->")),
+    quotes: &[],
+};
+
+fn comment_syntax(path: &str) -> Option<&'static CommentSyntax> {
+    match language_for_path(Path::new(path))? {
+        "javascript" | "typescript" | "java" | "rust" | "go" | "c" | "cpp" => Some(&C_LIKE),
+        "python" => Some(&PYTHON),
+        "css" => Some(&CSS),
+        "html" | "vue" => Some(&MARKUP),
+        _ => None,
+    }
+}
+
+/// 判断修改（含重命名、复制）文件的变更类型
+///
+/// 两侧各行去除行尾 `\r` 后相同（含只有末尾换行不同）为 `LineEndingsOnly`；去除全部空白后相同为 `WhitespaceOnly`；
+/// `comments` 为真且语言可识别时，再去除注释后相同为 `CommentOnly`；其余为 `Substantive`。新增、删除、未变更及二进制文件返回 `None`
+pub fn classify_change(diff: &FileDiff, comments: bool) -> Option<ChangeKind> {
+    if !matches!(
+        diff.status,
+        FileStatus::Modified | FileStatus::Renamed { .. } | FileStatus::Copied { .. }
+    ) {
+        return None;
+    }
+    let lines: Vec<_> = diff.lines.iter().filter(|line| !line.is_placeholder).collect();
+    if lines.iter().all(|line| line.diff_type == DiffType::Equal)
+        || lines.iter().any(|line| line.content.starts_with(BINARY_MARKER))
+    {
+        return None;
+    }
+
+    let side = |left: bool| -> String {
+        lines
+            .iter()
+            .filter(|line| if left { line.left_line_number.is_some() } else { line.right_line_number.is_some() })
+            .map(|line| line.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let (left, right) = (side(true), side(false));
+    if without_carriage_returns(&left) == without_carriage_returns(&right) {
+        return Some(ChangeKind::LineEndingsOnly);
+    }
+    if without_whitespace(&left) == without_whitespace(&right) {
+        return Some(ChangeKind::WhitespaceOnly);
+    }
+
+    if comments {
+        if let Some(syntax) = comment_syntax(&diff.path) {
+            if without_whitespace(&strip_comments(&left, syntax))
+                == without_whitespace(&strip_comments(&right, syntax))
+            {
+                return Some(ChangeKind::CommentOnly);
+            }
+        }
+    }
+    Some(ChangeKind::Substantive)
+}
+
+fn without_carriage_returns(text: &str) -> String {
+    text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect::<Vec<_>>().join("\n")
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// 注释或字符串字面量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LiteralKind {
+    Comment,
+    String,
+}
+
+/// 按文件语言的注释语法找出注释与字符串字面量的字节区间 `[start, end)`，按位置排列；语言无法识别时为空
+pub(crate) fn literal_spans(path: &Path, text: &str) -> Vec<(usize, usize, LiteralKind)> {
+    match comment_syntax(&path.to_string_lossy()) {
+        Some(syntax) => spans(text, syntax),
+        None => Vec::new(),
+    }
+}
+
+/// 注释与字符串字面量的字节区间，字符串字面量中的注释标记不视为注释；行注释不含结尾的换行
+fn spans(text: &str, syntax: &CommentSyntax) -> Vec<(usize, usize, LiteralKind)> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = text[pos..].chars().next() {
+        let rest = &text[pos..];
+        let (len, kind) = if syntax.quotes.contains(&c) {
+            (string_end(rest, c), LiteralKind::String)
+        } else if let Some(marker) = syntax.line.iter().find(|marker| rest.starts_with(**marker)) {
+            (rest[marker.len()..].find('\n').map_or(rest.len(), |i| marker.len() + i), LiteralKind::Comment)
+        } else if let Some((open, close)) = syntax.block.filter(|(open, _)| rest.starts_with(open)) {
+            let len = rest[open.len()..]
+                .find(close)
+                .map_or(rest.len(), |i| open.len() + i + close.len());
+            (len, LiteralKind::Comment)
+        } else {
+            pos += c.len_utf8();
+            continue;
+        };
+        spans.push((pos, pos + len, kind));
+        pos += len;
+    }
+    spans
+}
+
+/// 去除注释，字符串字面量中的注释标记保留；注释替换为一个空格
+fn strip_comments(text: &str, syntax: &CommentSyntax) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (start, end, kind) in spans(text, syntax) {
+        if kind == LiteralKind::Comment {
+            out.push_str(&text[pos..start]);
+            out.push(' ');
+            pos = end;
+        }
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// 以 `quote` 开始的字符串字面量的字节长度（含两端引号），未闭合时到行尾为止
+fn string_end(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' if quote != '`' => return i,
+            c if c == quote => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    text.len()
+}
@@ -0,0 +1,196 @@
+// 差异块上下文：为每处变更标注所在的函数/类，类似 git 块头中的函数名
+
+use crate::ast::{ASTParser, Symbol, SymbolKind};
+use crate::diff::engine::BINARY_MARKER;
+use crate::diff::types::{DiffLine, DiffType, FileDiff, FileStatus};
+use std::path::Path;
+
+/// 启发式识别时视为定义行的前缀（去除缩进后）
+const DEFINITION_PREFIXES: &[&str] = &[
+    "def ",
+    "async def ",
+    "function ",
+    "async function ",
+    "export function ",
+    "export async function ",
+    "export default function ",
+    "class ",
+    "export class ",
+    "export default class ",
+];
+
+/// 启发式标注的定义行最多保留的字符数
+const MAX_HEADING_CHARS: usize = 80;
+
+/// 为每组连续变更的第一行标注所在的符号，写入 `DiffLine.enclosing_symbol`
+///
+/// `symbols` 为右侧文件的 AST 符号时，取包含变更行的函数、方法、类等，由外到内以 `.` 连接（如 `Outer.inner`）；
+/// 为空时按右侧内容向上查找最近的 `def ` / `function ` / `class ` 定义行。二进制与未变更文件不处理
+pub fn annotate_enclosing_symbols(diff: &mut FileDiff, symbols: Option<&[Symbol]>) {
+    if diff.status == FileStatus::Unchanged
+        || diff.lines.iter().any(|line| line.content.starts_with(BINARY_MARKER))
+    {
+        return;
+    }
+
+    let right_lines: Vec<&str> = diff
+        .lines
+        .iter()
+        .filter(|line| line.right_line_number.is_some())
+        .map(|line| line.content.as_str())
+        .collect();
+
+    let mut annotations = Vec::new();
+    let mut last_right = 0;
+    for (i, line) in diff.lines.iter().enumerate() {
+        let group_start = line.diff_type != DiffType::Equal
+            && (i == 0 || diff.lines[i - 1].diff_type == DiffType::Equal);
+        if group_start {
+            // 纯删除的变更没有右侧行号，取其前一行
+            let right = diff.lines[i..]
+                .iter()
+                .take_while(|line| line.diff_type != DiffType::Equal)
+                .find_map(|line| line.right_line_number)
+                .unwrap_or(last_right)
+                .max(1);
+            let enclosing = match symbols {
+                Some(symbols) => symbol_chain(symbols, right),
+                None => preceding_definition(&right_lines, right),
+            };
+            annotations.push((i, enclosing));
+        }
+        if let Some(right) = line.right_line_number {
+            last_right = right;
+        }
+    }
+
+    for (i, enclosing) in annotations {
+        diff.lines[i].enclosing_symbol = enclosing;
+    }
+}
+
+/// 解析右侧内容得到 AST 符号；语言不受支持或解析失败时返回 `None`
+pub(crate) fn right_side_symbols(parser: &mut ASTParser, diff: &FileDiff) -> Option<Vec<Symbol>> {
+    side_symbols(parser, diff, |line| line.right_line_number)
+}
+
+/// 解析左侧内容得到 AST 符号；语言不受支持或解析失败时返回 `None`
+pub(crate) fn left_side_symbols(parser: &mut ASTParser, diff: &FileDiff) -> Option<Vec<Symbol>> {
+    side_symbols(parser, diff, |line| line.left_line_number)
+}
+
+/// 由差异行中 `line_number` 有值的行还原一侧的内容并解析
+fn side_symbols(
+    parser: &mut ASTParser,
+    diff: &FileDiff,
+    line_number: impl Fn(&DiffLine) -> Option<u32>,
+) -> Option<Vec<Symbol>> {
+    let content = diff
+        .lines
+        .iter()
+        .filter(|line| line_number(line).is_some())
+        .map(|line| line.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    parser
+        .parse_file(Path::new(&diff.path), &content)
+        .ok()
+        .filter(|symbols| !symbols.is_empty())
+}
+
+/// 包含第 `line` 行的定义符号，由外到内连接
+fn symbol_chain(symbols: &[Symbol], line: u32) -> Option<String> {
+    let mut enclosing: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|symbol| symbol.kind != SymbolKind::MethodCall)
+        .filter(|symbol| symbol.start_line <= line && line <= symbol.end_line)
+        .collect();
+    enclosing.sort_by_key(|symbol| (symbol.start_line, std::cmp::Reverse(symbol.end_line)));
+
+    let mut names: Vec<&str> = Vec::new();
+    for symbol in enclosing {
+        if names.last() != Some(&symbol.name.as_str()) {
+            names.push(&symbol.name);
+        }
+    }
+    (!names.is_empty()).then(|| names.join("."))
+}
+
+/// 从第 `line` 行向上查找最近的定义行
+fn preceding_definition(right_lines: &[&str], line: u32) -> Option<String> {
+    let end = (line as usize).min(right_lines.len());
+    right_lines[..end].iter().rev().find_map(|text| {
+        let trimmed = text.trim();
+        DEFINITION_PREFIXES
+            .iter()
+            .any(|prefix| trimmed.starts_with(prefix))
+            .then(|| {
+                let heading = trimmed.trim_end_matches(['{', ':']).trim_end();
+                heading.chars().take(MAX_HEADING_CHARS).collect()
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::engine::DiffEngine;
+    use crate::diff::types::*;
+    use std::fs;
+
+    const BEFORE: &str = "\
+class Outer:
+    def setup(self):
+        pass
+
+    def run(self):
+        value = 1
+        return value
+";
+
+    /// 比较同名的两个文件，返回各差异块的块头
+    fn hunk_headers(file_name: &str, before: &str, after: &str) -> Vec<String> {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+        fs::write(left.path().join(file_name), before).unwrap();
+        fs::write(right.path().join(file_name), after).unwrap();
+
+        let config = ComparisonConfig {
+            annotate_enclosing_symbols: true,
+            view_mode: DiffViewMode::Unified,
+            context_lines: 1,
+            ..ComparisonConfig::default()
+        };
+        let result = DiffEngine::new(config.clone())
+            .compare(ComparisonRequest {
+                source_a: left.path().join(file_name).to_string_lossy().to_string(),
+                source_b: right.path().join(file_name).to_string_lossy().to_string(),
+                config,
+                is_git_comparison: false,
+                git_params: None,
+                is_working_tree_comparison: false,
+            })
+            .unwrap();
+        result.file_diffs[0].hunks.iter().map(|hunk| hunk.header.clone()).collect()
+    }
+
+    #[test]
+    fn change_in_nested_method_names_class_and_method() {
+        let after = BEFORE.replace("value = 1", "value = 2");
+        assert_eq!(hunk_headers("app.py", BEFORE, &after), ["@@ -5,3 +5,3 @@ Outer.run"]);
+    }
+
+    #[test]
+    fn unsupported_language_falls_back_to_preceding_definition() {
+        let after = BEFORE.replace("value = 1", "value = 2");
+        assert_eq!(hunk_headers("app.txt", BEFORE, &after), ["@@ -5,3 +5,3 @@ def run(self)"]);
+    }
+
+    #[test]
+    fn change_in_javascript_class_method() {
+        let before = "class A {\n  run() {\n    return 1;\n  }\n}\n";
+        let after = before.replace("return 1", "return 2");
+        let headers = hunk_headers("app.js", before, &after);
+        assert_eq!(headers.len(), 1);
+        assert!(headers[0].ends_with(" A.run"), "{}", headers[0]);
+    }
+}
@@ -0,0 +1,46 @@
+// 文本编码检测：按 BOM 与内容启发式识别编码并解码为 UTF-8，文件系统比较与 Git 比较共用
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+/// 解码后的文本
+pub struct DecodedText {
+    pub text: String,
+    /// 检测到的编码名称，如 `UTF-8`、`GBK`、`Shift_JIS`
+    pub encoding: &'static str,
+}
+
+/// 将字节解码为文本：有 BOM 时按 BOM 解码；否则合法 UTF-8 直接使用，其余按内容推测编码
+///
+/// 无 BOM 却含 NUL 字节、或按推测的编码解码出错时视为二进制内容，返回 `None`
+pub fn decode_text(bytes: &[u8]) -> Option<DecodedText> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return decode_with(encoding, &bytes[bom_len..]);
+    }
+    if bytes.contains(&0) {
+        return None;
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Some(DecodedText {
+            text: text.to_string(),
+            encoding: UTF_8.name(),
+        });
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    decode_with(detector.guess(None, true), bytes)
+}
+
+/// 内容是否以 UTF-16/UTF-8 BOM 开头（UTF-16 文本含 NUL 字节，不能据此判断为二进制）
+pub fn has_text_bom(bytes: &[u8]) -> bool {
+    Encoding::for_bom(bytes).is_some()
+}
+
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> Option<DecodedText> {
+    let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+    Some(DecodedText {
+        text: text.into_owned(),
+        encoding: encoding.name(),
+    })
+}
@@ -1,21 +1,87 @@
-use crate::diff::git_integration::GitIntegration;
+use crate::diff::cache::DiffCache;
+use crate::ast::ASTParser;
+use crate::diff::classify::classify_change;
+use crate::diff::context::{annotate_enclosing_symbols, left_side_symbols, right_side_symbols};
+use crate::diff::semantic::changed_symbols;
+use crate::diff::encoding::{decode_text, has_text_bom, DecodedText};
+use crate::diff::image::{image_diff_info, read_image_info};
+use crate::diff::lfs::{lfs_file_diff, read_lfs_pointer, LfsSide};
+use crate::diff::git_integration::{GitIntegration, GitVersion, EMPTY_TREE_REF};
+use crate::diff::patch::render_patch;
+use crate::diff::report::render_html_report;
+use crate::diff::three_way::three_way_diff;
 use crate::diff::types::*;
+use crate::rules::model::{CompiledPathFilter, PathFilter};
 use anyhow::Result;
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 重命名检测使用的 MinHash 签名长度
+const MINHASH_SIZE: usize = 32;
+
+/// MinHash 估计值低于相似度阈值超过该幅度的候选不再计算精确相似度
+const MINHASH_MARGIN: f32 = 0.2;
+
+/// 二进制文件差异行的内容前缀（`[二进制文件]` / `[二进制文件比较]`）
+pub(crate) const BINARY_MARKER: &str = "[二进制文件";
+/// 读取失败的文件以此开头的单行说明代替差异行
+const READ_ERROR_MARKER: &str = "Error reading file:";
+/// 差异行超过 `max_diff_lines` 被截断时，末尾提示行的内容前缀
+const TRUNCATED_MARKER: &str = "[差异已截断]";
+/// `ignore_regions` 折叠的区域，说明行的内容前缀
+const IGNORED_REGION_MARKER: &str = "[已忽略的区域]";
+
+/// 进度回调，在比较线程上调用
+pub type ProgressCallback = Arc<dyn Fn(&DiffProgress) + Send + Sync>;
 
 /// 高性能差异比较引擎
 pub struct DiffEngine {
     config: ComparisonConfig,
+    cancel: Option<Arc<AtomicBool>>,
+    on_progress: Option<ProgressCallback>,
+    cache: Option<Arc<DiffCache>>,
 }
 
 impl DiffEngine {
     /// 创建新的差异引擎实例
     pub fn new(config: ComparisonConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            cancel: None,
+            on_progress: None,
+            cache: None,
+        }
+    }
+
+    /// 设置取消标记，置位后目录比较尽快停止并返回 [`ComparisonCancelled`]
+    pub fn with_cancel_flag(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// 设置目录比较的进度回调，每完成一个文件调用一次
+    pub fn with_progress(mut self, on_progress: ProgressCallback) -> Self {
+        self.on_progress = Some(on_progress);
+        self
+    }
+
+    /// 设置文件差异缓存，文件系统比较中未变化的文件直接复用缓存的差异（Git 比较不使用缓存）
+    pub fn with_cache(mut self, cache: Arc<DiffCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
     }
 
     /// 执行完整的比较
@@ -25,13 +91,53 @@ impl DiffEngine {
             .unwrap()
             .as_secs();
 
-        let file_diffs = if request.is_git_comparison {
+        let mut file_diffs = if request.is_git_comparison {
             self.git_compare(&request)?
+        } else if request.is_working_tree_comparison {
+            self.working_tree_compare(&request)?
         } else {
             self.file_system_compare(&request)?
         };
+        if self.is_cancelled() {
+            return Err(ComparisonCancelled.into());
+        }
+
+        for diff in &mut file_diffs {
+            diff.change_kind = classify_change(diff, self.config.classify_comment_changes);
+        }
+        if self.config.annotate_enclosing_symbols || self.config.detect_changed_symbols {
+            file_diffs
+                .par_iter_mut()
+                .for_each_init(ASTParser::new, |parser, diff| self.analyze_symbols(parser, diff));
+        }
+
+        // 统计基于完整的差异行，与显示模式无关
+        let mut summary = calculate_summary(&file_diffs);
+        let unlisted = self.count_unlisted_unchanged(&request, &file_diffs)?;
+        summary.files_unchanged += unlisted;
+        summary.files_compared += unlisted;
+
+        let context = self.config.context_lines as usize;
+        for diff in &mut file_diffs {
+            pair_replacements(&mut diff.lines);
+            truncate_diff_lines(diff, self.config.max_diff_lines as usize, context);
+            if self.config.view_mode != DiffViewMode::SideBySide {
+                diff.hunks = build_hunks(&diff.lines, context);
+                diff.lines = Vec::new();
+            }
+        }
 
-        let summary = self.calculate_summary(&file_diffs);
+        let (left_commit, right_commit, merge_base) = self.git_commits(&request)?;
+
+        let changed_symbols = file_diffs
+            .iter()
+            .flat_map(|diff| {
+                diff.changed_symbols.iter().map(|symbol| FileChangedSymbol {
+                    path: diff.path.clone(),
+                    symbol: symbol.clone(),
+                })
+            })
+            .collect();
 
         Ok(ComparisonResult {
             source_a: request.source_a,
@@ -39,9 +145,109 @@ impl DiffEngine {
             comparison_time: start_time as i64,
             file_diffs,
             summary,
+            changed_symbols,
+            left_commit,
+            right_commit,
+            merge_base,
+        })
+    }
+
+    /// 比较单个提交与其父提交：根提交与空树比较，合并提交按 `parent_index` 选择父提交，缺省为第一个父提交
+    pub fn compare_commit(&self, request: CommitComparisonRequest) -> Result<CommitComparisonResult> {
+        let CommitComparisonRequest {
+            repository_path,
+            commit,
+            parent_index,
+            file_paths,
+            config,
+        } = request;
+        let (commit_hash, parent_hash, parent_count) =
+            GitIntegration::new().resolve_commit_parent(&repository_path, &commit, parent_index)?;
+        let note = (parent_count > 1 && parent_index.is_none()).then(|| {
+            format!(
+                "Merge commit has {} parents; compared against the first parent (set parent_index to choose another)",
+                parent_count
+            )
+        });
+        let result = self.compare(ComparisonRequest {
+            source_a: parent_hash.clone(),
+            source_b: commit_hash.clone(),
+            config,
+            is_git_comparison: true,
+            git_params: Some(GitComparisonParams {
+                repository_path,
+                left_ref: parent_hash.clone(),
+                right_ref: commit_hash,
+                file_paths,
+            }),
+            is_working_tree_comparison: false,
+        })?;
+        Ok(CommitComparisonResult {
+            result,
+            parent_hash,
+            parent_count: parent_count as u32,
+            note,
         })
     }
 
+    /// 比较并生成 unified diff 补丁，同时返回比较统计
+    ///
+    /// 补丁需要可应用的原始内容，因此忽略 `ignore_whitespace` / `ignore_case` / `ignore_regions`，
+    /// 上下文行数取自 `context_lines`
+    pub fn generate_patch(&self, request: ComparisonRequest) -> Result<(String, ComparisonSummary)> {
+        let engine = DiffEngine {
+            config: ComparisonConfig {
+                ignore_whitespace: false,
+                ignore_case: false,
+                ignore_regions: Vec::new(),
+                view_mode: DiffViewMode::SideBySide,
+                // 补丁须包含完整的差异
+                max_diff_lines: u32::MAX,
+                ..self.config.clone()
+            },
+            cancel: self.cancel.clone(),
+            on_progress: self.on_progress.clone(),
+            cache: self.cache.clone(),
+        };
+        let result = engine.compare(request)?;
+        let patch = render_patch(&result, self.config.context_lines as usize);
+        Ok((patch, result.summary))
+    }
+
+    /// 比较并将补丁写入 `output_path`，返回比较统计
+    pub fn export_patch(&self, request: ComparisonRequest, output_path: &Path) -> Result<ComparisonSummary> {
+        let (patch, summary) = self.generate_patch(request)?;
+        fs::write(output_path, patch)
+            .map_err(|e| anyhow::anyhow!("Failed to write patch {}: {}", output_path.display(), e))?;
+        Ok(summary)
+    }
+
+    /// 比较并渲染 HTML 差异报告，返回报告内容与比较统计
+    ///
+    /// 比较时保留完整差异行，以便报告折叠未变更区域；布局与上下文行数仍按当前配置
+    pub fn generate_html_report(&self, request: ComparisonRequest) -> Result<(String, ComparisonSummary)> {
+        let engine = DiffEngine {
+            config: ComparisonConfig {
+                view_mode: DiffViewMode::SideBySide,
+                ..self.config.clone()
+            },
+            cancel: self.cancel.clone(),
+            on_progress: self.on_progress.clone(),
+            cache: self.cache.clone(),
+        };
+        let result = engine.compare(request)?;
+        let report = render_html_report(&result, &self.config);
+        Ok((report, result.summary))
+    }
+
+    /// 比较并将 HTML 差异报告写入 `output_path`，返回比较统计
+    pub fn export_diff_html(&self, request: ComparisonRequest, output_path: &Path) -> Result<ComparisonSummary> {
+        let (report, summary) = self.generate_html_report(request)?;
+        fs::write(output_path, report)
+            .map_err(|e| anyhow::anyhow!("Failed to write report {}: {}", output_path.display(), e))?;
+        Ok(summary)
+    }
+
     /// 文件系统比较（比较两个文件或目录）
     fn file_system_compare(&self, request: &ComparisonRequest) -> Result<Vec<FileDiff>> {
         let path_a = Path::new(&request.source_a);
@@ -49,7 +255,7 @@ impl DiffEngine {
 
         if path_a.is_file() && path_b.is_file() {
             // 单文件比较
-            let file_diff = self.compare_files(path_a, path_b)?;
+            let file_diff = self.cached_file_diff(Some(path_a), Some(path_b), || self.compare_files(path_a, path_b))?;
             Ok(vec![file_diff])
         } else if path_a.is_dir() && path_b.is_dir() {
             // 目录比较
@@ -71,8 +277,158 @@ impl DiffEngine {
         }
     }
 
+    /// 比较两段内存中的文本，不读写文件系统
+    ///
+    /// 结果的路径为 `label_b`；两个标识不同时状态为以 `label_a` 为原路径的重命名，便于生成补丁。
+    /// 统计信息由内容生成（无修改时间），比较选项与显示模式的处理与文件比较一致
+    pub fn compare_contents(&self, text_a: &str, text_b: &str, label_a: &str, label_b: &str) -> FileDiff {
+        let (lines_a, lines_b, LineDiff { lines, degraded }) = self.diff_text(text_a, text_b, text_a == text_b);
+        let stats = |text: &str, line_count: usize| FileStats {
+            size: text.len() as u64,
+            line_count: line_count as u32,
+            modified_time: None,
+            content_hash: Some(hash_bytes(text.as_bytes())),
+            encoding: Some("UTF-8".to_string()),
+        };
+        let status = if label_a != label_b {
+            FileStatus::Renamed {
+                old_path: label_a.to_string(),
+                similarity: line_similarity(&lines_a, &lines_b),
+            }
+        } else if lines.iter().all(|line| line.diff_type == DiffType::Equal) {
+            FileStatus::Unchanged
+        } else {
+            FileStatus::Modified
+        };
+
+        let mut diff = FileDiff {
+            path: label_b.to_string(),
+            status,
+            lines,
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded,
+            changed_symbols: Vec::new(),
+            truncated: false,
+            image_info: None,
+            is_lfs: false,
+            original_content: Some(text_a.to_string()),
+            modified_content: Some(text_b.to_string()),
+            left_stats: stats(text_a, lines_a.len()),
+            right_stats: stats(text_b, lines_b.len()),
+        };
+        diff.change_kind = classify_change(&diff, self.config.classify_comment_changes);
+        if self.config.annotate_enclosing_symbols || self.config.detect_changed_symbols {
+            self.analyze_symbols(&mut ASTParser::new(), &mut diff);
+        }
+        pair_replacements(&mut diff.lines);
+        truncate_diff_lines(&mut diff, self.config.max_diff_lines as usize, self.config.context_lines as usize);
+        if self.config.view_mode != DiffViewMode::SideBySide {
+            diff.hunks = build_hunks(&diff.lines, self.config.context_lines as usize);
+            diff.lines = Vec::new();
+        }
+        diff
+    }
+
+    /// 三方比较单个文件：`base` 为共同的基准版本，`a`（我方）与 `b`（对方）分别相对其比较，标出冲突区域
+    ///
+    /// 三个文件都须为文本文件且不超过 `max_file_size`；结果的路径为 `a`
+    pub fn compare_three_way(&self, base: &Path, a: &Path, b: &Path) -> Result<ThreeWayFileDiff> {
+        let read = |path: &Path| -> Result<String> {
+            let oversized = self
+                .is_oversized(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", path.display(), e))?;
+            if oversized {
+                return Err(anyhow::anyhow!(
+                    "File {} exceeds the size limit of {} bytes",
+                    path.display(),
+                    self.config.max_file_size
+                ));
+            }
+            self.read_text_file(path)?
+                .map(|decoded| decoded.text)
+                .ok_or_else(|| anyhow::anyhow!("Binary file cannot be compared line by line: {}", path.display()))
+        };
+        let (base_text, a_text, b_text) = (read(base)?, read(a)?, read(b)?);
+        Ok(self.compare_three_way_contents(&base_text, &a_text, &b_text, &a.to_string_lossy()))
+    }
+
+    /// 三方比较三段内存中的文本，结果的路径为 `label`
+    pub fn compare_three_way_contents(&self, base: &str, a: &str, b: &str, label: &str) -> ThreeWayFileDiff {
+        three_way_diff(base, a, b, label, &self.config)
+    }
+
+    /// 按配置解析文件两侧内容：标注变更所在的符号，并列出变更的函数/方法
+    fn analyze_symbols(&self, parser: &mut ASTParser, diff: &mut FileDiff) {
+        if diff.status == FileStatus::Unchanged {
+            return;
+        }
+        let right = right_side_symbols(parser, diff);
+        if self.config.detect_changed_symbols {
+            let left = left_side_symbols(parser, diff);
+            if left.is_some() || right.is_some() {
+                diff.changed_symbols = changed_symbols(
+                    diff,
+                    left.as_deref().unwrap_or_default(),
+                    right.as_deref().unwrap_or_default(),
+                );
+            }
+        }
+        if self.config.annotate_enclosing_symbols {
+            annotate_enclosing_symbols(diff, right.as_deref());
+        }
+    }
+
+    /// 按配置拆分两段文本并计算行差异，`identical` 为真时跳过差异计算；返回两侧的行与差异
+    fn diff_text(&self, content_a: &str, content_b: &str, identical: bool) -> (Vec<String>, Vec<String>, LineDiff) {
+        let lines_a = split_content_lines(content_a, &self.config);
+        let lines_b = split_content_lines(content_b, &self.config);
+
+        let mut diff = if identical {
+            let lines = lines_a
+                .iter()
+                .enumerate()
+                .map(|(i, line)| DiffLine {
+                    left_line_number: Some(i as u32 + 1),
+                    right_line_number: Some(i as u32 + 1),
+                    diff_type: DiffType::Equal,
+                    content: line.clone(),
+                    is_placeholder: false,
+                    move_id: None,
+                    enclosing_symbol: None,
+                    old_content: None,
+                })
+                .collect();
+            LineDiff { lines, degraded: false }
+        } else {
+            self.compute_line_diff(&lines_a, &lines_b)
+        };
+        mark_trailing_newline_change(&mut diff.lines, content_a, content_b, &lines_b, &self.config);
+        (lines_a, lines_b, diff)
+    }
+
+    /// 启用缓存时经由缓存取得单个文件的差异，`path_a` / `path_b` 为空表示该侧不存在
+    fn cached_file_diff(
+        &self,
+        path_a: Option<&Path>,
+        path_b: Option<&Path>,
+        compute: impl FnOnce() -> Result<FileDiff>,
+    ) -> Result<FileDiff> {
+        match &self.cache {
+            Some(cache) => cache.get_or_compute(path_a, path_b, &self.config, compute),
+            None => compute(),
+        }
+    }
+
     /// 比较两个文件
     fn compare_files(&self, path_a: &Path, path_b: &Path) -> Result<FileDiff> {
+        if let Some(diff) = self.compare_lfs_files(path_a, path_b)? {
+            return Ok(diff);
+        }
+        if self.is_oversized(path_a)? || self.is_oversized(path_b)? {
+            return self.create_oversized_file_diff(&path_b.to_string_lossy(), Some(path_a), Some(path_b));
+        }
+
         // 检查文件是否为二进制文件
         let is_binary_a = self.is_binary_file(path_a)?;
         let is_binary_b = self.is_binary_file(path_b)?;
@@ -83,44 +439,44 @@ impl DiffEngine {
         }
 
         // 文本文件比较
-        let content_a = match self.read_text_file(path_a) {
-            Ok(content) => content,
+        let decoded_a = match self.read_text_file(path_a) {
+            Ok(decoded) => decoded,
             Err(e) => {
                 // 如果读取失败，创建错误记录并返回
                 return self.create_error_file_diff(path_a, path_b, &e);
             }
         };
 
-        let content_b = match self.read_text_file(path_b) {
-            Ok(content) => content,
+        let decoded_b = match self.read_text_file(path_b) {
+            Ok(decoded) => decoded,
             Err(e) => {
                 // 如果读取失败，创建错误记录并返回
                 return self.create_error_file_diff(path_a, path_b, &e);
             }
         };
 
-        let lines_a: Vec<String> = if self.config.ignore_whitespace {
-            content_a
-                .lines()
-                .map(|line| line.trim().to_string())
-                .collect()
-        } else {
-            content_a.lines().map(|line| line.to_string()).collect()
+        // 无法按任何编码解码的一侧按二进制处理
+        let (content_a, encoding_a, content_b, encoding_b) = match (decoded_a, decoded_b) {
+            (Some(a), Some(b)) => (a.text, a.encoding, b.text, b.encoding),
+            (a, b) => return self.compare_binary_files(path_a, path_b, a.is_none(), b.is_none()),
         };
 
-        let lines_b: Vec<String> = if self.config.ignore_whitespace {
-            content_b
-                .lines()
-                .map(|line| line.trim().to_string())
-                .collect()
+        let metadata_a = fs::metadata(path_a)?;
+        let metadata_b = fs::metadata(path_b)?;
+
+        // 大小相同时先比较摘要，内容完全一致则跳过逐行差异计算
+        let (hash_a, hash_b) = if metadata_a.len() == metadata_b.len() {
+            (
+                Some(hash_bytes(content_a.as_bytes())),
+                Some(hash_bytes(content_b.as_bytes())),
+            )
         } else {
-            content_b.lines().map(|line| line.to_string()).collect()
+            (None, None)
         };
 
-        let diff_lines = self.compute_line_diff(&lines_a, &lines_b);
-
-        let metadata_a = fs::metadata(path_a)?;
-        let metadata_b = fs::metadata(path_b)?;
+        let identical = hash_a.is_some() && hash_a == hash_b;
+        let (lines_a, lines_b, LineDiff { lines: diff_lines, degraded }) =
+            self.diff_text(&content_a, &content_b, identical);
 
         let left_stats = FileStats {
             size: metadata_a.len(),
@@ -130,6 +486,8 @@ impl DiffEngine {
                 .ok()
                 .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
                 .map(|d| d.as_secs() as i64),
+            content_hash: hash_a,
+            encoding: Some(encoding_a.to_string()),
         };
 
         let right_stats = FileStats {
@@ -140,6 +498,8 @@ impl DiffEngine {
                 .ok()
                 .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
                 .map(|d| d.as_secs() as i64),
+            content_hash: hash_b,
+            encoding: Some(encoding_b.to_string()),
         };
 
         // 只有当文件不是太大时才包含原始内容，避免内存溢出
@@ -157,6 +517,13 @@ impl DiffEngine {
                 FileStatus::Modified
             },
             lines: diff_lines,
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded,
+            changed_symbols: Vec::new(),
+            truncated: false,
+            image_info: None,
+            is_lfs: false,
             original_content: if include_content {
                 Some(content_a)
             } else {
@@ -176,9 +543,16 @@ impl DiffEngine {
     fn compare_directories(&self, dir_a: &Path, dir_b: &Path) -> Result<Vec<FileDiff>> {
         let mut file_diffs = Vec::new();
 
-        // 获取两个目录中的所有文件
-        let files_a = self.get_files_recursive(dir_a)?;
-        let files_b = self.get_files_recursive(dir_b)?;
+        let exclude = PathFilter {
+            include: Vec::new(),
+            exclude: self.config.exclude_patterns.clone(),
+        }
+        .compile()
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+        // 获取两个目录中的所有文件，排除的路径在配对前即被过滤
+        let files_a = self.get_files_recursive(dir_a, &exclude)?;
+        let files_b = self.get_files_recursive(dir_b, &exclude)?;
 
         let files_a_set: HashMap<String, PathBuf> = files_a
             .into_iter()
@@ -204,33 +578,52 @@ impl DiffEngine {
             .into_iter()
             .collect();
 
-        // 并行处理所有文件
+        let files_paired = all_paths.len();
+        let files_processed = AtomicUsize::new(0);
+        self.report_progress(files_paired, 0, None);
+
+        // 并行处理所有文件，取消后剩余任务直接跳过
         let results: Vec<Result<FileDiff>> = all_paths
             .into_par_iter()
             .map(|relative_path| {
-                match (
-                    files_a_set.get(&relative_path),
-                    files_b_set.get(&relative_path),
-                ) {
-                    (Some(path_a), Some(path_b)) => {
-                        // 两个目录都有的文件，比较内容
-                        self.compare_files(path_a, path_b)
-                    }
-                    (Some(path_a), None) => {
-                        // 只在左侧存在的文件（删除）
-                        self.create_deleted_file_diff(&relative_path, path_a)
-                    }
-                    (None, Some(path_b)) => {
-                        // 只在右侧存在的文件（新增）
-                        self.create_added_file_diff(&relative_path, path_b)
-                    }
-                    (None, None) => {
-                        unreachable!("File path not found in either directory")
-                    }
+                if self.is_cancelled() {
+                    return Err(ComparisonCancelled.into());
                 }
+                let path_a = files_a_set.get(&relative_path).map(PathBuf::as_path);
+                let path_b = files_b_set.get(&relative_path).map(PathBuf::as_path);
+                let result = self
+                    .cached_file_diff(path_a, path_b, || match (path_a, path_b) {
+                        (Some(path_a), Some(path_b)) => {
+                            // 两个目录都有的文件，比较内容
+                            self.compare_files(path_a, path_b)
+                        }
+                        (Some(path_a), None) => {
+                            // 只在左侧存在的文件（删除）
+                            self.create_deleted_file_diff(&relative_path, path_a)
+                        }
+                        (None, Some(path_b)) => {
+                            // 只在右侧存在的文件（新增）
+                            self.create_added_file_diff(&relative_path, path_b)
+                        }
+                        (None, None) => {
+                            unreachable!("File path not found in either directory")
+                        }
+                    })
+                    .map(|mut diff| {
+                        // 缓存按绝对路径命中，显示路径取本次比较的相对路径
+                        diff.path = relative_path.clone();
+                        diff
+                    });
+                let processed = files_processed.fetch_add(1, Ordering::Relaxed) + 1;
+                self.report_progress(files_paired, processed, Some(relative_path));
+                result
             })
             .collect();
 
+        if self.is_cancelled() {
+            return Err(ComparisonCancelled.into());
+        }
+
         // 分离成功的结果和错误
         let mut diffs = Vec::new();
         // let mut skipped_files = Vec::new();
@@ -244,81 +637,66 @@ impl DiffEngine {
             }
         }
 
-        // 如果启用了重命名检测
+        // 如果启用了重命名检测（同时检测复制）
         if self.config.detect_renames {
             self.detect_renames(&mut diffs);
+            self.detect_copies(&mut diffs);
         }
 
         file_diffs.extend(diffs);
         Ok(file_diffs)
     }
 
-    /// 计算行级别的差异 (使用 similar crate 优化)
-    fn compute_line_diff(&self, lines_a: &[String], lines_b: &[String]) -> Vec<DiffLine> {
-        use similar::{Algorithm, ChangeTag, TextDiff};
-
-        let text_a = lines_a.join("\n");
-        let text_b = lines_b.join("\n");
-
-        let diff = TextDiff::configure()
-            .algorithm(Algorithm::Myers) // Myers is standard, Patience is cleaner but slower
-            .diff_lines(&text_a, &text_b);
-
-        let mut result = Vec::new();
-        let mut left_line_num = 1u32;
-        let mut right_line_num = 1u32;
-
-        for change in diff.iter_all_changes() {
-            let content = change.value().trim_end_matches('\n').to_string();
-
-            match change.tag() {
-                ChangeTag::Equal => {
-                    result.push(DiffLine {
-                        left_line_number: Some(left_line_num),
-                        right_line_number: Some(right_line_num),
-                        diff_type: DiffType::Equal,
-                        content,
-                        is_placeholder: false,
-                    });
-                    left_line_num += 1;
-                    right_line_num += 1;
-                }
-                ChangeTag::Delete => {
-                    result.push(DiffLine {
-                        left_line_number: Some(left_line_num),
-                        right_line_number: None,
-                        diff_type: DiffType::Delete,
-                        content,
-                        is_placeholder: false,
-                    });
-                    left_line_num += 1;
-                }
-                ChangeTag::Insert => {
-                    result.push(DiffLine {
-                        left_line_number: None,
-                        right_line_number: Some(right_line_num),
-                        diff_type: DiffType::Insert,
-                        content,
-                        is_placeholder: false,
-                    });
-                    right_line_num += 1;
-                }
-            }
+    fn report_progress(&self, files_paired: usize, files_processed: usize, current_path: Option<String>) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&DiffProgress {
+                files_paired,
+                files_processed,
+                current_path,
+            });
         }
+    }
 
-        result
+    /// 计算行级别的差异 (使用 similar crate 优化)，折叠 `ignore_regions` 标出的区域，并标记文件内移动的代码块
+    pub fn compute_line_diff(&self, lines_a: &[String], lines_b: &[String]) -> LineDiff {
+        let mut diff = line_diff(lines_a, lines_b, &self.config);
+        collapse_ignored_regions(&mut diff.lines, lines_a, lines_b, &self.config.ignore_regions);
+        if !diff.degraded {
+            detect_moved_blocks(&mut diff.lines, self.config.min_moved_lines as usize, self.config.ignore_case);
+        }
+        diff
     }
 
-    /// 递归获取目录中的所有文件
-    fn get_files_recursive(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+    /// 递归获取目录中的所有文件，跳过匹配排除 glob 的文件和目录
+    ///
+    /// 启用 `respect_gitignore` 时使用 ignore 遍历，遵循 .gitignore / .ignore（不要求是 Git 仓库），
+    /// 并跳过 .git 目录；隐藏文件仍参与比较
+    fn get_files_recursive(&self, dir: &Path, exclude: &CompiledPathFilter) -> Result<Vec<PathBuf>> {
         let mut files = Vec::new();
-
-        for entry in walkdir::WalkDir::new(dir)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_file() {
-                files.push(entry.path().to_path_buf());
+        if self.config.respect_gitignore {
+            let root = dir.to_path_buf();
+            let exclude = exclude.clone();
+            let walker = ignore::WalkBuilder::new(dir)
+                .hidden(false)
+                .require_git(false)
+                .filter_entry(move |entry| {
+                    entry.file_name() != ".git" && !is_path_excluded(&root, &exclude, entry.path())
+                })
+                .build();
+            for entry in walker.flatten() {
+                if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                    files.push(entry.into_path());
+                }
+            }
+        } else {
+            for entry in walkdir::WalkDir::new(dir)
+                .into_iter()
+                .filter_entry(|entry| !is_path_excluded(dir, exclude, entry.path()))
+                .filter_map(|e| e.ok())
+            {
+                if entry.file_type().is_file() {
+                    files.push(entry.path().to_path_buf());
+                }
             }
         }
 
@@ -327,42 +705,23 @@ impl DiffEngine {
 
     /// 创建删除文件的差异记录
     fn create_deleted_file_diff(&self, relative_path: &str, path: &Path) -> Result<FileDiff> {
+        if let Some(pointer) = read_lfs_pointer(path) {
+            let side = LfsSide::from_pointer(pointer, lfs_objects_dir(path).as_deref());
+            return Ok(lfs_file_diff(relative_path.to_string(), FileStatus::Deleted, Some(&side), None));
+        }
         let metadata = fs::metadata(path)?;
-        let is_binary = self.is_binary_file(path)?;
-
-        if is_binary {
-            // 二进制文件的删除记录
-            Ok(FileDiff {
-                path: relative_path.to_string(),
-                status: FileStatus::Deleted,
-                lines: vec![DiffLine {
-                    left_line_number: Some(1),
-                    right_line_number: None,
-                    diff_type: DiffType::Delete,
-                    content: format!("[二进制文件] 大小: {} 字节", metadata.len()),
-                    is_placeholder: false,
-                }],
-                original_content: None,
-                modified_content: None,
-                left_stats: FileStats {
-                    size: metadata.len(),
-                    line_count: 1,
-                    modified_time: metadata
-                        .modified()
-                        .ok()
-                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-                        .map(|d| d.as_secs() as i64),
-                },
-                right_stats: FileStats {
-                    size: 0,
-                    line_count: 0,
-                    modified_time: None,
-                },
-            })
+        if metadata.len() > self.config.max_file_size {
+            return self.create_oversized_file_diff(relative_path, Some(path), None);
+        }
+        let decoded = if self.is_binary_file(path)? {
+            None
         } else {
+            self.read_text_file(path)?
+        };
+
+        if let Some(DecodedText { text: content, encoding }) = decoded {
             // 文本文件的删除记录
-            let content = self.read_text_file(path)?;
-            let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
+            let lines = split_content_lines(&content, &self.config);
             let line_count = lines.len();
 
             let diff_lines: Vec<DiffLine> = lines
@@ -374,6 +733,9 @@ impl DiffEngine {
                     diff_type: DiffType::Delete,
                     content: line,
                     is_placeholder: false,
+                    move_id: None,
+                    enclosing_symbol: None,
+                    old_content: None,
                 })
                 .collect();
 
@@ -381,6 +743,13 @@ impl DiffEngine {
                 path: relative_path.to_string(),
                 status: FileStatus::Deleted,
                 lines: diff_lines,
+                hunks: Vec::new(),
+                change_kind: None,
+                degraded: false,
+                changed_symbols: Vec::new(),
+                truncated: false,
+                image_info: None,
+                is_lfs: false,
                 original_content: Some(content),
                 modified_content: None,
                 left_stats: FileStats {
@@ -391,41 +760,42 @@ impl DiffEngine {
                         .ok()
                         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
                         .map(|d| d.as_secs() as i64),
+                    content_hash: None,
+                    encoding: Some(encoding.to_string()),
                 },
                 right_stats: FileStats {
                     size: 0,
                     line_count: 0,
                     modified_time: None,
+                    content_hash: None,
+                    encoding: None,
                 },
             })
-        }
-    }
-
-    /// 创建新增文件的差异记录
-    fn create_added_file_diff(&self, relative_path: &str, path: &Path) -> Result<FileDiff> {
-        let metadata = fs::metadata(path)?;
-        let is_binary = self.is_binary_file(path)?;
-
-        if is_binary {
-            // 二进制文件的新增记录
+        } else {
+            // 二进制文件的删除记录
             Ok(FileDiff {
                 path: relative_path.to_string(),
-                status: FileStatus::Added,
+                status: FileStatus::Deleted,
                 lines: vec![DiffLine {
-                    left_line_number: None,
-                    right_line_number: Some(1),
-                    diff_type: DiffType::Insert,
+                    left_line_number: Some(1),
+                    right_line_number: None,
+                    diff_type: DiffType::Delete,
                     content: format!("[二进制文件] 大小: {} 字节", metadata.len()),
                     is_placeholder: false,
+                    move_id: None,
+                    enclosing_symbol: None,
+                    old_content: None,
                 }],
+                hunks: Vec::new(),
+                change_kind: None,
+                degraded: false,
+                changed_symbols: Vec::new(),
+                truncated: false,
+                image_info: image_diff_info(read_image_info(path), None, metadata.len(), 0),
+                is_lfs: false,
                 original_content: None,
                 modified_content: None,
                 left_stats: FileStats {
-                    size: 0,
-                    line_count: 0,
-                    modified_time: None,
-                },
-                right_stats: FileStats {
                     size: metadata.len(),
                     line_count: 1,
                     modified_time: metadata
@@ -433,12 +803,39 @@ impl DiffEngine {
                         .ok()
                         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
                         .map(|d| d.as_secs() as i64),
+                    content_hash: None,
+                    encoding: None,
+                },
+                right_stats: FileStats {
+                    size: 0,
+                    line_count: 0,
+                    modified_time: None,
+                    content_hash: None,
+                    encoding: None,
                 },
             })
+        }
+    }
+
+    /// 创建新增文件的差异记录
+    fn create_added_file_diff(&self, relative_path: &str, path: &Path) -> Result<FileDiff> {
+        if let Some(pointer) = read_lfs_pointer(path) {
+            let side = LfsSide::from_pointer(pointer, lfs_objects_dir(path).as_deref());
+            return Ok(lfs_file_diff(relative_path.to_string(), FileStatus::Added, None, Some(&side)));
+        }
+        let metadata = fs::metadata(path)?;
+        if metadata.len() > self.config.max_file_size {
+            return self.create_oversized_file_diff(relative_path, None, Some(path));
+        }
+        let decoded = if self.is_binary_file(path)? {
+            None
         } else {
+            self.read_text_file(path)?
+        };
+
+        if let Some(DecodedText { text: content, encoding }) = decoded {
             // 文本文件的新增记录
-            let content = self.read_text_file(path)?;
-            let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
+            let lines = split_content_lines(&content, &self.config);
             let line_count = lines.len();
 
             let diff_lines: Vec<DiffLine> = lines
@@ -450,6 +847,9 @@ impl DiffEngine {
                     diff_type: DiffType::Insert,
                     content: line,
                     is_placeholder: false,
+                    move_id: None,
+                    enclosing_symbol: None,
+                    old_content: None,
                 })
                 .collect();
 
@@ -457,12 +857,21 @@ impl DiffEngine {
                 path: relative_path.to_string(),
                 status: FileStatus::Added,
                 lines: diff_lines,
+                hunks: Vec::new(),
+                change_kind: None,
+                degraded: false,
+                changed_symbols: Vec::new(),
+                truncated: false,
+                image_info: None,
+                is_lfs: false,
                 original_content: None,
                 modified_content: Some(content),
                 left_stats: FileStats {
                     size: 0,
                     line_count: 0,
                     modified_time: None,
+                    content_hash: None,
+                    encoding: None,
                 },
                 right_stats: FileStats {
                     size: metadata.len(),
@@ -472,120 +881,179 @@ impl DiffEngine {
                         .ok()
                         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
                         .map(|d| d.as_secs() as i64),
+                    content_hash: None,
+                    encoding: Some(encoding.to_string()),
+                },
+            })
+        } else {
+            // 二进制文件的新增记录
+            Ok(FileDiff {
+                path: relative_path.to_string(),
+                status: FileStatus::Added,
+                lines: vec![DiffLine {
+                    left_line_number: None,
+                    right_line_number: Some(1),
+                    diff_type: DiffType::Insert,
+                    content: format!("[二进制文件] 大小: {} 字节", metadata.len()),
+                    is_placeholder: false,
+                    move_id: None,
+                    enclosing_symbol: None,
+                    old_content: None,
+                }],
+                hunks: Vec::new(),
+                change_kind: None,
+                degraded: false,
+                changed_symbols: Vec::new(),
+                truncated: false,
+                image_info: image_diff_info(None, read_image_info(path), 0, metadata.len()),
+                is_lfs: false,
+                original_content: None,
+                modified_content: None,
+                left_stats: FileStats {
+                    size: 0,
+                    line_count: 0,
+                    modified_time: None,
+                    content_hash: None,
+                    encoding: None,
+                },
+                right_stats: FileStats {
+                    size: metadata.len(),
+                    line_count: 1,
+                    modified_time: metadata
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map(|d| d.as_secs() as i64),
+                    content_hash: None,
+                    encoding: None,
                 },
             })
         }
     }
 
-    /// 检测文件重命名 (优化版)
+    /// 检测文件重命名
+    ///
+    /// 内容完全相同的文件按哈希直接配对；其余文件只与大小分段相近的候选比较，
+    /// 先用 MinHash 估计值筛掉明显不相似的候选，再计算行集合的精确相似度
     fn detect_renames(&self, diffs: &mut Vec<FileDiff>) {
-        // 先收集所有的信息
-        // 使用索引来避免借用问题
-        let mut added_indices: Vec<usize> = Vec::new();
-        let mut deleted_indices: Vec<usize> = Vec::new();
-
-        for (i, diff) in diffs.iter().enumerate() {
-            if matches!(diff.status, FileStatus::Added) {
-                added_indices.push(i);
-            } else if matches!(diff.status, FileStatus::Deleted) {
-                deleted_indices.push(i);
-            }
-        }
-
-        let mut rename_mappings: Vec<(usize, String)> = Vec::new();
-
-        // 检查重命名
-        // 优化：首先检查文件大小是否相近
-        for &add_idx in &added_indices {
-            let added_size = diffs[add_idx].right_stats.size;
-
-            for &del_idx in &deleted_indices {
-                let deleted_size = diffs[del_idx].left_stats.size;
-
-                // 如果大小差异超过 20%，则认为不太可能是重命名（快速过滤）
-                let size_diff = (added_size as i64 - deleted_size as i64).abs();
-                let max_size = std::cmp::max(added_size, deleted_size);
-
-                if max_size > 0 && (size_diff as f32 / max_size as f32) > 0.2 {
-                    continue;
-                }
-
-                // 计算内容相似度
-                // 注意：这里需要访问 lines，但 lines 已经被借用了
-                // 由于 Rust 的借用规则，我们需要小心
-                // 这里我们通过索引访问
-                let similarity =
-                    self.calculate_similarity(&diffs[del_idx].lines, &diffs[add_idx].lines);
+        let threshold = self.config.rename_similarity_threshold;
+        let signatures: Vec<Option<ContentSignature>> = diffs
+            .par_iter()
+            .map(|diff| match diff.status {
+                FileStatus::Added => ContentSignature::new(diff, |_| true, diff.right_stats.size),
+                FileStatus::Deleted => ContentSignature::new(diff, |_| true, diff.left_stats.size),
+                _ => None,
+            })
+            .collect();
 
-                if similarity >= self.config.rename_similarity_threshold {
-                    rename_mappings.push((add_idx, diffs[del_idx].path.clone()));
-                    break; // 找到一个匹配后就跳过当前 added 文件
+        let deleted = SignatureIndex::new(&signatures, |i| matches!(diffs[i].status, FileStatus::Deleted));
+        let mut used: HashSet<usize> = HashSet::new();
+        let mut rename_mappings: Vec<(usize, usize, f32)> = Vec::new();
+        let mut pending: Vec<(usize, &ContentSignature)> = Vec::new();
+        for (add_idx, signature) in added_signatures(diffs, &signatures) {
+            let exact = deleted
+                .by_hash
+                .get(&signature.content_hash)
+                .and_then(|candidates| candidates.iter().find(|i| !used.contains(*i)));
+            match exact {
+                Some(&del_idx) => {
+                    used.insert(del_idx);
+                    rename_mappings.push((add_idx, del_idx, 1.0));
                 }
+                None => pending.push((add_idx, signature)),
             }
         }
 
-        // 应用重命名标记
-        for (new_idx, old_path) in &rename_mappings {
-            if let Some(diff) = diffs.get_mut(*new_idx) {
-                diff.status = FileStatus::Renamed {
-                    old_path: old_path.clone(),
-                };
+        // 并行计算每个新增文件的候选（按相似度降序），再依次贪心配对
+        let candidates: Vec<(usize, Vec<(usize, f32)>)> = pending
+            .par_iter()
+            .map(|&(add_idx, signature)| (add_idx, deleted.similar(signature, &signatures, threshold)))
+            .collect();
+        for (add_idx, matches) in candidates {
+            if let Some(&(del_idx, similarity)) = matches.iter().find(|(i, _)| !used.contains(i)) {
+                used.insert(del_idx);
+                rename_mappings.push((add_idx, del_idx, similarity));
             }
         }
 
-        // 收集要删除的文件路径（被重命名的文件）
-        let paths_to_remove: std::collections::HashSet<String> = rename_mappings
-            .iter()
-            .map(|(_, old_path)| old_path.clone())
-            .collect();
+        // 应用重命名标记，并将差异改为旧文件与新文件之间的实际变更
+        for &(new_idx, old_idx, similarity) in &rename_mappings {
+            let old_path = diffs[old_idx].path.clone();
+            self.rebase_added_diff(diffs, new_idx, old_idx, FileStatus::Renamed { old_path, similarity });
+        }
 
         // 移除被重命名的删除文件
-        diffs.retain(|diff| {
-            if matches!(diff.status, FileStatus::Deleted) {
-                !paths_to_remove.contains(&diff.path)
-            } else {
-                true
-            }
+        let mut index = 0;
+        diffs.retain(|_| {
+            let keep = !used.contains(&index);
+            index += 1;
+            keep
         });
     }
 
-    /// 计算两个文件行序列的相似度
-    fn calculate_similarity(&self, lines_a: &[DiffLine], lines_b: &[DiffLine]) -> f32 {
-        if lines_a.is_empty() || lines_b.is_empty() {
-            return 0.0;
-        }
-
-        // 跳过二进制文件的相似度比较
-        if lines_a
-            .iter()
-            .any(|line| line.content.starts_with("[二进制文件]"))
-            || lines_b
-                .iter()
-                .any(|line| line.content.starts_with("[二进制文件]"))
-        {
-            return 0.0;
-        }
+    /// 检测文件复制：内容与左侧仍然存在（未变更或修改）的文件相近的新增文件标记为复制
+    ///
+    /// 同一源文件可以被多次复制；匹配方式与重命名检测相同，在重命名检测之后执行
+    fn detect_copies(&self, diffs: &mut [FileDiff]) {
+        let threshold = self.config.rename_similarity_threshold;
+        let signatures: Vec<Option<ContentSignature>> = diffs
+            .par_iter()
+            .map(|diff| match diff.status {
+                FileStatus::Added => ContentSignature::new(diff, |_| true, diff.right_stats.size),
+                // 源文件只取左侧内容
+                FileStatus::Unchanged | FileStatus::Modified => ContentSignature::new(
+                    diff,
+                    |line| line.left_line_number.is_some(),
+                    diff.left_stats.size,
+                ),
+                _ => None,
+            })
+            .collect();
 
-        let set_a: std::collections::HashSet<&str> = lines_a
-            .iter()
-            .filter(|line| !line.content.starts_with("[二进制文件]"))
-            .map(|line| line.content.trim())
+        let sources = SignatureIndex::new(&signatures, |i| {
+            matches!(diffs[i].status, FileStatus::Unchanged | FileStatus::Modified)
+        });
+        let copies: Vec<(usize, usize, f32)> = added_signatures(diffs, &signatures)
+            .par_iter()
+            .filter_map(|&(add_idx, signature)| {
+                if let Some(&source_idx) = sources
+                    .by_hash
+                    .get(&signature.content_hash)
+                    .and_then(|candidates| candidates.first())
+                {
+                    return Some((add_idx, source_idx, 1.0));
+                }
+                let (source_idx, similarity) = *sources.similar(signature, &signatures, threshold).first()?;
+                Some((add_idx, source_idx, similarity))
+            })
             .collect();
 
-        let set_b: std::collections::HashSet<&str> = lines_b
+        for (new_idx, source_idx, similarity) in copies {
+            let source_path = diffs[source_idx].path.clone();
+            self.rebase_added_diff(diffs, new_idx, source_idx, FileStatus::Copied { source_path, similarity });
+        }
+    }
+
+    /// 将新增文件的差异改为相对 `base_idx` 左侧内容的变更，并设置状态
+    fn rebase_added_diff(&self, diffs: &mut [FileDiff], new_idx: usize, base_idx: usize, status: FileStatus) {
+        let base = &diffs[base_idx];
+        let old_lines: Vec<String> = base
+            .lines
             .iter()
-            .filter(|line| !line.content.starts_with("[二进制文件]"))
-            .map(|line| line.content.trim())
+            .filter(|line| line.left_line_number.is_some())
+            .map(|line| line.content.clone())
             .collect();
-
-        let intersection = set_a.intersection(&set_b).count();
-        let union = set_a.union(&set_b).count();
-
-        if union == 0 {
-            1.0
-        } else {
-            intersection as f32 / union as f32
-        }
+        let original_content = base.original_content.clone();
+        let left_stats = base.left_stats.clone();
+
+        let diff = &mut diffs[new_idx];
+        let new_lines: Vec<String> = diff.lines.iter().map(|line| line.content.clone()).collect();
+        diff.status = status;
+        let line_diff = self.compute_line_diff(&old_lines, &new_lines);
+        diff.lines = line_diff.lines;
+        diff.degraded = line_diff.degraded;
+        diff.original_content = original_content;
+        diff.left_stats = left_stats;
     }
 
     /// Git比较实现
@@ -598,6 +1066,205 @@ impl DiffEngine {
         }
     }
 
+    /// Git 比较两侧的提交与合并基础；暂存区、工作目录与空树一侧没有提交，也不计算合并基础
+    fn git_commits(&self, request: &ComparisonRequest) -> Result<(Option<CommitInfo>, Option<CommitInfo>, Option<String>)> {
+        let Some(params) = request.git_params.as_ref().filter(|_| request.is_git_comparison) else {
+            return Ok((None, None, None));
+        };
+        let git = GitIntegration::new();
+        let commit = |reference: &str| match GitVersion::parse(reference) {
+            GitVersion::Commit(EMPTY_TREE_REF) => Ok(None),
+            GitVersion::Commit(commit_ref) => git.get_commit_info(&params.repository_path, commit_ref).map(Some),
+            _ => Ok(None),
+        };
+        let left = commit(&params.left_ref)?;
+        let right = commit(&params.right_ref)?;
+        let merge_base = match (&left, &right) {
+            (Some(left), Some(right)) => git.merge_base(&params.repository_path, &left.hash, &right.hash)?,
+            _ => None,
+        };
+        Ok((left, right, merge_base))
+    }
+
+    /// Git 与工作目录比较只列出有变化的文件，此处统计引用中未列出的文件数，作为未变化的文件计入统计
+    fn count_unlisted_unchanged(&self, request: &ComparisonRequest, diffs: &[FileDiff]) -> Result<u32> {
+        let git = GitIntegration::new();
+        let files = if request.is_git_comparison {
+            let Some(params) = &request.git_params else {
+                return Ok(0);
+            };
+            git.files_at_commit(&git.repository_root(Path::new(&params.repository_path))?, &params.right_ref, None)?
+                .into_iter()
+                .filter(|file| git.matches_file_paths(file, &params.file_paths))
+                .collect()
+        } else if request.is_working_tree_comparison {
+            let scope = Path::new(&request.source_b);
+            git.files_at_commit(&git.repository_root(scope)?, &request.source_a, Some(scope))?
+        } else {
+            Vec::new()
+        };
+
+        let listed: HashSet<&str> = diffs.iter().map(|diff| diff.path.as_str()).collect();
+        Ok(files.iter().filter(|file| !listed.contains(file.as_str())).count() as u32)
+    }
+
+    /// 工作目录与 Git 引用比较：左侧为 `source_a` 引用中的内容，右侧为 `source_b` 目录下磁盘上的文件
+    ///
+    /// 只比较相对引用有变化的已跟踪文件与未跟踪文件，按相对仓库根目录的路径配对；
+    /// 左侧内容从引用中逐个读出写入临时目录（保留相对路径以便按扩展名识别），再按文件比较
+    fn working_tree_compare(&self, request: &ComparisonRequest) -> Result<Vec<FileDiff>> {
+        let git = GitIntegration::new();
+        let repo_root = git.repository_root(Path::new(&request.source_b))?;
+        let changed = git.working_tree_changes(&repo_root, &request.source_a, Path::new(&request.source_b))?;
+        let commit_time = git.get_commit_time(&repo_root, &request.source_a).ok();
+        let scratch = std::env::temp_dir().join(format!("deepaudit-worktree-{}", uuid::Uuid::new_v4()));
+
+        let files_paired = changed.len();
+        let files_processed = AtomicUsize::new(0);
+        self.report_progress(files_paired, 0, None);
+
+        let results: Vec<Result<FileDiff>> = changed
+            .into_par_iter()
+            .map(|relative_path| {
+                if self.is_cancelled() {
+                    return Err(ComparisonCancelled.into());
+                }
+                let result = self.compare_with_commit(&git, &repo_root, &scratch, &request.source_a, commit_time, &relative_path);
+                let processed = files_processed.fetch_add(1, Ordering::Relaxed) + 1;
+                self.report_progress(files_paired, processed, Some(relative_path));
+                result
+            })
+            .collect();
+        let _ = fs::remove_dir_all(&scratch);
+
+        if self.is_cancelled() {
+            return Err(ComparisonCancelled.into());
+        }
+        let mut diffs = results.into_iter().collect::<Result<Vec<_>>>()?;
+        if self.config.detect_renames {
+            self.detect_renames(&mut diffs);
+            self.detect_copies(&mut diffs);
+        }
+        Ok(diffs)
+    }
+
+    /// 比较单个文件在 `commit_ref` 中的内容与工作目录中的内容，引用中的内容写入 `scratch` 下的同名文件；
+    /// 左侧的修改时间取提交时间
+    fn compare_with_commit(
+        &self,
+        git: &GitIntegration,
+        repo_root: &Path,
+        scratch: &Path,
+        commit_ref: &str,
+        commit_time: Option<i64>,
+        relative_path: &str,
+    ) -> Result<FileDiff> {
+        let disk_path = repo_root.join(relative_path);
+        let left_path = match git.blob_at_commit(repo_root, relative_path, commit_ref)? {
+            Some(blob) => {
+                let path = scratch.join(relative_path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, blob)?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        let in_commit = left_path.is_some();
+        let mut diff = match (left_path, disk_path.is_file()) {
+            (Some(left_path), true) => self.compare_files(&left_path, &disk_path)?,
+            (Some(left_path), false) => self.create_deleted_file_diff(relative_path, &left_path)?,
+            (None, true) => self.create_added_file_diff(relative_path, &disk_path)?,
+            (None, false) => {
+                return Err(anyhow::anyhow!("File {} not found in {} or working tree", relative_path, commit_ref))
+            }
+        };
+        diff.path = relative_path.to_string();
+        if in_commit {
+            diff.left_stats.modified_time = commit_time;
+        }
+        Ok(diff)
+    }
+
+    /// 文件大小是否超过 `max_file_size`
+    fn is_oversized(&self, path: &Path) -> Result<bool> {
+        Ok(fs::metadata(path)?.len() > self.config.max_file_size)
+    }
+
+    /// 超过大小上限的文件不读取内容，按 SHA-256 摘要判断是否变化；`path_a` / `path_b` 为空表示该侧不存在
+    fn create_oversized_file_diff(
+        &self,
+        relative_path: &str,
+        path_a: Option<&Path>,
+        path_b: Option<&Path>,
+    ) -> Result<FileDiff> {
+        let stats = |path: Option<&Path>| -> Result<FileStats> {
+            let Some(path) = path else {
+                return Ok(FileStats {
+                    size: 0,
+                    line_count: 0,
+                    modified_time: None,
+                    content_hash: None,
+                    encoding: None,
+                });
+            };
+            let metadata = fs::metadata(path)?;
+            Ok(FileStats {
+                size: metadata.len(),
+                line_count: 0,
+                modified_time: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+                content_hash: Some(hash_file(path)?),
+                encoding: None,
+            })
+        };
+        let left_stats = stats(path_a)?;
+        let right_stats = stats(path_b)?;
+        let status = match (path_a, path_b) {
+            (Some(_), None) => FileStatus::Deleted,
+            (None, Some(_)) => FileStatus::Added,
+            _ if left_stats.content_hash == right_stats.content_hash => FileStatus::Unchanged,
+            _ => FileStatus::Modified,
+        };
+
+        Ok(FileDiff {
+            path: relative_path.to_string(),
+            status,
+            lines: Vec::new(),
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded: false,
+            changed_symbols: Vec::new(),
+            truncated: true,
+            image_info: None,
+            is_lfs: false,
+            original_content: None,
+            modified_content: None,
+            left_stats,
+            right_stats,
+        })
+    }
+
+    /// 任一侧为 Git LFS 指针文件时按指针记录的对象比较，两侧都不是指针时返回 `None`
+    fn compare_lfs_files(&self, path_a: &Path, path_b: &Path) -> Result<Option<FileDiff>> {
+        if read_lfs_pointer(path_a).is_none() && read_lfs_pointer(path_b).is_none() {
+            return Ok(None);
+        }
+        let left = LfsSide::from_file(path_a, lfs_objects_dir(path_a).as_deref())?;
+        let right = LfsSide::from_file(path_b, lfs_objects_dir(path_b).as_deref())?;
+        Ok(Some(lfs_file_diff(
+            path_b.to_string_lossy().to_string(),
+            FileStatus::Modified,
+            Some(&left),
+            Some(&right),
+        )))
+    }
+
     /// 检查文件是否为二进制文件
     fn is_binary_file(&self, path: &Path) -> Result<bool> {
         // 基于扩展名的快速检查
@@ -621,8 +1288,8 @@ impl DiffEngine {
             let mut buffer = [0; 1024];
             let n = file.read(&mut buffer)?;
             if n > 0 {
-                // 检查是否有 null 字节
-                if buffer[..n].iter().any(|&b| b == 0) {
+                // 检查是否有 null 字节（带 BOM 的 UTF-16 文本除外）
+                if buffer[..n].contains(&0) && !has_text_bom(&buffer[..n]) {
                     return Ok(true);
                 }
             }
@@ -631,10 +1298,11 @@ impl DiffEngine {
         Ok(false)
     }
 
-    /// 读取文本文件内容
-    fn read_text_file(&self, path: &Path) -> Result<String> {
-        fs::read_to_string(path)
-            .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", path.display(), e))
+    /// 读取文本文件内容并按检测到的编码解码，无法解码时返回 `None`
+    fn read_text_file(&self, path: &Path) -> Result<Option<DecodedText>> {
+        let bytes = fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", path.display(), e))?;
+        Ok(decode_text(&bytes))
     }
 
     /// 创建错误记录
@@ -651,70 +1319,51 @@ impl DiffEngine {
                 left_line_number: None,
                 right_line_number: None,
                 diff_type: DiffType::Equal,
-                content: format!("Error reading file: {}", error),
+                content: format!("{} {}", READ_ERROR_MARKER, error),
                 is_placeholder: false,
+                move_id: None,
+                enclosing_symbol: None,
+                old_content: None,
             }],
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded: false,
+            changed_symbols: Vec::new(),
+            truncated: false,
+            image_info: None,
+            is_lfs: false,
             original_content: None,
             modified_content: None,
             left_stats: FileStats {
                 size: 0,
                 line_count: 0,
                 modified_time: None,
+                content_hash: None,
+                encoding: None,
             },
             right_stats: FileStats {
                 size: 0,
                 line_count: 0,
                 modified_time: None,
+                content_hash: None,
+                encoding: None,
             },
         })
     }
 
-    /// 计算汇总信息
-    fn calculate_summary(&self, diffs: &[FileDiff]) -> ComparisonSummary {
-        let mut summary = ComparisonSummary {
-            files_added: 0,
-            files_deleted: 0,
-            files_modified: 0,
-            files_renamed: 0,
-            lines_added: 0,
-            lines_deleted: 0,
-        };
-
-        for diff in diffs {
-            match diff.status {
-                FileStatus::Added => summary.files_added += 1,
-                FileStatus::Deleted => summary.files_deleted += 1,
-                FileStatus::Modified => summary.files_modified += 1,
-                FileStatus::Renamed { .. } => summary.files_renamed += 1,
-                FileStatus::Unchanged => {}
-            }
-
-            for line in &diff.lines {
-                match line.diff_type {
-                    DiffType::Insert => summary.lines_added += 1,
-                    DiffType::Delete => summary.lines_deleted += 1,
-                    _ => {}
-                }
-            }
-        }
-
-        summary
-    }
-
-    /// 比较二进制文件
+    /// 比较二进制文件：按 SHA-256 摘要判断内容是否一致
     fn compare_binary_files(
         &self,
-        _path_a: &Path,
+        path_a: &Path,
         path_b: &Path,
         is_binary_a: bool,
         is_binary_b: bool,
     ) -> Result<FileDiff> {
-        let metadata_a = fs::metadata(_path_a)?;
+        let metadata_a = fs::metadata(path_a)?;
         let metadata_b = fs::metadata(path_b)?;
-
-        // TODO: 比较二进制内容 (MD5 or SHA256)
-        // 这里简单比较大小
-        let modified = metadata_a.len() != metadata_b.len();
+        let hash_a = hash_file(path_a)?;
+        let hash_b = hash_file(path_b)?;
+        let modified = hash_a != hash_b;
 
         Ok(FileDiff {
             path: path_b.to_string_lossy().to_string(),
@@ -726,26 +1375,1086 @@ impl DiffEngine {
             lines: vec![DiffLine {
                 left_line_number: None,
                 right_line_number: None,
-                diff_type: DiffType::Equal,
+                diff_type: if modified {
+                    DiffType::Replace
+                } else {
+                    DiffType::Equal
+                },
                 content: format!(
-                    "[二进制文件比较] {} vs {}",
+                    "[二进制文件比较] {} ({} 字节, sha256:{}) vs {} ({} 字节, sha256:{})",
                     if is_binary_a { "Binary" } else { "Text" },
-                    if is_binary_b { "Binary" } else { "Text" }
+                    metadata_a.len(),
+                    hash_a,
+                    if is_binary_b { "Binary" } else { "Text" },
+                    metadata_b.len(),
+                    hash_b
                 ),
                 is_placeholder: false,
+                move_id: None,
+                enclosing_symbol: None,
+                old_content: None,
             }],
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded: false,
+            changed_symbols: Vec::new(),
+            truncated: false,
+            image_info: image_diff_info(
+                read_image_info(path_a),
+                read_image_info(path_b),
+                metadata_a.len(),
+                metadata_b.len(),
+            ),
+            is_lfs: false,
             original_content: None,
             modified_content: None,
             left_stats: FileStats {
                 size: metadata_a.len(),
                 line_count: 0,
                 modified_time: None,
+                content_hash: Some(hash_a),
+                encoding: None,
             },
             right_stats: FileStats {
                 size: metadata_b.len(),
                 line_count: 0,
                 modified_time: None,
+                content_hash: Some(hash_b),
+                encoding: None,
             },
         })
     }
 }
+
+/// 计算汇总信息
+pub(crate) fn calculate_summary(diffs: &[FileDiff]) -> ComparisonSummary {
+    let mut summary = ComparisonSummary {
+        files_added: 0,
+        files_deleted: 0,
+        files_modified: 0,
+        files_renamed: 0,
+        files_copied: 0,
+        lines_added: 0,
+        lines_deleted: 0,
+        lines_moved: 0,
+        files_line_endings_only: 0,
+        files_whitespace_only: 0,
+        files_comment_only: 0,
+        files_unchanged: 0,
+        files_compared: diffs.len() as u32,
+        files_skipped: 0,
+        bytes_added: 0,
+        bytes_deleted: 0,
+    };
+
+    for diff in diffs {
+        match diff.status {
+            FileStatus::Added => summary.files_added += 1,
+            FileStatus::Deleted => summary.files_deleted += 1,
+            FileStatus::Modified => summary.files_modified += 1,
+            FileStatus::Renamed { .. } => summary.files_renamed += 1,
+            FileStatus::Copied { .. } => summary.files_copied += 1,
+            FileStatus::Unchanged => summary.files_unchanged += 1,
+        }
+        // 二进制与读取失败的文件只有一行说明，超过大小上限的文件没有差异行，均不计入字节数
+        let skipped = (diff.truncated && diff.lines.is_empty())
+            || matches!(diff.lines.as_slice(), [line]
+                if line.content.starts_with(BINARY_MARKER) || line.content.starts_with(READ_ERROR_MARKER));
+        if skipped {
+            summary.files_skipped += 1;
+        }
+        match diff.change_kind {
+            Some(ChangeKind::LineEndingsOnly) => summary.files_line_endings_only += 1,
+            Some(ChangeKind::WhitespaceOnly) => summary.files_whitespace_only += 1,
+            Some(ChangeKind::CommentOnly) => summary.files_comment_only += 1,
+            _ => {}
+        }
+
+        for line in &diff.lines {
+            match line.diff_type {
+                DiffType::Insert => {
+                    summary.lines_added += 1;
+                    if !skipped {
+                        summary.bytes_added += line.content.len() as u64 + 1;
+                    }
+                }
+                DiffType::Delete => {
+                    summary.lines_deleted += 1;
+                    if !skipped {
+                        summary.bytes_deleted += line.content.len() as u64 + 1;
+                    }
+                }
+                DiffType::Moved if line.left_line_number.is_some() => summary.lines_moved += 1,
+                _ => {}
+            }
+        }
+    }
+
+    summary
+}
+
+/// 重命名/复制检测中单个文件的内容签名
+struct ContentSignature {
+    /// 按行顺序计算的内容哈希，用于识别内容完全相同的文件
+    content_hash: u64,
+    /// 去除首尾空白后各行的哈希集合
+    line_hashes: HashSet<u64>,
+    minhash: [u64; MINHASH_SIZE],
+    size: u64,
+}
+
+impl ContentSignature {
+    /// 根据差异中 `include` 选中的行计算签名；二进制文件与空文件不参与检测
+    fn new(diff: &FileDiff, include: impl Fn(&DiffLine) -> bool, size: u64) -> Option<Self> {
+        let lines: Vec<&str> = diff
+            .lines
+            .iter()
+            .filter(|line| include(line))
+            .map(|line| line.content.as_str())
+            .collect();
+        if lines.is_empty() || lines.iter().any(|line| line.starts_with(BINARY_MARKER)) {
+            return None;
+        }
+
+        let mut content_hasher = DefaultHasher::new();
+        let mut line_hashes = HashSet::with_capacity(lines.len());
+        for line in &lines {
+            line.hash(&mut content_hasher);
+            let mut line_hasher = DefaultHasher::new();
+            line.trim().hash(&mut line_hasher);
+            line_hashes.insert(line_hasher.finish());
+        }
+
+        let mut minhash = [u64::MAX; MINHASH_SIZE];
+        for &hash in &line_hashes {
+            for (seed, slot) in minhash.iter_mut().enumerate() {
+                *slot = (*slot).min(mix_hash(hash, seed as u64));
+            }
+        }
+
+        Some(Self {
+            content_hash: content_hasher.finish(),
+            line_hashes,
+            minhash,
+            size,
+        })
+    }
+
+    /// MinHash 估计的相似度
+    fn estimated_similarity(&self, other: &Self) -> f32 {
+        let equal = self
+            .minhash
+            .iter()
+            .zip(&other.minhash)
+            .filter(|(a, b)| a == b)
+            .count();
+        equal as f32 / MINHASH_SIZE as f32
+    }
+
+    /// 行集合的 Jaccard 相似度
+    fn similarity(&self, other: &Self) -> f32 {
+        let intersection = self.line_hashes.intersection(&other.line_hashes).count();
+        let union = self.line_hashes.len() + other.line_hashes.len() - intersection;
+        if union == 0 {
+            1.0
+        } else {
+            intersection as f32 / union as f32
+        }
+    }
+}
+
+/// 两组行去除首尾空白后的集合 Jaccard 相似度，任一侧为空时为 0
+pub(crate) fn line_similarity(lines_a: &[String], lines_b: &[String]) -> f32 {
+    if lines_a.is_empty() || lines_b.is_empty() {
+        return 0.0;
+    }
+    let set_a: HashSet<&str> = lines_a.iter().map(|line| line.trim()).collect();
+    let set_b: HashSet<&str> = lines_b.iter().map(|line| line.trim()).collect();
+    let intersection = set_a.intersection(&set_b).count();
+    intersection as f32 / (set_a.len() + set_b.len() - intersection) as f32
+}
+
+/// 候选文件签名按内容哈希与大小分段建立的索引
+struct SignatureIndex {
+    by_hash: HashMap<u64, Vec<usize>>,
+    by_band: HashMap<i32, Vec<usize>>,
+}
+
+impl SignatureIndex {
+    /// 为 `is_candidate` 选中且有签名的差异建立索引
+    fn new(signatures: &[Option<ContentSignature>], is_candidate: impl Fn(usize) -> bool) -> Self {
+        let mut index = Self {
+            by_hash: HashMap::new(),
+            by_band: HashMap::new(),
+        };
+        for (i, signature) in signatures.iter().enumerate() {
+            let Some(signature) = signature.as_ref().filter(|_| is_candidate(i)) else {
+                continue;
+            };
+            index.by_hash.entry(signature.content_hash).or_default().push(i);
+            index.by_band.entry(size_band(signature.size)).or_default().push(i);
+        }
+        index
+    }
+
+    /// 相似度不低于 `threshold` 的候选，按相似度降序排列
+    ///
+    /// 只比较相同或相邻大小分段的候选，MinHash 估计值明显偏低的候选不计算精确相似度
+    fn similar(
+        &self,
+        signature: &ContentSignature,
+        signatures: &[Option<ContentSignature>],
+        threshold: f32,
+    ) -> Vec<(usize, f32)> {
+        let band = size_band(signature.size);
+        let mut matches: Vec<(usize, f32)> = (band - 1..=band + 1)
+            .filter_map(|b| self.by_band.get(&b))
+            .flatten()
+            .filter_map(|&i| {
+                let candidate = signatures[i].as_ref()?;
+                if !sizes_close(signature.size, candidate.size)
+                    || signature.estimated_similarity(candidate) + MINHASH_MARGIN < threshold
+                {
+                    return None;
+                }
+                let similarity = signature.similarity(candidate);
+                (similarity >= threshold).then_some((i, similarity))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        matches
+    }
+}
+
+/// 有签名的新增文件
+fn added_signatures<'a>(
+    diffs: &[FileDiff],
+    signatures: &'a [Option<ContentSignature>],
+) -> Vec<(usize, &'a ContentSignature)> {
+    signatures
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| matches!(diffs[*i].status, FileStatus::Added))
+        .filter_map(|(i, signature)| Some((i, signature.as_ref()?)))
+        .collect()
+}
+
+/// 为每个 MinHash 槽位派生独立的哈希值（splitmix64）
+fn mix_hash(hash: u64, seed: u64) -> u64 {
+    let mut z = hash ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 文件大小分段，相邻分段相差 20%；大小相差不超过 20% 的文件落在相同或相邻分段
+fn size_band(size: u64) -> i32 {
+    ((size.max(1) as f64).ln() / 1.2f64.ln()).floor() as i32
+}
+
+/// 大小相差不超过 20% 的文件才可能是重命名
+fn sizes_close(a: u64, b: u64) -> bool {
+    let max_size = a.max(b);
+    max_size == 0 || (a.abs_diff(b) as f32 / max_size as f32) <= 0.2
+}
+
+/// `path` 相对 `root` 的路径是否匹配排除 glob（根目录本身不排除）
+fn is_path_excluded(root: &Path, exclude: &CompiledPathFilter, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .is_ok_and(|relative| !relative.as_os_str().is_empty() && exclude.is_excluded(relative))
+}
+
+/// 流式计算文件的 SHA-256 摘要（十六进制小写），不会将整个文件读入内存
+/// 磁盘文件所在仓库的本地 LFS 对象存储目录，不在仓库中时为空
+fn lfs_objects_dir(path: &Path) -> Option<PathBuf> {
+    GitIntegration::new().lfs_objects_dir(path.parent()?)
+}
+
+pub(crate) fn hash_file(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open file {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// 计算内存中内容的 SHA-256 摘要（十六进制小写）
+fn hash_bytes(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// 将文本拆分为比较用的行
+///
+/// `ignore_whitespace` 时去除首尾空白；否则除非 `ignore_line_endings`，保留 CRLF 行尾的 `\r`，
+/// 使仅换行符不同的行显示为变更
+pub(crate) fn split_content_lines(content: &str, config: &ComparisonConfig) -> Vec<String> {
+    let mut lines: Vec<&str> = content.split('\n').collect();
+    if content.is_empty() || content.ends_with('\n') {
+        lines.pop();
+    }
+    lines
+        .into_iter()
+        .map(|line| {
+            if config.ignore_whitespace {
+                line.trim().to_string()
+            } else if config.ignore_line_endings {
+                line.strip_suffix('\r').unwrap_or(line).to_string()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+/// 两侧只有一侧以换行结尾时，将成对的最后一行改为删除 + 插入
+///
+/// `ignore_trailing_newline` 或 `ignore_whitespace` 时不处理；空文件不视为缺少末尾换行
+pub(crate) fn mark_trailing_newline_change(
+    lines: &mut Vec<DiffLine>,
+    content_a: &str,
+    content_b: &str,
+    lines_b: &[String],
+    config: &ComparisonConfig,
+) {
+    if config.ignore_trailing_newline
+        || config.ignore_whitespace
+        || content_a.is_empty()
+        || content_b.is_empty()
+        || content_a.ends_with('\n') == content_b.ends_with('\n')
+    {
+        return;
+    }
+    let last_a = lines.iter().filter_map(|line| line.left_line_number).max();
+    let last_b = lines_b.len() as u32;
+    let Some(index) = lines.iter().position(|line| {
+        line.diff_type == DiffType::Equal
+            && line.left_line_number == last_a
+            && line.right_line_number == Some(last_b)
+    }) else {
+        return;
+    };
+
+    let equal = lines[index].clone();
+    lines[index] = DiffLine {
+        right_line_number: None,
+        diff_type: DiffType::Delete,
+        ..equal.clone()
+    };
+    lines.insert(
+        index + 1,
+        DiffLine {
+            left_line_number: None,
+            diff_type: DiffType::Insert,
+            content: lines_b[last_b as usize - 1].clone(),
+            ..equal
+        },
+    );
+}
+
+/// 行级差异结果
+pub struct LineDiff {
+    pub lines: Vec<DiffLine>,
+    /// 超过时限，`lines` 为整文件替换
+    pub degraded: bool,
+}
+
+/// 逐行比较两组文本行
+///
+/// 按 `config.algorithm` 计算，`ignore_case` 为真时按大小写折叠后的内容比较（先转大写再转小写，如 `ß` 与 `SS` 视为相同），
+/// 输出的行内容保持原样；两侧仅大小写不同的相同行显示左侧内容。
+/// 设置了 `deadline_ms` 且计算超时时，放弃近似结果，改为左侧全部删除、右侧全部插入
+pub(crate) fn line_diff(lines_a: &[String], lines_b: &[String], config: &ComparisonConfig) -> LineDiff {
+    use similar::{capture_diff_slices_deadline, Algorithm, DiffTag};
+
+    let keys_a = comparison_keys(lines_a, config.ignore_case);
+    let keys_b = comparison_keys(lines_b, config.ignore_case);
+
+    let line = |diff_type: DiffType, left: Option<usize>, right: Option<usize>, content: &str| DiffLine {
+        left_line_number: left.map(|i| i as u32 + 1),
+        right_line_number: right.map(|i| i as u32 + 1),
+        diff_type,
+        content: content.to_string(),
+        is_placeholder: false,
+        move_id: None,
+        enclosing_symbol: None,
+        old_content: None,
+    };
+    let replace_all = |result: &mut Vec<DiffLine>, old_range: std::ops::Range<usize>, new_range: std::ops::Range<usize>| {
+        for i in old_range {
+            result.push(line(DiffType::Delete, Some(i), None, &lines_a[i]));
+        }
+        for j in new_range {
+            result.push(line(DiffType::Insert, None, Some(j), &lines_b[j]));
+        }
+    };
+
+    let algorithm = match config.algorithm {
+        DiffAlgorithm::Myers => Algorithm::Myers,
+        DiffAlgorithm::Patience => Algorithm::Patience,
+        DiffAlgorithm::Lcs => Algorithm::Lcs,
+    };
+    let deadline = config
+        .deadline_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+    let ops = capture_diff_slices_deadline(algorithm, &keys_a, &keys_b, deadline);
+
+    let mut result = Vec::new();
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        replace_all(&mut result, 0..lines_a.len(), 0..lines_b.len());
+        return LineDiff { lines: result, degraded: true };
+    }
+    for op in ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                for (i, j) in old_range.zip(new_range) {
+                    result.push(line(DiffType::Equal, Some(i), Some(j), &lines_a[i]));
+                }
+            }
+            DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => replace_all(&mut result, old_range, new_range),
+        }
+    }
+    LineDiff { lines: result, degraded: false }
+}
+
+/// 各行是否位于成对的起止标记之间（不含标记行本身）
+///
+/// 起始标记可以嵌套，结束标记闭合最近一个同类的起始标记，其间未闭合的起始标记随之丢弃；
+/// 到文件末尾仍未闭合的起始标记不生效，没有对应起始标记的结束标记按普通行处理
+fn ignored_region_mask(lines: &[String], regions: &[(String, String)]) -> Vec<bool> {
+    let mut mask = vec![false; lines.len()];
+    // 未闭合的起始标记：(标记对序号, 行下标)
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let closing = regions
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, end))| !end.is_empty() && line.contains(end.as_str()))
+            .find_map(|(pair, _)| open.iter().rposition(|&(open_pair, _)| open_pair == pair));
+        if let Some(pos) = closing {
+            let (_, start) = open[pos];
+            open.truncate(pos);
+            mask[start + 1..i].fill(true);
+        } else if let Some(pair) = regions
+            .iter()
+            .position(|(start, _)| !start.is_empty() && line.contains(start.as_str()))
+        {
+            open.push((pair, i));
+        }
+    }
+    mask
+}
+
+/// 将位于忽略区域内的连续差异行（任一侧的行在区域内）折叠为一行无行号的说明，其中的变更不再计入增删行数
+pub(crate) fn collapse_ignored_regions(
+    lines: &mut Vec<DiffLine>,
+    lines_a: &[String],
+    lines_b: &[String],
+    regions: &[(String, String)],
+) {
+    if regions.is_empty() {
+        return;
+    }
+    let mask_a = ignored_region_mask(lines_a, regions);
+    let mask_b = ignored_region_mask(lines_b, regions);
+    if !mask_a.contains(&true) && !mask_b.contains(&true) {
+        return;
+    }
+    let ignored = |line: &DiffLine| {
+        line.left_line_number.is_some_and(|n| mask_a[n as usize - 1])
+            || line.right_line_number.is_some_and(|n| mask_b[n as usize - 1])
+    };
+
+    let mut result = Vec::with_capacity(lines.len());
+    let mut pending = std::mem::take(lines).into_iter().peekable();
+    while let Some(line) = pending.next() {
+        if !ignored(&line) {
+            result.push(line);
+            continue;
+        }
+        let mut region = vec![line];
+        while let Some(next) = pending.next_if(|next| ignored(next)) {
+            region.push(next);
+        }
+        let left = region.iter().filter(|line| line.left_line_number.is_some()).count();
+        let right = region.iter().filter(|line| line.right_line_number.is_some()).count();
+        let changed = region.iter().any(|line| line.diff_type != DiffType::Equal);
+        result.push(DiffLine {
+            left_line_number: None,
+            right_line_number: None,
+            diff_type: DiffType::Equal,
+            content: format!(
+                "{} 左侧 {} 行，右侧 {} 行{}",
+                IGNORED_REGION_MARKER,
+                left,
+                right,
+                if changed { "，有变更" } else { "" }
+            ),
+            is_placeholder: false,
+            move_id: None,
+            enclosing_symbol: None,
+            old_content: None,
+        });
+    }
+    *lines = result;
+}
+
+/// 将每组连续变更整理为并排显示的行：删除行与插入行按次序配对为 `Replace` 行，其余行补占位行对齐
+///
+/// 组内左侧的行（删除、移出）与右侧的行（插入、移入）按次序逐行对应：删除与插入合并为一行 `Replace`
+/// （`content` 取右侧，`old_content` 取左侧，保留两侧行号）；无法合并时各占一行，另一栏补一行占位
+/// （右栏为 `Insert` 类型、左栏为 `Delete` 类型）。整理后按左右两栏拆分时两栏行数一致。
+/// 文件系统、Git 与文本比较共用，在统计之后执行
+pub(crate) fn pair_replacements(lines: &mut Vec<DiffLine>) {
+    if !lines.iter().any(|line| matches!(line.diff_type, DiffType::Delete | DiffType::Insert | DiffType::Moved)) {
+        return;
+    }
+
+    let placeholder = |diff_type: DiffType| DiffLine {
+        left_line_number: None,
+        right_line_number: None,
+        diff_type,
+        content: String::new(),
+        is_placeholder: true,
+        move_id: None,
+        enclosing_symbol: None,
+        old_content: None,
+    };
+
+    let mut result = Vec::with_capacity(lines.len());
+    let mut pending = std::mem::take(lines).into_iter().peekable();
+    while let Some(line) = pending.next() {
+        // 二进制等无行号的说明行与相同行原样保留
+        let in_group = |line: &DiffLine| {
+            !line.is_placeholder
+                && matches!(line.diff_type, DiffType::Delete | DiffType::Insert | DiffType::Moved)
+                && (line.left_line_number.is_some() || line.right_line_number.is_some())
+        };
+        if !in_group(&line) {
+            result.push(line);
+            continue;
+        }
+
+        let mut group = vec![line];
+        while let Some(next) = pending.next_if(|next| in_group(next)) {
+            group.push(next);
+        }
+        let enclosing_symbol = group[0].enclosing_symbol.take();
+        let start = result.len();
+
+        let (left, right): (Vec<DiffLine>, Vec<DiffLine>) =
+            group.into_iter().partition(|line| line.left_line_number.is_some());
+        let mut right = right.into_iter();
+        for left_line in left {
+            match right.next() {
+                Some(right_line)
+                    if left_line.diff_type == DiffType::Delete && right_line.diff_type == DiffType::Insert =>
+                {
+                    result.push(DiffLine {
+                        left_line_number: left_line.left_line_number,
+                        diff_type: DiffType::Replace,
+                        old_content: Some(left_line.content),
+                        ..right_line
+                    });
+                }
+                Some(right_line) => {
+                    result.push(left_line);
+                    result.push(placeholder(DiffType::Insert));
+                    result.push(placeholder(DiffType::Delete));
+                    result.push(right_line);
+                }
+                None => {
+                    result.push(left_line);
+                    result.push(placeholder(DiffType::Insert));
+                }
+            }
+        }
+        for right_line in right {
+            result.push(placeholder(DiffType::Delete));
+            result.push(right_line);
+        }
+        result[start].enclosing_symbol = enclosing_symbol;
+    }
+    *lines = result;
+}
+
+/// 将至少 `min_lines` 行连续删除、且与另一处连续插入内容相同的块两侧标记为 `Moved`
+///
+/// 比较时合并行内空白（忽略大小写时同时折叠大小写），全为空行的块不算移动；
+/// 相邻的删除与插入属于同一处修改，不视为移动。`min_lines` 为 0 时不检测
+pub(crate) fn detect_moved_blocks(lines: &mut [DiffLine], min_lines: usize, ignore_case: bool) {
+    if min_lines == 0 {
+        return;
+    }
+
+    // 相邻的非相等行属于同一变更组
+    let mut groups = vec![0usize; lines.len()];
+    let mut group = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if line.diff_type == DiffType::Equal {
+            continue;
+        }
+        if i == 0 || lines[i - 1].diff_type == DiffType::Equal {
+            group += 1;
+        }
+        groups[i] = group;
+    }
+
+    let keys: Vec<String> = lines.iter().map(|line| move_key(&line.content, ignore_case)).collect();
+    let deleted: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].diff_type == DiffType::Delete).collect();
+    let inserted: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].diff_type == DiffType::Insert).collect();
+    let mut inserted_by_key: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (p, &i) in inserted.iter().enumerate() {
+        inserted_by_key.entry(keys[i].as_str()).or_default().push(p);
+    }
+
+    let left_numbers: Vec<Option<u32>> = lines.iter().map(|line| line.left_line_number).collect();
+    let right_numbers: Vec<Option<u32>> = lines.iter().map(|line| line.right_line_number).collect();
+    let mut moved = vec![false; lines.len()];
+    // 从 deleted[d] 与 inserted[p] 开始、两侧行号都连续的相同内容长度
+    let block_len = |d: usize, p: usize, moved: &[bool]| {
+        let mut len = 0;
+        while let (Some(&del), Some(&ins)) = (deleted.get(d + len), inserted.get(p + len)) {
+            let continues = len == 0
+                || (left_numbers[del] == left_numbers[deleted[d + len - 1]].map(|n| n + 1)
+                    && right_numbers[ins] == right_numbers[inserted[p + len - 1]].map(|n| n + 1));
+            if !continues || moved[ins] || groups[del] == groups[ins] || keys[del] != keys[ins] {
+                break;
+            }
+            len += 1;
+        }
+        len
+    };
+
+    let mut move_id = 0;
+    let mut d = 0;
+    while d < deleted.len() {
+        let best = inserted_by_key
+            .get(keys[deleted[d]].as_str())
+            .into_iter()
+            .flatten()
+            .map(|&p| (p, block_len(d, p, &moved)))
+            .max_by_key(|&(p, len)| (len, std::cmp::Reverse(p)))
+            .filter(|&(_, len)| len >= min_lines)
+            .filter(|&(_, len)| deleted[d..d + len].iter().any(|&i| !keys[i].is_empty()));
+        let Some((p, len)) = best else {
+            d += 1;
+            continue;
+        };
+
+        move_id += 1;
+        for &i in deleted[d..d + len].iter().chain(&inserted[p..p + len]) {
+            moved[i] = true;
+            lines[i].diff_type = DiffType::Moved;
+            lines[i].move_id = Some(move_id);
+        }
+        d += len;
+    }
+}
+
+/// 移动块检测的比较键：合并空白，忽略大小写时折叠大小写
+fn move_key(content: &str, ignore_case: bool) -> String {
+    let key = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if ignore_case {
+        key.to_uppercase().to_lowercase()
+    } else {
+        key
+    }
+}
+
+/// 将差异行分组为差异块，每个块保留变更前后各 `context` 行未变更内容
+///
+/// 间隔不超过 `2 * context` 行的变更合并到同一块中，块外的未变更行被丢弃
+pub fn build_hunks(lines: &[DiffLine], context: usize) -> Vec<DiffHunk> {
+    hunk_ranges(lines, context)
+        .into_iter()
+        .map(|(start, end)| {
+            let hunk_lines = lines[start..end].to_vec();
+            let (left_start, left_count) =
+                hunk_range(&lines[..start], &hunk_lines, |l| l.left_line_number);
+            let (right_start, right_count) =
+                hunk_range(&lines[..start], &hunk_lines, |l| l.right_line_number);
+            let mut header = format!(
+                "@@ -{},{} +{},{} @@",
+                left_start, left_count, right_start, right_count
+            );
+            // 与 git 一致，块头后附第一处变更所在的函数/类
+            if let Some(symbol) = hunk_lines
+                .iter()
+                .find(|line| line.diff_type != DiffType::Equal)
+                .and_then(|line| line.enclosing_symbol.as_deref())
+            {
+                header.push(' ');
+                header.push_str(symbol);
+            }
+            DiffHunk {
+                left_start,
+                left_count,
+                right_start,
+                right_count,
+                header,
+                lines: hunk_lines,
+            }
+        })
+        .collect()
+}
+
+/// 每个差异块在 `lines` 中的范围 [start, end)
+fn hunk_ranges(lines: &[DiffLine], context: usize) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in lines.iter().enumerate().filter(|(_, line)| line.diff_type != DiffType::Equal) {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+/// 差异行超过 `max_lines` 时截断并标记 `truncated`
+///
+/// 只保留前 `max_lines` 行内完整的差异块（第一个块即超出时按行截断），末尾追加一行无行号的截断提示
+pub(crate) fn truncate_diff_lines(diff: &mut FileDiff, max_lines: usize, context: usize) {
+    let total = diff.lines.len();
+    if total <= max_lines {
+        return;
+    }
+    // 截断点落在块内部时退回到该块之前
+    let cut = hunk_ranges(&diff.lines, context)
+        .into_iter()
+        .find(|&(start, end)| start < max_lines && max_lines < end)
+        .map_or(max_lines, |(start, _)| start);
+    let cut = if cut == 0 { max_lines } else { cut };
+
+    diff.lines.truncate(cut);
+    diff.lines.push(DiffLine {
+        left_line_number: None,
+        right_line_number: None,
+        diff_type: DiffType::Equal,
+        content: format!("{} 仅显示前 {} 行，共 {} 行", TRUNCATED_MARKER, cut, total),
+        is_placeholder: false,
+        move_id: None,
+        enclosing_symbol: None,
+        old_content: None,
+    });
+    diff.truncated = true;
+}
+
+/// 计算差异块在一侧的起始行号与行数
+fn hunk_range(
+    before: &[DiffLine],
+    hunk_lines: &[DiffLine],
+    line_number: impl Fn(&DiffLine) -> Option<u32>,
+) -> (u32, u32) {
+    let count = hunk_lines.iter().filter_map(&line_number).count() as u32;
+    let start = match hunk_lines.iter().find_map(&line_number) {
+        Some(first) => first,
+        None => before.iter().rev().find_map(&line_number).unwrap_or(0),
+    };
+    (start, count)
+}
+
+/// 行的比较键：忽略大小写时为折叠后的内容，否则为原内容
+pub(crate) fn comparison_keys(lines: &[String], ignore_case: bool) -> Vec<std::borrow::Cow<'_, str>> {
+    lines
+        .iter()
+        .map(|line| {
+            if ignore_case {
+                std::borrow::Cow::Owned(line.to_uppercase().to_lowercase())
+            } else {
+                std::borrow::Cow::Borrowed(line.as_str())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::test_repo::TestRepo;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn engine(ignore_case: bool) -> DiffEngine {
+        DiffEngine::new(ComparisonConfig {
+            ignore_case,
+            ..ComparisonConfig::default()
+        })
+    }
+
+    fn diff_types(diff: &LineDiff) -> Vec<DiffType> {
+        diff.lines.iter().map(|line| line.diff_type).collect()
+    }
+
+    #[test]
+    fn ignore_case_matches_mixed_case_identifiers() {
+        let left = lines("let UserName = getUserID();\nconst MAX_SIZE = 10;\n");
+        let right = lines("let username = GetUserId();\nconst max_size = 10;\n");
+
+        let diff = engine(true).compute_line_diff(&left, &right);
+        assert_eq!(diff_types(&diff), [DiffType::Equal, DiffType::Equal]);
+        // 输出保持原内容，不转为小写
+        assert_eq!(diff.lines[0].content, "let UserName = getUserID();");
+
+        let diff = engine(false).compute_line_diff(&left, &right);
+        assert!(diff.lines.iter().all(|line| line.diff_type != DiffType::Equal));
+    }
+
+    #[test]
+    fn ignore_case_folds_non_ascii() {
+        let equal = |a: &str, b: &str| {
+            let diff = engine(true).compute_line_diff(&lines(a), &lines(b));
+            diff.lines.iter().all(|line| line.diff_type == DiffType::Equal)
+        };
+        assert!(equal("STRASSE", "straße"));
+        assert!(equal("ÄRGER", "ärger"));
+        assert!(equal("ΟΔΟΣ", "οδος"));
+        assert!(equal("İstanbul", "i\u{307}stanbul"));
+        assert!(equal("ırmak", "IRMAK"));
+        // 与区域设置无关：İ 折叠为 i 加上组合点，而不是普通的 i
+        assert!(!equal("İstanbul", "istanbul"));
+        assert!(!equal("STRASSE", "strasze"));
+    }
+
+    fn region_engine() -> DiffEngine {
+        DiffEngine::new(ComparisonConfig {
+            ignore_regions: vec![("// BEGIN".to_string(), "// END".to_string())],
+            ..ComparisonConfig::default()
+        })
+    }
+
+    fn collapsed_regions(diff: &LineDiff) -> Vec<&str> {
+        diff.lines
+            .iter()
+            .filter(|line| line.content.starts_with(IGNORED_REGION_MARKER))
+            .map(|line| line.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn nested_ignore_region_merges_into_outer() {
+        let left = lines("keep\n// BEGIN\ngen 1\n// BEGIN\ninner\n// END\ngen 2\n// END\ntail\n");
+        let right = lines("keep\n// BEGIN\nGEN 1\n// BEGIN\nINNER\n// END\nGEN 2\n// END\ntail\n");
+        let regions = [("// BEGIN".to_string(), "// END".to_string())];
+        assert_eq!(
+            ignored_region_mask(&left, &regions),
+            [false, false, true, true, true, true, true, false, false]
+        );
+
+        let diff = region_engine().compute_line_diff(&left, &right);
+        assert_eq!(collapsed_regions(&diff), ["[已忽略的区域] 左侧 5 行，右侧 5 行，有变更"]);
+        // 标记行与区域外的行照常比较
+        assert_eq!(diff.lines.len(), 5);
+        assert!(diff.lines.iter().all(|line| line.diff_type == DiffType::Equal));
+    }
+
+    #[test]
+    fn unterminated_start_marker_is_not_collapsed() {
+        let left = lines("a\n// BEGIN\nx\ny\n");
+        let right = lines("a\n// BEGIN\nX\ny\n");
+        let diff = region_engine().compute_line_diff(&left, &right);
+        assert!(collapsed_regions(&diff).is_empty());
+        assert_eq!(
+            diff_types(&diff),
+            [DiffType::Equal, DiffType::Equal, DiffType::Delete, DiffType::Insert, DiffType::Equal]
+        );
+
+        // 之后出现的结束标记只闭合它之前的起始标记
+        let left = lines("// END\n// BEGIN\nx\n");
+        let right = lines("// END\n// BEGIN\nX\n");
+        let diff = region_engine().compute_line_diff(&left, &right);
+        assert!(collapsed_regions(&diff).is_empty());
+        assert_eq!(diff.lines.iter().filter(|line| line.diff_type != DiffType::Equal).count(), 2);
+    }
+
+    fn request(source_a: &str, source_b: &str) -> ComparisonRequest {
+        ComparisonRequest {
+            source_a: source_a.to_string(),
+            source_b: source_b.to_string(),
+            config: ComparisonConfig::default(),
+            is_git_comparison: false,
+            git_params: None,
+            is_working_tree_comparison: false,
+        }
+    }
+
+    #[test]
+    fn summary_counts_unchanged_skipped_and_bytes() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+        for dir in [&left, &right] {
+            fs::write(dir.path().join("same.txt"), "same\n").unwrap();
+        }
+        fs::write(left.path().join("image.bin"), [0u8, 1, 2, 3]).unwrap();
+        fs::write(right.path().join("image.bin"), [0u8, 9, 9, 9]).unwrap();
+        fs::write(left.path().join("edit.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(right.path().join("edit.txt"), "one\nTWO!\n3\n").unwrap();
+
+        let result = engine(false)
+            .compare(request(left.path().to_str().unwrap(), right.path().to_str().unwrap()))
+            .unwrap();
+        let summary = result.summary;
+        assert_eq!(summary.files_compared, 3);
+        assert_eq!(summary.files_unchanged, 1);
+        assert_eq!(summary.files_skipped, 1);
+        assert_eq!(summary.files_modified, 2);
+        // "TWO!\n" + "3\n" 与 "two\n" + "three\n"
+        assert_eq!((summary.bytes_added, summary.bytes_deleted), (7, 10));
+    }
+
+    #[test]
+    fn excluded_paths_are_neither_added_nor_deleted() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+        let write = |dir: &tempfile::TempDir, path: &str, content: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(&left, "app.py", "a = 1\n");
+        write(&right, "app.py", "a = 2\n");
+        // 只在左侧（否则为删除）、只在右侧（否则为新增）以及两侧不同的排除文件
+        write(&left, "node_modules/left/index.js", "left\n");
+        write(&right, "node_modules/right/index.js", "right\n");
+        write(&left, "debug.log", "left\n");
+        write(&right, "build/out.log", "right\n");
+        write(&left, "server.log", "old\n");
+        write(&right, "server.log", "new\n");
+        write(&right, "added.py", "b = 1\n");
+
+        // 两种遍历方式（walkdir 与遵循 .gitignore 的 ignore）都在配对前排除
+        for respect_gitignore in [false, true] {
+            let engine = DiffEngine::new(ComparisonConfig {
+                exclude_patterns: vec!["node_modules".to_string(), "**/*.log".to_string()],
+                respect_gitignore,
+                ..ComparisonConfig::default()
+            });
+            let result = engine
+                .compare(request(left.path().to_str().unwrap(), right.path().to_str().unwrap()))
+                .unwrap();
+            let mut statuses: Vec<(&str, &FileStatus)> =
+                result.file_diffs.iter().map(|d| (d.path.as_str(), &d.status)).collect();
+            statuses.sort_by_key(|(path, _)| *path);
+            assert!(
+                matches!(statuses[..], [("added.py", FileStatus::Added), ("app.py", FileStatus::Modified)]),
+                "{:?}",
+                statuses
+            );
+            assert_eq!((result.summary.files_added, result.summary.files_deleted), (1, 0));
+        }
+    }
+
+    #[test]
+    fn git_summary_counts_files_outside_the_diff_as_unchanged() {
+        let repo = TestRepo::new();
+        for name in ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"] {
+            repo.write(name, "original\n");
+        }
+        let first = repo.commit("first");
+        repo.write("a.txt", "changed\n");
+        let second = repo.commit("second");
+
+        let git_request = ComparisonRequest {
+            is_git_comparison: true,
+            git_params: Some(GitComparisonParams {
+                repository_path: repo.path_str().to_string(),
+                left_ref: first,
+                right_ref: second,
+                file_paths: Vec::new(),
+            }),
+            ..request("", "")
+        };
+        let summary = engine(false).compare(git_request).unwrap().summary;
+        assert_eq!((summary.files_modified, summary.files_unchanged, summary.files_compared), (1, 4, 5));
+
+        // 工作目录：一处未提交的修改和一个未跟踪文件
+        repo.write("b.txt", "edited\n").write("untracked.txt", "new\n");
+        let working_tree = ComparisonRequest {
+            is_working_tree_comparison: true,
+            ..request("HEAD", repo.path_str())
+        };
+        let summary = engine(false).compare(working_tree).unwrap().summary;
+        assert_eq!(summary.files_added, 1);
+        assert_eq!(summary.files_modified, 1);
+        assert_eq!((summary.files_unchanged, summary.files_compared), (4, 6));
+    }
+
+    /// 按并排显示拆分为左右两栏，返回两栏的内容
+    fn columns(lines: &[DiffLine]) -> (Vec<String>, Vec<String>) {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for line in lines {
+            match line.diff_type {
+                DiffType::Equal => {
+                    left.push(line.content.clone());
+                    right.push(line.content.clone());
+                }
+                DiffType::Replace => {
+                    left.push(line.old_content.clone().unwrap());
+                    right.push(line.content.clone());
+                }
+                DiffType::Delete => left.push(line.content.clone()),
+                DiffType::Insert => right.push(line.content.clone()),
+                _ if line.left_line_number.is_some() => left.push(line.content.clone()),
+                _ => right.push(line.content.clone()),
+            }
+        }
+        (left, right)
+    }
+
+    #[test]
+    fn unequal_length_replacements_keep_columns_aligned() {
+        let cases = [
+            ("a\nold1\nold2\nold3\nz\n", "a\nnew1\nz\n"),
+            ("a\nold1\nz\n", "a\nnew1\nnew2\nnew3\nz\n"),
+        ];
+        for (before, after) in cases {
+            let mut diff = engine(false).compute_line_diff(&lines(before), &lines(after)).lines;
+            pair_replacements(&mut diff);
+
+            let (left, right) = columns(&diff);
+            assert_eq!(left.len(), right.len(), "{:?}", diff);
+            // 非占位行还原两侧原文
+            let real = |column: &[String]| column.iter().filter(|c| !c.is_empty()).cloned().collect::<Vec<_>>();
+            assert_eq!(real(&left), lines(before));
+            assert_eq!(real(&right), lines(after));
+
+            let replace: Vec<&DiffLine> = diff.iter().filter(|l| l.diff_type == DiffType::Replace).collect();
+            assert_eq!(replace.len(), 1);
+            assert_eq!(replace[0].old_content.as_deref(), Some("old1"));
+            assert_eq!(replace[0].content, "new1");
+            assert_eq!((replace[0].left_line_number, replace[0].right_line_number), (Some(2), Some(2)));
+            assert_eq!(diff.iter().filter(|l| l.is_placeholder).count(), 2);
+        }
+    }
+
+    #[test]
+    fn paired_rows_count_the_same_as_the_summary() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+        fs::write(left.path().join("f.txt"), "a\nold1\nold2\nold3\nz\n").unwrap();
+        fs::write(right.path().join("f.txt"), "a\nnew1\nz\n").unwrap();
+
+        let result = engine(false)
+            .compare(request(left.path().to_str().unwrap(), right.path().to_str().unwrap()))
+            .unwrap();
+        let entry = result.file_diffs[0].entry();
+        assert_eq!((entry.lines_added, entry.lines_deleted), (1, 3));
+        assert_eq!((result.summary.lines_added, result.summary.lines_deleted), (1, 3));
+    }
+}
@@ -0,0 +1,591 @@
+// git 命令行实现的 Git 操作，仅在启用 `git-cli` 特性且 libgit2 出错时使用；与 git_lib 中的实现一一对应
+
+use crate::diff::git_integration::{ChangedFile, GitChange, GitError, GitVersion, INDEX_REF, WORKTREE_REF};
+use crate::diff::types::{CommitInfo, FileHistoryEntry, GitRef, GitRefKind, LineBlame};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+#[cfg(test)]
+thread_local! {
+    /// 当前线程启动的 git 进程数，测试据此检查批量读取不随文件数启动进程
+    static SPAWNED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// 记录一次 git 进程启动，仅测试时计数
+fn count_spawn() {
+    #[cfg(test)]
+    SPAWNED.with(|spawned| spawned.set(spawned.get() + 1));
+}
+
+/// 在 `repo_path` 下执行 git 命令，失败时返回包含 stderr 的错误
+fn git(repo_path: &Path, args: &[&str]) -> Result<Output> {
+    count_spawn();
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to execute git {}", args[0]))?;
+    check_status(args, output)
+}
+
+/// 执行 git 命令并写入标准输入，失败时返回包含 stderr 的错误
+fn git_with_input(repo_path: &Path, args: &[&str], input: Vec<u8>) -> Result<Output> {
+    count_spawn();
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to execute git {}", args[0]))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // 另起线程写入，避免输出填满管道后与写入互相等待
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to execute git {}", args[0]))?;
+    let written = writer.join().map_err(|_| anyhow::anyhow!("Writing to git {} panicked", args[0]))?;
+    let output = check_status(args, output)?;
+    written.with_context(|| format!("Failed to write to git {}", args[0]))?;
+    Ok(output)
+}
+
+fn check_status(args: &[&str], output: Output) -> Result<Output> {
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Git {} command failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output)
+}
+
+/// 检查作为参数传给 git 的引用：以 `-` 开头的引用会被 git 当作命令行选项解析
+fn revision(commit_ref: &str) -> Result<&str> {
+    if commit_ref.starts_with('-') {
+        anyhow::bail!("Invalid git reference: {}", commit_ref);
+    }
+    Ok(commit_ref)
+}
+
+/// 按 NUL 分隔的输出字段
+fn nul_fields(stdout: &[u8]) -> impl Iterator<Item = String> + '_ {
+    stdout
+        .split(|&b| b == 0)
+        .filter(|field| !field.is_empty())
+        .map(|field| String::from_utf8_lossy(field).to_string())
+}
+
+/// `git diff --name-status -z --ignore-submodules=all`：每项为状态字段后跟一个路径，重命名与复制为旧路径、新路径两个；
+/// 工作目录一侧另加 `git ls-files --others --exclude-standard` 列出的未跟踪文件
+pub(crate) fn changed_files(repo_path: &Path, left: GitVersion, right: GitVersion) -> Result<Vec<ChangedFile>> {
+    let reverse = left.rank() > right.rank();
+    let (old, new) = if reverse { (right, left) } else { (left, right) };
+    let mut args = vec!["diff", "--name-status", "-z", "--ignore-submodules=all"];
+    if reverse {
+        args.push("-R");
+    }
+    match (old, new) {
+        (GitVersion::Commit(old), GitVersion::Commit(new)) => args.extend([revision(old)?, revision(new)?]),
+        (GitVersion::Commit(old), GitVersion::Index) => args.extend(["--cached", revision(old)?]),
+        (GitVersion::Commit(old), GitVersion::Worktree) => args.push(revision(old)?),
+        (GitVersion::Index, GitVersion::Worktree) => {}
+        _ => return Ok(Vec::new()),
+    }
+    let output = git(repo_path, &args)?;
+    let mut fields = nul_fields(&output.stdout);
+    let mut files = Vec::new();
+    if matches!(new, GitVersion::Worktree) {
+        let untracked = git(repo_path, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+        files.extend(nul_fields(&untracked.stdout).map(|path| ChangedFile {
+            path,
+            change: if reverse { GitChange::Deleted } else { GitChange::Added },
+        }));
+    }
+    while let Some(status) = fields.next() {
+        let Some(path) = fields.next() else {
+            break;
+        };
+        let change = match &status[..1] {
+            "A" => GitChange::Added,
+            "D" => GitChange::Deleted,
+            "R" | "C" => {
+                let Some(new_path) = fields.next() else {
+                    break;
+                };
+                let change = if status.starts_with('R') {
+                    GitChange::Renamed { old_path: path }
+                } else {
+                    GitChange::Added
+                };
+                files.push(ChangedFile { path: new_path, change });
+                continue;
+            }
+            _ => GitChange::Modified,
+        };
+        files.push(ChangedFile { path, change });
+    }
+    Ok(files)
+}
+
+/// 一个 `git cat-file --batch` 进程读取所有 `<ref>:<path>`（暂存区为 `:<path>`），与 `file_paths` 一一对应；
+/// 引用或文件不存在、或路径不是文件时为 `None`。含换行符的路径无法按行输入，改用 `git show` 单独读取
+pub(crate) fn blobs_at_commit(repo_path: &Path, file_paths: &[&str], commit_ref: &str) -> Result<Vec<Option<Vec<u8>>>> {
+    let commit_ref = if commit_ref == INDEX_REF { "" } else { revision(commit_ref)? };
+    let object_name = |path: &str| format!("{}:{}", commit_ref, path);
+    let batched: Vec<&str> = file_paths.iter().copied().filter(|path| !path.contains('\n')).collect();
+    let input: String = batched.iter().map(|path| object_name(path) + "\n").collect();
+    let output = if batched.is_empty() {
+        Vec::new()
+    } else {
+        git_with_input(repo_path, &["cat-file", "--batch"], input.into_bytes())?.stdout
+    };
+
+    // 每项输出为 `<oid> <type> <size>` 一行后跟内容与换行，对象不存在时只有 `<name> missing` 一行
+    let mut rest = output.as_slice();
+    let mut blobs: HashMap<&str, Vec<u8>> = HashMap::new();
+    for path in &batched {
+        let Some(end) = rest.iter().position(|&b| b == b'\n') else {
+            break;
+        };
+        let header = String::from_utf8_lossy(&rest[..end]).to_string();
+        rest = &rest[end + 1..];
+        let fields: Vec<&str> = header.split(' ').collect();
+        let Some(size) = fields.get(2).filter(|_| fields.len() == 3).and_then(|size| size.parse::<usize>().ok()) else {
+            continue;
+        };
+        let content = &rest[..size.min(rest.len())];
+        if fields[1] == "blob" {
+            blobs.insert(path, content.to_vec());
+        }
+        rest = rest.get(size + 1..).unwrap_or_default();
+    }
+
+    Ok(file_paths
+        .iter()
+        .map(|path| match blobs.get(path) {
+            Some(blob) => Some(blob.clone()),
+            None if path.contains('\n') => git(repo_path, &["show", &object_name(path)]).ok().map(|output| output.stdout),
+            None => None,
+        })
+        .collect())
+}
+
+/// `git ls-files --debug -z` 输出中的 `mtime: <秒>:<纳秒>`，与 `file_paths` 一一对应，文件未暂存时为空
+///
+/// 每个路径以 NUL 结尾，其后是该项的调试信息行，再接下一个路径；一次列出整个暂存区
+pub(crate) fn index_times(repo_path: &Path, file_paths: &[&str]) -> Result<Vec<Option<i64>>> {
+    let output = git(repo_path, &["ls-files", "--debug", "-z"])?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let mut segments = listing.split('\0');
+    let mut path = segments.next().unwrap_or_default();
+    let mut times: HashMap<&str, i64> = HashMap::new();
+    for segment in segments {
+        let (debug, next) = segment.rsplit_once('\n').unwrap_or((segment, ""));
+        let mtime = debug
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("mtime: "))
+            .and_then(|time| time.split(':').next()?.parse().ok());
+        if let Some(mtime) = mtime {
+            times.entry(path).or_insert(mtime);
+        }
+        path = next;
+    }
+    Ok(file_paths.iter().map(|path| times.get(path).copied()).collect())
+}
+
+/// `git show -s --format=%ct`，引用无法解析时为 0
+pub(crate) fn commit_time(repo_path: &Path, commit_ref: &str) -> Result<i64> {
+    let commit = format!("{}^{{commit}}", revision(commit_ref)?);
+    let Ok(output) = git(repo_path, &["show", "-s", "--format=%ct", &commit]) else {
+        return Ok(0);
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<i64>()
+        .with_context(|| "Invalid timestamp format")
+}
+
+/// `git log` 的提交格式，字段之间以单元分隔符（0x1F）分隔，与 `parse_commit` 对应
+const COMMIT_FORMAT: &str = "%H%x1f%h%x1f%an%x1f%at%x1f%ct%x1f%s";
+
+fn parse_commit(record: &str) -> Option<CommitInfo> {
+    let mut fields = record.split('\x1f').map(str::to_string);
+    Some(CommitInfo {
+        hash: fields.next()?,
+        short_hash: fields.next()?,
+        author: fields.next()?,
+        author_time: fields.next()?.parse().unwrap_or(0),
+        commit_time: fields.next()?.parse().unwrap_or(0),
+        subject: fields.next()?,
+    })
+}
+
+/// `git show -s`，引用无法解析为提交时为空
+pub(crate) fn commit_at(repo_path: &Path, commit_ref: &str) -> Result<Option<CommitInfo>> {
+    let format = format!("--format={}", COMMIT_FORMAT);
+    let commit = format!("{}^{{commit}}", revision(commit_ref)?);
+    let Ok(output) = git(repo_path, &["show", "-s", &format, &commit]) else {
+        return Ok(None);
+    };
+    Ok(parse_commit(String::from_utf8_lossy(&output.stdout).trim_end_matches('\n')))
+}
+
+/// `git rev-list --parents -n 1`：输出提交哈希后跟各父提交哈希，以空格分隔
+pub(crate) fn commit_parents(repo_path: &Path, commit_ref: &str) -> Result<Option<(String, Vec<String>)>> {
+    let commit = format!("{}^{{commit}}", revision(commit_ref)?);
+    let Ok(output) = git(repo_path, &["rev-list", "--parents", "-n", "1", &commit, "--"]) else {
+        return Ok(None);
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut hashes = stdout.split_whitespace().map(str::to_string);
+    Ok(hashes.next().map(|hash| (hash, hashes.collect())))
+}
+
+/// `git merge-base`，没有共同祖先时以状态 1 退出，与引用无法解析一样为空
+pub(crate) fn merge_base(repo_path: &Path, left_ref: &str, right_ref: &str) -> Result<Option<String>> {
+    let Ok(output) = git(repo_path, &["merge-base", revision(left_ref)?, revision(right_ref)?]) else {
+        return Ok(None);
+    };
+    Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// `git log -z`：提交之间以 NUL 分隔
+pub(crate) fn commits(
+    repo_path: &Path,
+    commit_ref: &str,
+    path: Option<&str>,
+    matches: &dyn Fn(&CommitInfo) -> bool,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<CommitInfo>> {
+    let format = format!("--format={}", COMMIT_FORMAT);
+    let mut args = vec!["log", "-z", &format, revision(commit_ref)?, "--"];
+    args.extend(path);
+    let output = git(repo_path, &args)?;
+    Ok(nul_fields(&output.stdout)
+        .filter_map(|record| parse_commit(&record))
+        .filter(|commit| matches(commit))
+        .skip(offset)
+        .take(limit)
+        .collect())
+}
+
+/// `git log --follow --name-status -z`：每个提交以记录分隔符（0x1E）开头，提交信息之后为 NUL 分隔的状态与路径，
+/// 最后一个路径为文件在该提交中的路径；合并提交没有变更列表，沿用之前跟踪的路径
+pub(crate) fn file_history(repo_path: &Path, file_path: &str, limit: usize) -> Result<Vec<FileHistoryEntry>> {
+    let format = format!("--format=%x1e{}", COMMIT_FORMAT);
+    let limit = format!("--max-count={}", limit);
+    let output = git(
+        repo_path,
+        &["log", "--follow", "--name-status", "-z", &format, &limit, "--", file_path],
+    )?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut path = file_path.to_string();
+    let mut history = Vec::new();
+    for record in stdout.split('\x1e').filter(|record| !record.is_empty()) {
+        let mut fields = record.split('\0');
+        let Some(commit) = fields.next().and_then(parse_commit) else {
+            continue;
+        };
+        // 状态字段以换行开头，之后为一个或两个（重命名、复制）路径
+        if let Some(last) = fields.skip(1).filter(|field| !field.is_empty()).last() {
+            path = last.to_string();
+        }
+        history.push(FileHistoryEntry {
+            commit,
+            path: path.clone(),
+        });
+    }
+    Ok(history)
+}
+
+/// `git for-each-ref` 列出的本地分支、远程分支与标签，不含符号引用；每项以记录分隔符（0x1E）结尾，字段之间以 NUL 分隔。
+/// 标签指向的对象仍是标签时（嵌套标签）另用 `git show` 解析所指提交，不指向提交的标签不列出
+pub(crate) fn refs(repo_path: &Path) -> Result<Vec<GitRef>> {
+    let output = git(
+        repo_path,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(symref)%00%(HEAD)%00%(objecttype)%00%(objectname)%00%(committerdate:unix)\
+             %00%(*objecttype)%00%(*objectname)%00%(*committerdate:unix)%00%(contents)%1e",
+            "refs/heads",
+            "refs/remotes",
+            "refs/tags",
+        ],
+    )?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut refs = Vec::new();
+    for record in stdout.split('\x1e').map(|record| record.trim_start_matches('\n')).filter(|record| !record.is_empty()) {
+        let fields: Vec<&str> = record.splitn(10, '\0').collect();
+        let [full_name, symref, head, object_type, object_name, committed_at, peeled_type, peeled_name, peeled_committed_at, contents] =
+            fields[..]
+        else {
+            continue;
+        };
+        if !symref.is_empty() {
+            continue;
+        }
+        let (name, kind) = if let Some(branch) = full_name.strip_prefix("refs/heads/") {
+            (branch, GitRefKind::Branch)
+        } else if let Some(branch) = full_name.strip_prefix("refs/remotes/") {
+            (branch, GitRefKind::RemoteBranch)
+        } else if let Some(tag) = full_name.strip_prefix("refs/tags/") {
+            (tag, GitRefKind::Tag)
+        } else {
+            continue;
+        };
+        let target = match (object_type, peeled_type) {
+            ("commit", _) => Some((object_name.to_string(), committed_at.to_string())),
+            ("tag", "commit") => Some((peeled_name.to_string(), peeled_committed_at.to_string())),
+            ("tag", "tag") => {
+                let commit = format!("{}^{{commit}}", full_name);
+                git(repo_path, &["show", "-s", "--format=%H%x1f%ct", &commit]).ok().and_then(|output| {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let (hash, time) = stdout.trim().split_once('\x1f')?;
+                    Some((hash.to_string(), time.to_string()))
+                })
+            }
+            _ => None,
+        };
+        let Some((target_hash, committed_at)) = target else {
+            continue;
+        };
+        refs.push(GitRef {
+            name: name.to_string(),
+            kind,
+            target_hash,
+            committed_at: committed_at.parse().unwrap_or(0),
+            is_head: head == "*",
+            message: (object_type == "tag").then(|| contents.trim_end().to_string()),
+        });
+    }
+    Ok(refs)
+}
+
+/// `git rev-parse --show-toplevel`
+pub(crate) fn repository_root(path: &Path) -> Result<PathBuf> {
+    let output = git(path, &["rev-parse", "--show-toplevel"])
+        .map_err(|_| GitError::NotARepository(path.to_path_buf()))?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// `git rev-parse --git-common-dir`，输出可能是相对 `path` 的路径
+pub(crate) fn common_dir(path: &Path) -> Result<PathBuf> {
+    let output = git(path, &["rev-parse", "--git-common-dir"])
+        .map_err(|_| GitError::NotARepository(path.to_path_buf()))?;
+    Ok(path.join(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// `git ls-tree -r --name-only`，暂存区与工作目录为 `git ls-files`
+pub(crate) fn files_at_commit(repo_root: &Path, commit_ref: &str, pathspec: &str) -> Result<Vec<String>> {
+    if commit_ref == INDEX_REF || commit_ref == WORKTREE_REF {
+        let output = git(repo_root, &["ls-files", "-z", "--", pathspec])?;
+        return Ok(nul_fields(&output.stdout).collect());
+    }
+    let output = git(
+        repo_root,
+        &["ls-tree", "-r", "--name-only", "-z", revision(commit_ref)?, "--", pathspec],
+    )?;
+    Ok(nul_fields(&output.stdout).collect())
+}
+
+/// `git diff --name-only` 与 `git ls-files --others --exclude-standard` 的合并结果
+pub(crate) fn working_tree_changes(repo_root: &Path, commit_ref: &str, pathspec: &str) -> Result<Vec<String>> {
+    let commit_ref = revision(commit_ref)?;
+    let mut files = Vec::new();
+    for args in [
+        vec!["diff", "--name-only", "--no-renames", "-z", commit_ref, "--", pathspec],
+        vec!["ls-files", "--others", "--exclude-standard", "-z", "--", pathspec],
+    ] {
+        files.extend(nul_fields(&git(repo_root, &args)?.stdout));
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// `git blame --porcelain -L <line>,<line>`
+pub(crate) fn blame_line(repo_root: &Path, file_path: &str, line: u32, commit_ref: &str) -> Result<Option<LineBlame>> {
+    let range = format!("{},{}", line, line);
+    let commit_ref = revision(commit_ref)?;
+    let Ok(output) = git(repo_root, &["blame", "--porcelain", "-L", &range, commit_ref, "--", file_path]) else {
+        return Ok(None);
+    };
+
+    let porcelain = String::from_utf8_lossy(&output.stdout);
+    let mut lines = porcelain.lines();
+    let Some(commit) = lines.next().and_then(|header| header.split_whitespace().next()) else {
+        return Ok(None);
+    };
+    let mut blame = LineBlame {
+        commit: commit.to_string(),
+        author: String::new(),
+        author_time: 0,
+        summary: String::new(),
+    };
+    // 头部字段在内容行（以制表符开头）之前
+    for field in lines.take_while(|l| !l.starts_with('\t')) {
+        if let Some(author) = field.strip_prefix("author ") {
+            blame.author = author.to_string();
+        } else if let Some(time) = field.strip_prefix("author-time ") {
+            blame.author_time = time.parse().unwrap_or(0);
+        } else if let Some(summary) = field.strip_prefix("summary ") {
+            blame.summary = summary.to_string();
+        }
+    }
+    Ok(Some(blame))
+}
+
+/// `git blame --porcelain -L <start>,<end> -- <file>`，不指定版本时按工作目录内容计算，未提交的行哈希全为 0
+pub(crate) fn blame_lines(repo_root: &Path, file_path: &str, start: u32, end: u32) -> Result<Vec<Option<LineBlame>>> {
+    let range = format!("{},{}", start, end);
+    let output = git(repo_root, &["blame", "--porcelain", "-L", &range, "--", file_path])?;
+    let porcelain = String::from_utf8_lossy(&output.stdout);
+
+    // 每个提交的作者等字段只在其首次出现时输出
+    let mut commits: HashMap<String, LineBlame> = HashMap::new();
+    let mut blames = Vec::new();
+    let mut lines = porcelain.lines();
+    while let Some(header) = lines.next() {
+        let Some(commit) = header.split_whitespace().next() else {
+            continue;
+        };
+        let blame = commits.entry(commit.to_string()).or_insert_with(|| LineBlame {
+            commit: commit.to_string(),
+            author: String::new(),
+            author_time: 0,
+            summary: String::new(),
+        });
+        for field in lines.by_ref().take_while(|l| !l.starts_with('\t')) {
+            if let Some(author) = field.strip_prefix("author ") {
+                blame.author = author.to_string();
+            } else if let Some(time) = field.strip_prefix("author-time ") {
+                blame.author_time = time.parse().unwrap_or(0);
+            } else if let Some(summary) = field.strip_prefix("summary ") {
+                blame.summary = summary.to_string();
+            }
+        }
+        blames.push((!commit.bytes().all(|b| b == b'0')).then(|| blame.clone()));
+    }
+    Ok(blames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::test_repo::TestRepo;
+
+    #[test]
+    fn option_like_refs_are_rejected() {
+        let repo = TestRepo::new();
+        repo.write("a.txt", "one\n");
+        repo.commit("init");
+        let target = repo.file("pwned");
+        let injected = format!("--output={}", target.display());
+
+        assert!(changed_files(repo.path(), GitVersion::Commit(&injected), GitVersion::Commit("HEAD")).is_err());
+        assert!(changed_files(repo.path(), GitVersion::Commit(&injected), GitVersion::Worktree).is_err());
+        assert!(merge_base(repo.path(), &injected, "HEAD").is_err());
+        assert!(commits(repo.path(), &injected, None, &|_| true, 0, 10).is_err());
+        assert!(files_at_commit(repo.path(), &injected, ".").is_err());
+        assert!(blame_line(repo.path(), "a.txt", 1, &injected).is_err());
+        assert!(commit_at(repo.path(), &injected).is_err());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn changed_files_between_commits() {
+        let repo = TestRepo::new();
+        repo.write("kept.txt", "same\n").write("edited.txt", "old\n").write("gone.txt", "bye\n");
+        let first = repo.commit("first");
+        repo.write("edited.txt", "new\n").remove("gone.txt").write("added.txt", "hi\n");
+        let second = repo.commit("second");
+
+        let mut files: Vec<(String, &str)> = changed_files(repo.path(), GitVersion::Commit(&first), GitVersion::Commit(&second))
+            .unwrap()
+            .into_iter()
+            .map(|f| {
+                let change = match f.change {
+                    GitChange::Added => "A",
+                    GitChange::Deleted => "D",
+                    GitChange::Modified => "M",
+                    GitChange::Renamed { .. } => "R",
+                };
+                (f.path, change)
+            })
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [("added.txt".to_string(), "A"), ("edited.txt".to_string(), "M"), ("gone.txt".to_string(), "D")]
+        );
+        assert_eq!(merge_base(repo.path(), &first, &second).unwrap(), Some(first));
+    }
+
+    #[test]
+    fn repository_root_from_worktree_subdirectory() {
+        let repo = TestRepo::new();
+        repo.write("src/a.txt", "one\n");
+        repo.commit("init");
+        let outer = tempfile::tempdir().unwrap();
+        let worktree = outer.path().join("wt");
+        repo.git(&["worktree", "add", "-q", "-b", "wt-branch", worktree.to_str().unwrap()]);
+
+        let root = repository_root(&worktree.join("src")).unwrap();
+        assert_eq!(root.canonicalize().unwrap(), worktree.canonicalize().unwrap());
+        assert!(refs(&root).unwrap().iter().any(|r| r.name == "wt-branch" && r.is_head));
+        assert!(repository_root(outer.path()).is_err());
+    }
+
+    /// 执行 `f` 期间当前线程启动的 git 进程数
+    fn spawns<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = SPAWNED.with(|spawned| spawned.get());
+        let result = f();
+        (result, SPAWNED.with(|spawned| spawned.get()) - before)
+    }
+
+    #[test]
+    fn batch_reads_spawn_git_once_regardless_of_file_count() {
+        for file_count in [1, 25] {
+            let repo = TestRepo::new();
+            let paths: Vec<String> = (0..file_count).map(|i| format!("dir/file {}.txt", i)).collect();
+            for (i, path) in paths.iter().enumerate() {
+                repo.write(path, &format!("old {}\n", i));
+            }
+            let first = repo.commit("first");
+            for (i, path) in paths.iter().enumerate() {
+                repo.write(path, &format!("new {}\n", i));
+            }
+            let second = repo.commit("second");
+            repo.write(&paths[0], "staged\n");
+            repo.git(&["add", "-A"]);
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+            let (changed, count) =
+                spawns(|| changed_files(repo.path(), GitVersion::Commit(&first), GitVersion::Commit(&second)).unwrap());
+            assert_eq!((changed.len(), count), (file_count, 1));
+
+            let (blobs, count) = spawns(|| blobs_at_commit(repo.path(), &paths, &first).unwrap());
+            assert_eq!(count, 1);
+            assert_eq!(blobs[file_count - 1].as_deref(), Some(format!("old {}\n", file_count - 1).as_bytes()));
+
+            let (blobs, count) = spawns(|| blobs_at_commit(repo.path(), &paths, INDEX_REF).unwrap());
+            assert_eq!(count, 1);
+            assert_eq!(blobs[0].as_deref(), Some(b"staged\n".as_slice()));
+
+            let (times, count) = spawns(|| index_times(repo.path(), &paths).unwrap());
+            assert_eq!(count, 1);
+            assert!(times.iter().all(Option::is_some));
+        }
+    }
+}
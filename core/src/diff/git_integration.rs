@@ -1,11 +1,128 @@
+use crate::diff::encoding::decode_text;
+use crate::diff::engine::{
+    collapse_ignored_regions, line_diff, line_similarity, mark_trailing_newline_change, split_content_lines, LineDiff,
+};
+use crate::diff::lfs::{lfs_file_diff, parse_lfs_pointer, LfsSide};
 use crate::diff::types::*;
+use crate::diff::{git_cli, git_lib};
 use anyhow::{Context, Result};
-use std::path::Path;
-use std::process::Command;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 /// Git集成处理器
+///
+/// 通过 libgit2 读取仓库，不依赖 PATH 中的 git；启用 `git-cli` 特性时，libgit2 出错后改用 git 命令行重试
+#[derive(Default)]
 pub struct GitIntegration;
 
+/// `GitComparisonParams` 中表示工作目录的特殊引用：已跟踪文件的磁盘内容，以及未被忽略的未跟踪文件
+pub const WORKTREE_REF: &str = "WORKTREE";
+
+/// `GitComparisonParams` 中表示暂存区的特殊引用
+pub const INDEX_REF: &str = "INDEX";
+
+/// 空树的哈希，根提交与之比较时所有文件列为新增；git 与 libgit2 均内置该对象，不要求仓库中存在
+pub const EMPTY_TREE_REF: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// 调用方需要区分处理的 Git 错误，附加在返回的 `anyhow::Error` 上，可用 `downcast_ref` 取出
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    #[error("Not a git repository: {}", .0.display())]
+    NotARepository(PathBuf),
+    /// 引用（分支、标签、提交或 `HEAD~1` 等表达式）无法解析为提交
+    #[error("Unknown revision: {0}")]
+    UnknownRevision(String),
+}
+
+/// 比较的一侧版本
+#[derive(Clone, Copy)]
+pub(crate) enum GitVersion<'a> {
+    /// 提交（分支、标签、提交哈希或 `HEAD~1` 等表达式）
+    Commit(&'a str),
+    Index,
+    Worktree,
+}
+
+impl<'a> GitVersion<'a> {
+    pub(crate) fn parse(reference: &'a str) -> Self {
+        match reference {
+            WORKTREE_REF => Self::Worktree,
+            INDEX_REF => Self::Index,
+            _ => Self::Commit(reference),
+        }
+    }
+
+    /// 由旧到新的次序：提交、暂存区、工作目录
+    pub(crate) fn rank(self) -> u8 {
+        match self {
+            Self::Commit(_) => 0,
+            Self::Index => 1,
+            Self::Worktree => 2,
+        }
+    }
+}
+
+/// 两个版本之间变更的文件
+pub(crate) struct ChangedFile {
+    /// 右侧版本中的路径，删除的文件为左侧路径
+    pub path: String,
+    pub change: GitChange,
+}
+
+impl ChangedFile {
+    /// 左侧版本中的路径，重命名的文件为旧路径
+    fn left_path(&self) -> &str {
+        match &self.change {
+            GitChange::Renamed { old_path } => old_path,
+            _ => &self.path,
+        }
+    }
+}
+
+/// 文件在一侧版本中的内容与修改时间，文件不存在时内容为空
+struct FileVersion {
+    content: String,
+    encoding: Option<&'static str>,
+    modified_time: Option<i64>,
+    /// 无法解码的内容的原始字节，`content` 为其有损转换
+    binary: Option<Vec<u8>>,
+}
+
+impl FileVersion {
+    fn stats(&self) -> FileStats {
+        FileStats {
+            size: self.content.len() as u64,
+            line_count: self.content.lines().count() as u32,
+            modified_time: self.modified_time,
+            content_hash: None,
+            encoding: self.encoding.map(str::to_string),
+        }
+    }
+}
+
+/// 文件在两个版本之间的变更类型
+pub(crate) enum GitChange {
+    Added,
+    Deleted,
+    Modified,
+    Renamed { old_path: String },
+}
+
+/// 返回 libgit2 的执行结果；启用 `git-cli` 特性时，出错后改用 git 命令行重试
+fn with_cli_fallback<T>(operation: &str, result: Result<T>, cli: impl FnOnce() -> Result<T>) -> Result<T> {
+    if !cfg!(feature = "git-cli") {
+        return result;
+    }
+    result.or_else(|error| {
+        log::warn!("libgit2 {} failed, retrying with git: {:#}", operation, error);
+        // 两者都失败时，保留 libgit2 已识别出的仓库或引用错误
+        cli().map_err(|cli_error| match error.downcast_ref::<GitError>() {
+            Some(_) => error,
+            None => cli_error,
+        })
+    })
+}
+
 impl GitIntegration {
     /// 创建新的Git集成实例
     pub fn new() -> Self {
@@ -13,159 +130,139 @@ impl GitIntegration {
     }
 
     /// 执行Git比较
+    ///
+    /// `repository_path` 可以是仓库（含工作树与子模块）内的任意目录，按所在仓库的根目录比较
     pub fn compare(
         &self,
         params: &GitComparisonParams,
         config: &ComparisonConfig,
     ) -> Result<Vec<FileDiff>> {
-        let repo_path = Path::new(&params.repository_path);
-
-        // 验证是否为Git仓库
-        if !self.is_git_repository(repo_path)? {
-            return Err(anyhow::anyhow!(
-                "Not a git repository: {}",
-                params.repository_path
-            ));
-        }
+        let repo_root = self.repository_root(Path::new(&params.repository_path))?;
+        let repo_path = repo_root.as_path();
 
         // 获取两个版本之间的文件变更列表
-        let changed_files = self.get_changed_files(params)?;
+        let changed_files = self.get_changed_files(repo_path, params)?;
 
         // 如果指定了特定文件路径，则过滤
-        let files_to_compare: Vec<String> = if params.file_paths.is_empty() {
-            changed_files
-        } else {
-            changed_files
-                .into_iter()
-                .filter(|file| {
-                    params.file_paths.iter().any(|pattern| {
-                        file.contains(pattern) || self.matches_pattern(file, pattern)
-                    })
-                })
-                .collect()
-        };
+        let files_to_compare: Vec<ChangedFile> = changed_files
+            .into_iter()
+            .filter(|file| self.matches_file_paths(&file.path, &params.file_paths))
+            .collect();
+
+        // 两侧内容与修改时间各批量读取一次，不随文件数启动进程或重复打开仓库
+        let left_paths: Vec<&str> = files_to_compare.iter().map(ChangedFile::left_path).collect();
+        let right_paths: Vec<&str> = files_to_compare.iter().map(|file| file.path.as_str()).collect();
+        let left_versions = self.read_versions(repo_path, &left_paths, &params.left_ref)?;
+        let right_versions = self.read_versions(repo_path, &right_paths, &params.right_ref)?;
+        let lfs_objects = self.lfs_objects_dir(repo_path);
 
         // 并行处理文件比较
         use rayon::prelude::*;
-        let file_diffs: Result<Vec<FileDiff>> = files_to_compare
+        Ok(files_to_compare
             .into_par_iter()
-            .map(|file_path| self.compare_git_file(repo_path, &file_path, params, config))
-            .collect();
-
-        file_diffs
-    }
-
-    /// 检查是否为Git仓库
-    fn is_git_repository(&self, path: &Path) -> Result<bool> {
-        let git_dir = path.join(".git");
-        Ok(git_dir.exists() || git_dir.is_dir())
-    }
-
-    /// 获取两个版本之间的变更文件列表
-    fn get_changed_files(&self, params: &GitComparisonParams) -> Result<Vec<String>> {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                &params.repository_path,
-                "diff",
-                "--name-status",
-                &params.left_ref,
-                &params.right_ref,
-            ])
-            .output()
-            .with_context(|| "Failed to execute git diff --name-status")?;
-
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Git diff command failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let mut changed_files = Vec::new();
-
-        for line in output_str.lines() {
-            if let Some((status, file_path)) = line.split_once('\t') {
-                match status {
-                    "A" => changed_files.push(file_path.to_string()), // Added
-                    "D" => changed_files.push(file_path.to_string()), // Deleted
-                    "M" => changed_files.push(file_path.to_string()), // Modified
-                    "R" => {
-                        // Renamed - git shows "R100\told_path\tnew_path"
-                        if let Some((_, new_path)) = file_path.split_once('\t') {
-                            changed_files.push(new_path.to_string());
-                        } else {
-                            changed_files.push(file_path.to_string());
-                        }
-                    }
-                    "C" => {
-                        // Copied - git shows "C100\tsource\tdestination"
-                        if let Some((_, new_path)) = file_path.split_once('\t') {
-                            changed_files.push(new_path.to_string());
-                        } else {
-                            changed_files.push(file_path.to_string());
-                        }
-                    }
-                    _ => changed_files.push(file_path.to_string()),
-                }
-            }
-        }
+            .zip(left_versions)
+            .zip(right_versions)
+            .map(|((file, left), right)| self.compare_git_file(&file, left, right, config, lfs_objects.as_deref()))
+            .collect())
+    }
 
-        Ok(changed_files)
+    /// 获取两个版本之间的变更文件列表，重命名的文件按新路径列出
+    ///
+    /// 引用可以是 `INDEX`（暂存区）或 `WORKTREE`（工作目录，未跟踪的文件按新增或删除列出）
+    fn get_changed_files(&self, repo_path: &Path, params: &GitComparisonParams) -> Result<Vec<ChangedFile>> {
+        let left = GitVersion::parse(&params.left_ref);
+        let right = GitVersion::parse(&params.right_ref);
+        with_cli_fallback(
+            "diff",
+            git_lib::changed_files(repo_path, left, right),
+            || git_cli::changed_files(repo_path, left, right),
+        )
     }
 
-    /// 比较Git中的单个文件
+    /// 比较Git中的单个文件；任一侧为 LFS 指针时按指针记录的对象比较，`lfs_objects` 为本地 LFS 对象存储目录
     fn compare_git_file(
         &self,
-        repo_path: &Path,
-        file_path: &str,
-        params: &GitComparisonParams,
+        file: &ChangedFile,
+        left: FileVersion,
+        right: FileVersion,
         config: &ComparisonConfig,
-    ) -> Result<FileDiff> {
-        // 获取文件在左侧版本的内容
-        let left_content =
-            self.get_file_content_at_commit(repo_path, file_path, &params.left_ref)?;
-
-        // 获取文件在右侧版本的内容
-        let right_content =
-            self.get_file_content_at_commit(repo_path, file_path, &params.right_ref)?;
-
-        // 获取文件状态
-        let file_status = self.get_file_status(repo_path, file_path, params)?;
-
-        // 处理内容
-        let left_lines: Vec<String> = if config.ignore_whitespace {
-            left_content
-                .lines()
-                .map(|line| line.trim().to_string())
-                .collect()
-        } else {
-            left_content.lines().map(|line| line.to_string()).collect()
-        };
+        lfs_objects: Option<&Path>,
+    ) -> FileDiff {
+        let left_exists = !matches!(file.change, GitChange::Added);
+        let right_exists = !matches!(file.change, GitChange::Deleted);
+        let is_pointer = |version: &FileVersion| parse_lfs_pointer(version.content.as_bytes()).is_some();
+        if (left_exists && is_pointer(&left)) || (right_exists && is_pointer(&right)) {
+            let side = |version: &FileVersion, exists: bool| {
+                exists.then(|| {
+                    LfsSide::from_bytes(version.binary.as_deref().unwrap_or(version.content.as_bytes()), lfs_objects)
+                })
+            };
+            let (left_side, right_side) = (side(&left, left_exists), side(&right, right_exists));
+            let status = match &file.change {
+                GitChange::Added => FileStatus::Added,
+                GitChange::Deleted => FileStatus::Deleted,
+                GitChange::Modified => FileStatus::Modified,
+                GitChange::Renamed { old_path } => FileStatus::Renamed { old_path: old_path.clone(), similarity: 0.0 },
+            };
+            let mut diff = lfs_file_diff(file.path.clone(), status, left_side.as_ref(), right_side.as_ref());
+            diff.left_stats.modified_time = left_side.and(left.modified_time);
+            diff.right_stats.modified_time = right_side.and(right.modified_time);
+            return diff;
+        }
 
-        let right_lines: Vec<String> = if config.ignore_whitespace {
-            right_content
-                .lines()
-                .map(|line| line.trim().to_string())
-                .collect()
+        let left_stats = left.stats();
+        let right_stats = right.stats();
+        let left_content = left.content;
+        let right_content = right.content;
+
+        // 任一侧超过大小上限时不做行比较，状态沿用 git 的判断
+        let oversized = [&left_content, &right_content]
+            .iter()
+            .any(|content| content.len() as u64 > config.max_file_size);
+        let LineDiff { lines: diff_lines, degraded } = if oversized {
+            LineDiff { lines: Vec::new(), degraded: false }
         } else {
-            right_content.lines().map(|line| line.to_string()).collect()
+            let left_lines = split_content_lines(&left_content, config);
+            let right_lines = split_content_lines(&right_content, config);
+            let mut diff = self.compute_git_line_diff(&left_lines, &right_lines, config);
+            mark_trailing_newline_change(&mut diff.lines, &left_content, &right_content, &right_lines, config);
+            diff
         };
 
-        // 计算差异
-        let diff_lines = self.compute_git_line_diff(&left_lines, &right_lines);
-
-        // 获取文件统计信息
-        let (left_stats, right_stats) = self.get_git_file_stats(repo_path, file_path, params)?;
+        let file_status = match &file.change {
+            GitChange::Added => FileStatus::Added,
+            GitChange::Deleted => FileStatus::Deleted,
+            GitChange::Modified => FileStatus::Modified,
+            GitChange::Renamed { old_path } => {
+                // 相似度与补丁解析一致，按两侧行集合计算；超过大小上限时只区分内容是否相同
+                let similarity = if left_content == right_content {
+                    1.0
+                } else if oversized {
+                    0.0
+                } else {
+                    line_similarity(
+                        &split_content_lines(&left_content, config),
+                        &split_content_lines(&right_content, config),
+                    )
+                };
+                FileStatus::Renamed { old_path: old_path.clone(), similarity }
+            }
+        };
 
         // 限制内容大小为 1MB
         let include_content = left_stats.size < 1024 * 1024 && right_stats.size < 1024 * 1024;
 
-        Ok(FileDiff {
-            path: file_path.to_string(),
+        FileDiff {
+            path: file.path.clone(),
             status: file_status,
             lines: diff_lines,
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded,
+            changed_symbols: Vec::new(),
+            truncated: oversized,
+            image_info: None,
+            is_lfs: false,
             original_content: if include_content {
                 Some(left_content)
             } else {
@@ -178,238 +275,200 @@ impl GitIntegration {
             },
             left_stats,
             right_stats,
-        })
-    }
-
-    /// 获取文件在特定commit的内容
-    fn get_file_content_at_commit(
-        &self,
-        repo_path: &Path,
-        file_path: &str,
-        commit_ref: &str,
-    ) -> Result<String> {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                &repo_path.to_string_lossy(),
-                "show",
-                &format!("{}:{}", commit_ref, file_path),
-            ])
-            .output()
-            .with_context(|| format!("Failed to get file content at commit {}", commit_ref))?;
-
-        if !output.status.success() {
-            // 文件可能在指定commit中不存在，返回空字符串
-            Ok(String::new())
-        } else {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
         }
     }
 
-    /// 获取文件在两个版本之间的状态
-    fn get_file_status(
+    /// `head_ref` 相对 `base_ref` 新增或修改的行及 `head_ref` 一侧的内容，返回仓库根目录与各文件的变更行
+    ///
+    /// 重命名的文件按新路径列出，行号对应新内容；删除的文件、二进制文件、超过大小上限的文件
+    /// 以及只有删除或移动行的文件不列出
+    pub fn changed_line_ranges(
         &self,
-        repo_path: &Path,
-        file_path: &str,
-        params: &GitComparisonParams,
-    ) -> Result<FileStatus> {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                &repo_path.to_string_lossy(),
-                "diff",
-                "--name-status",
-                &params.left_ref,
-                &params.right_ref,
-                "--",
-                file_path,
-            ])
-            .output()
-            .with_context(|| "Failed to get file status")?;
-
-        if !output.status.success() {
-            return Ok(FileStatus::Unchanged);
-        }
+        repo_path: &str,
+        base_ref: &str,
+        head_ref: &str,
+    ) -> Result<(PathBuf, Vec<ChangedLineRanges>)> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        let params = GitComparisonParams {
+            repository_path: repo_path.to_string(),
+            left_ref: base_ref.to_string(),
+            right_ref: head_ref.to_string(),
+            file_paths: Vec::new(),
+        };
+        let files: Vec<ChangedFile> = self
+            .get_changed_files(&repo_root, &params)?
+            .into_iter()
+            .filter(|file| !matches!(file.change, GitChange::Deleted))
+            .collect();
+        let left_paths: Vec<&str> = files.iter().map(ChangedFile::left_path).collect();
+        let right_paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        let left_versions = self.read_versions(&repo_root, &left_paths, base_ref)?;
+        let right_versions = self.read_versions(&repo_root, &right_paths, head_ref)?;
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        for line in output_str.lines() {
-            if let Some((status, path)) = line.split_once('\t') {
-                if path == file_path {
-                    match status {
-                        "A" => return Ok(FileStatus::Added),
-                        "D" => return Ok(FileStatus::Deleted),
-                        "M" => return Ok(FileStatus::Modified),
-                        "R" => {
-                            // 对于重命名，我们需要获取旧路径
-                            if let Some(old_path) =
-                                self.get_renamed_from_path(repo_path, file_path, params)?
-                            {
-                                return Ok(FileStatus::Renamed { old_path });
-                            }
-                        }
-                        "C" => return Ok(FileStatus::Added), // Copy treated as add
-                        _ => {}
+        let config = ComparisonConfig::default();
+        use rayon::prelude::*;
+        let changed = files
+            .into_par_iter()
+            .zip(left_versions)
+            .zip(right_versions)
+            .filter(|(_, right)| right.encoding.is_some())
+            .filter_map(|((file, left), right)| {
+                let content = right.content.clone();
+                let diff = self.compare_git_file(&file, left, right, &config, None);
+                let mut ranges: Vec<(u32, u32)> = Vec::new();
+                let changed_lines = diff.lines.iter().filter(|line| {
+                    !line.is_placeholder && matches!(line.diff_type, DiffType::Insert | DiffType::Replace)
+                });
+                for line in changed_lines.filter_map(|line| line.right_line_number) {
+                    match ranges.last_mut() {
+                        Some((_, end)) if *end + 1 >= line => *end = (*end).max(line),
+                        _ => ranges.push((line, line)),
                     }
                 }
-            }
-        }
+                (!ranges.is_empty()).then_some(ChangedLineRanges { path: diff.path, content, ranges })
+            })
+            .collect();
+        Ok((repo_root, changed))
+    }
 
-        Ok(FileStatus::Unchanged)
+    /// `path` 所在仓库的本地 LFS 对象存储目录，不在仓库中时为空
+    pub(crate) fn lfs_objects_dir(&self, path: &Path) -> Option<PathBuf> {
+        with_cli_fallback("repository lookup", git_lib::common_dir(path), || git_cli::common_dir(path))
+            .ok()
+            .map(|dir| dir.join("lfs").join("objects"))
     }
 
-    /// 获取重命名文件的原始路径
-    fn get_renamed_from_path(
-        &self,
-        repo_path: &Path,
-        new_path: &str,
-        params: &GitComparisonParams,
-    ) -> Result<Option<String>> {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                &repo_path.to_string_lossy(),
-                "log",
-                "--follow",
-                "--name-status",
-                "--pretty=format:",
-                &format!("{}..{}", params.left_ref, params.right_ref),
-                "--",
-                new_path,
-            ])
-            .output()
-            .with_context(|| "Failed to follow file renames")?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        for line in output_str.lines() {
-            if let Some((status, path)) = line.split_once('\t') {
-                if status == "R" && path != new_path {
-                    return Ok(Some(path.to_string()));
-                }
-            }
-        }
+    /// 获取 `path` 所在仓库的根目录
+    pub(crate) fn repository_root(&self, path: &Path) -> Result<PathBuf> {
+        with_cli_fallback("discover", git_lib::repository_root(path), || git_cli::repository_root(path))
+    }
 
-        Ok(None)
+    /// 文件是否被 `file_paths` 选中（包含其中任一片段或匹配通配符），为空时选中所有文件
+    pub(crate) fn matches_file_paths(&self, file: &str, file_paths: &[String]) -> bool {
+        file_paths.is_empty()
+            || file_paths
+                .iter()
+                .any(|pattern| file.contains(pattern) || self.matches_pattern(file, pattern))
     }
 
-    /// 计算Git文件行级别的差异
-    fn compute_git_line_diff(&self, lines_a: &[String], lines_b: &[String]) -> Vec<DiffLine> {
-        use similar::{Algorithm, ChangeTag, TextDiff};
-
-        let text_a = lines_a.join("\n");
-        let text_b = lines_b.join("\n");
-
-        let diff = TextDiff::configure()
-            .algorithm(Algorithm::Myers)
-            .diff_lines(&text_a, &text_b);
-
-        let mut result = Vec::new();
-        let mut left_line_num = 1u32;
-        let mut right_line_num = 1u32;
-
-        for change in diff.iter_all_changes() {
-            let content = change.value().trim_end_matches('\n').to_string();
-
-            match change.tag() {
-                ChangeTag::Equal => {
-                    result.push(DiffLine {
-                        left_line_number: Some(left_line_num),
-                        right_line_number: Some(right_line_num),
-                        diff_type: DiffType::Equal,
-                        content,
-                        is_placeholder: false,
-                    });
-                    left_line_num += 1;
-                    right_line_num += 1;
-                }
-                ChangeTag::Delete => {
-                    result.push(DiffLine {
-                        left_line_number: Some(left_line_num),
-                        right_line_number: None,
-                        diff_type: DiffType::Delete,
-                        content,
-                        is_placeholder: false,
-                    });
-                    left_line_num += 1;
-                }
-                ChangeTag::Insert => {
-                    result.push(DiffLine {
-                        left_line_number: None,
-                        right_line_number: Some(right_line_num),
-                        diff_type: DiffType::Insert,
-                        content,
-                        is_placeholder: false,
-                    });
-                    right_line_num += 1;
-                }
-            }
-        }
+    /// `commit_ref` 版本中 `scope` 目录下（为空时为整个仓库）的所有文件，路径相对仓库根目录
+    ///
+    /// `INDEX` 与 `WORKTREE` 均列出暂存区中的文件
+    pub(crate) fn files_at_commit(&self, repo_root: &Path, commit_ref: &str, scope: Option<&Path>) -> Result<Vec<String>> {
+        let pathspec = scope.map_or_else(|| ".".to_string(), |scope| scope_pathspec(repo_root, scope));
+        with_cli_fallback(
+            "tree walk",
+            git_lib::files_at_commit(repo_root, commit_ref, &pathspec),
+            || git_cli::files_at_commit(repo_root, commit_ref, &pathspec),
+        )
+    }
 
-        result
+    /// 工作目录中相对 `commit_ref` 有变化的文件（含已删除的已跟踪文件）及未被忽略的未跟踪文件
+    ///
+    /// 只列出 `scope` 目录下的文件，路径相对仓库根目录；不做重命名检测，重命名表现为一删一增
+    pub(crate) fn working_tree_changes(&self, repo_root: &Path, commit_ref: &str, scope: &Path) -> Result<Vec<String>> {
+        let pathspec = scope_pathspec(repo_root, scope);
+        with_cli_fallback(
+            "workdir diff",
+            git_lib::working_tree_changes(repo_root, commit_ref, &pathspec),
+            || git_cli::working_tree_changes(repo_root, commit_ref, &pathspec),
+        )
     }
 
-    /// 获取Git文件的统计信息
-    fn get_git_file_stats(
-        &self,
-        repo_path: &Path,
-        file_path: &str,
-        params: &GitComparisonParams,
-    ) -> Result<(FileStats, FileStats)> {
-        // 左侧版本统计
-        let left_content =
-            self.get_file_content_at_commit(repo_path, file_path, &params.left_ref)?;
-        let left_size = left_content.len() as u64;
-        let left_line_count = left_content.lines().count() as u32;
-
-        // 右侧版本统计
-        let right_content =
-            self.get_file_content_at_commit(repo_path, file_path, &params.right_ref)?;
-        let right_size = right_content.len() as u64;
-        let right_line_count = right_content.lines().count() as u32;
-
-        // 获取修改时间（获取commit时间）
-        let left_time = self.get_commit_time(repo_path, &params.left_ref)?;
-        let right_time = self.get_commit_time(repo_path, &params.right_ref)?;
-
-        let left_stats = FileStats {
-            size: left_size,
-            line_count: left_line_count,
-            modified_time: Some(left_time),
-        };
+    /// 获取文件在特定commit的原始内容，文件在该版本中不存在时返回 `None`
+    ///
+    /// `INDEX` 读取暂存区中的内容，`WORKTREE` 读取仓库根目录 `repo_path` 下的磁盘文件
+    pub(crate) fn blob_at_commit(&self, repo_path: &Path, file_path: &str, commit_ref: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.blobs_at_commit(repo_path, &[file_path], commit_ref)?.pop().flatten())
+    }
 
-        let right_stats = FileStats {
-            size: right_size,
-            line_count: right_line_count,
-            modified_time: Some(right_time),
-        };
+    /// 批量读取文件在特定commit的原始内容，与 `file_paths` 一一对应
+    fn blobs_at_commit(&self, repo_path: &Path, file_paths: &[&str], commit_ref: &str) -> Result<Vec<Option<Vec<u8>>>> {
+        if commit_ref == WORKTREE_REF {
+            return file_paths
+                .iter()
+                .map(|file_path| {
+                    let path = repo_path.join(file_path);
+                    if !path.is_file() {
+                        return Ok(None);
+                    }
+                    Ok(Some(std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?))
+                })
+                .collect();
+        }
+        with_cli_fallback(
+            "blob lookup",
+            git_lib::blobs_at_commit(repo_path, file_paths, commit_ref),
+            || git_cli::blobs_at_commit(repo_path, file_paths, commit_ref),
+        )
+    }
 
-        Ok((left_stats, right_stats))
+    /// 读取各文件在一侧版本中的内容（检测编码）与修改时间，与 `file_paths` 一一对应
+    ///
+    /// 与文件系统比较共用同一解码逻辑；无法解码时按 UTF-8 有损转换，编码为 `None`；文件在该版本中不存在时内容为空
+    fn read_versions(&self, repo_path: &Path, file_paths: &[&str], reference: &str) -> Result<Vec<FileVersion>> {
+        let blobs = self.blobs_at_commit(repo_path, file_paths, reference)?;
+        let times = self.version_times(repo_path, file_paths, reference)?;
+        Ok(blobs
+            .into_iter()
+            .zip(times)
+            .map(|(bytes, modified_time)| {
+                let (content, encoding, binary) = match bytes {
+                    None => (String::new(), None, None),
+                    Some(bytes) => match decode_text(&bytes) {
+                        Some(decoded) => (decoded.text, Some(decoded.encoding), None),
+                        None => (String::from_utf8_lossy(&bytes).to_string(), None, Some(bytes)),
+                    },
+                };
+                FileVersion { content, encoding, modified_time, binary }
+            })
+            .collect())
     }
 
-    /// 获取commit的Unix时间戳
-    fn get_commit_time(&self, repo_path: &Path, commit_ref: &str) -> Result<i64> {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                &repo_path.to_string_lossy(),
-                "show",
-                "-s",
-                "--format=%ct",
-                commit_ref,
-            ])
-            .output()
-            .with_context(|| format!("Failed to get commit time for {}", commit_ref))?;
-
-        if !output.status.success() {
-            return Ok(0);
+    /// 计算Git文件行级别的差异
+    fn compute_git_line_diff(&self, lines_a: &[String], lines_b: &[String], config: &ComparisonConfig) -> LineDiff {
+        let mut diff = crate::diff::engine::line_diff(lines_a, lines_b, config);
+        collapse_ignored_regions(&mut diff.lines, lines_a, lines_b, &config.ignore_regions);
+        if !diff.degraded {
+            crate::diff::engine::detect_moved_blocks(&mut diff.lines, config.min_moved_lines as usize, config.ignore_case);
+        }
+        diff
+    }
+
+    /// 一侧版本中各文件的修改时间，与 `file_paths` 一一对应：提交为提交时间，
+    /// 工作目录为磁盘文件的修改时间（文件不存在时为空），暂存区为文件暂存时记录的修改时间
+    fn version_times(&self, repo_path: &Path, file_paths: &[&str], reference: &str) -> Result<Vec<Option<i64>>> {
+        match GitVersion::parse(reference) {
+            GitVersion::Commit(EMPTY_TREE_REF) => Ok(vec![None; file_paths.len()]),
+            GitVersion::Commit(commit_ref) => {
+                let time = self.get_commit_time(repo_path, commit_ref)?;
+                Ok(vec![Some(time); file_paths.len()])
+            }
+            GitVersion::Worktree => Ok(file_paths
+                .iter()
+                .map(|file_path| {
+                    std::fs::metadata(repo_path.join(file_path))
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|duration| duration.as_secs() as i64)
+                })
+                .collect()),
+            GitVersion::Index => with_cli_fallback(
+                "index lookup",
+                git_lib::index_times(repo_path, file_paths),
+                || git_cli::index_times(repo_path, file_paths),
+            ),
         }
+    }
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let timestamp_str = output_str.trim();
-        timestamp_str
-            .parse::<i64>()
-            .with_context(|| "Invalid timestamp format")
+    /// 获取commit的Unix时间戳
+    pub(crate) fn get_commit_time(&self, repo_path: &Path, commit_ref: &str) -> Result<i64> {
+        with_cli_fallback(
+            "commit lookup",
+            git_lib::commit_time(repo_path, commit_ref),
+            || git_cli::commit_time(repo_path, commit_ref),
+        )
     }
 
     /// 检查文件路径是否匹配模式
@@ -429,51 +488,482 @@ impl GitIntegration {
         }
     }
 
-    /// 获取分支和标签列表
-    pub fn get_refs(&self, repo_path: &str) -> Result<Vec<(String, String)>> {
-        let repo_path = Path::new(repo_path);
+    /// 获取分支和标签列表，按所指提交的提交时间倒序（相同时依次为本地分支、远程分支、标签，再按名称）；
+    /// 远程分支与同名本地分支指向同一提交时只列出本地分支。`repo_path` 可以是仓库内的任意目录
+    pub fn get_refs(&self, repo_path: &str) -> Result<Vec<GitRef>> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        let mut refs = with_cli_fallback("reference listing", git_lib::refs(&repo_root), || git_cli::refs(&repo_root))?;
 
-        if !self.is_git_repository(repo_path)? {
-            return Err(anyhow::anyhow!("Not a git repository"));
-        }
+        let local: HashSet<(String, String)> = refs
+            .iter()
+            .filter(|git_ref| git_ref.kind == GitRefKind::Branch)
+            .map(|git_ref| (git_ref.name.clone(), git_ref.target_hash.clone()))
+            .collect();
+        refs.retain(|git_ref| {
+            git_ref.kind != GitRefKind::RemoteBranch
+                || !git_ref.name.split_once('/').is_some_and(|(_, branch)| {
+                    local.contains(&(branch.to_string(), git_ref.target_hash.clone()))
+                })
+        });
+        refs.sort_by(|a, b| {
+            b.committed_at
+                .cmp(&a.committed_at)
+                .then(a.kind.cmp(&b.kind))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(refs)
+    }
 
-        // 获取分支
-        let branches_output = Command::new("git")
-            .args(["-C", repo_path.to_string_lossy().as_ref(), "branch", "-a"])
-            .output()
-            .with_context(|| "Failed to get branches")?;
-
-        // 获取标签
-        let tags_output = Command::new("git")
-            .args(["-C", repo_path.to_string_lossy().as_ref(), "tag"])
-            .output()
-            .with_context(|| "Failed to get tags")?;
-
-        let mut refs = Vec::new();
-
-        // 处理分支
-        if branches_output.status.success() {
-            let branches_str = String::from_utf8_lossy(&branches_output.stdout);
-            for line in branches_str.lines() {
-                let line_trim = line.trim().replace('*', "");
-                let branch = line_trim.trim();
-                if !branch.is_empty() {
-                    refs.push((branch.to_string(), "branch".to_string()));
-                }
+    /// 引用（分支、标签、提交或 `HEAD~1` 等表达式）所指提交的完整哈希、作者、提交时间与标题，
+    /// `repo_path` 可以是仓库内的任意目录
+    pub fn get_commit_info(&self, repo_path: &str, reference: &str) -> Result<CommitInfo> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        with_cli_fallback(
+            "commit lookup",
+            git_lib::commit_at(&repo_root, reference),
+            || git_cli::commit_at(&repo_root, reference),
+        )?
+        .ok_or_else(|| GitError::UnknownRevision(reference.to_string()).into())
+    }
+
+    /// 解析单个提交比较的左侧版本，返回提交的完整哈希、所用父提交（根提交为 `EMPTY_TREE_REF`）与父提交数
+    ///
+    /// `parent_index` 从 0 开始，缺省为第一个父提交；超出父提交数时报错
+    pub(crate) fn resolve_commit_parent(
+        &self,
+        repo_path: &str,
+        commit_ref: &str,
+        parent_index: Option<u32>,
+    ) -> Result<(String, String, usize)> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        let (hash, parents) = with_cli_fallback(
+            "commit lookup",
+            git_lib::commit_parents(&repo_root, commit_ref),
+            || git_cli::commit_parents(&repo_root, commit_ref),
+        )?
+        .ok_or_else(|| GitError::UnknownRevision(commit_ref.to_string()))?;
+        let parent = match parent_index {
+            None if parents.is_empty() => EMPTY_TREE_REF.to_string(),
+            index => parents.get(index.unwrap_or(0) as usize).cloned().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Commit {} has {} parent(s), parent index {} is out of range",
+                    commit_ref,
+                    parents.len(),
+                    index.unwrap_or(0)
+                )
+            })?,
+        };
+        Ok((hash, parent, parents.len()))
+    }
+
+    /// 两个引用所指提交的合并基础，没有共同祖先时为空
+    pub(crate) fn merge_base(&self, repo_path: &str, left_ref: &str, right_ref: &str) -> Result<Option<String>> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        with_cli_fallback(
+            "merge base lookup",
+            git_lib::merge_base(&repo_root, left_ref, right_ref),
+            || git_cli::merge_base(&repo_root, left_ref, right_ref),
+        )
+    }
+
+    /// 分页列出提交，用于选择比较的版本；同一仓库状态下分页结果稳定
+    ///
+    /// `search` 不区分大小写地匹配提交标题与作者，或作为提交哈希前缀匹配；先筛选再分页。
+    /// `repository_path` 可以是仓库内的任意目录，`path` 始终相对仓库根目录
+    pub fn get_commits(&self, query: &CommitQuery) -> Result<Vec<CommitInfo>> {
+        let repo_root = self.repository_root(Path::new(&query.repository_path))?;
+        let repo_path = repo_root.as_path();
+
+        let commit_ref = query.reference.as_deref().filter(|r| !r.is_empty()).unwrap_or("HEAD");
+        // git 路径规范统一使用 `/`，不带末尾分隔符
+        let path = query.path.as_deref().map(|path| path.replace('\\', "/").trim_end_matches('/').to_string());
+        let path = path.as_deref().filter(|path| !path.is_empty() && *path != ".");
+        let search = query.search.as_deref().map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty());
+        let matches = |commit: &CommitInfo| match &search {
+            Some(search) => {
+                commit.subject.to_lowercase().contains(search)
+                    || commit.author.to_lowercase().contains(search)
+                    || commit.hash.starts_with(search.as_str())
             }
+            None => true,
+        };
+
+        with_cli_fallback(
+            "commit listing",
+            git_lib::commits(repo_path, commit_ref, path, &matches, query.offset, query.limit),
+            || git_cli::commits(repo_path, commit_ref, path, &matches, query.offset, query.limit),
+        )
+    }
+
+    /// 文件的修改历史（最近的在前，最多 `limit` 项），跟随重命名；返回的提交哈希可直接用作 `GitComparisonParams` 的引用
+    ///
+    /// `file_path` 可以是相对仓库根目录的路径，也可以是仓库内的绝对路径；在 `HEAD` 中已删除的文件从删除它的提交开始列出
+    pub fn get_file_history(&self, repo_path: &str, file_path: &str, limit: usize) -> Result<Vec<FileHistoryEntry>> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        let relative = repository_relative_path(&repo_root, file_path)
+            .ok_or_else(|| anyhow::anyhow!("File is outside the repository: {}", file_path))?;
+        if repo_root.join(&relative).is_dir() {
+            return Err(anyhow::anyhow!("Not a file: {}", file_path));
+        }
+        with_cli_fallback(
+            "file history",
+            git_lib::file_history(&repo_root, &relative, limit),
+            || git_cli::file_history(&repo_root, &relative, limit),
+        )
+    }
+
+    /// 工作目录中文件第 `line_start`..=`line_end` 行的来源提交，`line_end` 超出文件末尾时截断
+    ///
+    /// `file_path` 可以是相对仓库根目录的路径，也可以是仓库内的绝对路径；
+    /// 未提交的行 `committed` 为 false，不在 `HEAD` 中的文件（如未跟踪文件）所有行均未提交
+    pub fn get_blame(&self, repo_path: &str, file_path: &str, line_start: u32, line_end: u32) -> Result<Vec<BlameLine>> {
+        let repo_root = self.repository_root(Path::new(repo_path))?;
+        let relative = repository_relative_path(&repo_root, file_path)
+            .ok_or_else(|| anyhow::anyhow!("File is outside the repository: {}", file_path))?;
+        let full_path = repo_root.join(&relative);
+        if full_path.is_dir() {
+            return Err(anyhow::anyhow!("Not a file: {}", file_path));
         }
+        let content = std::fs::read(&full_path).with_context(|| format!("Failed to read {}", full_path.display()))?;
+        let text = decode_text(&content).map_or_else(|| String::from_utf8_lossy(&content).to_string(), |decoded| decoded.text);
+        let lines: Vec<&str> = text.lines().collect();
 
-        // 处理标签
-        if tags_output.status.success() {
-            let tags_str = String::from_utf8_lossy(&tags_output.stdout);
-            for tag in tags_str.lines() {
-                let tag_trim = tag.trim();
-                if !tag_trim.is_empty() {
-                    refs.push((tag_trim.to_string(), "tag".to_string()));
+        if line_start == 0 || line_start > line_end {
+            return Err(anyhow::anyhow!("Invalid line range: {}-{}", line_start, line_end));
+        }
+        if line_start as usize > lines.len() {
+            return Err(anyhow::anyhow!("Line {} is out of range for {} ({} lines)", line_start, relative, lines.len()));
+        }
+        let line_end = line_end.min(lines.len() as u32);
+
+        let blames = if self.blob_at_commit(&repo_root, &relative, "HEAD")?.is_some() {
+            with_cli_fallback(
+                "blame",
+                git_lib::blame_lines(&repo_root, &relative, &content, line_start, line_end),
+                || git_cli::blame_lines(&repo_root, &relative, line_start, line_end),
+            )?
+        } else {
+            Vec::new()
+        };
+
+        Ok((line_start..=line_end)
+            .map(|line| {
+                let blame = blames.get((line - line_start) as usize).cloned().flatten();
+                BlameLine {
+                    line,
+                    content: lines[line as usize - 1].to_string(),
+                    committed: blame.is_some(),
+                    blame,
+                }
+            })
+            .collect())
+    }
+
+    /// 对比文件第 `line` 行（按 `right_ref` 版本计）在 `left_ref` 与 `right_ref` 之间的变化，并查询引入该行的提交
+    ///
+    /// `file_path` 为工作目录中的文件路径，仓库由其所在目录确定。文件在 `left_ref` 中不存在时该行视为新增；
+    /// 在 `right_ref` 中不存在或行号超出范围时返回错误
+    pub fn line_history(&self, file_path: &Path, line: u32, left_ref: &str, right_ref: &str) -> Result<LineHistory> {
+        let dir = file_path.parent().unwrap_or(Path::new("."));
+        let repo_root = self.repository_root(dir)?;
+        let relative = std::fs::canonicalize(dir)
+            .ok()
+            .zip(std::fs::canonicalize(&repo_root).ok())
+            .and_then(|(dir, root)| dir.strip_prefix(root).ok().map(Path::to_path_buf))
+            .and_then(|dir| Some(dir.join(file_path.file_name()?)))
+            .ok_or_else(|| anyhow::anyhow!("File is outside the repository: {}", file_path.display()))?;
+        // git 路径规范统一使用 `/`
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        let config = ComparisonConfig::default();
+        let read_lines = |commit_ref: &str| -> Result<Option<Vec<String>>> {
+            Ok(self.blob_at_commit(&repo_root, &relative, commit_ref)?.map(|bytes| {
+                let text = decode_text(&bytes).map_or_else(|| String::from_utf8_lossy(&bytes).to_string(), |decoded| decoded.text);
+                split_content_lines(&text, &config)
+            }))
+        };
+        let right_lines = read_lines(right_ref)?
+            .ok_or_else(|| anyhow::anyhow!("File {} does not exist at {}", relative, right_ref))?;
+        let right_content = line
+            .checked_sub(1)
+            .and_then(|i| right_lines.get(i as usize))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Line {} is out of range for {} at {}", line, relative, right_ref))?;
+        let left_lines = read_lines(left_ref)?;
+        let exists_at_left = left_lines.is_some();
+
+        let (change, left_line) = match &left_lines {
+            None => (LineChange::Added, None),
+            Some(left_lines) => {
+                let lines = line_diff(left_lines, &right_lines, &config).lines;
+                let pos = lines
+                    .iter()
+                    .position(|l| l.right_line_number == Some(line))
+                    .expect("line diff covers every right-hand line");
+                if lines[pos].diff_type == DiffType::Equal {
+                    (LineChange::Unchanged, lines[pos].left_line_number)
+                } else {
+                    // 同一变更组内的删除行视为被修改的原内容，按次序与插入行对应，多出的插入行为新增
+                    let start = lines[..pos].iter().rposition(|l| l.diff_type == DiffType::Equal).map_or(0, |i| i + 1);
+                    let end = lines[pos..].iter().position(|l| l.diff_type == DiffType::Equal).map_or(lines.len(), |i| pos + i);
+                    let group = &lines[start..end];
+                    let deleted: Vec<u32> = group.iter().filter_map(|l| l.left_line_number).collect();
+                    let offset = group[..pos - start].iter().filter(|l| l.right_line_number.is_some()).count();
+                    match deleted.get(offset) {
+                        Some(&left) => (LineChange::Modified, Some(left)),
+                        None => (LineChange::Added, None),
+                    }
                 }
             }
+        };
+        let left_content = left_line
+            .zip(left_lines.as_ref())
+            .and_then(|(left, lines)| lines.get(left as usize - 1).cloned());
+
+        Ok(LineHistory {
+            file_path: relative.clone(),
+            left_ref: left_ref.to_string(),
+            right_ref: right_ref.to_string(),
+            line,
+            exists_at_left,
+            left_line,
+            left_content,
+            right_content,
+            change,
+            blame: self.blame_line(&repo_root, &relative, line, right_ref)?,
+        })
+    }
+
+    /// 查询 `commit_ref` 版本中第 `line` 行的来源提交
+    fn blame_line(&self, repo_root: &Path, file_path: &str, line: u32, commit_ref: &str) -> Result<Option<LineBlame>> {
+        with_cli_fallback(
+            "blame",
+            git_lib::blame_line(repo_root, file_path, line, commit_ref),
+            || git_cli::blame_line(repo_root, file_path, line, commit_ref),
+        )
+    }
+}
+
+/// 文件相对仓库根目录的路径（使用 `/` 分隔）；绝对路径按规范化后的目录（目录不存在时按字面）去掉根目录，文件本身可以已不存在
+fn repository_relative_path(repo_root: &Path, file_path: &str) -> Option<String> {
+    let path = Path::new(file_path);
+    let relative = if path.is_absolute() {
+        let roots = [Some(repo_root.to_path_buf()), std::fs::canonicalize(repo_root).ok()];
+        let canonical = path
+            .parent()
+            .and_then(|dir| std::fs::canonicalize(dir).ok())
+            .zip(path.file_name())
+            .map(|(dir, name)| dir.join(name));
+        [canonical, Some(path.to_path_buf())]
+            .iter()
+            .flatten()
+            .find_map(|path| roots.iter().flatten().find_map(|root| path.strip_prefix(root).ok()))?
+            .to_string_lossy()
+            .to_string()
+    } else {
+        file_path.to_string()
+    };
+    let relative = relative.replace('\\', "/");
+    let relative = relative.trim_matches('/');
+    (!relative.is_empty() && !relative.split('/').any(|part| part == "..")).then(|| relative.to_string())
+}
+
+/// `scope` 相对仓库根目录的路径规范，`scope` 为根目录或不在仓库内时为 `.`
+fn scope_pathspec(repo_root: &Path, scope: &Path) -> String {
+    std::fs::canonicalize(scope)
+        .ok()
+        .zip(std::fs::canonicalize(repo_root).ok())
+        .and_then(|(scope, root)| scope.strip_prefix(root).ok().map(Path::to_path_buf))
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map_or_else(|| ".".to_string(), |relative| relative.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::test_repo::TestRepo;
+
+    fn params(repo: &TestRepo, left: &str, right: &str) -> GitComparisonParams {
+        GitComparisonParams {
+            repository_path: repo.path_str().to_string(),
+            left_ref: left.to_string(),
+            right_ref: right.to_string(),
+            file_paths: Vec::new(),
         }
+    }
 
-        Ok(refs)
+    #[test]
+    fn compare_reads_changes_from_fixture_repository() {
+        let repo = TestRepo::new();
+        repo.write("src/app.py", "a = 1\nb = 2\n").write("old.txt", "x\n");
+        let first = repo.commit("first");
+        repo.write("src/app.py", "a = 1\nb = 3\n").remove("old.txt").write("new.txt", "y\n");
+        let second = repo.commit("second");
+
+        let git = GitIntegration::new();
+        let mut diffs = git.compare(&params(&repo, &first, &second), &ComparisonConfig::default()).unwrap();
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        let statuses: Vec<(&str, &FileStatus)> = diffs.iter().map(|d| (d.path.as_str(), &d.status)).collect();
+        assert!(matches!(
+            statuses[..],
+            [("new.txt", FileStatus::Added), ("old.txt", FileStatus::Deleted), ("src/app.py", FileStatus::Modified)]
+        ));
+        assert_eq!(diffs[2].original_content.as_deref(), Some("a = 1\nb = 2\n"));
+        assert_eq!(diffs[2].modified_content.as_deref(), Some("a = 1\nb = 3\n"));
+    }
+
+    #[test]
+    fn worktree_and_index_versions() {
+        let repo = TestRepo::new();
+        repo.write("a.txt", "committed\n");
+        repo.commit("init");
+        repo.write("a.txt", "staged\n");
+        repo.git(&["add", "a.txt"]);
+        repo.write("a.txt", "working\n").write("untracked.txt", "u\n");
+
+        let git = GitIntegration::new();
+        assert_eq!(git.blob_at_commit(repo.path(), "a.txt", "HEAD").unwrap().unwrap(), b"committed\n");
+        assert_eq!(git.blob_at_commit(repo.path(), "a.txt", INDEX_REF).unwrap().unwrap(), b"staged\n");
+        assert!(git.blob_at_commit(repo.path(), "missing.txt", "HEAD").unwrap().is_none());
+
+        let diffs = git.compare(&params(&repo, INDEX_REF, WORKTREE_REF), &ComparisonConfig::default()).unwrap();
+        let mut paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, ["a.txt", "untracked.txt"]);
+    }
+
+    #[test]
+    fn ignore_case_in_git_comparison() {
+        let repo = TestRepo::new();
+        repo.write("app.js", "let UserName = getUserID();\nİstanbul\nSTRASSE\n");
+        let first = repo.commit("first");
+        repo.write("app.js", "let username = GetUserId();\ni\u{307}stanbul\nstraße\n");
+        let second = repo.commit("second");
+
+        let git = GitIntegration::new();
+        let config = ComparisonConfig {
+            ignore_case: true,
+            ..ComparisonConfig::default()
+        };
+        let diffs = git.compare(&params(&repo, &first, &second), &config).unwrap();
+        let lines = &diffs[0].lines;
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.diff_type == DiffType::Equal));
+        assert_eq!(lines[0].content, "let UserName = getUserID();");
+
+        let diffs = git.compare(&params(&repo, &first, &second), &ComparisonConfig::default()).unwrap();
+        assert!(diffs[0].lines.iter().all(|line| line.diff_type != DiffType::Equal));
+    }
+
+    #[test]
+    fn refs_and_commit_time() {
+        let repo = TestRepo::new();
+        repo.write("a.txt", "one\n");
+        let head = repo.commit("init");
+        repo.git(&["tag", "-a", "v1", "-m", "release"]);
+        repo.git(&["branch", "feature"]);
+
+        let git = GitIntegration::new();
+        let refs = git.get_refs(repo.path_str()).unwrap();
+        let names: HashSet<&str> = refs.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, HashSet::from(["main", "feature", "v1"]));
+        assert!(refs.iter().all(|r| r.target_hash == head));
+        assert!(refs.iter().any(|r| r.name == "main" && r.is_head));
+        assert_eq!(refs.iter().find(|r| r.name == "v1").unwrap().message.as_deref(), Some("release"));
+
+        let time: i64 = repo.git(&["show", "-s", "--format=%ct", "HEAD"]).parse().unwrap();
+        assert_eq!(git.get_commit_time(repo.path(), "main").unwrap(), time);
+        assert_eq!(git.get_commit_info(repo.path_str(), "v1").unwrap().hash, head);
+    }
+
+    fn commits(git: &GitIntegration, repository_path: &Path) -> Vec<String> {
+        let query = CommitQuery {
+            repository_path: repository_path.to_str().unwrap().to_string(),
+            reference: None,
+            path: None,
+            search: None,
+            limit: 10,
+            offset: 0,
+        };
+        git.get_commits(&query).unwrap().into_iter().map(|c| c.hash).collect()
+    }
+
+    #[test]
+    fn worktree_subdirectory_is_accepted() {
+        let repo = TestRepo::new();
+        repo.write("src/app.py", "a = 1\n");
+        let first = repo.commit("first");
+        repo.write("src/app.py", "a = 2\n");
+        let second = repo.commit("second");
+
+        let outer = tempfile::tempdir().unwrap();
+        let worktree = outer.path().join("wt");
+        repo.git(&["worktree", "add", "-q", "-b", "wt-branch", worktree.to_str().unwrap()]);
+        let subdir = worktree.join("src");
+
+        let git = GitIntegration::new();
+        assert_eq!(git.repository_root(&subdir).unwrap(), worktree.canonicalize().unwrap());
+        for path in [&worktree, &subdir] {
+            let names: HashSet<String> = git.get_refs(path.to_str().unwrap()).unwrap().into_iter().map(|r| r.name).collect();
+            assert!(names.contains("wt-branch") && names.contains("main"), "{:?}", names);
+            assert_eq!(commits(&git, path), [second.clone(), first.clone()]);
+
+            let params = GitComparisonParams {
+                repository_path: path.to_str().unwrap().to_string(),
+                left_ref: first.clone(),
+                right_ref: second.clone(),
+                file_paths: Vec::new(),
+            };
+            let diffs = git.compare(&params, &ComparisonConfig::default()).unwrap();
+            assert_eq!(diffs.len(), 1);
+            assert_eq!(diffs[0].path, "src/app.py");
+        }
+
+        let outside = tempfile::tempdir().unwrap();
+        assert!(git.get_refs(outside.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn submodule_checkout_and_gitlink_changes() {
+        let library = TestRepo::new();
+        library.write("lib.py", "x = 1\n");
+        let library_head = library.commit("library");
+
+        let repo = TestRepo::new();
+        repo.write("main.py", "import lib\n");
+        let first = repo.commit("first");
+        repo.git(&["-c", "protocol.file.allow=always", "submodule", "add", "-q", library.path_str(), "vendor/lib"]);
+        repo.write("main.py", "import lib\nlib.x\n");
+        let second = repo.commit("add submodule");
+
+        // 超级项目中新增子模块：gitlink 不作为文件比较
+        let git = GitIntegration::new();
+        let mut diffs = git.compare(&params(&repo, &first, &second), &ComparisonConfig::default()).unwrap();
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        assert!(paths.contains(&"main.py"), "{:?}", paths);
+        assert!(!paths.contains(&"vendor/lib"), "{:?}", paths);
+
+        // 子模块检出目录按子模块自身的仓库处理
+        let checkout = repo.file("vendor/lib");
+        assert_eq!(git.repository_root(&checkout).unwrap(), checkout.canonicalize().unwrap());
+        assert_eq!(commits(&git, &checkout), [library_head]);
+    }
+
+    #[test]
+    fn missing_repository_and_revision_are_typed_errors() {
+        let repo = TestRepo::new();
+        repo.write("a.txt", "a\n");
+        let head = repo.commit("first");
+        let outside = tempfile::tempdir().unwrap();
+        let git = GitIntegration::new();
+
+        let error = git.get_refs(outside.path().to_str().unwrap()).unwrap_err();
+        assert!(matches!(error.downcast_ref::<GitError>(), Some(GitError::NotARepository(_))), "{:#}", error);
+
+        let error = git.compare(&params(&repo, "no-such-branch", &head), &ComparisonConfig::default()).unwrap_err();
+        assert!(matches!(error.downcast_ref::<GitError>(), Some(GitError::UnknownRevision(r)) if r == "no-such-branch"), "{:#}", error);
+        let error = git.get_commit_info(repo.path_str(), "no-such-branch").unwrap_err();
+        assert!(matches!(error.downcast_ref::<GitError>(), Some(GitError::UnknownRevision(_))), "{:#}", error);
     }
 }
@@ -0,0 +1,460 @@
+// libgit2 实现的 Git 操作，`GitIntegration` 默认使用，不依赖 PATH 中的 git；与 git_cli 中的命令行实现一一对应
+
+use crate::diff::git_integration::{ChangedFile, GitChange, GitError, GitVersion, INDEX_REF, WORKTREE_REF};
+use crate::diff::types::{CommitInfo, FileHistoryEntry, GitRef, GitRefKind, LineBlame};
+use anyhow::{Context, Result};
+use git2::{
+    BlameOptions, Commit, Delta, DiffFindOptions, DiffOptions, FileMode, ObjectType, Oid, Repository, Tree, TreeWalkMode,
+    TreeWalkResult,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// 打开 `path` 所在的仓库（可以是仓库内的子目录）
+fn open_repository(path: &Path) -> Result<Repository> {
+    Repository::discover(path).with_context(|| GitError::NotARepository(path.to_path_buf()))
+}
+
+/// 引用（分支、标签、提交或 `HEAD~1` 等表达式）对应的目录树
+fn tree_at<'r>(repo: &'r Repository, commit_ref: &str) -> Result<Tree<'r>> {
+    repo.revparse_single(commit_ref)
+        .and_then(|object| object.peel_to_tree())
+        .with_context(|| GitError::UnknownRevision(commit_ref.to_string()))
+}
+
+fn path_string(path: &[u8]) -> String {
+    String::from_utf8_lossy(path).to_string()
+}
+
+fn commit_info(commit: &Commit) -> Result<CommitInfo> {
+    let author = commit.author();
+    Ok(CommitInfo {
+        hash: commit.id().to_string(),
+        short_hash: path_string(&commit.as_object().short_id()?),
+        author: String::from_utf8_lossy(author.name_bytes()).to_string(),
+        author_time: author.when().seconds(),
+        commit_time: commit.time().seconds(),
+        subject: commit.summary().unwrap_or_default().to_string(),
+    })
+}
+
+/// 路径在提交中对应的对象，目录为其目录树；路径不存在时为空
+fn entry_id(commit: &Commit, path: &str) -> Result<Option<Oid>> {
+    Ok(commit.tree()?.get_path(Path::new(path)).ok().map(|entry| entry.id()))
+}
+
+/// 提交是否改动了 `path`：根提交中路径存在，或与父提交中的内容不同（合并提交须与所有父提交都不同）
+fn touches_path(commit: &Commit, path: &str) -> Result<bool> {
+    let current = entry_id(commit, path)?;
+    if commit.parent_count() == 0 {
+        return Ok(current.is_some());
+    }
+    for parent in commit.parents() {
+        if entry_id(&parent, path)? == current {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// 两个版本之间变更的文件，按 git 的默认规则（相似度 50%）检测重命名
+///
+/// 工作目录一侧包含未被忽略的未跟踪文件；两侧同为暂存区或同为工作目录时没有变更
+pub(crate) fn changed_files(repo_path: &Path, left: GitVersion, right: GitVersion) -> Result<Vec<ChangedFile>> {
+    let repo = open_repository(repo_path)?;
+    // libgit2 只能由旧到新（提交、暂存区、工作目录）比较，反向时交换两侧并反转结果
+    let reverse = left.rank() > right.rank();
+    let (old, new) = if reverse { (right, left) } else { (left, right) };
+    let mut options = DiffOptions::new();
+    // 子模块以提交（gitlink）记录，内容不在本仓库中，与 `--ignore-submodules=all` 一致地跳过；
+    // 该选项不会去掉树之间新增或删除的子模块，下面再按文件模式过滤
+    options
+        .reverse(reverse)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .ignore_submodules(true);
+    let mut diff = match (old, new) {
+        (GitVersion::Commit(old), GitVersion::Commit(new)) => repo.diff_tree_to_tree(
+            Some(&tree_at(&repo, old)?),
+            Some(&tree_at(&repo, new)?),
+            Some(&mut options),
+        )?,
+        (GitVersion::Commit(old), GitVersion::Index) => {
+            repo.diff_tree_to_index(Some(&tree_at(&repo, old)?), None, Some(&mut options))?
+        }
+        (GitVersion::Commit(old), GitVersion::Worktree) => {
+            repo.diff_tree_to_workdir_with_index(Some(&tree_at(&repo, old)?), Some(&mut options))?
+        }
+        (GitVersion::Index, GitVersion::Worktree) => repo.diff_index_to_workdir(None, Some(&mut options))?,
+        _ => return Ok(Vec::new()),
+    };
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    Ok(diff
+        .deltas()
+        .filter(|delta| delta.old_file().mode() != FileMode::Commit && delta.new_file().mode() != FileMode::Commit)
+        .filter_map(|delta| {
+            let change = match delta.status() {
+                Delta::Added | Delta::Copied => GitChange::Added,
+                Delta::Deleted => GitChange::Deleted,
+                // 反转不改变未跟踪状态，工作目录在左侧时未跟踪文件即为删除
+                Delta::Untracked if reverse => GitChange::Deleted,
+                Delta::Untracked => GitChange::Added,
+                Delta::Modified | Delta::Typechange => GitChange::Modified,
+                Delta::Renamed => GitChange::Renamed {
+                    old_path: path_string(delta.old_file().path_bytes()?),
+                },
+                _ => return None,
+            };
+            // 删除的文件两侧路径相同
+            let path = path_string(delta.new_file().path_bytes()?);
+            Some(ChangedFile { path, change })
+        })
+        .collect())
+}
+
+/// 批量读取文件在 `commit_ref`（或暂存区 `INDEX`）中的原始内容，与 `file_paths` 一一对应；仓库与目录树只打开一次
+pub(crate) fn blobs_at_commit(repo_path: &Path, file_paths: &[&str], commit_ref: &str) -> Result<Vec<Option<Vec<u8>>>> {
+    let repo = open_repository(repo_path)?;
+    if commit_ref == INDEX_REF {
+        let index = repo.index()?;
+        return file_paths
+            .iter()
+            .map(|path| match index.get_path(Path::new(path), 0) {
+                Some(entry) => Ok(Some(repo.find_blob(entry.id)?.content().to_vec())),
+                None => Ok(None),
+            })
+            .collect();
+    }
+    let Ok(tree) = tree_at(&repo, commit_ref) else {
+        return Ok(vec![None; file_paths.len()]);
+    };
+    file_paths
+        .iter()
+        .map(|path| {
+            let Ok(entry) = tree.get_path(Path::new(path)) else {
+                return Ok(None);
+            };
+            let object = entry.to_object(&repo)?;
+            Ok(object.as_blob().map(|blob| blob.content().to_vec()))
+        })
+        .collect()
+}
+
+/// 暂存区中各文件记录的修改时间，与 `file_paths` 一一对应，文件未暂存时为空
+pub(crate) fn index_times(repo_path: &Path, file_paths: &[&str]) -> Result<Vec<Option<i64>>> {
+    let repo = open_repository(repo_path)?;
+    let index = repo.index()?;
+    Ok(file_paths
+        .iter()
+        .map(|path| index.get_path(Path::new(path), 0).map(|entry| entry.mtime.seconds() as i64))
+        .collect())
+}
+
+/// 引用所指提交的提交时间（Unix 时间戳），引用无法解析时为 0
+pub(crate) fn commit_time(repo_path: &Path, commit_ref: &str) -> Result<i64> {
+    let repo = open_repository(repo_path)?;
+    Ok(repo
+        .revparse_single(commit_ref)
+        .and_then(|object| object.peel_to_commit())
+        .map_or(0, |commit| commit.time().seconds()))
+}
+
+/// 引用所指提交的信息，引用无法解析为提交时为空
+pub(crate) fn commit_at(repo_path: &Path, commit_ref: &str) -> Result<Option<CommitInfo>> {
+    let repo = open_repository(repo_path)?;
+    let Ok(commit) = repo.revparse_single(commit_ref).and_then(|object| object.peel_to_commit()) else {
+        return Ok(None);
+    };
+    commit_info(&commit).map(Some)
+}
+
+/// 引用所指提交的完整哈希与按顺序排列的父提交哈希，引用无法解析为提交时为空
+pub(crate) fn commit_parents(repo_path: &Path, commit_ref: &str) -> Result<Option<(String, Vec<String>)>> {
+    let repo = open_repository(repo_path)?;
+    let Ok(commit) = repo.revparse_single(commit_ref).and_then(|object| object.peel_to_commit()) else {
+        return Ok(None);
+    };
+    Ok(Some((commit.id().to_string(), commit.parent_ids().map(|id| id.to_string()).collect())))
+}
+
+/// 两个引用所指提交的合并基础，没有共同祖先或引用无法解析时为空
+pub(crate) fn merge_base(repo_path: &Path, left_ref: &str, right_ref: &str) -> Result<Option<String>> {
+    let repo = open_repository(repo_path)?;
+    let commit_id = |reference: &str| repo.revparse_single(reference).and_then(|object| object.peel_to_commit()).map(|commit| commit.id());
+    let (Ok(left), Ok(right)) = (commit_id(left_ref), commit_id(right_ref)) else {
+        return Ok(None);
+    };
+    Ok(repo.merge_base(left, right).ok().map(|id| id.to_string()))
+}
+
+/// 从 `commit_ref` 向前按 git 的默认次序（提交时间倒序）列出提交，跳过前 `offset` 个满足 `matches` 的提交后最多返回 `limit` 个；
+/// 指定 `path` 时只列出改动了该路径的提交
+pub(crate) fn commits(
+    repo_path: &Path,
+    commit_ref: &str,
+    path: Option<&str>,
+    matches: &dyn Fn(&CommitInfo) -> bool,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<CommitInfo>> {
+    let repo = open_repository(repo_path)?;
+    let start = repo
+        .revparse_single(commit_ref)
+        .and_then(|object| object.peel_to_commit())
+        .with_context(|| GitError::UnknownRevision(commit_ref.to_string()))?;
+    let mut walk = repo.revwalk()?;
+    walk.push(start.id())?;
+
+    let mut commits = Vec::new();
+    let mut skipped = 0;
+    for id in walk {
+        if commits.len() >= limit {
+            break;
+        }
+        let commit = repo.find_commit(id?)?;
+        if let Some(path) = path {
+            if !touches_path(&commit, path)? {
+                continue;
+            }
+        }
+        let info = commit_info(&commit)?;
+        if !matches(&info) {
+            continue;
+        }
+        if skipped < offset {
+            skipped += 1;
+        } else {
+            commits.push(info);
+        }
+    }
+    Ok(commits)
+}
+
+/// 从 `HEAD` 向前列出改动了 `file_path` 的最多 `limit` 个提交，跟随重命名（同 `git log --follow`）
+///
+/// 文件在某个提交中新增时，在该提交的变更中查找重命名，找到后继续跟踪旧路径；文件在 `HEAD` 中已删除时从删除它的提交开始
+pub(crate) fn file_history(repo_path: &Path, file_path: &str, limit: usize) -> Result<Vec<FileHistoryEntry>> {
+    let repo = open_repository(repo_path)?;
+    let head = repo.head()?.peel_to_commit()?;
+    let mut walk = repo.revwalk()?;
+    walk.push(head.id())?;
+
+    let mut path = file_path.to_string();
+    let mut history = Vec::new();
+    for id in walk {
+        if history.len() >= limit {
+            break;
+        }
+        let commit = repo.find_commit(id?)?;
+        if !touches_path(&commit, &path)? {
+            continue;
+        }
+        history.push(FileHistoryEntry {
+            commit: commit_info(&commit)?,
+            path: path.clone(),
+        });
+
+        let Ok(parent) = commit.parent(0) else {
+            continue;
+        };
+        if entry_id(&commit, &path)?.is_none() || entry_id(&parent, &path)?.is_some() {
+            continue;
+        }
+        let mut diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&commit.tree()?), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+        let renamed_from = diff
+            .deltas()
+            .filter(|delta| delta.status() == Delta::Renamed)
+            .find(|delta| delta.new_file().path_bytes() == Some(path.as_bytes()))
+            .and_then(|delta| delta.old_file().path_bytes().map(path_string));
+        if let Some(old_path) = renamed_from {
+            path = old_path;
+        }
+    }
+    Ok(history)
+}
+
+/// 本地分支、远程分支与标签（不含 `origin/HEAD` 等符号引用），未排序；不指向提交的标签不列出
+pub(crate) fn refs(repo_path: &Path) -> Result<Vec<GitRef>> {
+    let repo = open_repository(repo_path)?;
+    let head = repo.head().ok().filter(|head| head.is_branch()).and_then(|head| head.name().map(str::to_string));
+
+    let mut refs = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        if reference.symbolic_target().is_some() {
+            continue;
+        }
+        let Some(full_name) = reference.name() else {
+            continue;
+        };
+        let (name, kind) = if let Some(branch) = full_name.strip_prefix("refs/heads/") {
+            (branch, GitRefKind::Branch)
+        } else if let Some(branch) = full_name.strip_prefix("refs/remotes/") {
+            (branch, GitRefKind::RemoteBranch)
+        } else if let Some(tag) = full_name.strip_prefix("refs/tags/") {
+            (tag, GitRefKind::Tag)
+        } else {
+            continue;
+        };
+        let Ok(commit) = reference.peel_to_commit() else {
+            continue;
+        };
+        let message = match kind {
+            GitRefKind::Tag => reference
+                .peel_to_tag()
+                .ok()
+                .and_then(|tag| tag.message_bytes().map(|message| String::from_utf8_lossy(message).trim_end().to_string())),
+            _ => None,
+        };
+        refs.push(GitRef {
+            name: name.to_string(),
+            kind,
+            target_hash: commit.id().to_string(),
+            committed_at: commit.time().seconds(),
+            is_head: head.as_deref() == Some(full_name),
+            message,
+        });
+    }
+    Ok(refs)
+}
+
+/// `path` 所在仓库工作目录的根目录
+pub(crate) fn repository_root(path: &Path) -> Result<PathBuf> {
+    let repo = open_repository(path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::NotARepository(path.to_path_buf()))?;
+    // 去掉末尾的路径分隔符
+    Ok(workdir.components().collect())
+}
+
+/// 仓库的公共 git 目录（工作树共用主仓库的目录）
+pub(crate) fn common_dir(path: &Path) -> Result<PathBuf> {
+    Ok(open_repository(path)?.commondir().to_path_buf())
+}
+
+/// `commit_ref` 版本中 `pathspec` 目录下（`.` 为整个仓库）的所有文件，路径相对仓库根目录；
+/// `INDEX` 与 `WORKTREE` 列出暂存区中的文件
+pub(crate) fn files_at_commit(repo_root: &Path, commit_ref: &str, pathspec: &str) -> Result<Vec<String>> {
+    let repo = open_repository(repo_root)?;
+    if commit_ref == INDEX_REF || commit_ref == WORKTREE_REF {
+        let prefix = format!("{}/", pathspec);
+        return Ok(repo
+            .index()?
+            .iter()
+            .map(|entry| path_string(&entry.path))
+            .filter(|path| pathspec == "." || path == pathspec || path.starts_with(&prefix))
+            .collect());
+    }
+    let tree = tree_at(&repo, commit_ref)?;
+    let (tree, prefix) = if pathspec == "." {
+        (tree, String::new())
+    } else {
+        match tree.get_path(Path::new(pathspec)) {
+            Ok(entry) if entry.kind() == Some(ObjectType::Tree) => {
+                (entry.to_object(&repo)?.peel_to_tree()?, format!("{}/", pathspec))
+            }
+            Ok(_) => return Ok(vec![pathspec.to_string()]),
+            Err(_) => return Ok(Vec::new()),
+        }
+    };
+
+    let mut files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() != Some(ObjectType::Tree) {
+            files.push(format!("{}{}{}", prefix, root, path_string(entry.name_bytes())));
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(files)
+}
+
+/// 工作目录（含暂存区）中相对 `commit_ref` 有变化的文件及未被忽略的未跟踪文件，不做重命名检测
+pub(crate) fn working_tree_changes(repo_root: &Path, commit_ref: &str, pathspec: &str) -> Result<Vec<String>> {
+    let repo = open_repository(repo_root)?;
+    let tree = tree_at(&repo, commit_ref)?;
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    if pathspec != "." {
+        options.pathspec(pathspec);
+    }
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?;
+
+    let mut files: Vec<String> = diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path_bytes().map(path_string))
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// 查询 `commit_ref` 版本中第 `line` 行的来源提交；引用、文件或行不存在时返回 `None`
+pub(crate) fn blame_line(repo_root: &Path, file_path: &str, line: u32, commit_ref: &str) -> Result<Option<LineBlame>> {
+    let repo = open_repository(repo_root)?;
+    let Ok(newest) = repo.revparse_single(commit_ref).and_then(|object| object.peel_to_commit()) else {
+        return Ok(None);
+    };
+    let mut options = BlameOptions::new();
+    options
+        .newest_commit(newest.id())
+        .min_line(line as usize)
+        .max_line(line as usize);
+    let Ok(blame) = repo.blame_file(Path::new(file_path), Some(&mut options)) else {
+        return Ok(None);
+    };
+    let Some(hunk) = blame.get_line(line as usize) else {
+        return Ok(None);
+    };
+
+    let commit = repo.find_commit(hunk.final_commit_id())?;
+    let author = commit.author();
+    Ok(Some(LineBlame {
+        commit: commit.id().to_string(),
+        author: String::from_utf8_lossy(author.name_bytes()).to_string(),
+        author_time: author.when().seconds(),
+        summary: commit.summary().unwrap_or_default().to_string(),
+    }))
+}
+
+/// 工作目录中 `file_path`（内容为 `content`）第 `start`..=`end` 行的来源提交，未提交的行为 `None`
+pub(crate) fn blame_lines(
+    repo_root: &Path,
+    file_path: &str,
+    content: &[u8],
+    start: u32,
+    end: u32,
+) -> Result<Vec<Option<LineBlame>>> {
+    let repo = open_repository(repo_root)?;
+    // 先按 HEAD 计算，再叠加工作目录内容，新增或修改的行归属于空提交
+    let blame = repo.blame_file(Path::new(file_path), None)?;
+    let blame = blame.blame_buffer(content)?;
+
+    let mut commits: HashMap<Oid, LineBlame> = HashMap::new();
+    (start..=end)
+        .map(|line| {
+            let Some(hunk) = blame.get_line(line as usize) else {
+                return Ok(None);
+            };
+            let id = hunk.final_commit_id();
+            if id.is_zero() {
+                return Ok(None);
+            }
+            if let Some(blame) = commits.get(&id) {
+                return Ok(Some(blame.clone()));
+            }
+            let commit = repo.find_commit(id)?;
+            let author = commit.author();
+            let blame = LineBlame {
+                commit: id.to_string(),
+                author: String::from_utf8_lossy(author.name_bytes()).to_string(),
+                author_time: author.when().seconds(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+            };
+            commits.insert(id, blame.clone());
+            Ok(Some(blame))
+        })
+        .collect()
+}
@@ -0,0 +1,235 @@
+// 图片元数据：只读取常见图片格式的文件头（尺寸、颜色类型），不解码像素数据
+
+use crate::diff::types::{ImageColorType, ImageDiffInfo, ImageFormat, ImageInfo};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// 读取文件头的字节数上限；JPEG 的帧头可能位于较大的 EXIF 段之后
+const IMAGE_HEADER_LIMIT: u64 = 256 * 1024;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// 读取文件开头识别图片；无法读取、格式不受支持或文件头截断、损坏时返回 `None`
+pub fn read_image_info(path: &Path) -> Option<ImageInfo> {
+    let mut header = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(IMAGE_HEADER_LIMIT)
+        .read_to_end(&mut header)
+        .ok()?;
+    parse_image_info(&header)
+}
+
+/// 由文件开头的字节解析图片信息，支持 PNG、JPEG、GIF、BMP 与 WebP；宽或高为 0 视为损坏
+pub fn parse_image_info(header: &[u8]) -> Option<ImageInfo> {
+    let info = if header.starts_with(PNG_SIGNATURE) {
+        png_info(header)
+    } else if header.starts_with(&[0xFF, 0xD8]) {
+        jpeg_info(header)
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        gif_info(header)
+    } else if header.starts_with(b"BM") {
+        bmp_info(header)
+    } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+        webp_info(header)
+    } else {
+        None
+    };
+    info.filter(|info| info.width > 0 && info.height > 0)
+}
+
+/// 比较两侧的图片信息；两侧都无法识别为图片时返回 `None`，按普通二进制文件处理
+pub fn image_diff_info(
+    left: Option<ImageInfo>,
+    right: Option<ImageInfo>,
+    left_size: u64,
+    right_size: u64,
+) -> Option<ImageDiffInfo> {
+    if left.is_none() && right.is_none() {
+        return None;
+    }
+    let dimensions_changed = match (&left, &right) {
+        (Some(left), Some(right)) => (left.width, left.height) != (right.width, right.height),
+        _ => false,
+    };
+    Some(ImageDiffInfo {
+        left,
+        right,
+        size_delta: right_size as i64 - left_size as i64,
+        dimensions_changed,
+    })
+}
+
+/// PNG：签名后的第一个块必须是 IHDR
+fn png_info(data: &[u8]) -> Option<ImageInfo> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let color_type = match *data.get(25)? {
+        0 => ImageColorType::Grayscale,
+        2 => ImageColorType::Rgb,
+        3 => ImageColorType::Indexed,
+        4 => ImageColorType::GrayscaleAlpha,
+        6 => ImageColorType::Rgba,
+        _ => return None,
+    };
+    Some(ImageInfo {
+        format: ImageFormat::Png,
+        width: be_u32(data, 16)?,
+        height: be_u32(data, 20)?,
+        color_type: Some(color_type),
+        bit_depth: Some(*data.get(24)?),
+    })
+}
+
+/// JPEG：逐段跳过，直到帧头（SOF）段
+fn jpeg_info(data: &[u8]) -> Option<ImageInfo> {
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // 填充字节
+            0xFF => pos += 1,
+            // 无长度的独立标记
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            // 图像结束或扫描开始之前仍未见到帧头
+            0xD9 | 0xDA => return None,
+            // SOF0..SOF15，不含 DHT（C4）、JPG（C8）与 DAC（CC）
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let color_type = match *data.get(pos + 9)? {
+                    1 => Some(ImageColorType::Grayscale),
+                    3 => Some(ImageColorType::YCbCr),
+                    4 => Some(ImageColorType::Cmyk),
+                    _ => None,
+                };
+                return Some(ImageInfo {
+                    format: ImageFormat::Jpeg,
+                    width: be_u16(data, pos + 7)? as u32,
+                    height: be_u16(data, pos + 5)? as u32,
+                    color_type,
+                    bit_depth: Some(*data.get(pos + 4)?),
+                });
+            }
+            _ => {
+                let length = be_u16(data, pos + 2)? as usize;
+                if length < 2 {
+                    return None;
+                }
+                pos += 2 + length;
+            }
+        }
+    }
+}
+
+/// GIF：逻辑屏幕描述符中的宽高，颜色深度取全局颜色表的大小
+fn gif_info(data: &[u8]) -> Option<ImageInfo> {
+    let flags = *data.get(10)?;
+    Some(ImageInfo {
+        format: ImageFormat::Gif,
+        width: le_u16(data, 6)? as u32,
+        height: le_u16(data, 8)? as u32,
+        color_type: Some(ImageColorType::Indexed),
+        bit_depth: (flags & 0x80 != 0).then_some((flags & 0x07) + 1),
+    })
+}
+
+/// BMP：支持 OS/2 的 12 字节信息头与 Windows 的 40 字节及更长的信息头
+fn bmp_info(data: &[u8]) -> Option<ImageInfo> {
+    let (width, height, bits) = match le_u32(data, 14)? {
+        12 => (le_u16(data, 18)? as u32, le_u16(data, 20)? as u32, le_u16(data, 24)?),
+        size if size >= 40 => (
+            le_i32(data, 18)?.unsigned_abs(),
+            // 高度为负表示自上而下存储
+            le_i32(data, 22)?.unsigned_abs(),
+            le_u16(data, 28)?,
+        ),
+        _ => return None,
+    };
+    let color_type = match bits {
+        1 | 2 | 4 | 8 => ImageColorType::Indexed,
+        16 | 24 => ImageColorType::Rgb,
+        32 => ImageColorType::Rgba,
+        _ => return None,
+    };
+    Some(ImageInfo {
+        format: ImageFormat::Bmp,
+        width,
+        height,
+        color_type: Some(color_type),
+        bit_depth: Some(bits as u8),
+    })
+}
+
+/// WebP：按第一个块的类型读取有损（VP8）、无损（VP8L）或扩展（VP8X）格式的尺寸
+fn webp_info(data: &[u8]) -> Option<ImageInfo> {
+    let (width, height, color_type) = match data.get(12..16)? {
+        b"VP8 " => {
+            // 关键帧起始码之后为 14 位宽高
+            if data.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            (
+                (le_u16(data, 26)? & 0x3FFF) as u32,
+                (le_u16(data, 28)? & 0x3FFF) as u32,
+                None,
+            )
+        }
+        b"VP8L" => {
+            if *data.get(20)? != 0x2F {
+                return None;
+            }
+            let bits = le_u32(data, 21)?;
+            let alpha = bits & (1 << 28) != 0;
+            (
+                (bits & 0x3FFF) + 1,
+                ((bits >> 14) & 0x3FFF) + 1,
+                Some(if alpha { ImageColorType::Rgba } else { ImageColorType::Rgb }),
+            )
+        }
+        b"VP8X" => {
+            let alpha = *data.get(20)? & 0x10 != 0;
+            (
+                le_u24(data, 24)? + 1,
+                le_u24(data, 27)? + 1,
+                Some(if alpha { ImageColorType::Rgba } else { ImageColorType::Rgb }),
+            )
+        }
+        _ => return None,
+    };
+    Some(ImageInfo {
+        format: ImageFormat::WebP,
+        width,
+        height,
+        color_type,
+        bit_depth: None,
+    })
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le_u24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn le_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_i32(data: &[u8], at: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
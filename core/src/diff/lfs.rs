@@ -0,0 +1,198 @@
+// Git LFS 指针文件：识别指针内容，按指针记录的对象（SHA-256 与大小）比较，而不是比较指针文本；
+// 目录比较与 Git 比较共用
+
+use crate::diff::engine::hash_file;
+use crate::diff::image::{image_diff_info, read_image_info};
+use crate::diff::types::*;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 指针文件的首行（git-lfs 规范 v1）
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// 指针文件的大小上限，超过的文件不是指针
+const MAX_POINTER_SIZE: u64 = 1024;
+
+/// LFS 指针记录的对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// 对象内容的 SHA-256（小写十六进制）
+    pub oid: String,
+    /// 对象大小（字节）
+    pub size: u64,
+}
+
+/// 解析 LFS 指针文件，内容不是指针时返回 `None`
+pub fn parse_lfs_pointer(bytes: &[u8]) -> Option<LfsPointer> {
+    if bytes.len() as u64 > MAX_POINTER_SIZE {
+        return None;
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != POINTER_VERSION {
+        return None;
+    }
+    let (mut oid, mut size) = (None, None);
+    for line in lines {
+        match line.split_once(' ')? {
+            ("oid", value) => {
+                oid = value
+                    .strip_prefix("sha256:")
+                    .filter(|oid| oid.len() == 64 && oid.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+                    .map(str::to_string)
+            }
+            ("size", value) => size = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(LfsPointer { oid: oid?, size: size? })
+}
+
+/// 读取磁盘上的 LFS 指针文件，文件不是指针或无法读取时返回 `None`
+pub(crate) fn read_lfs_pointer(path: &Path) -> Option<LfsPointer> {
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.len() > MAX_POINTER_SIZE {
+        return None;
+    }
+    parse_lfs_pointer(&fs::read(path).ok()?)
+}
+
+/// LFS 比较的一侧：指针记录的对象，或未经 LFS 管理的实际内容
+pub(crate) struct LfsSide {
+    oid: String,
+    size: u64,
+    pointer: bool,
+    /// 本地 LFS 对象存储中的对象文件
+    object: Option<PathBuf>,
+}
+
+impl LfsSide {
+    /// 指针记录的对象；`objects_dir`（`<git 目录>/lfs/objects`）中有该对象时校验其摘要，
+    /// 与指针不符（对象损坏）时按实际内容的摘要比较
+    pub(crate) fn from_pointer(pointer: LfsPointer, objects_dir: Option<&Path>) -> Self {
+        let object = objects_dir
+            .map(|dir| dir.join(&pointer.oid[..2]).join(&pointer.oid[2..4]).join(&pointer.oid))
+            .filter(|path| path.is_file());
+        let mut oid = pointer.oid;
+        if let Some(path) = &object {
+            match hash_file(path) {
+                Ok(hash) if hash != oid => {
+                    log::warn!("LFS object {} does not match its oid (sha256:{})", path.display(), hash);
+                    oid = hash;
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to hash LFS object {}: {}", path.display(), e),
+            }
+        }
+        Self { oid, size: pointer.size, pointer: true, object }
+    }
+
+    /// 由文件内容构建：指针文件取记录的对象，其他内容按实际内容计算摘要
+    pub(crate) fn from_bytes(bytes: &[u8], objects_dir: Option<&Path>) -> Self {
+        match parse_lfs_pointer(bytes) {
+            Some(pointer) => Self::from_pointer(pointer, objects_dir),
+            None => Self {
+                oid: format!("{:x}", Sha256::digest(bytes)),
+                size: bytes.len() as u64,
+                pointer: false,
+                object: None,
+            },
+        }
+    }
+
+    /// 由磁盘文件构建，`objects_dir` 同 `from_pointer`
+    pub(crate) fn from_file(path: &Path, objects_dir: Option<&Path>) -> anyhow::Result<Self> {
+        if let Some(pointer) = read_lfs_pointer(path) {
+            return Ok(Self::from_pointer(pointer, objects_dir));
+        }
+        Ok(Self {
+            oid: hash_file(path)?,
+            size: fs::metadata(path)?.len(),
+            pointer: false,
+            object: None,
+        })
+    }
+
+    fn describe(&self) -> String {
+        let kind = match (self.pointer, &self.object) {
+            (true, Some(_)) => "LFS (本地对象)",
+            (true, None) => "LFS",
+            (false, _) => "File",
+        };
+        format!("{} ({} 字节, sha256:{})", kind, self.size, self.oid)
+    }
+
+    fn stats(&self) -> FileStats {
+        FileStats {
+            size: self.size,
+            line_count: 0,
+            modified_time: None,
+            content_hash: Some(self.oid.clone()),
+            encoding: None,
+        }
+    }
+}
+
+/// 按二进制文件的形式比较 LFS 对象：一行说明两侧的对象与大小，`is_lfs` 为真
+///
+/// 两侧都存在且对象相同时修改状态改为未变更（如只改了指针中的扩展字段），重命名的相似度按对象是否相同取 1 或 0；
+/// 本地有对象时附带图片信息
+pub(crate) fn lfs_file_diff(path: String, status: FileStatus, left: Option<&LfsSide>, right: Option<&LfsSide>) -> FileDiff {
+    let same = matches!((left, right), (Some(left), Some(right)) if left.oid == right.oid);
+    let status = match status {
+        FileStatus::Modified if same => FileStatus::Unchanged,
+        FileStatus::Renamed { old_path, .. } => FileStatus::Renamed {
+            old_path,
+            similarity: if same { 1.0 } else { 0.0 },
+        },
+        status => status,
+    };
+    let diff_type = match (left, right) {
+        _ if same => DiffType::Equal,
+        (None, _) => DiffType::Insert,
+        (_, None) => DiffType::Delete,
+        _ => DiffType::Replace,
+    };
+    let describe = |side: Option<&LfsSide>| side.map_or_else(|| "-".to_string(), LfsSide::describe);
+    let stats = |side: Option<&LfsSide>| {
+        side.map_or(
+            FileStats {
+                size: 0,
+                line_count: 0,
+                modified_time: None,
+                content_hash: None,
+                encoding: None,
+            },
+            LfsSide::stats,
+        )
+    };
+    let image = |side: Option<&LfsSide>| side.and_then(|side| side.object.as_deref()).and_then(read_image_info);
+    let size = |side: Option<&LfsSide>| side.map_or(0, |side| side.size);
+
+    FileDiff {
+        path,
+        status,
+        lines: vec![DiffLine {
+            left_line_number: None,
+            right_line_number: None,
+            diff_type,
+            content: format!("[二进制文件比较] {} vs {}", describe(left), describe(right)),
+            is_placeholder: false,
+            move_id: None,
+            enclosing_symbol: None,
+            old_content: None,
+        }],
+        hunks: Vec::new(),
+        change_kind: None,
+        degraded: false,
+        changed_symbols: Vec::new(),
+        truncated: false,
+        image_info: image_diff_info(image(left), image(right), size(left), size(right)),
+        is_lfs: true,
+        original_content: None,
+        modified_content: None,
+        left_stats: stats(left),
+        right_stats: stats(right),
+    }
+}
@@ -1,7 +1,32 @@
 pub mod engine;
 pub mod types;
 pub mod git_integration;
+mod git_cli;
+mod git_lib;
+pub mod patch;
+pub mod classify;
+pub mod encoding;
+pub mod cache;
+pub mod context;
+pub mod report;
+pub mod semantic;
+pub mod image;
+pub mod lfs;
+pub mod three_way;
 
 pub use engine::*;
 pub use types::*;
-pub use git_integration::*;
\ No newline at end of file
+pub use git_integration::*;
+pub use patch::*;
+pub use classify::*;
+pub use encoding::*;
+pub use cache::*;
+pub use context::*;
+pub use report::*;
+pub use semantic::*;
+pub use image::*;
+pub use lfs::*;
+pub use three_way::*;
+
+#[cfg(test)]
+mod test_repo;
@@ -0,0 +1,924 @@
+// 补丁导出与应用：将比较结果渲染为 git 风格的 unified diff，或将 unified diff 应用到目录
+
+use crate::diff::engine::{build_hunks, calculate_summary, line_diff, line_similarity, BINARY_MARKER};
+use crate::diff::types::*;
+use anyhow::{anyhow, bail, Result};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 定位差异块时最多忽略的首尾上下文行数
+const MAX_FUZZ: usize = 2;
+
+/// 将比较结果渲染为 unified diff 补丁，按路径排序，未变更的文件不输出
+///
+/// 已分组的 `hunks` 直接使用，否则按 `context_lines` 从 `lines` 分组
+pub fn render_patch(result: &ComparisonResult, context_lines: usize) -> String {
+    let mut diffs: Vec<&FileDiff> = result.file_diffs.iter().collect();
+    diffs.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut patch = String::new();
+    for diff in diffs {
+        render_file_diff(&mut patch, diff, context_lines);
+    }
+    patch
+}
+
+fn render_file_diff(out: &mut String, diff: &FileDiff, context_lines: usize) {
+    let new_path = patch_path(&diff.path);
+    let old_path = match &diff.status {
+        FileStatus::Unchanged => return,
+        FileStatus::Renamed { old_path, .. } => patch_path(old_path),
+        FileStatus::Copied { source_path, .. } => patch_path(source_path),
+        _ => new_path.clone(),
+    };
+
+    let binary = diff
+        .lines
+        .iter()
+        .any(|line| !line.is_placeholder && line.content.starts_with(BINARY_MARKER));
+    let hunks: Cow<[DiffHunk]> = if binary {
+        Cow::Borrowed(&[])
+    } else if diff.hunks.is_empty() {
+        Cow::Owned(build_hunks(&diff.lines, context_lines))
+    } else {
+        Cow::Borrowed(&diff.hunks)
+    };
+    // 没有实际变更的修改（如读取失败的文件）不输出
+    if diff.status == FileStatus::Modified && !binary && hunks.is_empty() {
+        return;
+    }
+
+    let (old_label, new_label) = match diff.status {
+        FileStatus::Added => ("/dev/null".to_string(), format!("b/{}", new_path)),
+        FileStatus::Deleted => (format!("a/{}", old_path), "/dev/null".to_string()),
+        _ => (format!("a/{}", old_path), format!("b/{}", new_path)),
+    };
+
+    let _ = writeln!(out, "diff --git a/{} b/{}", old_path, new_path);
+    match diff.status {
+        FileStatus::Added => out.push_str("new file mode 100644\n"),
+        FileStatus::Deleted => out.push_str("deleted file mode 100644\n"),
+        FileStatus::Renamed { similarity, .. } => {
+            let _ = writeln!(out, "similarity index {:.0}%", similarity * 100.0);
+            let _ = writeln!(out, "rename from {}", old_path);
+            let _ = writeln!(out, "rename to {}", new_path);
+        }
+        FileStatus::Copied { similarity, .. } => {
+            let _ = writeln!(out, "similarity index {:.0}%", similarity * 100.0);
+            let _ = writeln!(out, "copy from {}", old_path);
+            let _ = writeln!(out, "copy to {}", new_path);
+        }
+        _ => {}
+    }
+
+    if binary {
+        let _ = writeln!(out, "Binary files {} and {} differ", old_label, new_label);
+        return;
+    }
+    if hunks.is_empty() {
+        // 纯重命名/复制或空文件的新增/删除
+        return;
+    }
+
+    let _ = writeln!(out, "--- {}", old_label);
+    let _ = writeln!(out, "+++ {}", new_label);
+    let left_unterminated = unterminated_last_line(diff.original_content.as_deref());
+    let right_unterminated = unterminated_last_line(diff.modified_content.as_deref());
+    let is_last = |line_number: Option<u32>, last: Option<u32>| last.is_some() && line_number == last;
+    let write_line = |out: &mut String, prefix: char, content: &str, missing_newline: bool| {
+        let _ = writeln!(out, "{}{}", prefix, content);
+        if missing_newline {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    };
+    for hunk in hunks.iter() {
+        let _ = writeln!(out, "{}", hunk.header);
+        let lines: Vec<&DiffLine> = hunk.lines.iter().filter(|line| !line.is_placeholder).collect();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            if line.diff_type == DiffType::Equal {
+                let missing_newline = is_last(line.left_line_number, left_unterminated)
+                    || is_last(line.right_line_number, right_unterminated);
+                write_line(out, ' ', &line.content, missing_newline);
+                i += 1;
+                continue;
+            }
+
+            // 一组变更先输出左侧的删除行，再输出右侧的插入行；Replace 行两侧各输出一次
+            let end = lines[i..]
+                .iter()
+                .position(|line| line.diff_type == DiffType::Equal)
+                .map_or(lines.len(), |len| i + len);
+            for line in &lines[i..end] {
+                if line.left_line_number.is_some() {
+                    let content = line.old_content.as_deref().unwrap_or(&line.content);
+                    write_line(out, '-', content, is_last(line.left_line_number, left_unterminated));
+                }
+            }
+            for line in &lines[i..end] {
+                if line.right_line_number.is_some() {
+                    write_line(out, '+', &line.content, is_last(line.right_line_number, right_unterminated));
+                }
+            }
+            i = end;
+        }
+    }
+}
+
+/// 补丁中使用的路径：统一为 `/` 分隔；单文件比较的绝对路径只保留文件名
+fn patch_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    if Path::new(&path).is_absolute() {
+        if let Some(name) = Path::new(&path).file_name() {
+            return name.to_string_lossy().into_owned();
+        }
+    }
+    path
+}
+
+/// 内容不以换行结尾时返回最后一行的行号
+fn unterminated_last_line(content: Option<&str>) -> Option<u32> {
+    let content = content?;
+    if content.is_empty() || content.ends_with('\n') {
+        return None;
+    }
+    Some(content.lines().count() as u32)
+}
+
+/// 解析后的单个文件补丁
+#[derive(Default)]
+struct FilePatch {
+    /// 原路径，新增文件为空
+    old_path: Option<String>,
+    /// 新路径，删除文件为空
+    new_path: Option<String>,
+    hunks: Vec<PatchHunk>,
+    binary: bool,
+    /// 复制文件（`copy from` / `copy to`），应用后保留原路径
+    copy: bool,
+    /// 已读到 `---` 行；非 git 格式的补丁以此区分下一个文件
+    has_labels: bool,
+}
+
+impl FilePatch {
+    fn display_path(&self) -> String {
+        self.new_path.clone().or_else(|| self.old_path.clone()).unwrap_or_default()
+    }
+}
+
+struct PatchHunk {
+    /// 原文件中的起始行号
+    old_start: usize,
+    old_count: usize,
+    header: String,
+    /// `(' ' | '-' | '+', 行内容)`
+    lines: Vec<(char, String)>,
+    /// 原内容的最后一行没有换行
+    old_missing_newline: bool,
+    /// 新内容的最后一行没有换行
+    new_missing_newline: bool,
+}
+
+/// 应用补丁后单个文件的变更
+struct FileChange {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    copy: bool,
+    original: Option<String>,
+    modified: Option<String>,
+}
+
+impl FileChange {
+    fn to_file_diff(&self) -> FileDiff {
+        let lines_of = |content: &Option<String>| -> Vec<String> {
+            content.as_deref().map(split_lines).unwrap_or_default().into_iter().map(str::to_string).collect()
+        };
+        let (old_lines, new_lines) = (lines_of(&self.original), lines_of(&self.modified));
+        let status = match (&self.old_path, &self.new_path) {
+            (None, _) => FileStatus::Added,
+            (_, None) => FileStatus::Deleted,
+            (Some(old), Some(_)) if self.copy => FileStatus::Copied {
+                source_path: old.clone(),
+                similarity: line_similarity(&old_lines, &new_lines),
+            },
+            (Some(old), Some(new)) if old != new => FileStatus::Renamed {
+                old_path: old.clone(),
+                similarity: line_similarity(&old_lines, &new_lines),
+            },
+            _ => FileStatus::Modified,
+        };
+        let stats = |content: &Option<String>| FileStats {
+            size: content.as_ref().map_or(0, |c| c.len() as u64),
+            line_count: content.as_deref().map_or(0, |c| split_lines(c).len() as u32),
+            modified_time: None,
+            content_hash: None,
+            encoding: content.as_ref().map(|_| "UTF-8".to_string()),
+        };
+        FileDiff {
+            path: self.new_path.clone().or_else(|| self.old_path.clone()).unwrap_or_default(),
+            status,
+            lines: line_diff(&old_lines, &new_lines, &ComparisonConfig::default()).lines,
+            hunks: Vec::new(),
+            change_kind: None,
+            degraded: false,
+            changed_symbols: Vec::new(),
+            truncated: false,
+            image_info: None,
+            left_stats: stats(&self.original),
+            right_stats: stats(&self.modified),
+            is_lfs: false,
+            original_content: self.original.clone(),
+            modified_content: self.modified.clone(),
+        }
+    }
+}
+
+/// 将 unified diff 补丁应用到 `target_dir`
+///
+/// 差异块从原行号开始就近匹配，匹配不到时最多忽略首尾 [`MAX_FUZZ`] 行上下文。
+/// 有冲突时不写入任何文件，除非 `partial` 为真，此时只写入没有冲突的文件；`dry_run` 只返回预览。
+/// 写入失败时返回 [`PatchWriteFailed`]，目标目录保持原样
+pub fn apply_patch(patch_path: &Path, target_dir: &Path, dry_run: bool, partial: bool) -> Result<PatchApplyResult> {
+    let text = fs::read_to_string(patch_path)
+        .map_err(|e| anyhow!("Failed to read patch {}: {}", patch_path.display(), e))?;
+    let file_patches = parse_patch(&text)?;
+
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+    for file_patch in &file_patches {
+        match plan_file_change(target_dir, file_patch) {
+            Ok(change) => changes.push(change),
+            Err(mut file_conflicts) => conflicts.append(&mut file_conflicts),
+        }
+    }
+
+    let applied = !dry_run && (conflicts.is_empty() || partial);
+    if applied {
+        write_file_changes(target_dir, &changes)?;
+    }
+
+    let file_diffs: Vec<FileDiff> = changes.iter().map(FileChange::to_file_diff).collect();
+    Ok(PatchApplyResult {
+        applied,
+        preview: ComparisonResult {
+            source_a: target_dir.to_string_lossy().to_string(),
+            source_b: patch_path.to_string_lossy().to_string(),
+            comparison_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64),
+            summary: calculate_summary(&file_diffs),
+            changed_symbols: Vec::new(),
+            file_diffs,
+            left_commit: None,
+            right_commit: None,
+            merge_base: None,
+        },
+        conflicts,
+    })
+}
+
+/// 解析 unified diff，支持 git 格式（含新增、删除、重命名）与普通 `diff -u` 格式
+fn parse_patch(text: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end_matches('\r');
+        i += 1;
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            files.extend(current.take());
+            let (old_path, new_path) = git_header_paths(rest);
+            current = Some(FilePatch {
+                old_path,
+                new_path,
+                ..FilePatch::default()
+            });
+        } else if let Some(label) = line.strip_prefix("--- ") {
+            if current.as_ref().is_none_or(|file| file.has_labels) {
+                files.extend(current.take());
+            }
+            let file = current.get_or_insert_with(FilePatch::default);
+            file.old_path = label_path(label);
+            file.has_labels = true;
+        } else if let Some(label) = line.strip_prefix("+++ ") {
+            let file = current.as_mut().ok_or_else(|| anyhow!("Line {}: '+++' without '---'", i))?;
+            file.new_path = label_path(label);
+        } else if line.starts_with("@@ ") {
+            let file = current.as_mut().ok_or_else(|| anyhow!("Line {}: hunk without file header", i))?;
+            let (hunk, consumed) = parse_hunk(line, &lines[i..]).map_err(|e| anyhow!("Line {}: {}", i, e))?;
+            file.hunks.push(hunk);
+            i += consumed;
+        } else if let Some(file) = current.as_mut() {
+            if line.starts_with("new file mode") {
+                file.old_path = None;
+            } else if line.starts_with("deleted file mode") {
+                file.new_path = None;
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                file.old_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.new_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("copy from ") {
+                file.old_path = Some(path.to_string());
+                file.copy = true;
+            } else if let Some(path) = line.strip_prefix("copy to ") {
+                file.new_path = Some(path.to_string());
+            } else if line.starts_with("Binary files ") || line.starts_with("GIT binary patch") {
+                file.binary = true;
+            }
+        }
+    }
+    files.extend(current);
+
+    if files.is_empty() {
+        bail!("Patch contains no file changes");
+    }
+    if let Some(file) = files.iter().find(|f| f.old_path.is_none() && f.new_path.is_none()) {
+        bail!("Patch has a file section without paths ({} hunks)", file.hunks.len());
+    }
+    Ok(files)
+}
+
+/// 解析差异块，`body` 为块头之后的行；返回差异块与消耗的行数
+fn parse_hunk(header: &str, body: &[&str]) -> Result<(PatchHunk, usize)> {
+    let (old_start, old_count, new_count) =
+        parse_hunk_header(header).ok_or_else(|| anyhow!("Invalid hunk header '{}'", header))?;
+    let mut hunk = PatchHunk {
+        old_start,
+        old_count,
+        header: header.to_string(),
+        lines: Vec::new(),
+        old_missing_newline: false,
+        new_missing_newline: false,
+    };
+
+    let (mut old_seen, mut new_seen) = (0, 0);
+    let mut consumed = 0;
+    loop {
+        let Some(line) = body.get(consumed) else {
+            if old_seen < old_count || new_seen < new_count {
+                bail!("Hunk '{}' is truncated", header);
+            }
+            break;
+        };
+        if line.starts_with('\\') {
+            // `\ No newline at end of file` 作用于上一行
+            match hunk.lines.last().map(|(tag, _)| *tag) {
+                Some('-') => hunk.old_missing_newline = true,
+                Some('+') => hunk.new_missing_newline = true,
+                Some(_) => {
+                    hunk.old_missing_newline = true;
+                    hunk.new_missing_newline = true;
+                }
+                None => {}
+            }
+            consumed += 1;
+            continue;
+        }
+        if old_seen >= old_count && new_seen >= new_count {
+            break;
+        }
+        let (tag, content) = match line.chars().next() {
+            // 部分工具会去掉空上下文行的前导空格
+            None => (' ', ""),
+            Some(tag @ (' ' | '-' | '+')) => (tag, &line[1..]),
+            Some(_) => bail!("Unexpected line '{}' in hunk '{}'", line, header),
+        };
+        match tag {
+            ' ' => {
+                old_seen += 1;
+                new_seen += 1;
+            }
+            '-' => old_seen += 1,
+            _ => new_seen += 1,
+        }
+        if old_seen > old_count || new_seen > new_count {
+            bail!("Hunk '{}' has more lines than its header declares", header);
+        }
+        hunk.lines.push((tag, content.to_string()));
+        consumed += 1;
+    }
+    Ok((hunk, consumed))
+}
+
+/// 解析 `@@ -l,c +r,c @@`，返回 (原起始行, 原行数, 新行数)
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let (ranges, _) = header.strip_prefix("@@ -")?.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = parse_range(old)?;
+    let (_, new_count) = parse_range(new)?;
+    Some((old_start, old_count, new_count))
+}
+
+/// 从 `diff --git a/x b/y` 中取出两侧路径
+fn git_header_paths(rest: &str) -> (Option<String>, Option<String>) {
+    match rest.strip_prefix("a/").and_then(|r| r.split_once(" b/")) {
+        Some((old, new)) => (Some(old.to_string()), Some(new.to_string())),
+        None => (None, None),
+    }
+}
+
+/// `---` / `+++` 行中的路径：去掉时间戳与 `a/`、`b/` 前缀，`/dev/null` 为空
+fn label_path(label: &str) -> Option<String> {
+    let path = label.split('\t').next().unwrap_or(label);
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// 计算单个文件应用补丁后的内容；失败时返回该文件的全部冲突
+fn plan_file_change(target_dir: &Path, patch: &FilePatch) -> Result<FileChange, Vec<PatchConflict>> {
+    let file_path = patch.display_path();
+    let conflict = |reason: String| {
+        vec![PatchConflict {
+            file_path: file_path.clone(),
+            hunk_index: None,
+            line: None,
+            reason,
+        }]
+    };
+
+    if patch.binary {
+        return Err(conflict("Binary patches are not supported".to_string()));
+    }
+    for path in [&patch.old_path, &patch.new_path].into_iter().flatten() {
+        if !is_safe_relative_path(path) {
+            return Err(conflict(format!("Path '{}' escapes the target directory", path)));
+        }
+    }
+
+    let original = match &patch.old_path {
+        Some(old_path) => match fs::read_to_string(target_dir.join(old_path)) {
+            Ok(content) => Some(content),
+            Err(e) => return Err(conflict(format!("Cannot read '{}': {}", old_path, e))),
+        },
+        None => None,
+    };
+    if let Some(new_path) = &patch.new_path {
+        if patch.old_path.as_ref() != Some(new_path) && target_dir.join(new_path).exists() {
+            return Err(conflict(format!("'{}' already exists", new_path)));
+        }
+    }
+
+    let modified = apply_hunks(&file_path, original.as_deref().unwrap_or(""), &patch.hunks)?;
+    if patch.new_path.is_none() && !modified.is_empty() {
+        return Err(conflict("File content differs from the deleted content".to_string()));
+    }
+
+    Ok(FileChange {
+        old_path: patch.old_path.clone(),
+        new_path: patch.new_path.clone(),
+        copy: patch.copy,
+        original,
+        modified: patch.new_path.as_ref().map(|_| modified),
+    })
+}
+
+/// 依次应用差异块，返回新内容；任一差异块无法定位时返回全部冲突
+fn apply_hunks(file_path: &str, content: &str, hunks: &[PatchHunk]) -> Result<String, Vec<PatchConflict>> {
+    let original = split_lines(content);
+    let mut trailing_newline = content.is_empty() || content.ends_with('\n');
+    let mut output: Vec<&str> = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut cursor = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        match locate_hunk(&original, cursor, hunk) {
+            Some((start, old_len, new_lines)) => {
+                output.extend_from_slice(&original[cursor..start]);
+                output.extend(new_lines);
+                cursor = start + old_len;
+                if hunk.new_missing_newline {
+                    trailing_newline = false;
+                } else if hunk.old_missing_newline {
+                    trailing_newline = true;
+                }
+            }
+            None => conflicts.push(PatchConflict {
+                file_path: file_path.to_string(),
+                hunk_index: Some(index),
+                line: Some(hunk.old_start as u32),
+                reason: format!("Context does not match for hunk '{}'", hunk.header),
+            }),
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+
+    output.extend_from_slice(&original[cursor..]);
+    let mut result = output.join("\n");
+    if trailing_newline && !output.is_empty() {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// 在原文件中定位差异块，返回 (起始下标, 替换的原行数, 新行)
+///
+/// 先按完整上下文匹配，失败后逐步忽略首尾的上下文行
+fn locate_hunk<'a>(
+    original: &[&str],
+    min_start: usize,
+    hunk: &'a PatchHunk,
+) -> Option<(usize, usize, Vec<&'a str>)> {
+    let leading = hunk.lines.iter().take_while(|(tag, _)| *tag == ' ').count();
+    let trailing = hunk.lines.iter().rev().take_while(|(tag, _)| *tag == ' ').count();
+    // 新增内容的块（原行数为 0）的起始行号指向插入位置之前的一行
+    let expected = if hunk.old_count == 0 {
+        hunk.old_start
+    } else {
+        hunk.old_start.saturating_sub(1)
+    };
+
+    let mut tried = None;
+    for fuzz in 0..=MAX_FUZZ {
+        let skip = (fuzz.min(leading), fuzz.min(trailing));
+        if tried == Some(skip) {
+            continue;
+        }
+        if skip.0 + skip.1 > hunk.lines.len() {
+            break;
+        }
+        tried = Some(skip);
+
+        let lines = &hunk.lines[skip.0..hunk.lines.len() - skip.1];
+        let old: Vec<&str> = lines
+            .iter()
+            .filter(|(tag, _)| *tag != '+')
+            .map(|(_, line)| line.as_str())
+            .collect();
+        if let Some(start) = find_block(original, &old, expected + skip.0, min_start) {
+            let new = lines
+                .iter()
+                .filter(|(tag, _)| *tag != '-')
+                .map(|(_, line)| line.as_str())
+                .collect();
+            return Some((start, old.len(), new));
+        }
+    }
+    None
+}
+
+/// 从 `expected` 开始向两侧交替查找 `block`，起点不早于 `min_start`
+fn find_block(original: &[&str], block: &[&str], expected: usize, min_start: usize) -> Option<usize> {
+    let max_start = original.len().checked_sub(block.len())?;
+    if min_start > max_start {
+        return None;
+    }
+    let expected = expected.clamp(min_start, max_start);
+    let matches = |start: usize| original[start..start + block.len()] == *block;
+    (0..=max_start - min_start).find_map(|distance| {
+        let after = expected + distance;
+        if after <= max_start && matches(after) {
+            return Some(after);
+        }
+        let before = expected.checked_sub(distance).filter(|&s| s >= min_start && distance > 0)?;
+        matches(before).then_some(before)
+    })
+}
+
+/// 写入全部文件变更：新内容先写入目标旁的临时文件，全部写好后再依次移动到位并移除原路径；
+/// 任一步失败时删除临时文件，并用原内容恢复已完成的变更
+fn write_file_changes(target_dir: &Path, changes: &[FileChange]) -> Result<()> {
+    let mut staged = Vec::with_capacity(changes.len());
+    for change in changes {
+        match stage_file_change(target_dir, change) {
+            Ok(temp) => staged.push(temp),
+            Err(e) => {
+                remove_staged(&staged);
+                return Err(e.into());
+            }
+        }
+    }
+
+    for (index, (change, temp)) in changes.iter().zip(&staged).enumerate() {
+        if let Err(e) = commit_file_change(target_dir, change, temp.as_deref()) {
+            remove_staged(&staged[index..]);
+            for change in changes[..=index].iter().rev() {
+                restore_file_change(target_dir, change);
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// 将新内容写入目标所在目录的临时文件，沿用原文件的权限；删除文件时没有临时文件
+fn stage_file_change(target_dir: &Path, change: &FileChange) -> Result<Option<PathBuf>, PatchWriteFailed> {
+    let (Some(new_path), Some(content)) = (&change.new_path, &change.modified) else {
+        return Ok(None);
+    };
+    let path = target_dir.join(new_path);
+    let failed = |source| PatchWriteFailed { path: path.clone(), source };
+    let parent = path.parent().unwrap_or(target_dir);
+    fs::create_dir_all(parent).map_err(failed)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = parent.join(format!(".{}.{}.patch", file_name, uuid::Uuid::new_v4()));
+    fs::write(&temp, content).map_err(failed)?;
+    let source = change.old_path.as_ref().map_or_else(|| path.clone(), |old_path| target_dir.join(old_path));
+    if let Ok(metadata) = fs::metadata(&source) {
+        if let Err(e) = fs::set_permissions(&temp, metadata.permissions()) {
+            let _ = fs::remove_file(&temp);
+            return Err(failed(e));
+        }
+    }
+    Ok(Some(temp))
+}
+
+/// 将暂存的内容移动到新路径，删除或重命名时移除原路径
+fn commit_file_change(target_dir: &Path, change: &FileChange, temp: Option<&Path>) -> Result<(), PatchWriteFailed> {
+    if let (Some(new_path), Some(temp)) = (&change.new_path, temp) {
+        let path = target_dir.join(new_path);
+        fs::rename(temp, &path).map_err(|source| PatchWriteFailed { path, source })?;
+    }
+    if let Some(old_path) = change.old_path.as_ref().filter(|_| !change.copy) {
+        if change.new_path.as_ref() != Some(old_path) {
+            let path = target_dir.join(old_path);
+            fs::remove_file(&path).map_err(|source| PatchWriteFailed { path, source })?;
+        }
+    }
+    Ok(())
+}
+
+/// 尽力撤销单个文件的变更：移除新增的路径，写回原路径的原内容
+fn restore_file_change(target_dir: &Path, change: &FileChange) {
+    if let Some(new_path) = &change.new_path {
+        if change.old_path.as_ref() != Some(new_path) {
+            let _ = fs::remove_file(target_dir.join(new_path));
+        }
+    }
+    if let (Some(old_path), Some(original)) = (&change.old_path, &change.original) {
+        let path = target_dir.join(old_path);
+        if fs::read_to_string(&path).ok().as_ref() != Some(original) {
+            let _ = fs::write(&path, original);
+        }
+    }
+}
+
+fn remove_staged(staged: &[Option<PathBuf>]) {
+    for temp in staged.iter().flatten() {
+        let _ = fs::remove_file(temp);
+    }
+}
+
+/// 只允许目标目录内的相对路径
+fn is_safe_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// 按 `\n` 拆分内容，不含末尾换行产生的空行
+fn split_lines(content: &str) -> Vec<&str> {
+    if content.is_empty() {
+        return Vec::new();
+    }
+    content.strip_suffix('\n').unwrap_or(content).split('\n').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply_patch;
+    use crate::diff::engine::DiffEngine;
+    use crate::diff::test_repo::TestRepo;
+    use crate::diff::types::*;
+    use std::fs;
+
+    const RENAMED: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+
+    /// 将第 5 行 five 改为 FIVE，上下文为第 4、6 行
+    const FIVE_PATCH: &str = "--- a/numbers.txt\n+++ b/numbers.txt\n@@ -4,3 +4,3 @@\n four\n-five\n+FIVE\n six\n";
+
+    fn target(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let file = dir.path().join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+        }
+        dir
+    }
+
+    fn apply(target: &tempfile::TempDir, patch: &str, dry_run: bool, partial: bool) -> anyhow::Result<PatchApplyResult> {
+        let patch_file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(patch_file.path(), patch).unwrap();
+        apply_patch(patch_file.path(), target.path(), dry_run, partial)
+    }
+
+    fn read(target: &tempfile::TempDir, path: &str) -> String {
+        fs::read_to_string(target.path().join(path)).unwrap()
+    }
+
+    /// 目标目录中的全部文件名，用于确认没有遗留临时文件
+    fn file_names(target: &tempfile::TempDir) -> Vec<String> {
+        let mut names: Vec<String> = walkdir::WalkDir::new(target.path())
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.path().strip_prefix(target.path()).unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn shifted_hunk_is_located_near_its_line() {
+        let dir = target(&[("numbers.txt", &format!("zero\nzero\n{}", RENAMED))]);
+        let result = apply(&dir, FIVE_PATCH, false, false).unwrap();
+        assert!(result.applied);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(read(&dir, "numbers.txt"), format!("zero\nzero\n{}", RENAMED.replace("five", "FIVE")));
+    }
+
+    #[test]
+    fn fuzz_ignores_mismatched_outer_context() {
+        let dir = target(&[("numbers.txt", &RENAMED.replace("four", "FOUR"))]);
+        let result = apply(&dir, FIVE_PATCH, false, false).unwrap();
+        assert!(result.applied, "{:?}", result.conflicts);
+        assert_eq!(read(&dir, "numbers.txt"), RENAMED.replace("four", "FOUR").replace("five", "FIVE"));
+
+        // 被修改的行本身不匹配时不能靠忽略上下文应用
+        let dir = target(&[("numbers.txt", &RENAMED.replace("five", "5"))]);
+        let result = apply(&dir, FIVE_PATCH, false, false).unwrap();
+        assert!(!result.applied);
+        assert_eq!(result.conflicts.len(), 1);
+    }
+
+    #[test]
+    fn conflicts_are_reported_per_hunk() {
+        let patch = "--- a/numbers.txt\n+++ b/numbers.txt\n@@ -1,2 +1,2 @@\n-one\n+ONE\n two\n@@ -8,3 +8,3 @@\n eight\n-nine\n+NINE\n ten\n";
+        let dir = target(&[("numbers.txt", &RENAMED.replace("nine", "9"))]);
+        let result = apply(&dir, patch, false, false).unwrap();
+        assert!(!result.applied);
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!(conflict.file_path, "numbers.txt");
+        assert_eq!((conflict.hunk_index, conflict.line), (Some(1), Some(8)));
+        assert_eq!(read(&dir, "numbers.txt"), RENAMED.replace("nine", "9"));
+    }
+
+    #[test]
+    fn added_deleted_and_renamed_files() {
+        let patch = "\
+diff --git a/old.txt b/new.txt
+similarity index 90%
+rename from old.txt
+rename to new.txt
+--- a/old.txt
++++ b/new.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+diff --git a/nested/added.txt b/nested/added.txt
+new file mode 100644
+--- /dev/null
++++ b/nested/added.txt
+@@ -0,0 +1 @@
++hello
+";
+        let dir = target(&[("old.txt", "one\ntwo\nthree\n"), ("gone.txt", "bye\n")]);
+        let result = apply(&dir, patch, false, false).unwrap();
+        assert!(result.applied, "{:?}", result.conflicts);
+        assert_eq!(file_names(&dir), ["nested/added.txt", "new.txt"]);
+        assert_eq!(read(&dir, "new.txt"), "one\nTWO\nthree\n");
+        assert_eq!(read(&dir, "nested/added.txt"), "hello\n");
+
+        let mut statuses: Vec<(&str, &FileStatus)> =
+            result.preview.file_diffs.iter().map(|d| (d.path.as_str(), &d.status)).collect();
+        statuses.sort_by_key(|(path, _)| *path);
+        assert!(
+            matches!(
+                statuses[..],
+                [
+                    ("gone.txt", FileStatus::Deleted),
+                    ("nested/added.txt", FileStatus::Added),
+                    ("new.txt", FileStatus::Renamed { .. })
+                ]
+            ),
+            "{:?}",
+            statuses
+        );
+    }
+
+    #[test]
+    fn dry_run_previews_without_writing() {
+        let dir = target(&[("numbers.txt", RENAMED)]);
+        let result = apply(&dir, FIVE_PATCH, true, false).unwrap();
+        assert!(!result.applied);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.preview.file_diffs[0].modified_content.as_deref(), Some(&*RENAMED.replace("five", "FIVE")));
+        assert_eq!(read(&dir, "numbers.txt"), RENAMED);
+    }
+
+    #[test]
+    fn partial_writes_only_conflict_free_files() {
+        let patch = format!("{}{}", FIVE_PATCH, "--- a/other.txt\n+++ b/other.txt\n@@ -1 +1 @@\n-missing\n+replaced\n");
+        let files = [("numbers.txt", RENAMED), ("other.txt", "present\n")];
+
+        let dir = target(&files);
+        let result = apply(&dir, &patch, false, false).unwrap();
+        assert!(!result.applied);
+        assert_eq!(read(&dir, "numbers.txt"), RENAMED);
+
+        let dir = target(&files);
+        let result = apply(&dir, &patch, false, true).unwrap();
+        assert!(result.applied);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].file_path, "other.txt");
+        assert_eq!(read(&dir, "numbers.txt"), RENAMED.replace("five", "FIVE"));
+        assert_eq!(read(&dir, "other.txt"), "present\n");
+    }
+
+    #[test]
+    fn write_failure_leaves_target_untouched() {
+        // `blocker` 是文件，无法在其下创建 new.txt；此前的修改也不能写入
+        let patch = format!("{}{}", FIVE_PATCH, "--- /dev/null\n+++ b/blocker/new.txt\n@@ -0,0 +1 @@\n+new\n");
+        let dir = target(&[("numbers.txt", RENAMED), ("blocker", "file\n")]);
+        let error = apply(&dir, &patch, false, false).unwrap_err();
+        let failed = error.downcast_ref::<PatchWriteFailed>().expect("write failure");
+        assert!(failed.path.ends_with("blocker/new.txt"), "{}", error);
+        assert_eq!(read(&dir, "numbers.txt"), RENAMED);
+        assert_eq!(file_names(&dir), ["blocker", "numbers.txt"]);
+    }
+
+    #[test]
+    fn exported_patch_applies_with_git() {
+        let repo = TestRepo::new();
+        repo.write("src/main.rs", "fn main() {\n    println!(\"hi\");\n}\n")
+            .write("deleted.txt", "gone\n")
+            .write("old_name.txt", RENAMED)
+            .write("no_newline.txt", "first\nlast");
+        repo.commit("base");
+
+        // 目标目录：修改、删除、新增、重命名并修改、末尾无换行
+        let target = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let file = target.path().join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+        };
+        write("src/main.rs", "fn main() {\n    println!(\"hello\");\n}\n");
+        write("added.txt", "new file\n");
+        write("new_name.txt", &RENAMED.replace("five", "FIVE"));
+        write("no_newline.txt", "first\nchanged");
+
+        let request = ComparisonRequest {
+            source_a: repo.path_str().to_string(),
+            source_b: target.path().to_str().unwrap().to_string(),
+            config: ComparisonConfig::default(),
+            is_git_comparison: false,
+            git_params: None,
+            is_working_tree_comparison: false,
+        };
+        // 左侧为仓库工作区，跳过 .git 目录
+        let config = ComparisonConfig {
+            respect_gitignore: true,
+            ..ComparisonConfig::default()
+        };
+        let patch_file = tempfile::NamedTempFile::new().unwrap();
+        DiffEngine::new(config)
+            .export_patch(request, patch_file.path())
+            .unwrap();
+        let patch = fs::read_to_string(patch_file.path()).unwrap();
+        assert!(patch.contains("rename from old_name.txt"), "{}", patch);
+        assert!(patch.contains("\\ No newline at end of file"), "{}", patch);
+
+        let patch_path = patch_file.path().to_str().unwrap();
+        repo.git(&["apply", "--check", patch_path]);
+        repo.git(&["apply", patch_path]);
+
+        for path in ["src/main.rs", "added.txt", "new_name.txt", "no_newline.txt"] {
+            assert_eq!(
+                fs::read_to_string(repo.file(path)).unwrap(),
+                fs::read_to_string(target.path().join(path)).unwrap(),
+                "{}",
+                path
+            );
+        }
+        assert!(!repo.file("deleted.txt").exists());
+        assert!(!repo.file("old_name.txt").exists());
+    }
+}
@@ -0,0 +1,413 @@
+// 差异报告：将比较结果渲染为独立的 HTML 文件（内联样式，无外部依赖），便于评审交接
+
+use crate::diff::engine::BINARY_MARKER;
+use crate::diff::types::*;
+use std::fmt::Write;
+
+/// 单个文件最多渲染的差异行数，超出部分截断并提示
+const MAX_REPORT_LINES: usize = 5000;
+
+const REPORT_STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 0; color: #1f2328; }
+header, main { padding: 16px 24px; }
+header { border-bottom: 1px solid #d0d7de; background: #f6f8fa; }
+h1 { font-size: 20px; margin: 0 0 8px; }
+h2 { font-size: 15px; margin: 0; padding: 8px 12px; background: #f6f8fa; border-bottom: 1px solid #d0d7de; }
+.sources { font-family: monospace; color: #57606a; }
+.summary td { padding: 2px 12px 2px 0; }
+nav ul { list-style: none; padding: 0; margin: 8px 0 0; }
+nav li { font-family: monospace; padding: 1px 0; }
+.status { display: inline-block; min-width: 72px; font-size: 12px; color: #57606a; }
+.added { color: #1a7f37; } .deleted { color: #cf222e; }
+section { border: 1px solid #d0d7de; border-radius: 6px; margin: 16px 0; overflow: hidden; }
+.note { padding: 8px 12px; color: #57606a; font-style: italic; }
+table.diff { width: 100%; border-collapse: collapse; table-layout: fixed; font-family: monospace; font-size: 12px; }
+table.diff td { padding: 0 6px; vertical-align: top; white-space: pre-wrap; word-break: break-all; }
+td.ln { width: 48px; text-align: right; color: #8c959f; user-select: none; }
+td.mk { width: 12px; user-select: none; }
+tr.hunk td { background: #ddf4ff; color: #57606a; }
+td.del, tr.del td { background: #ffebe9; }
+td.ins, tr.ins td { background: #e6ffec; }
+td.moved, tr.moved td { background: #fff8c5; }
+td.empty { background: #f6f8fa; }
+details > summary { cursor: pointer; padding: 2px 12px; background: #f6f8fa; color: #57606a; font-size: 12px; }
+"#;
+
+/// 将比较结果渲染为 HTML 报告，未变更的文件不输出
+///
+/// 按 `config.view_mode` 选择并排或统一布局；有完整 `lines` 时距变更超过 `context_lines` 的相同行折叠显示，
+/// 只有 `hunks` 时按块显示。顶部为统计与文件列表导航
+pub fn render_html_report(result: &ComparisonResult, config: &ComparisonConfig) -> String {
+    let mut diffs: Vec<&FileDiff> = result
+        .file_diffs
+        .iter()
+        .filter(|diff| diff.status != FileStatus::Unchanged)
+        .collect();
+    diffs.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut body = String::new();
+    let _ = write!(
+        body,
+        "<header><h1>Diff report</h1><div class=\"sources\">{} &rarr; {}</div>",
+        escape_html(&result.source_a),
+        escape_html(&result.source_b)
+    );
+    render_summary(&mut body, &result.summary);
+    body.push_str("<nav><ul>");
+    for (index, diff) in diffs.iter().enumerate() {
+        let (added, deleted) = line_counts(diff);
+        let _ = write!(
+            body,
+            "<li><span class=\"status\">{}</span><a href=\"#file-{}\">{}</a> <span class=\"added\">+{}</span> <span class=\"deleted\">-{}</span></li>",
+            status_label(&diff.status),
+            index,
+            escape_html(&diff.path),
+            added,
+            deleted
+        );
+    }
+    body.push_str("</ul></nav></header><main>");
+    for (index, diff) in diffs.iter().enumerate() {
+        render_file(&mut body, index, diff, config);
+    }
+    body.push_str("</main>");
+
+    html_document("Diff report", REPORT_STYLE, &body)
+}
+
+/// 包装为完整的 HTML 文档
+pub(crate) fn html_document(title: &str, style: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        escape_html(title),
+        style,
+        body
+    )
+}
+
+/// 转义 HTML 特殊字符，可用于元素内容与属性值
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_summary(out: &mut String, summary: &ComparisonSummary) {
+    let rows = [
+        ("Files added", summary.files_added),
+        ("Files deleted", summary.files_deleted),
+        ("Files modified", summary.files_modified),
+        ("Files renamed", summary.files_renamed),
+        ("Files copied", summary.files_copied),
+        ("Lines added", summary.lines_added),
+        ("Lines deleted", summary.lines_deleted),
+        ("Lines moved", summary.lines_moved),
+    ];
+    out.push_str("<table class=\"summary\">");
+    for (label, value) in rows {
+        let _ = write!(out, "<tr><td>{}</td><td>{}</td></tr>", label, value);
+    }
+    out.push_str("</table>");
+}
+
+fn status_label(status: &FileStatus) -> &'static str {
+    match status {
+        FileStatus::Added => "added",
+        FileStatus::Deleted => "deleted",
+        FileStatus::Modified => "modified",
+        FileStatus::Renamed { .. } => "renamed",
+        FileStatus::Copied { .. } => "copied",
+        FileStatus::Unchanged => "unchanged",
+    }
+}
+
+/// 文件的新增与删除行数，移动的行不计入
+fn line_counts(diff: &FileDiff) -> (usize, usize) {
+    let lines: Box<dyn Iterator<Item = &DiffLine>> = if diff.lines.is_empty() {
+        Box::new(diff.hunks.iter().flat_map(|hunk| &hunk.lines))
+    } else {
+        Box::new(diff.lines.iter())
+    };
+    lines
+        .filter(|line| !line.is_placeholder)
+        .fold((0, 0), |(added, deleted), line| match line.diff_type {
+            DiffType::Insert => (added + 1, deleted),
+            DiffType::Delete => (added, deleted + 1),
+            DiffType::Replace if line.left_line_number.is_some() => (added + 1, deleted + 1),
+            _ => (added, deleted),
+        })
+}
+
+/// 报告中的一段差异行：`Visible` 直接显示，`Hunk` 为已分组的差异块，`Collapsed` 为折叠的相同行，`Gap` 为块之间省略的内容
+enum Segment<'a> {
+    Visible(&'a [DiffLine]),
+    Hunk(&'a DiffHunk),
+    Collapsed(&'a [DiffLine]),
+    Gap,
+}
+
+impl Segment<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Segment::Visible(lines) | Segment::Collapsed(lines) => lines.len(),
+            Segment::Hunk(hunk) => hunk.lines.len(),
+            Segment::Gap => 0,
+        }
+    }
+}
+
+fn render_file(out: &mut String, index: usize, diff: &FileDiff, config: &ComparisonConfig) {
+    let _ = write!(out, "<section id=\"file-{}\"><h2>", index);
+    match &diff.status {
+        FileStatus::Renamed { old_path, .. } | FileStatus::Copied { source_path: old_path, .. } => {
+            let _ = write!(out, "{} &rarr; ", escape_html(old_path));
+        }
+        _ => {}
+    }
+    let _ = write!(
+        out,
+        "{} <span class=\"status\">({})</span></h2>",
+        escape_html(&diff.path),
+        status_label(&diff.status)
+    );
+
+    if diff.lines.iter().any(|line| line.content.starts_with(BINARY_MARKER)) {
+        out.push_str("<div class=\"note\">Binary files differ</div></section>");
+        return;
+    }
+    if diff.degraded {
+        out.push_str("<div class=\"note\">Line diff timed out; showing the whole file as replaced</div>");
+    }
+
+    let segments = file_segments(diff, config.context_lines as usize);
+    if segments.is_empty() && diff.truncated {
+        out.push_str("<div class=\"note\">File exceeds the size limit; contents were not compared</div>");
+    } else if segments.is_empty() {
+        out.push_str("<div class=\"note\">No line changes</div>");
+    }
+    let side_by_side = config.view_mode == DiffViewMode::SideBySide;
+    let mut rendered = 0;
+    let total: usize = segments.iter().map(Segment::len).sum();
+    for segment in segments {
+        if rendered >= MAX_REPORT_LINES {
+            break;
+        }
+        let budget = MAX_REPORT_LINES - rendered;
+        match segment {
+            Segment::Visible(lines) => {
+                let lines = &lines[..lines.len().min(budget)];
+                rendered += lines.len();
+                render_table(out, None, lines, side_by_side);
+            }
+            Segment::Hunk(hunk) => {
+                let lines = &hunk.lines[..hunk.lines.len().min(budget)];
+                rendered += lines.len();
+                render_table(out, Some(&hunk.header), lines, side_by_side);
+            }
+            Segment::Collapsed(lines) => {
+                let _ = write!(out, "<details><summary>{} unchanged lines</summary>", lines.len());
+                let lines = &lines[..lines.len().min(budget)];
+                rendered += lines.len();
+                render_table(out, None, lines, side_by_side);
+                out.push_str("</details>");
+            }
+            Segment::Gap => out.push_str("<div class=\"note\">&#8943;</div>"),
+        }
+    }
+    if total > rendered {
+        let _ = write!(
+            out,
+            "<div class=\"note\">File truncated: only the first {} of {} diff lines are shown</div>",
+            rendered, total
+        );
+    }
+    out.push_str("</section>");
+}
+
+/// 将文件的差异行分段：距变更超过 `context` 行的连续相同行折叠
+fn file_segments(diff: &FileDiff, context: usize) -> Vec<Segment<'_>> {
+    if diff.lines.is_empty() {
+        let mut segments = Vec::new();
+        for (i, hunk) in diff.hunks.iter().enumerate() {
+            if i > 0 {
+                segments.push(Segment::Gap);
+            }
+            segments.push(Segment::Hunk(hunk));
+        }
+        return segments;
+    }
+    let mut visible = vec![false; diff.lines.len()];
+    for (i, line) in diff.lines.iter().enumerate() {
+        if line.diff_type != DiffType::Equal {
+            let start = i.saturating_sub(context);
+            let end = (i + context + 1).min(diff.lines.len());
+            visible[start..end].iter_mut().for_each(|v| *v = true);
+        }
+    }
+    if !visible.contains(&true) {
+        return Vec::new();
+    }
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < diff.lines.len() {
+        let shown = visible[start];
+        let end = visible[start..]
+            .iter()
+            .position(|&v| v != shown)
+            .map_or(diff.lines.len(), |len| start + len);
+        let run = &diff.lines[start..end];
+        segments.push(if shown { Segment::Visible(run) } else { Segment::Collapsed(run) });
+        start = end;
+    }
+    segments
+}
+
+fn render_table(out: &mut String, header: Option<&str>, lines: &[DiffLine], side_by_side: bool) {
+    out.push_str("<table class=\"diff\">");
+    if side_by_side {
+        out.push_str("<colgroup><col style=\"width:48px\"><col><col style=\"width:48px\"><col></colgroup>");
+        if let Some(header) = header {
+            let _ = write!(out, "<tr class=\"hunk\"><td colspan=\"4\">{}</td></tr>", escape_html(header));
+        }
+        render_side_by_side(out, lines);
+    } else {
+        out.push_str("<colgroup><col style=\"width:48px\"><col style=\"width:48px\"><col style=\"width:12px\"><col></colgroup>");
+        if let Some(header) = header {
+            let _ = write!(out, "<tr class=\"hunk\"><td colspan=\"4\">{}</td></tr>", escape_html(header));
+        }
+        render_unified(out, lines);
+    }
+    out.push_str("</table>");
+}
+
+/// 统一布局：每组变更先列出左侧的行，再列出右侧的行，`Replace` 行两侧各占一行，占位行不显示
+fn render_unified(out: &mut String, lines: &[DiffLine]) {
+    let row = |out: &mut String, class: &str, left: Option<u32>, right: Option<u32>, marker: char, text: &str| {
+        let _ = writeln!(
+            out,
+            "<tr class=\"{}\"><td class=\"ln\">{}</td><td class=\"ln\">{}</td><td class=\"mk\">{}</td><td>{}</td></tr>",
+            class,
+            line_number(left),
+            line_number(right),
+            marker,
+            escape_html(text)
+        );
+    };
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        if line.diff_type == DiffType::Equal {
+            row(out, "", line.left_line_number, line.right_line_number, ' ', &line.content);
+            i += 1;
+            continue;
+        }
+        let end = change_group_end(lines, i);
+        for line in &lines[i..end] {
+            if line.left_line_number.is_some() {
+                row(out, cell_class(line, true), line.left_line_number, None, '-', left_text(line));
+            }
+        }
+        for line in &lines[i..end] {
+            if line.right_line_number.is_some() {
+                row(out, cell_class(line, false), None, line.right_line_number, '+', &line.content);
+            }
+        }
+        i = end;
+    }
+}
+
+/// 并排布局：相同行左右对齐；每组变更拆为左栏（删除、移出、`Replace` 的左侧及左栏占位）与右栏依次对应
+fn render_side_by_side(out: &mut String, lines: &[DiffLine]) {
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].diff_type == DiffType::Equal {
+            let line = &lines[i];
+            let _ = writeln!(
+                out,
+                "<tr><td class=\"ln\">{}</td><td>{}</td><td class=\"ln\">{}</td><td>{}</td></tr>",
+                line_number(line.left_line_number),
+                escape_html(&line.content),
+                line_number(line.right_line_number),
+                escape_html(&line.content)
+            );
+            i += 1;
+            continue;
+        }
+
+        let end = change_group_end(lines, i);
+        let group = &lines[i..end];
+        let column = |left: bool| -> Vec<Option<&DiffLine>> {
+            let (placeholder_type, number): (DiffType, fn(&DiffLine) -> Option<u32>) = if left {
+                (DiffType::Delete, |line| line.left_line_number)
+            } else {
+                (DiffType::Insert, |line| line.right_line_number)
+            };
+            group
+                .iter()
+                .filter(|line| number(line).is_some() || (line.is_placeholder && line.diff_type == placeholder_type))
+                .map(|line| (!line.is_placeholder).then_some(line))
+                .collect()
+        };
+        let (left, right) = (column(true), column(false));
+        for row in 0..left.len().max(right.len()) {
+            out.push_str("<tr>");
+            for (cells, is_left) in [(&left, true), (&right, false)] {
+                match cells.get(row).copied().flatten() {
+                    Some(line) => {
+                        let (number, text) = if is_left {
+                            (line.left_line_number, left_text(line))
+                        } else {
+                            (line.right_line_number, line.content.as_str())
+                        };
+                        let _ = write!(
+                            out,
+                            "<td class=\"ln\">{}</td><td class=\"{}\">{}</td>",
+                            line_number(number),
+                            cell_class(line, is_left),
+                            escape_html(text)
+                        );
+                    }
+                    None => out.push_str("<td class=\"ln\"></td><td class=\"empty\"></td>"),
+                }
+            }
+            out.push_str("</tr>\n");
+        }
+        i = end;
+    }
+}
+
+/// 从 `start` 开始的一组连续变更（含占位行）的结束位置
+fn change_group_end(lines: &[DiffLine], start: usize) -> usize {
+    lines[start..]
+        .iter()
+        .position(|line| line.diff_type == DiffType::Equal)
+        .map_or(lines.len(), |len| start + len)
+}
+
+/// 行在左栏显示的内容，`Replace` 行取原内容
+fn left_text(line: &DiffLine) -> &str {
+    line.old_content.as_deref().unwrap_or(&line.content)
+}
+
+/// 变更行在左栏或右栏的样式类名
+fn cell_class(line: &DiffLine, left: bool) -> &'static str {
+    match line.diff_type {
+        DiffType::Moved => "moved",
+        _ if left => "del",
+        _ => "ins",
+    }
+}
+
+fn line_number(number: Option<u32>) -> String {
+    number.map(|n| n.to_string()).unwrap_or_default()
+}
@@ -0,0 +1,161 @@
+// 函数级语义差异：解析两侧内容，将变更行映射到函数/方法，得出新增、删除与修改的符号
+
+use crate::ast::{Symbol, SymbolKind};
+use crate::diff::types::{ChangedSymbol, DiffLine, DiffType, FileDiff, SymbolChange};
+use std::collections::{HashMap, HashSet};
+
+/// 按限定名匹配两侧的函数/方法：仅一侧存在为新增或删除，两侧都有且范围内有变更行为修改
+///
+/// 重命名表现为旧名删除、新名新增；同名的多个定义（如重载）任一被改动即视为修改。
+/// 结果先按右侧顺序列出新增与修改，再按左侧顺序列出删除
+pub fn changed_symbols(diff: &FileDiff, left: &[Symbol], right: &[Symbol]) -> Vec<ChangedSymbol> {
+    let left_functions = qualified_functions(left);
+    let right_functions = qualified_functions(right);
+    let mut left_by_name: HashMap<&str, Vec<&Symbol>> = HashMap::new();
+    for (name, symbol) in &left_functions {
+        left_by_name.entry(name.as_str()).or_default().push(symbol);
+    }
+    let right_names: HashSet<&str> =
+        right_functions.iter().map(|(name, _)| name.as_str()).collect();
+
+    let changed_in = |symbol: &Symbol, line_number: fn(&DiffLine) -> Option<u32>| {
+        diff.lines.iter().any(|line| {
+            line.diff_type != DiffType::Equal
+                && line_number(line).is_some_and(|n| symbol.start_line <= n && n <= symbol.end_line)
+        })
+    };
+
+    let mut changes: Vec<ChangedSymbol> = Vec::new();
+    let mut push = |name: &str, kind: SymbolKind, change: SymbolChange| {
+        if !changes.iter().any(|c| c.name == name && c.change == change) {
+            changes.push(ChangedSymbol {
+                name: name.to_string(),
+                kind,
+                change,
+            });
+        }
+    };
+
+    for (name, symbol) in &right_functions {
+        match left_by_name.get(name.as_str()) {
+            None => push(name, symbol.kind, SymbolChange::Added),
+            Some(left_symbols) => {
+                let modified = changed_in(symbol, |line| line.right_line_number)
+                    || left_symbols.iter().any(|left| changed_in(left, |line| line.left_line_number));
+                if modified {
+                    push(name, symbol.kind, SymbolChange::Modified);
+                }
+            }
+        }
+    }
+    for (name, symbol) in &left_functions {
+        if !right_names.contains(name.as_str()) {
+            push(name, symbol.kind, SymbolChange::Removed);
+        }
+    }
+    changes
+}
+
+/// 函数与方法及其限定名（外层的类、函数等由外到内以 `.` 连接），按起始行排序
+fn qualified_functions(symbols: &[Symbol]) -> Vec<(String, &Symbol)> {
+    let containers: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|symbol| symbol.kind != SymbolKind::MethodCall)
+        .collect();
+    let mut functions: Vec<(String, &Symbol)> = symbols
+        .iter()
+        .filter(|symbol| matches!(symbol.kind, SymbolKind::Function | SymbolKind::Method))
+        .map(|symbol| {
+            let mut outer: Vec<&Symbol> = containers
+                .iter()
+                .copied()
+                .filter(|other| {
+                    !std::ptr::eq(*other, symbol)
+                        && other.start_line <= symbol.start_line
+                        && symbol.end_line <= other.end_line
+                        && (other.start_line, other.end_line) != (symbol.start_line, symbol.end_line)
+                })
+                .collect();
+            outer.sort_by_key(|other| (other.start_line, std::cmp::Reverse(other.end_line)));
+            let mut names: Vec<&str> = Vec::new();
+            for name in outer.iter().map(|other| other.name.as_str()).chain([symbol.name.as_str()]) {
+                if names.last() != Some(&name) {
+                    names.push(name);
+                }
+            }
+            (names.join("."), symbol)
+        })
+        .collect();
+    functions.sort_by_key(|(_, symbol)| symbol.start_line);
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::engine::DiffEngine;
+    use crate::diff::test_repo::TestRepo;
+    use crate::diff::types::*;
+
+    const SERVICE: &str = "\
+class Service:
+    def load(self):
+        return 1
+
+    def save(self):
+        return 2
+
+
+def helper():
+    return 3
+";
+
+    #[test]
+    fn fixture_repo_reports_changed_and_renamed_symbols() {
+        let repo = TestRepo::new();
+        repo.write("service.py", SERVICE)
+            .write("utils.py", "def parse(text):\n    return text.split()\n\n\ndef join(parts):\n    return ' '.join(parts)\n");
+        let first = repo.commit("first");
+        // 修改 Service.load 的函数体并重命名 helper；utils.py 只移动位置
+        repo.write("service.py", &SERVICE.replace("return 1", "return 10").replace("def helper", "def helper_renamed"));
+        repo.git(&["mv", "utils.py", "tools.py"]);
+        let second = repo.commit("second");
+
+        let engine = DiffEngine::new(ComparisonConfig {
+            detect_changed_symbols: true,
+            ..ComparisonConfig::default()
+        });
+        let result = engine
+            .compare(ComparisonRequest {
+                source_a: first.clone(),
+                source_b: second.clone(),
+                config: ComparisonConfig::default(),
+                is_git_comparison: true,
+                git_params: Some(GitComparisonParams {
+                    repository_path: repo.path_str().to_string(),
+                    left_ref: first,
+                    right_ref: second,
+                    file_paths: Vec::new(),
+                }),
+                is_working_tree_comparison: false,
+            })
+            .unwrap();
+
+        let renamed = result.file_diffs.iter().find(|d| d.path == "tools.py").unwrap();
+        assert!(matches!(&renamed.status, FileStatus::Renamed { old_path, .. } if old_path == "utils.py"));
+        assert!(renamed.changed_symbols.is_empty(), "{:?}", renamed.changed_symbols);
+
+        let changes: Vec<(&str, &str, SymbolChange)> = result
+            .changed_symbols
+            .iter()
+            .map(|c| (c.path.as_str(), c.symbol.name.as_str(), c.symbol.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("service.py", "Service.load", SymbolChange::Modified),
+                ("service.py", "helper_renamed", SymbolChange::Added),
+                ("service.py", "helper", SymbolChange::Removed),
+            ]
+        );
+    }
+}
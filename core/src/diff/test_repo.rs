@@ -0,0 +1,78 @@
+// 测试用的临时 Git 仓库，通过 git 命令行创建，不读取用户与系统的 git 配置
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+pub(crate) struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    /// 在临时目录中初始化仓库，默认分支为 `main`
+    pub(crate) fn new() -> Self {
+        let repo = Self {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        repo.git(&["init", "-q", "-b", "main"]);
+        repo
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// 仓库路径的字符串形式，用于以 `&str` 接收仓库路径的接口
+    pub(crate) fn path_str(&self) -> &str {
+        self.path().to_str().unwrap()
+    }
+
+    pub(crate) fn file(&self, path: &str) -> PathBuf {
+        self.path().join(path)
+    }
+
+    /// 写入文件，必要时创建父目录
+    pub(crate) fn write(&self, path: &str, content: &str) -> &Self {
+        let file = self.file(path);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, content).unwrap();
+        self
+    }
+
+    pub(crate) fn remove(&self, path: &str) -> &Self {
+        fs::remove_file(self.file(path)).unwrap();
+        self
+    }
+
+    /// 暂存所有变更并提交，返回新提交的哈希
+    pub(crate) fn commit(&self, message: &str) -> String {
+        self.git(&["add", "-A"]);
+        self.git(&["commit", "-q", "--allow-empty", "-m", message]);
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    /// 在仓库中执行 git，要求成功，返回去掉首尾空白的标准输出
+    pub(crate) fn git(&self, args: &[&str]) -> String {
+        git_in(self.path(), args)
+    }
+}
+
+/// 在 `dir` 中执行 git，要求成功，返回去掉首尾空白的标准输出
+pub(crate) fn git_in(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
@@ -0,0 +1,222 @@
+// 三方比较：`a`、`b` 分别与共同的基准版本逐行比较，按三方都相同的行切分区域，区分单侧修改、相同修改与冲突
+
+use crate::diff::engine::{comparison_keys, line_diff, split_content_lines};
+use crate::diff::types::*;
+
+/// 区域的变更情况
+#[derive(Clone, Copy, PartialEq)]
+enum Region {
+    Unchanged,
+    Changed(MergeSide),
+    Conflict,
+}
+
+/// 比较 `a` 与 `b` 相对 `base` 的变更
+///
+/// 在三方都相同的行之间切分区域（同 diff3）：只有一侧与基准不同为该侧的修改，两侧相同为相同修改，
+/// 否则为冲突。行的比较遵循 `ignore_whitespace`、`ignore_case` 等选项，输出的内容保持原样
+pub fn three_way_diff(base: &str, a: &str, b: &str, path: &str, config: &ComparisonConfig) -> ThreeWayFileDiff {
+    let texts = [base, a, b];
+    let raw: Vec<Vec<&str>> = texts.iter().map(|text| raw_lines(text)).collect();
+    let normalized: Vec<Vec<String>> = texts.iter().map(|text| split_content_lines(text, config)).collect();
+    let keys: Vec<Vec<_>> = normalized.iter().map(|lines| comparison_keys(lines, config.ignore_case)).collect();
+
+    // 基准中的每一行在 a、b 中对应的相同行
+    let mut degraded = false;
+    let mut matches = |side: usize| {
+        let diff = line_diff(&normalized[0], &normalized[side], config);
+        degraded |= diff.degraded;
+        let mut matched = vec![None; normalized[0].len()];
+        for line in diff.lines.iter().filter(|line| line.diff_type == DiffType::Equal) {
+            if let (Some(left), Some(right)) = (line.left_line_number, line.right_line_number) {
+                matched[left as usize - 1] = Some(right as usize - 1);
+            }
+        }
+        matched
+    };
+    let (matched_a, matched_b) = (matches(1), matches(2));
+
+    let mut lines = Vec::new();
+    let mut conflicts = 0;
+    let mut merged: Vec<&str> = Vec::new();
+    // 合并结果最后一段所取的文本，决定末尾是否换行
+    let mut last_source = base;
+    let mut start = [0usize; 3];
+    loop {
+        // 下一处三方相同的行
+        let sync = (start[0]..normalized[0].len()).find_map(|i| {
+            let sync = [i, matched_a[i]?, matched_b[i]?];
+            (sync[1] >= start[1] && sync[2] >= start[2]).then_some(sync)
+        });
+        let end = sync.unwrap_or([normalized[0].len(), normalized[1].len(), normalized[2].len()]);
+
+        if start != end {
+            let range = |side: usize| start[side]..end[side];
+            let same = |x: usize, y: usize| keys[x][range(x)] == keys[y][range(y)];
+            let region = match (same(1, 0), same(2, 0)) {
+                (true, true) => Region::Unchanged,
+                (true, false) => Region::Changed(MergeSide::B),
+                (false, true) => Region::Changed(MergeSide::A),
+                (false, false) if same(1, 2) => Region::Changed(MergeSide::Both),
+                (false, false) => Region::Conflict,
+            };
+            let source = match region {
+                Region::Unchanged => Some(0),
+                Region::Changed(MergeSide::B) => Some(2),
+                Region::Changed(_) => Some(1),
+                Region::Conflict => None,
+            };
+            match source {
+                Some(side) => {
+                    merged.extend(&raw[side][range(side)]);
+                    last_source = texts[side];
+                }
+                None => conflicts += 1,
+            }
+
+            let rows = (0..3).map(|side| range(side).len()).max().unwrap_or(0);
+            for row in 0..rows {
+                let cell = |side: usize| {
+                    let index = start[side] + row;
+                    (index < end[side]).then(|| ThreeWayCell {
+                        line_number: index as u32 + 1,
+                        content: raw[side][index].to_string(),
+                    })
+                };
+                let (base_cell, a_cell, b_cell) = (cell(0), cell(1), cell(2));
+                let (diff_type, changed_in) = match region {
+                    Region::Unchanged => (DiffType::Equal, None),
+                    Region::Conflict => (DiffType::Conflict, Some(MergeSide::Both)),
+                    Region::Changed(side) => {
+                        let changed = if side == MergeSide::B { &b_cell } else { &a_cell };
+                        let diff_type = match (&base_cell, changed) {
+                            (Some(_), Some(_)) => DiffType::Replace,
+                            (Some(_), None) => DiffType::Delete,
+                            _ => DiffType::Insert,
+                        };
+                        (diff_type, Some(side))
+                    }
+                };
+                lines.push(ThreeWayLine {
+                    base: base_cell,
+                    a: a_cell,
+                    b: b_cell,
+                    diff_type,
+                    changed_in,
+                });
+            }
+        }
+
+        let Some([i, j, k]) = sync else {
+            break;
+        };
+        let cell = |side: usize, index: usize| {
+            Some(ThreeWayCell {
+                line_number: index as u32 + 1,
+                content: raw[side][index].to_string(),
+            })
+        };
+        lines.push(ThreeWayLine {
+            base: cell(0, i),
+            a: cell(1, j),
+            b: cell(2, k),
+            diff_type: DiffType::Equal,
+            changed_in: None,
+        });
+        merged.push(raw[0][i]);
+        last_source = base;
+        start = [i + 1, j + 1, k + 1];
+    }
+
+    let merged_content = (conflicts == 0).then(|| {
+        let mut content = merged.join("\n");
+        if !merged.is_empty() && last_source.ends_with('\n') {
+            content.push('\n');
+        }
+        content
+    });
+    ThreeWayFileDiff {
+        path: path.to_string(),
+        lines,
+        conflicts,
+        merged_content,
+        degraded,
+    }
+}
+
+/// 按 `\n` 拆分的原始行，与 `split_content_lines` 的行一一对应
+fn raw_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    if text.is_empty() || text.ends_with('\n') {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 一行的 (类型, 修改的一侧, 基准内容, a 内容, b 内容)
+    type Row<'a> = (DiffType, Option<MergeSide>, Option<&'a str>, Option<&'a str>, Option<&'a str>);
+
+    fn rows(diff: &ThreeWayFileDiff) -> Vec<Row<'_>> {
+        fn content(cell: &Option<ThreeWayCell>) -> Option<&str> {
+            cell.as_ref().map(|cell| cell.content.as_str())
+        }
+        diff.lines
+            .iter()
+            .map(|line| (line.diff_type, line.changed_in, content(&line.base), content(&line.a), content(&line.b)))
+            .collect()
+    }
+
+    #[test]
+    fn clean_merge_takes_each_sides_changes() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "a\nB\nc\nd\ne\nf\n";
+        let theirs = "a\nb\nc\nD\ne\nf\n";
+        let diff = three_way_diff(base, ours, theirs, "file.txt", &ComparisonConfig::default());
+
+        assert_eq!(diff.conflicts, 0);
+        assert_eq!(diff.merged_content.as_deref(), Some("a\nB\nc\nD\ne\nf\n"));
+        assert_eq!(
+            rows(&diff),
+            [
+                (DiffType::Equal, None, Some("a"), Some("a"), Some("a")),
+                (DiffType::Replace, Some(MergeSide::A), Some("b"), Some("B"), Some("b")),
+                (DiffType::Equal, None, Some("c"), Some("c"), Some("c")),
+                (DiffType::Replace, Some(MergeSide::B), Some("d"), Some("d"), Some("D")),
+                (DiffType::Equal, None, Some("e"), Some("e"), Some("e")),
+                (DiffType::Insert, Some(MergeSide::Both), None, Some("f"), Some("f")),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicting_region_has_no_merged_content() {
+        let base = "x\ny\nz\nw";
+        let ours = "x\nours\nz\nW";
+        let theirs = "x\ntheirs\nextra\nz\nw";
+        let diff = three_way_diff(base, ours, theirs, "file.txt", &ComparisonConfig::default());
+
+        assert_eq!(diff.conflicts, 1);
+        assert_eq!(diff.merged_content, None);
+        assert_eq!(
+            rows(&diff),
+            [
+                (DiffType::Equal, None, Some("x"), Some("x"), Some("x")),
+                (DiffType::Conflict, Some(MergeSide::Both), Some("y"), Some("ours"), Some("theirs")),
+                (DiffType::Conflict, Some(MergeSide::Both), None, None, Some("extra")),
+                (DiffType::Equal, None, Some("z"), Some("z"), Some("z")),
+                (DiffType::Replace, Some(MergeSide::A), Some("w"), Some("W"), Some("w")),
+            ]
+        );
+        // 冲突区域各列保留原行号
+        let extra = &diff.lines[2];
+        assert_eq!(extra.b.as_ref().map(|cell| cell.line_number), Some(3));
+
+        // 只保留一侧的改动后可以合并，末尾没有换行与所取的一侧一致
+        let diff = three_way_diff(base, ours, "x\ny\nz\nw", "file.txt", &ComparisonConfig::default());
+        assert_eq!(diff.merged_content.as_deref(), Some("x\nours\nz\nW"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ast::SymbolKind;
 use serde::{Deserialize, Serialize};
 
 /// 差异类型
@@ -9,8 +10,12 @@ pub enum DiffType {
     Insert,
     /// 删除的内容
     Delete,
-    /// 修改的内容
+    /// 修改的内容：左侧一行被右侧一行替换，`content` 为右侧内容，`old_content` 为左侧内容
     Replace,
+    /// 在文件内移动的内容：左侧行为移出位置，右侧行为移入位置，两侧通过 `move_id` 关联
+    Moved,
+    /// 三方比较中两侧对同一区域做了不同的修改
+    Conflict,
 }
 
 /// 文件差异中的一行
@@ -24,8 +29,17 @@ pub struct DiffLine {
     pub diff_type: DiffType,
     /// 行内容
     pub content: String,
-    /// 是否为空白行（用于对齐）
+    /// 是否为空白行（用于对齐）：`Delete` 类型的占位行位于左栏，`Insert` 类型的位于右栏
     pub is_placeholder: bool,
+    /// 移动块编号（文件内唯一），仅 `Moved` 行有值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub move_id: Option<u32>,
+    /// 变更所在的函数/类，仅每组连续变更的第一行有值（需开启 `annotate_enclosing_symbols`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enclosing_symbol: Option<String>,
+    /// `Replace` 行左侧的原内容
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_content: Option<String>,
 }
 
 /// 单个文件的差异信息
@@ -35,8 +49,11 @@ pub struct FileDiff {
     pub path: String,
     /// 文件状态（新增、删除、修改、重命名）
     pub status: FileStatus,
-    /// 差异行列表
+    /// 差异行列表（并排视图）；统一/精简视图下为空，改用 `hunks`
     pub lines: Vec<DiffLine>,
+    /// 按上下文行数分组的差异块（统一/精简视图）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hunks: Vec<DiffHunk>,
     /// 原始内容（用于Monaco Editor等高级编辑器）
     pub original_content: Option<String>,
     /// 修改后的内容（用于Monaco Editor等高级编辑器）
@@ -45,10 +62,84 @@ pub struct FileDiff {
     pub left_stats: FileStats,
     /// 右侧文件的统计信息
     pub right_stats: FileStats,
+    /// 修改文件的变更类型（仅空白、仅注释或实质变更），新增、删除及未变更文件为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_kind: Option<ChangeKind>,
+    /// 行差异计算超过 `deadline_ms` 时限，`lines` 退化为整文件替换
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
+    /// 新增、删除或修改的函数/方法（需开启 `detect_changed_symbols`，语言不受支持时为空）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed_symbols: Vec<ChangedSymbol>,
+    /// 文件超过 `max_file_size` 未做行比较（`lines` 为空，状态由摘要判断），或差异行超过 `max_diff_lines` 被截断
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// 二进制文件为可识别的图片时，两侧的尺寸、颜色类型等头部信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_info: Option<ImageDiffInfo>,
+    /// 任一侧为 Git LFS 指针文件：按指针记录的对象比较，`lines` 为一行对象说明，统计中的大小与摘要为对象的
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_lfs: bool,
+}
+
+/// 函数/方法级的变更
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// 发生变更的函数/方法
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedSymbol {
+    /// 含外层类或函数的限定名，如 `Outer.run`
+    pub name: String,
+    pub kind: SymbolKind,
+    pub change: SymbolChange,
+}
+
+/// 比较结果中汇总的符号变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChangedSymbol {
+    /// 所在文件路径
+    pub path: String,
+    #[serde(flatten)]
+    pub symbol: ChangedSymbol,
+}
+
+/// 修改文件的变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    /// 只改动了换行符（CRLF/LF）或文件末尾换行
+    LineEndingsOnly,
+    /// 只改动了空白（缩进、换行等）
+    WhitespaceOnly,
+    /// 除空白外只改动了注释
+    CommentOnly,
+    /// 实质性变更
+    Substantive,
+}
+
+/// 差异块：一组相邻的变更行及其上下文
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffHunk {
+    /// 左侧起始行号；左侧无行时为块之前的最后一行（文件开头为 0）
+    pub left_start: u32,
+    /// 左侧行数
+    pub left_count: u32,
+    /// 右侧起始行号；右侧无行时为块之前的最后一行（文件开头为 0）
+    pub right_start: u32,
+    /// 右侧行数
+    pub right_count: u32,
+    /// 块头，格式同 unified diff：`@@ -left_start,left_count +right_start,right_count @@`
+    pub header: String,
+    /// 块内的差异行
+    pub lines: Vec<DiffLine>,
 }
 
 /// 文件状态
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FileStatus {
     /// 新增文件
     Added,
@@ -56,8 +147,18 @@ pub enum FileStatus {
     Deleted,
     /// 修改文件
     Modified,
-    /// 重命名文件
-    Renamed { old_path: String },
+    /// 重命名文件，`similarity` 为新旧内容的相似度（0-1）
+    Renamed {
+        old_path: String,
+        #[serde(default)]
+        similarity: f32,
+    },
+    /// 复制文件：新增文件与左侧仍存在的 `source_path` 内容相近
+    Copied {
+        source_path: String,
+        #[serde(default)]
+        similarity: f32,
+    },
     /// 未修改
     Unchanged,
 }
@@ -71,6 +172,112 @@ pub struct FileStats {
     pub line_count: u32,
     /// 最后修改时间（Unix时间戳）
     pub modified_time: Option<i64>,
+    /// 文件内容的 SHA-256（十六进制），仅在比较过程中计算过时提供
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// 文本文件检测到的编码，如 `UTF-8`、`GBK`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// 图片格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    WebP,
+}
+
+/// 图片的颜色类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageColorType {
+    Grayscale,
+    GrayscaleAlpha,
+    Rgb,
+    Rgba,
+    /// 调色板索引色
+    Indexed,
+    Cmyk,
+    YCbCr,
+}
+
+/// 从文件头读取的图片信息，不解码像素数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    /// 宽度（像素）
+    pub width: u32,
+    /// 高度（像素）
+    pub height: u32,
+    /// 颜色类型，文件头未给出时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_type: Option<ImageColorType>,
+    /// 每个通道（索引色为每个像素）的位数，文件头未给出时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit_depth: Option<u8>,
+}
+
+/// 二进制图片文件两侧的结构化比较；内容摘要见两侧 `FileStats.content_hash`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageDiffInfo {
+    /// 左侧图片信息，文件不存在或无法识别（格式不支持、文件头截断或损坏）时为空
+    pub left: Option<ImageInfo>,
+    /// 右侧图片信息，为空的情形同 `left`
+    pub right: Option<ImageInfo>,
+    /// 右侧相对左侧的字节数变化
+    pub size_delta: i64,
+    /// 两侧都可识别且宽高不同
+    pub dimensions_changed: bool,
+}
+
+/// 三方比较中做了修改的一侧
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeSide {
+    A,
+    B,
+    /// 两侧都修改：改法相同，或为冲突
+    Both,
+}
+
+/// 三方比较中一列的一行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreeWayCell {
+    /// 行号（从 1 开始）
+    pub line_number: u32,
+    pub content: String,
+}
+
+/// 三方比较的一行，三列分别为基准版本、`a`（我方）与 `b`（对方）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreeWayLine {
+    /// 基准版本中的行，该列在此行没有内容时为空
+    pub base: Option<ThreeWayCell>,
+    pub a: Option<ThreeWayCell>,
+    pub b: Option<ThreeWayCell>,
+    /// `Equal` 为三方相同；`Insert`/`Delete`/`Replace` 为修改一侧相对基准的变化；两侧改法不同为 `Conflict`
+    pub diff_type: DiffType,
+    /// 做了修改的一侧，三方相同时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_in: Option<MergeSide>,
+}
+
+/// 单个文件的三方比较结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreeWayFileDiff {
+    /// 文件路径（`a` 一侧）
+    pub path: String,
+    /// 按区域排列的行：三方相同的行逐行对齐，变更区域内各列按次序逐行排列
+    pub lines: Vec<ThreeWayLine>,
+    /// 冲突区域数
+    pub conflicts: u32,
+    /// 无冲突时的合并结果：只有一侧修改的区域取该侧内容，两侧改法相同的区域取 `a`；有冲突时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_content: Option<String>,
+    /// 任一侧的行差异计算超过 `deadline_ms` 时限，对齐退化为整段变更
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
 }
 
 /// 两个版本之间的整体差异比较结果
@@ -86,6 +293,18 @@ pub struct ComparisonResult {
     pub file_diffs: Vec<FileDiff>,
     /// 总体统计信息
     pub summary: ComparisonSummary,
+    /// 所有文件中变更的函数/方法（需开启 `detect_changed_symbols`）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed_symbols: Vec<FileChangedSymbol>,
+    /// Git 比较左侧版本的提交，暂存区或工作目录一侧为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_commit: Option<CommitInfo>,
+    /// Git 比较右侧版本的提交，暂存区或工作目录一侧为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_commit: Option<CommitInfo>,
+    /// 两侧提交的合并基础（完整哈希），没有共同祖先时为空；与两侧提交都不同时说明两侧已分叉
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_base: Option<String>,
 }
 
 /// 比较结果的总体统计
@@ -99,10 +318,172 @@ pub struct ComparisonSummary {
     pub files_modified: u32,
     /// 重命名文件数
     pub files_renamed: u32,
+    /// 复制文件数
+    #[serde(default)]
+    pub files_copied: u32,
+    /// 新增行数
+    pub lines_added: u32,
+    /// 删除行数
+    pub lines_deleted: u32,
+    /// 文件内移动的行数，按移出位置计数，不计入新增/删除
+    #[serde(default)]
+    pub lines_moved: u32,
+    /// 只改动了换行符的修改文件数
+    #[serde(default)]
+    pub files_line_endings_only: u32,
+    /// 只改动了空白的修改文件数
+    #[serde(default)]
+    pub files_whitespace_only: u32,
+    /// 只改动了注释的修改文件数
+    #[serde(default)]
+    pub files_comment_only: u32,
+    /// 内容未变化的文件数
+    #[serde(default)]
+    pub files_unchanged: u32,
+    /// 参与比较的文件总数（含未变化与跳过的文件）
+    #[serde(default)]
+    pub files_compared: u32,
+    /// 未做行比较的文件数（二进制或读取失败）
+    #[serde(default)]
+    pub files_skipped: u32,
+    /// 新增行的字节数（按 UTF-8 计，含换行符），移动的行不计入
+    #[serde(default)]
+    pub bytes_added: u64,
+    /// 删除行的字节数（按 UTF-8 计，含换行符），移动的行不计入
+    #[serde(default)]
+    pub bytes_deleted: u64,
+}
+
+/// 比较结果的文件列表视图：只含各文件的状态与统计，差异行按需单独获取
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonOverview {
+    pub source_a: String,
+    pub source_b: String,
+    pub comparison_time: i64,
+    pub files: Vec<FileDiffEntry>,
+    pub summary: ComparisonSummary,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_commit: Option<CommitInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_commit: Option<CommitInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_base: Option<String>,
+}
+
+/// 文件列表中的单个文件，不含差异行与文件内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDiffEntry {
+    pub path: String,
+    pub status: FileStatus,
+    pub left_stats: FileStats,
+    pub right_stats: FileStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_kind: Option<ChangeKind>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
     /// 新增行数
     pub lines_added: u32,
     /// 删除行数
     pub lines_deleted: u32,
+    /// 文件内移动的行数，按移出位置计数，不计入新增/删除
+    #[serde(default)]
+    pub lines_moved: u32,
+    /// 变更强度，见 `FileDiff::churn_score`
+    #[serde(default)]
+    pub churn_score: f64,
+}
+
+impl FileDiff {
+    /// 文件列表条目，行数同时统计 `lines` 与 `hunks` 中的变更行，`Replace` 行计为一增一删
+    pub fn entry(&self) -> FileDiffEntry {
+        let (lines_added, lines_deleted, lines_moved) = self.line_changes();
+        FileDiffEntry {
+            path: self.path.clone(),
+            status: self.status.clone(),
+            left_stats: self.left_stats.clone(),
+            right_stats: self.right_stats.clone(),
+            change_kind: self.change_kind,
+            degraded: self.degraded,
+            lines_added,
+            lines_deleted,
+            lines_moved,
+            churn_score: self.churn(lines_added, lines_deleted),
+        }
+    }
+
+    /// 变更强度：新增与删除行数之和除以两侧行数的较大值（至少为 1），整文件改写时可超过 1；
+    /// 二进制文件、未变更文件及超过大小限制未做行比较的文件为 0
+    pub fn churn_score(&self) -> f64 {
+        let (lines_added, lines_deleted, _) = self.line_changes();
+        self.churn(lines_added, lines_deleted)
+    }
+
+    fn churn(&self, lines_added: u32, lines_deleted: u32) -> f64 {
+        // 二进制文件两侧都没有文本编码，差异行只是描述文件的说明行
+        if self.left_stats.encoding.is_none() && self.right_stats.encoding.is_none() {
+            return 0.0;
+        }
+        let total_lines = self.left_stats.line_count.max(self.right_stats.line_count).max(1);
+        (lines_added + lines_deleted) as f64 / total_lines as f64
+    }
+
+    /// 新增、删除与移动的行数
+    fn line_changes(&self) -> (u32, u32, u32) {
+        let (mut lines_added, mut lines_deleted, mut lines_moved) = (0, 0, 0);
+        for line in self.lines.iter().chain(self.hunks.iter().flat_map(|hunk| &hunk.lines)) {
+            if line.is_placeholder {
+                continue;
+            }
+            match line.diff_type {
+                DiffType::Insert => lines_added += 1,
+                DiffType::Delete => lines_deleted += 1,
+                DiffType::Replace if line.left_line_number.is_some() => {
+                    lines_added += 1;
+                    lines_deleted += 1;
+                }
+                DiffType::Moved if line.left_line_number.is_some() => lines_moved += 1,
+                _ => {}
+            }
+        }
+        (lines_added, lines_deleted, lines_moved)
+    }
+}
+
+impl ComparisonResult {
+    /// 不含差异行的文件列表视图
+    pub fn overview(&self) -> ComparisonOverview {
+        ComparisonOverview {
+            source_a: self.source_a.clone(),
+            source_b: self.source_b.clone(),
+            comparison_time: self.comparison_time,
+            files: self.file_diffs.iter().map(FileDiff::entry).collect(),
+            summary: self.summary.clone(),
+            left_commit: self.left_commit.clone(),
+            right_commit: self.right_commit.clone(),
+            merge_base: self.merge_base.clone(),
+        }
+    }
+
+    /// 按路径查找单个文件的差异
+    pub fn file_diff(&self, path: &str) -> Option<&FileDiff> {
+        self.file_diffs.iter().find(|diff| diff.path == path)
+    }
+
+    /// 变更强度大于 0 的文件，按 `churn_score` 从高到低排列，相同时按路径排列
+    pub fn hotspots(&self) -> Vec<FileDiffEntry> {
+        let mut entries: Vec<FileDiffEntry> = self
+            .file_diffs
+            .iter()
+            .map(FileDiff::entry)
+            .filter(|entry| entry.churn_score > 0.0)
+            .collect();
+        entries.sort_by(|a, b| {
+            b.churn_score
+                .total_cmp(&a.churn_score)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        entries
+    }
 }
 
 /// 差异显示模式
@@ -129,10 +510,72 @@ pub struct ComparisonConfig {
     pub context_lines: u32,
     /// 是否进行语法高亮
     pub enable_syntax_highlight: bool,
-    /// 是否检测文件移动和重命名
+    /// 是否检测文件移动、重命名和复制
     pub detect_renames: bool,
     /// 文件相似度阈值（用于重命名检测）
     pub rename_similarity_threshold: f32,
+    /// 目录比较时排除的路径 glob（相对比较根目录），匹配的目录整体跳过
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// 目录比较时是否遵循 .gitignore / .ignore 规则
+    #[serde(default)]
+    pub respect_gitignore: bool,
+    /// 文件内移动块检测的最小连续行数，0 表示不检测
+    #[serde(default = "default_min_moved_lines")]
+    pub min_moved_lines: u32,
+    /// 变更分类时是否识别只改动注释的文件（按扩展名识别语言）
+    #[serde(default)]
+    pub classify_comment_changes: bool,
+    /// 是否忽略换行符差异（CRLF 与 LF），原始内容保持不变
+    #[serde(default)]
+    pub ignore_line_endings: bool,
+    /// 是否忽略文件末尾有无换行的差异
+    #[serde(default)]
+    pub ignore_trailing_newline: bool,
+    /// 行差异算法
+    #[serde(default)]
+    pub algorithm: DiffAlgorithm,
+    /// 单个文件行差异计算的时限（毫秒），超时后退化为整文件替换；为空时不限制
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
+    /// 是否为每处变更标注所在的函数/类，并写入差异块头
+    #[serde(default)]
+    pub annotate_enclosing_symbols: bool,
+    /// 是否解析两侧内容，列出新增、删除与修改的函数/方法
+    #[serde(default)]
+    pub detect_changed_symbols: bool,
+    /// 参与行比较的文件大小上限（字节），任一侧超过时只按摘要判断是否变化
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// 单个文件保留的差异行数上限，超出时只保留前面完整的差异块并追加截断提示行
+    #[serde(default = "default_max_diff_lines")]
+    pub max_diff_lines: u32,
+    /// 忽略区域的起止标记对，如 `("@generated-start", "@generated-end")`：两侧包含起始标记与对应结束标记的行之间的内容
+    /// 折叠为一行说明，不计入增删行数（标记行本身照常比较）；嵌套的区域并入外层，缺少结束标记的起始标记不生效
+    #[serde(default)]
+    pub ignore_regions: Vec<(String, String)>,
+}
+
+/// 行差异算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    /// 以唯一行为锚点，适合大段重排或重新格式化的文件
+    Patience,
+    Lcs,
+}
+
+fn default_min_moved_lines() -> u32 {
+    3
+}
+
+fn default_max_file_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_max_diff_lines() -> u32 {
+    200_000
 }
 
 impl Default for ComparisonConfig {
@@ -145,10 +588,71 @@ impl Default for ComparisonConfig {
             enable_syntax_highlight: true,
             detect_renames: true,
             rename_similarity_threshold: 0.8,
+            exclude_patterns: Vec::new(),
+            respect_gitignore: false,
+            min_moved_lines: default_min_moved_lines(),
+            classify_comment_changes: false,
+            ignore_line_endings: false,
+            ignore_trailing_newline: false,
+            algorithm: DiffAlgorithm::Myers,
+            deadline_ms: None,
+            annotate_enclosing_symbols: false,
+            detect_changed_symbols: false,
+            max_file_size: default_max_file_size(),
+            max_diff_lines: default_max_diff_lines(),
+            ignore_regions: Vec::new(),
         }
     }
 }
 
+/// 目录比较进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffProgress {
+    /// 两侧配对后需要比较的文件总数
+    pub files_paired: usize,
+    /// 已完成比较的文件数
+    pub files_processed: usize,
+    /// 最近完成比较的文件（相对路径）
+    pub current_path: Option<String>,
+}
+
+/// 比较被取消时返回的错误，可通过 `anyhow::Error::downcast_ref` 识别
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Comparison cancelled")]
+pub struct ComparisonCancelled;
+
+/// 补丁应用时写入目标目录失败，已完成的变更均已恢复；可通过 `anyhow::Error::downcast_ref` 识别
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to write {}: {source}", .path.display())]
+pub struct PatchWriteFailed {
+    pub path: std::path::PathBuf,
+    pub source: std::io::Error,
+}
+
+/// 补丁应用结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchApplyResult {
+    /// 是否已写入文件；预览或因冲突放弃时为 false
+    pub applied: bool,
+    /// 成功应用（或预览中可以应用）的文件的变更
+    pub preview: ComparisonResult,
+    /// 无法应用的文件或差异块
+    pub conflicts: Vec<PatchConflict>,
+}
+
+/// 补丁冲突
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchConflict {
+    /// 文件路径（相对于目标目录）
+    pub file_path: String,
+    /// 冲突的差异块序号（从 0 开始）；文件级冲突（如文件已存在）为空
+    pub hunk_index: Option<usize>,
+    /// 差异块在原文件中的预期起始行号
+    pub line: Option<u32>,
+    /// 冲突原因
+    pub reason: String,
+}
+
 /// 比较请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComparisonRequest {
@@ -162,17 +666,198 @@ pub struct ComparisonRequest {
     pub is_git_comparison: bool,
     /// Git特定的参数
     pub git_params: Option<GitComparisonParams>,
+    /// 工作目录与 Git 引用比较：`source_a` 为引用（commit、分支或标签），`source_b` 为仓库内的目录，
+    /// 右侧取磁盘上的内容（含未提交的修改与未跟踪文件）
+    #[serde(default)]
+    pub is_working_tree_comparison: bool,
+}
+
+/// 某一行在两个版本之间的变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineChange {
+    /// 左侧版本中没有对应行（含左侧版本中文件不存在）
+    Added,
+    /// 所在位置的内容被修改
+    Modified,
+    Unchanged,
+}
+
+/// `git blame` 给出的行作者信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineBlame {
+    pub commit: String,
+    pub author: String,
+    /// 作者提交时间（Unix 时间戳）
+    pub author_time: i64,
+    pub summary: String,
+}
+
+/// 工作目录中文件一行的来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlameLine {
+    pub line: u32,
+    pub content: String,
+    /// 是否已提交；暂存区或工作目录中未提交的修改为 false
+    pub committed: bool,
+    /// 引入该行的提交，未提交时为空
+    pub blame: Option<LineBlame>,
+}
+
+/// 文件中一行在两个版本之间的对比结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineHistory {
+    /// 相对仓库根目录的路径
+    pub file_path: String,
+    pub left_ref: String,
+    pub right_ref: String,
+    /// 右侧版本中的行号
+    pub line: u32,
+    /// 文件在左侧版本中是否存在
+    pub exists_at_left: bool,
+    /// 左侧版本中对应的行号，新增时为空
+    pub left_line: Option<u32>,
+    pub left_content: Option<String>,
+    pub right_content: String,
+    pub change: LineChange,
+    /// 右侧版本中引入该行的提交
+    pub blame: Option<LineBlame>,
 }
 
 /// Git比较参数
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitComparisonParams {
-    /// 仓库路径
+    /// 仓库路径，可以是仓库（含工作树与子模块）内的任意目录
     pub repository_path: String,
-    /// 左侧的commit hash、分支名或标签
+    /// 左侧的commit hash、分支名或标签，`INDEX` 为暂存区，`WORKTREE` 为工作目录
     pub left_ref: String,
-    /// 右侧的commit hash、分支名或标签
+    /// 右侧的commit hash、分支名或标签，`INDEX` 为暂存区，`WORKTREE` 为工作目录
     pub right_ref: String,
     /// 指定要比较的文件路径（可选，为空则比较所有变更）
     pub file_paths: Vec<String>,
-}
\ No newline at end of file
+}
+
+/// 单个提交的比较请求：提交与其父提交比较
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitComparisonRequest {
+    /// 仓库路径，可以是仓库内的任意目录
+    pub repository_path: String,
+    /// 要查看的提交（分支、标签、提交哈希或 `HEAD~1` 等表达式）
+    pub commit: String,
+    /// 合并提交比较的父提交序号，从 0 开始，缺省为第一个父提交
+    #[serde(default)]
+    pub parent_index: Option<u32>,
+    /// 指定要比较的文件路径（可选，为空则比较所有变更）
+    #[serde(default)]
+    pub file_paths: Vec<String>,
+    #[serde(default)]
+    pub config: ComparisonConfig,
+}
+
+/// 单个提交的比较结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitComparisonResult {
+    #[serde(flatten)]
+    pub result: ComparisonResult,
+    /// 比较所用的父提交哈希，根提交为空树的哈希
+    pub parent_hash: String,
+    /// 提交的父提交数，根提交为 0，合并提交大于 1
+    pub parent_count: u32,
+    /// 合并提交未指定父提交时的说明
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// 文件在右侧版本中新增或修改的行，用于只扫描变更的代码
+#[derive(Debug, Clone)]
+pub struct ChangedLineRanges {
+    /// 右侧版本中相对仓库根目录的路径，重命名的文件为新路径
+    pub path: String,
+    /// 右侧版本的文件内容
+    pub content: String,
+    /// 新增或修改的行号区间 `(start, end)`，从 1 开始、含两端，按行号排列且互不相邻
+    pub ranges: Vec<(u32, u32)>,
+}
+
+impl ChangedLineRanges {
+    /// 行号区间 `[start, end]` 是否包含新增或修改的行
+    pub fn intersects(&self, start: u32, end: u32) -> bool {
+        let index = self.ranges.partition_point(|&(_, range_end)| range_end < start);
+        self.ranges.get(index).is_some_and(|&(range_start, _)| range_start <= end)
+    }
+}
+
+/// 提交列表的查询条件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitQuery {
+    /// 仓库路径，可以是仓库内的任意目录
+    pub repository_path: String,
+    /// 从该引用（分支、标签或提交）向前列出，缺省为 `HEAD`
+    #[serde(default, rename = "ref")]
+    pub reference: Option<String>,
+    /// 只列出改动了该文件或目录的提交，路径相对仓库根目录
+    #[serde(default)]
+    pub path: Option<String>,
+    /// 按提交标题、作者（不区分大小写）或提交哈希前缀筛选
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default = "default_commit_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+fn default_commit_limit() -> usize {
+    50
+}
+
+/// 提交列表中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub hash: String,
+    /// 仓库内唯一的缩写哈希
+    pub short_hash: String,
+    pub author: String,
+    /// 作者提交时间（Unix 时间戳）
+    pub author_time: i64,
+    /// 提交者提交时间（Unix 时间戳）
+    #[serde(default)]
+    pub commit_time: i64,
+    /// 提交说明的第一段
+    pub subject: String,
+}
+
+/// 引用的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitRefKind {
+    Branch,
+    RemoteBranch,
+    Tag,
+}
+
+/// 分支或标签，名称可直接用作 `GitComparisonParams` 的引用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitRef {
+    /// 本地分支与标签为短名称，远程分支为 `<remote>/<branch>`
+    pub name: String,
+    pub kind: GitRefKind,
+    /// 引用所指提交的完整哈希（附注标签为其指向的提交）
+    pub target_hash: String,
+    /// 所指提交的提交时间（Unix 时间戳）
+    pub committed_at: i64,
+    /// 当前检出的分支
+    pub is_head: bool,
+    /// 附注标签的说明，轻量标签与分支为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// 文件历史中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileHistoryEntry {
+    #[serde(flatten)]
+    pub commit: CommitInfo,
+    /// 文件在该提交中的路径（跟随重命名）；删除文件的提交为删除前的路径
+    pub path: String,
+}
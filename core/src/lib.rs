@@ -1,16 +1,19 @@
 // CTX-Audit Core Library
 // 核心功能库，包含AST引擎、扫描器、规则系统和差异对比
 
-mod ast;
-mod scanner;
+// ast / scanner / diff 作为库的公开模块：私有时其中未被根部重新导出的公开项
+// 会被判定为死代码，无法通过 `clippy -D warnings`
+pub mod ast;
+pub mod scanner;
 pub mod rules;
-mod diff;
+pub mod diff;
 
 // 重新导出常用类型
 pub use ast::{ASTEngine, ASTParser, CacheData, CacheManager, FileIndex, QueryEngine, Symbol, SymbolKind};
 pub use diff::DiffEngine;
 pub use scanner::{Finding, Scanner, scan_directory};
 pub use scanner::manager::ScannerManager;
+pub use scanner::external_scanner::{ExternalScanner, ExternalScannerConfig};
 
 // 规则系统
 pub use rules::{loader::load_rules_from_dir, model::Rule, scanner::RuleScanner};
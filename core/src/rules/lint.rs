@@ -0,0 +1,266 @@
+// 规则编写检查：静态分析正则中易导致回溯爆炸或匹配缓慢的写法，并用对抗性输入测量匹配耗时
+//
+// 本项目使用的 regex 引擎保证线性时间匹配，但嵌套量词、两端无界 `.*` 等写法仍会放大匹配开销，
+// 且规则在其他回溯型引擎中复用时会直接导致扫描挂起，因此以警告形式提示作者
+
+use crate::rules::model::Rule;
+use crate::rules::scanner::CompiledRule;
+use regex::Regex;
+use regex_syntax::ast::{parse::Parser, Ast, RepetitionKind, RepetitionRange};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// 正则复杂度预算，按展开重复次数后的节点数估算
+pub const COMPLEXITY_BUDGET: u64 = 1_000;
+
+/// 单个对抗性输入的匹配时间预算
+pub const MATCH_TIME_BUDGET: Duration = Duration::from_millis(100);
+
+/// 对抗性输入长度
+const ADVERSARIAL_LEN: usize = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticLevel {
+    /// 规则无法编译，不会参与扫描
+    Error,
+    /// 规则可用，但可能导致扫描缓慢
+    Warning,
+}
+
+/// 规则检查结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleDiagnostic {
+    pub rule_id: String,
+    pub level: DiagnosticLevel,
+    /// 问题所在的正则
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    pub message: String,
+}
+
+/// 检查一组规则；`measure` 为真时额外用对抗性输入测量匹配耗时
+pub fn verify_rules(rules: &[Rule], measure: bool) -> Vec<RuleDiagnostic> {
+    rules.iter().flat_map(|r| verify_rule(r, measure)).collect()
+}
+
+/// 检查单条规则：编译错误记为 error，可疑正则记为 warning
+pub fn verify_rule(rule: &Rule, measure: bool) -> Vec<RuleDiagnostic> {
+    if let Err(e) = CompiledRule::compile(rule) {
+        return vec![RuleDiagnostic {
+            rule_id: rule.id.clone(),
+            level: DiagnosticLevel::Error,
+            pattern: None,
+            message: e,
+        }];
+    }
+
+    let mut diagnostics = Vec::new();
+    for pattern in rule_patterns(rule) {
+        let warn = |message: String| RuleDiagnostic {
+            rule_id: rule.id.clone(),
+            level: DiagnosticLevel::Warning,
+            pattern: Some(pattern.to_string()),
+            message,
+        };
+        diagnostics.extend(lint_pattern(pattern).into_iter().map(warn));
+        if measure {
+            if let Some(message) = measure_pattern(pattern) {
+                diagnostics.push(warn(message));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// 静态检查单条正则，返回警告信息；语法错误由编译阶段报告
+pub fn lint_pattern(pattern: &str) -> Vec<String> {
+    let Ok(ast) = Parser::new().parse(pattern) else {
+        return Vec::new();
+    };
+
+    let mut warnings = Vec::new();
+    let mut nested = BTreeSet::new();
+    find_nested_quantifiers(&ast, None, pattern, &mut nested);
+    warnings.extend(nested);
+
+    if has_unbounded_dot_at_both_ends(&ast) {
+        warnings.push("以无界 `.*` 开头并结尾：两端的 `.*` 对查找没有帮助，只会增加每个位置的匹配开销".to_string());
+    }
+
+    let complexity = complexity(&ast);
+    if complexity > COMPLEXITY_BUDGET {
+        warnings.push(format!(
+            "复杂度估计为 {}，超出预算 {}：请减少重复次数上限或拆分规则",
+            complexity, COMPLEXITY_BUDGET
+        ));
+    }
+    warnings
+}
+
+/// 规则中的全部正则
+fn rule_patterns(rule: &Rule) -> Vec<&str> {
+    let mut patterns: Vec<&str> = rule.pattern.iter().map(String::as_str).collect();
+    if let Some(set) = &rule.patterns {
+        patterns.extend(
+            set.all_of
+                .iter()
+                .chain(&set.any_of)
+                .chain(&set.none_of)
+                .map(String::as_str),
+        );
+    }
+    patterns
+}
+
+fn is_unbounded(kind: &RepetitionKind) -> bool {
+    matches!(
+        kind,
+        RepetitionKind::ZeroOrMore
+            | RepetitionKind::OneOrMore
+            | RepetitionKind::Range(RepetitionRange::AtLeast(_))
+    )
+}
+
+/// 查找无界量词内部再次出现的无界量词，如 `(a+)+`
+fn find_nested_quantifiers(ast: &Ast, outer: Option<&str>, pattern: &str, out: &mut BTreeSet<String>) {
+    match ast {
+        Ast::Repetition(rep) => {
+            let text = &pattern[rep.span.start.offset..rep.span.end.offset];
+            if is_unbounded(&rep.op.kind) {
+                if let Some(outer) = outer {
+                    out.insert(format!(
+                        "嵌套量词：`{}` 内的 `{}` 可能导致回溯爆炸",
+                        outer, text
+                    ));
+                    return;
+                }
+                find_nested_quantifiers(&rep.ast, Some(text), pattern, out);
+            } else {
+                find_nested_quantifiers(&rep.ast, outer, pattern, out);
+            }
+        }
+        Ast::Group(group) => find_nested_quantifiers(&group.ast, outer, pattern, out),
+        Ast::Concat(concat) => {
+            for ast in &concat.asts {
+                find_nested_quantifiers(ast, outer, pattern, out);
+            }
+        }
+        Ast::Alternation(alt) => {
+            for ast in &alt.asts {
+                find_nested_quantifiers(ast, outer, pattern, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_unbounded_dot_at_both_ends(ast: &Ast) -> bool {
+    let ast = match ast {
+        Ast::Group(group) => &group.ast,
+        ast => ast,
+    };
+    let Ast::Concat(concat) = ast else {
+        return false;
+    };
+    let items: Vec<&Ast> = concat
+        .asts
+        .iter()
+        .filter(|a| !matches!(a, Ast::Flags(_)))
+        .collect();
+    let is_dot_star = |ast: &Ast| {
+        matches!(ast, Ast::Repetition(rep)
+            if is_unbounded(&rep.op.kind) && matches!(*rep.ast, Ast::Dot(_)))
+    };
+    items.len() >= 2 && is_dot_star(items[0]) && is_dot_star(items[items.len() - 1])
+}
+
+/// 估算正则展开后的节点数，有界重复按上限展开
+fn complexity(ast: &Ast) -> u64 {
+    match ast {
+        Ast::Repetition(rep) => {
+            let factor = match &rep.op.kind {
+                RepetitionKind::Range(RepetitionRange::Exactly(n))
+                | RepetitionKind::Range(RepetitionRange::AtLeast(n))
+                | RepetitionKind::Range(RepetitionRange::Bounded(_, n)) => (*n).max(1) as u64,
+                _ => 1,
+            };
+            complexity(&rep.ast).saturating_mul(factor).saturating_add(1)
+        }
+        Ast::Group(group) => complexity(&group.ast),
+        Ast::Concat(concat) => concat.asts.iter().map(complexity).fold(1, u64::saturating_add),
+        Ast::Alternation(alt) => alt.asts.iter().map(complexity).fold(1, u64::saturating_add),
+        _ => 1,
+    }
+}
+
+/// 用对抗性输入测量匹配耗时，超出预算时返回警告信息
+fn measure_pattern(pattern: &str) -> Option<String> {
+    let regex = Regex::new(pattern).ok()?;
+    for (label, input) in adversarial_inputs(pattern) {
+        if exceeds_time_budget(regex.clone(), input) {
+            return Some(format!(
+                "对抗性输入（{}，{} 字符）匹配耗时超过 {}ms",
+                label,
+                ADVERSARIAL_LEN,
+                MATCH_TIME_BUDGET.as_millis()
+            ));
+        }
+    }
+    None
+}
+
+/// 构造对抗性输入：重复正则中出现的字面字符，以及常见的长空白/长单词
+fn adversarial_inputs(pattern: &str) -> Vec<(String, String)> {
+    let mut literals = BTreeSet::new();
+    if let Ok(ast) = Parser::new().parse(pattern) {
+        collect_literals(&ast, &mut literals);
+    }
+    let literal_run: String = literals.iter().collect();
+
+    let mut inputs = vec![
+        ("重复 'a'".to_string(), "a".repeat(ADVERSARIAL_LEN)),
+        ("重复空格".to_string(), " ".repeat(ADVERSARIAL_LEN)),
+    ];
+    if !literal_run.is_empty() {
+        inputs.push((
+            format!("重复字面字符 {:?}", literal_run),
+            literal_run.chars().cycle().take(ADVERSARIAL_LEN).collect(),
+        ));
+        for c in literals.iter().take(4) {
+            inputs.push((format!("重复 {:?}", c), c.to_string().repeat(ADVERSARIAL_LEN)));
+        }
+    }
+    inputs
+}
+
+fn collect_literals(ast: &Ast, out: &mut BTreeSet<char>) {
+    match ast {
+        Ast::Literal(lit) => {
+            out.insert(lit.c);
+        }
+        Ast::Repetition(rep) => collect_literals(&rep.ast, out),
+        Ast::Group(group) => collect_literals(&group.ast, out),
+        Ast::Concat(concat) => concat.asts.iter().for_each(|a| collect_literals(a, out)),
+        Ast::Alternation(alt) => alt.asts.iter().for_each(|a| collect_literals(a, out)),
+        _ => {}
+    }
+}
+
+/// 在独立线程中按扫描器的方式（逐个捕获）匹配，判断是否超出时间预算
+///
+/// 匹配无法中断，超时的线程在后台运行至结束
+fn exceeds_time_budget(regex: Regex, input: String) -> bool {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let _ = regex.captures_iter(&input).count();
+        let _ = tx.send(start.elapsed());
+    });
+    match rx.recv_timeout(MATCH_TIME_BUDGET) {
+        Ok(elapsed) => elapsed > MATCH_TIME_BUDGET,
+        Err(_) => true,
+    }
+}
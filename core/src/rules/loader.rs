@@ -1,13 +1,24 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use anyhow::{Context, Result};
 use walkdir::WalkDir;
 use crate::rules::model::{Rule, RuleSet};
 
 pub fn load_rules_from_dir<P: AsRef<Path>>(path: P) -> Result<Vec<Rule>> {
+    load_rules_from_dir_excluding(path, &[])
+}
+
+/// 递归加载目录中的规则，跳过 `excluded` 中列出的子目录
+///
+/// 子目录中的规则未指定 `pack` 时，以相对 `path` 的第一级子目录名作为规则包名
+pub fn load_rules_from_dir_excluding<P: AsRef<Path>>(path: P, excluded: &[PathBuf]) -> Result<Vec<Rule>> {
+    let root = path.as_ref();
     let mut rules = Vec::new();
 
-    for entry in WalkDir::new(path) {
+    let walker = WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| !excluded.iter().any(|dir| e.path() == dir));
+    for entry in walker {
         let entry = entry?;
         if entry.file_type().is_file() {
             let path = entry.path();
@@ -16,13 +27,20 @@ pub fn load_rules_from_dir<P: AsRef<Path>>(path: P) -> Result<Vec<Rule>> {
                     let content = fs::read_to_string(path)
                         .with_context(|| format!("Failed to read rule file: {:?}", path))?;
                     
-                    // Try to parse as RuleSet first, then as single Rule
-                    if let Ok(rule_set) = serde_yaml::from_str::<RuleSet>(&content) {
-                        rules.extend(rule_set.rules);
-                    } else if let Ok(rule) = serde_yaml::from_str::<Rule>(&content) {
-                        rules.push(rule);
-                    } else {
-                        eprintln!("Failed to parse rule file: {:?}", path);
+                    match parse_rules(&content) {
+                        Some(parsed) => {
+                            let pack = pack_from_path(path.strip_prefix(root).unwrap_or(path));
+                            for mut rule in parsed {
+                                if rule.pack.is_none() {
+                                    rule.pack = pack.clone();
+                                }
+                                match rule.validate() {
+                                    Ok(_) => rules.push(rule),
+                                    Err(e) => eprintln!("Invalid rule in {:?}: {}", path, e),
+                                }
+                            }
+                        }
+                        None => eprintln!("Failed to parse rule file: {:?}", path),
                     }
                 }
             }
@@ -31,3 +49,23 @@ pub fn load_rules_from_dir<P: AsRef<Path>>(path: P) -> Result<Vec<Rule>> {
 
     Ok(rules)
 }
+
+/// 由规则文件的相对路径推导规则包名：位于子目录中时取第一级目录名
+pub fn pack_from_path(relative: &Path) -> Option<String> {
+    let mut components = relative.components();
+    let first = components.next()?;
+    components.next()?;
+    Some(first.as_os_str().to_string_lossy().to_string())
+}
+
+/// 解析单个 YAML 规则文件内容，支持 RuleSet 和单条 Rule 两种格式
+pub fn parse_rules(content: &str) -> Option<Vec<Rule>> {
+    // Try to parse as RuleSet first, then as single Rule
+    if let Ok(rule_set) = serde_yaml::from_str::<RuleSet>(content) {
+        Some(rule_set.rules)
+    } else if let Ok(rule) = serde_yaml::from_str::<Rule>(content) {
+        Some(vec![rule])
+    } else {
+        None
+    }
+}
@@ -1,3 +1,5 @@
 pub mod model;
+pub mod lint;
 pub mod loader;
+pub mod overlap;
 pub mod scanner;
@@ -1,4 +1,6 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Rule {
@@ -9,12 +11,157 @@ pub struct Rule {
     pub language: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pattern: Option<String>,
+    /// 正则以 `(?s)` 模式匹配整个文件，`.` 可匹配换行，用于跨多行的问题（如分多行拼接的 SQL）
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub multiline: bool,
+    /// 组合正则条件，与 `pattern` 同时存在时 `pattern` 视为 `all_of` 的一项
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patterns: Option<PatternSet>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub category: Option<String>,
+    /// 自由标签，用于检索与分组，如 `sql`、`auth`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 命中结果为真实问题的把握程度
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// 所属规则包，如 `web`、`crypto`；未指定时由规则目录下的子目录名决定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cwe: Option<String>,
+    /// OWASP 分类，如 `A03:2021-Injection`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owasp: Option<String>,
+    /// 修复建议
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+    /// 参考链接（OWASP/CWE 页面等）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    /// 修复模板，替换匹配到的内容，支持 `{{name}}` 捕获组插值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+    /// 文件路径范围，与 `language` 同时生效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paths: Option<PathFilter>,
+    /// 是否为随程序内置的规则（不写入规则文件）
+    #[serde(default, skip_serializing)]
+    pub builtin: bool,
+    /// 是否启用，禁用的规则不参与扫描
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// 多正则组合条件，按文件整体求值
+///
+/// 文件命中需同时满足：`all_of` 全部匹配、`any_of` 至少一个匹配（为空时忽略）、
+/// `none_of` 均不匹配。至少需要一个正向条件（`all_of` 或 `any_of`）
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PatternSet {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub all_of: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub any_of: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub none_of: Vec<String>,
+}
+
+/// 规则适用的文件路径 glob，`include` 为空时表示全部文件
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PathFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl PathFilter {
+    /// 编译 glob，语法错误时返回包含具体 glob 的错误信息
+    pub fn compile(&self) -> Result<CompiledPathFilter, String> {
+        Ok(CompiledPathFilter {
+            include: build_glob_set(&self.include)?,
+            exclude: build_glob_set(&self.exclude)?,
+        })
+    }
+}
+
+/// 编译后的路径过滤器
+#[derive(Debug, Clone)]
+pub struct CompiledPathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl CompiledPathFilter {
+    /// 路径是否匹配排除 glob
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(path))
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        if let Some(exclude) = &self.exclude {
+            if exclude.is_match(path) {
+                return false;
+            }
+        }
+        match &self.include {
+            Some(include) => include.is_match(path),
+            None => true,
+        }
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid path glob '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("Invalid path globs: {}", e))
+}
+
+/// 未归入任何规则包的规则所在的包名
+pub const DEFAULT_PACK: &str = "default";
+
+impl Rule {
+    /// 规则所属的规则包名
+    pub fn pack_name(&self) -> &str {
+        self.pack.as_deref().unwrap_or(DEFAULT_PACK)
+    }
+
+    /// 校验规则中需要编译的字段（目前为路径 glob）
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(paths) = &self.paths {
+            paths
+                .compile()
+                .map_err(|e| format!("Rule {}: {}", self.id, e))?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -27,6 +174,72 @@ pub enum Severity {
     Info,
 }
 
+impl Severity {
+    /// 排序值，越严重越大
+    pub fn rank(&self) -> u8 {
+        match self {
+            Severity::Critical => 4,
+            Severity::High => 3,
+            Severity::Medium => 2,
+            Severity::Low => 1,
+            Severity::Info => 0,
+        }
+    }
+
+    /// 解析发现中的严重程度字符串（不区分大小写），无法识别时返回 None
+    pub fn parse(severity: &str) -> Option<Self> {
+        match severity.to_ascii_lowercase().as_str() {
+            "critical" => Some(Severity::Critical),
+            "high" => Some(Severity::High),
+            "medium" => Some(Severity::Medium),
+            "low" => Some(Severity::Low),
+            "info" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+
+    /// 排序优先级中的权重，与发现的置信度相乘
+    pub fn weight(&self) -> f32 {
+        match self {
+            Severity::Critical => 1.0,
+            Severity::High => 0.8,
+            Severity::Medium => 0.5,
+            Severity::Low => 0.25,
+            Severity::Info => 0.1,
+        }
+    }
+}
+
+/// 规则的置信度
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    High,
+    Medium,
+    Low,
+}
+
+impl Confidence {
+    /// 发现的初始置信度分值（0-1）
+    pub fn score(self) -> f32 {
+        match self {
+            Confidence::High => 0.9,
+            Confidence::Medium => 0.6,
+            Confidence::Low => 0.3,
+        }
+    }
+
+    /// 解析外部工具输出中的置信度（不区分大小写），无法识别时返回 None
+    pub fn parse(confidence: &str) -> Option<Self> {
+        match confidence.to_ascii_lowercase().as_str() {
+            "high" => Some(Confidence::High),
+            "medium" => Some(Confidence::Medium),
+            "low" => Some(Confidence::Low),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct RuleSet {
     pub name: String,
@@ -0,0 +1,108 @@
+// 规则重叠分析：在样本语料上运行全部规则，找出总是同时命中的规则对
+
+use crate::rules::model::Rule;
+use crate::rules::scanner::RuleScanner;
+use crate::scanner::{is_supported_file, Scanner};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// 一对在相同位置命中的规则
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleOverlap {
+    pub rule_a: String,
+    pub rule_b: String,
+    /// 两条规则同时命中的位置数
+    pub co_fired: usize,
+    /// 仅 `rule_a` 命中的位置数
+    pub a_only: usize,
+    /// 仅 `rule_b` 命中的位置数
+    pub b_only: usize,
+    /// 两条规则的命中位置完全一致，可能是重复规则
+    pub always_co_fire: bool,
+}
+
+/// 重叠分析结果
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapReport {
+    pub files_analyzed: usize,
+    pub rules_analyzed: usize,
+    /// 每条规则的命中位置数
+    pub fire_counts: HashMap<String, usize>,
+    /// 至少同时命中过一次的规则对，完全重叠的排在前面
+    pub pairs: Vec<RuleOverlap>,
+}
+
+/// 读取目录中扫描器支持的文件作为分析语料
+pub fn load_corpus(root: &Path) -> Vec<(PathBuf, String)> {
+    ignore::Walk::new(root)
+        .flatten()
+        .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()) && is_supported_file(e.path()))
+        .filter_map(|e| {
+            let content = std::fs::read_to_string(e.path()).ok()?;
+            Some((e.path().to_path_buf(), content))
+        })
+        .collect()
+}
+
+/// 在语料上运行所有规则，按命中位置（文件 + 起始行）统计规则对的重叠情况
+pub async fn analyze_rule_overlap(rules: Vec<Rule>, corpus: &[(PathBuf, String)]) -> OverlapReport {
+    let rules_analyzed = rules.iter().filter(|r| r.enabled).count();
+    let scanner = RuleScanner::new(rules);
+
+    let mut locations: HashMap<(PathBuf, usize), BTreeSet<String>> = HashMap::new();
+    for (path, content) in corpus {
+        for finding in scanner.scan_file(Path::new(""), path, content).await {
+            if let Some(rule_id) = finding.rule_id {
+                locations
+                    .entry((path.clone(), finding.line_start))
+                    .or_default()
+                    .insert(rule_id);
+            }
+        }
+    }
+
+    let mut fire_counts: HashMap<String, usize> = HashMap::new();
+    let mut co_fired: HashMap<(String, String), usize> = HashMap::new();
+    for rule_ids in locations.values() {
+        for id in rule_ids {
+            *fire_counts.entry(id.clone()).or_default() += 1;
+        }
+        let ids: Vec<&String> = rule_ids.iter().collect();
+        for (i, a) in ids.iter().enumerate() {
+            for b in &ids[i + 1..] {
+                *co_fired.entry(((*a).clone(), (*b).clone())).or_default() += 1;
+            }
+        }
+    }
+
+    let mut pairs: Vec<RuleOverlap> = co_fired
+        .into_iter()
+        .map(|((rule_a, rule_b), co_fired)| {
+            let a_only = fire_counts[&rule_a] - co_fired;
+            let b_only = fire_counts[&rule_b] - co_fired;
+            RuleOverlap {
+                always_co_fire: a_only == 0 && b_only == 0,
+                rule_a,
+                rule_b,
+                co_fired,
+                a_only,
+                b_only,
+            }
+        })
+        .collect();
+    pairs.sort_by(|x, y| {
+        y.always_co_fire
+            .cmp(&x.always_co_fire)
+            .then_with(|| y.co_fired.cmp(&x.co_fired))
+            .then_with(|| x.rule_a.cmp(&y.rule_a))
+            .then_with(|| x.rule_b.cmp(&y.rule_b))
+    });
+
+    OverlapReport {
+        files_analyzed: corpus.len(),
+        rules_analyzed,
+        fire_counts,
+        pairs,
+    }
+}
@@ -1,74 +1,295 @@
-use crate::rules::model::Rule;
+use crate::rules::model::{CompiledPathFilter, Confidence, Rule};
+use crate::scanner::confidence::{ConfidenceContext, DEFAULT_CONFIDENCE};
 use crate::scanner::{Finding, Scanner};
 use async_trait::async_trait;
-use regex::Regex;
-use std::path::PathBuf;
+use regex::{Captures, Regex, RegexBuilder};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::path::Path;
 use tree_sitter::{Language, Parser, Query, QueryCursor};
 use uuid::Uuid;
 
 pub enum RuleMatcher {
     Regex(Regex),
+    Composite(CompositeMatcher),
     TreeSitter(Query),
 }
 
+/// 编译后的多正则组合条件
+pub struct CompositeMatcher {
+    pub all_of: Vec<Regex>,
+    pub any_of: Vec<Regex>,
+    pub none_of: Vec<Regex>,
+}
+
+impl CompositeMatcher {
+    /// 对整个文件求值，命中时返回第一个正向匹配的正则及其捕获结果
+    pub fn find<'c>(&self, content: &'c str) -> Option<(&Regex, Captures<'c>)> {
+        if self.none_of.iter().any(|r| r.is_match(content)) {
+            return None;
+        }
+
+        let mut hits: Vec<(&Regex, Captures<'c>)> = Vec::new();
+
+        for regex in &self.all_of {
+            hits.push((regex, regex.captures(content)?));
+        }
+
+        if !self.any_of.is_empty() {
+            let before = hits.len();
+            hits.extend(
+                self.any_of
+                    .iter()
+                    .filter_map(|regex| regex.captures(content).map(|caps| (regex, caps))),
+            );
+            if hits.len() == before {
+                return None;
+            }
+        }
+
+        hits.into_iter()
+            .min_by_key(|(_, caps)| caps.get(0).map_or(0, |m| m.start()))
+    }
+}
+
 pub struct CompiledRule {
     pub rule: Rule,
     pub matcher: RuleMatcher,
     pub language: Option<Language>,
+    pub paths: Option<CompiledPathFilter>,
 }
 
+impl CompiledRule {
+    /// 编译单条规则
+    ///
+    /// 匹配方式优先级：`query`（AST）> `patterns`（组合正则，含 `pattern`）> `pattern`（单正则）
+    pub fn compile(rule: &Rule) -> Result<Self, String> {
+        let paths = rule
+            .paths
+            .as_ref()
+            .map(|p| p.compile())
+            .transpose()
+            .map_err(|e| format!("Rule {}: {}", rule.id, e))?;
+        let mut compiled = Self::compile_matcher(rule)?;
+        compiled.paths = paths;
+        Ok(compiled)
+    }
+
+    /// 文件路径是否落在规则的 `paths` 范围内
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.paths.as_ref().is_none_or(|p| p.is_match(path))
+    }
+
+    fn compile_matcher(rule: &Rule) -> Result<Self, String> {
+        if let Some(query_str) = &rule.query {
+            let lang = get_language_for_rule(&rule.language).ok_or_else(|| {
+                format!(
+                    "Unsupported language for Tree-sitter rule {}: {}",
+                    rule.id, rule.language
+                )
+            })?;
+            let query = Query::new(&lang, query_str)
+                .map_err(|e| format!("Invalid Tree-sitter query for rule {}: {}", rule.id, e))?;
+            return Ok(Self {
+                rule: rule.clone(),
+                matcher: RuleMatcher::TreeSitter(query),
+                language: Some(lang),
+                paths: None,
+            });
+        }
+
+        if let Some(patterns) = &rule.patterns {
+            let compile_all = |list: &[String]| -> Result<Vec<Regex>, String> {
+                list.iter().map(|p| build_regex(rule, p)).collect()
+            };
+            let mut all_of = compile_all(&patterns.all_of)?;
+            if let Some(pattern) = &rule.pattern {
+                all_of.extend(compile_all(std::slice::from_ref(pattern))?);
+            }
+            let any_of = compile_all(&patterns.any_of)?;
+            let none_of = compile_all(&patterns.none_of)?;
+            if all_of.is_empty() && any_of.is_empty() {
+                return Err(format!(
+                    "Rule {} patterns need at least one all_of or any_of entry",
+                    rule.id
+                ));
+            }
+            return Ok(Self {
+                rule: rule.clone(),
+                matcher: RuleMatcher::Composite(CompositeMatcher {
+                    all_of,
+                    any_of,
+                    none_of,
+                }),
+                language: None,
+                paths: None,
+            });
+        }
+
+        if let Some(pattern) = &rule.pattern {
+            let regex = build_regex(rule, pattern)?;
+            return Ok(Self {
+                rule: rule.clone(),
+                matcher: RuleMatcher::Regex(regex),
+                language: None,
+                paths: None,
+            });
+        }
+
+        Err(format!("Rule {} has no query, pattern or patterns", rule.id))
+    }
+}
+
+/// 编译规则中的正则，`multiline` 规则启用 `(?s)`
+fn build_regex(rule: &Rule, pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .dot_matches_new_line(rule.multiline)
+        .build()
+        .map_err(|e| format!("Invalid regex pattern for rule {}: {}: {}", rule.id, pattern, e))
+}
+
+/// 每行起始字节偏移，用于将匹配位置换算为行号
+struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(content: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// 字节偏移所在的行号（从 1 开始）
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    /// 匹配范围 `[start, end)` 覆盖的起止行号；以换行结尾的匹配不计入下一行
+    fn line_range(&self, start: usize, end: usize) -> (usize, usize) {
+        let line_start = self.line_of(start);
+        (line_start, self.line_of(end.saturating_sub(1).max(start)).max(line_start))
+    }
+}
+
+/// 描述模板中单个插值的最大字符数
+const MAX_INTERPOLATED_LEN: usize = 120;
+
 pub struct RuleScanner {
     compiled_rules: Vec<CompiledRule>,
+    /// 已提示过存在未匹配占位符的规则及模板字段，每个字段只警告一次
+    warned_rules: Mutex<HashSet<(String, &'static str)>>,
 }
 
 impl RuleScanner {
+    /// 扫描器名称，用于在 ScannerManager 中定位并替换
+    pub const NAME: &'static str = "RuleBasedScanner";
+
     pub fn new(rules: Vec<Rule>) -> Self {
         let mut compiled_rules = Vec::new();
-        for rule in rules {
-            // Priority: Query (AST) > Pattern (Regex)
-            if let Some(query_str) = &rule.query {
-                if let Some(lang) = get_language_for_rule(&rule.language) {
-                    match Query::new(&lang, query_str) {
-                        Ok(query) => {
-                            compiled_rules.push(CompiledRule {
-                                rule: rule.clone(),
-                                matcher: RuleMatcher::TreeSitter(query),
-                                language: Some(lang),
-                            });
-                        }
-                        Err(e) => {
-                            eprintln!("Invalid Tree-sitter query for rule {}: {}", rule.id, e);
-                        }
-                    }
-                } else {
-                    eprintln!(
-                        "Unsupported language for Tree-sitter rule {}: {}",
-                        rule.id, rule.language
-                    );
-                }
-            } else if let Some(pattern) = &rule.pattern {
-                if let Ok(regex) = Regex::new(pattern) {
-                    compiled_rules.push(CompiledRule {
-                        rule: rule.clone(),
-                        matcher: RuleMatcher::Regex(regex),
-                        language: None,
-                    });
-                } else {
-                    eprintln!("Invalid regex pattern for rule {}: {}", rule.id, pattern);
-                }
+        for rule in rules.into_iter().filter(|r| r.enabled) {
+            match CompiledRule::compile(&rule) {
+                Ok(compiled) => compiled_rules.push(compiled),
+                Err(e) => eprintln!("{}", e),
             }
         }
-        Self { compiled_rules }
+        Self {
+            compiled_rules,
+            warned_rules: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 展开描述模板中的 `{{name}}` 占位符
+    ///
+    /// 正则规则使用命名捕获组，AST 规则使用查询的捕获名；未匹配的占位符渲染为空
+    fn render_description(&self, rule: &Rule, values: &HashMap<String, String>) -> String {
+        let (output, missing) = expand_template(&rule.description, values, true);
+        if !missing.is_empty() {
+            self.warn_unmatched(rule, "description", &missing);
+        }
+        output
+    }
+
+    /// 渲染修复模板，存在未匹配的占位符时不提供修复建议
+    fn render_fix(&self, rule: &Rule, values: &HashMap<String, String>) -> Option<String> {
+        let (output, missing) = expand_template(rule.fix.as_ref()?, values, false);
+        if !missing.is_empty() {
+            self.warn_unmatched(rule, "fix", &missing);
+            return None;
+        }
+        Some(output)
+    }
+
+    /// 每条规则的每个模板字段只提示一次未匹配的占位符
+    fn warn_unmatched(&self, rule: &Rule, field: &'static str, missing: &[String]) {
+        let mut warned = self.warned_rules.lock().unwrap_or_else(|e| e.into_inner());
+        if warned.insert((rule.id.clone(), field)) {
+            eprintln!(
+                "Rule {} {} references unmatched placeholders: {}",
+                rule.id,
+                field,
+                missing.join(", ")
+            );
+        }
+    }
+}
+
+/// 展开模板中的 `{{name}}` 占位符，返回结果及未匹配的占位符名
+fn expand_template(
+    template: &str,
+    values: &HashMap<String, String>,
+    truncate: bool,
+) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    let mut missing = Vec::new();
+
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open + 2..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..open]);
+        let name = rest[open + 2..open + 2 + close].trim();
+        match values.get(name) {
+            Some(value) if truncate => output.push_str(&truncate_value(value)),
+            Some(value) => output.push_str(value),
+            None => missing.push(name.to_string()),
+        }
+        rest = &rest[open + 2 + close + 2..];
+    }
+    output.push_str(rest);
+
+    (output, missing)
+}
+
+/// 从正则捕获中收集命名捕获组的值
+fn named_captures(regex: &Regex, caps: &Captures) -> HashMap<String, String> {
+    regex
+        .capture_names()
+        .flatten()
+        .filter_map(|name| caps.name(name).map(|m| (name.to_string(), m.as_str().to_string())))
+        .collect()
+}
+
+/// 截断过长的插值，避免超大匹配写入数据库
+fn truncate_value(value: &str) -> String {
+    if value.chars().count() <= MAX_INTERPOLATED_LEN {
+        value.to_string()
+    } else {
+        let truncated: String = value.chars().take(MAX_INTERPOLATED_LEN).collect();
+        format!("{}...", truncated)
     }
 }
 
 #[async_trait]
 impl Scanner for RuleScanner {
     fn name(&self) -> String {
-        "RuleBasedScanner".to_string()
+        Self::NAME.to_string()
     }
 
-    async fn scan_file(&self, path: &PathBuf, content: &str) -> Vec<Finding> {
+    async fn scan_file(&self, root: &Path, path: &Path, content: &str) -> Vec<Finding> {
         let mut findings = Vec::new();
         let extension = path
             .extension()
@@ -76,9 +297,20 @@ impl Scanner for RuleScanner {
             .unwrap_or("")
             .to_lowercase();
 
+        let line_index = LineIndex::new(content);
+        // 注释与字符串字面量的位置只在文件有命中时计算
+        let context = OnceCell::new();
+        let confidence = |rule: &Rule, offset: usize| {
+            context
+                .get_or_init(|| ConfidenceContext::new(root, path, content))
+                .score(rule.confidence.map_or(DEFAULT_CONFIDENCE, Confidence::score), offset)
+        };
+
         for compiled in &self.compiled_rules {
-            // Simple language check based on extension
-            if !rule_matches_extension(&compiled.rule.language, &extension) {
+            // Simple language check based on extension; paths 范围需同时满足
+            if !rule_matches_extension(&compiled.rule.language, &extension)
+                || !compiled.matches_path(path)
+            {
                 continue;
             }
 
@@ -86,21 +318,46 @@ impl Scanner for RuleScanner {
                 RuleMatcher::Regex(regex) => {
                     for cap in regex.captures_iter(content) {
                         if let Some(m) = cap.get(0) {
-                            let start_pos = m.start();
-                            let end_pos = m.end();
+                            let (line_start, line_end) = line_index.line_range(m.start(), m.end());
 
-                            // Convert byte offset to line number
-                            let line_start = content[..start_pos].matches('\n').count() + 1;
-                            let line_end = content[..end_pos].matches('\n').count() + 1;
+                            let values = named_captures(regex, &cap);
+                            findings.push(Finding {
+                                confidence: confidence(&compiled.rule, m.start()),
+                                ..create_finding(
+                                    &compiled.rule,
+                                    path,
+                                    line_start,
+                                    line_end,
+                                    format!("RegexRule: {}", compiled.rule.id),
+                                    self.render_description(&compiled.rule, &values),
+                                    self.render_fix(&compiled.rule, &values)
+                                        .map(|fix| (m.as_str().to_string(), fix)),
+                                )
+                            });
+                        }
+                    }
+                }
+                RuleMatcher::Composite(composite) => {
+                    if let Some((regex, caps)) = composite.find(content) {
+                        let (start_pos, end_pos) =
+                            caps.get(0).map_or((0, 0), |m| (m.start(), m.end()));
+                        let matched = &content[start_pos..end_pos];
+                        let (line_start, line_end) = line_index.line_range(start_pos, end_pos);
 
-                            findings.push(create_finding(
+                        let values = named_captures(regex, &caps);
+                        findings.push(Finding {
+                            confidence: confidence(&compiled.rule, start_pos),
+                            ..create_finding(
                                 &compiled.rule,
                                 path,
                                 line_start,
                                 line_end,
                                 format!("RegexRule: {}", compiled.rule.id),
-                            ));
-                        }
+                                self.render_description(&compiled.rule, &values),
+                                self.render_fix(&compiled.rule, &values)
+                                    .map(|fix| (matched.to_string(), fix)),
+                            )
+                        });
                     }
                 }
                 RuleMatcher::TreeSitter(query) => {
@@ -112,6 +369,7 @@ impl Scanner for RuleScanner {
                                 let matches =
                                     cursor.matches(query, tree.root_node(), content.as_bytes());
 
+                                let capture_names = query.capture_names();
                                 for m in matches {
                                     // Use the first capture for location
                                     if let Some(capture) = m.captures.first() {
@@ -119,13 +377,30 @@ impl Scanner for RuleScanner {
                                         let start_pos = node.start_position();
                                         let end_pos = node.end_position();
 
-                                        findings.push(create_finding(
-                                            &compiled.rule,
-                                            path,
-                                            start_pos.row + 1,
-                                            end_pos.row + 1,
-                                            format!("ASTRule: {}", compiled.rule.id),
-                                        ));
+                                        let values: HashMap<String, String> = m
+                                            .captures
+                                            .iter()
+                                            .filter_map(|c| {
+                                                let name = capture_names.get(c.index as usize)?;
+                                                let text = c.node.utf8_text(content.as_bytes()).ok()?;
+                                                Some((name.to_string(), text.to_string()))
+                                            })
+                                            .collect();
+                                        findings.push(Finding {
+                                            confidence: confidence(&compiled.rule, node.start_byte()),
+                                            ..create_finding(
+                                                &compiled.rule,
+                                                path,
+                                                start_pos.row + 1,
+                                                end_pos.row + 1,
+                                                format!("ASTRule: {}", compiled.rule.id),
+                                                self.render_description(&compiled.rule, &values),
+                                                self.render_fix(&compiled.rule, &values).and_then(|fix| {
+                                                    let matched = node.utf8_text(content.as_bytes()).ok()?;
+                                                    Some((matched.to_string(), fix))
+                                                }),
+                                            )
+                                        });
                                     }
                                 }
                             }
@@ -141,20 +416,33 @@ impl Scanner for RuleScanner {
 
 fn create_finding(
     rule: &Rule,
-    path: &PathBuf,
+    path: &Path,
     line_start: usize,
     line_end: usize,
     detector: String,
+    description: String,
+    fix: Option<(String, String)>,
 ) -> Finding {
+    let (matched_text, suggested_fix) = fix.unzip();
     Finding {
         finding_id: Uuid::new_v4().to_string(),
         file_path: path.to_string_lossy().to_string(),
         line_start,
         line_end,
         detector,
+        detectors: Vec::new(),
         vuln_type: rule.cwe.clone().unwrap_or_else(|| "Unknown".to_string()),
         severity: format!("{:?}", rule.severity).to_lowercase(),
-        description: rule.description.clone(),
+        confidence: DEFAULT_CONFIDENCE,
+        description,
+        rule_id: Some(rule.id.clone()),
+        cwe: rule.cwe.clone(),
+        owasp: rule.owasp.clone(),
+        remediation: rule.remediation.clone(),
+        references: rule.references.clone(),
+        matched_text,
+        suggested_fix,
+        fingerprint: String::new(),
         analysis_trail: None,
         llm_output: None,
     }
@@ -191,3 +479,89 @@ fn rule_matches_extension(language: &str, extension: &str) -> bool {
         _ => language.eq_ignore_ascii_case(extension),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按 `patterns` 构建组合正则规则
+    fn composite_rule(patterns: &str) -> Result<CompiledRule, String> {
+        let rule: Rule = serde_yaml::from_str(&format!(
+            "id: composite\nname: Composite\ndescription: Composite rule\nseverity: high\nlanguage: python\npatterns:\n{}",
+            patterns
+        ))
+        .unwrap();
+        CompiledRule::compile(&rule)
+    }
+
+    /// 组合条件命中时返回命中文本
+    fn find(patterns: &str, content: &str) -> Option<String> {
+        let compiled = composite_rule(patterns).unwrap();
+        let RuleMatcher::Composite(matcher) = &compiled.matcher else {
+            panic!("expected a composite matcher");
+        };
+        matcher
+            .find(content)
+            .map(|(_, caps)| caps.get(0).unwrap().as_str().to_string())
+    }
+
+    const ALL_OF: &str = "  all_of: ['import pickle', 'pickle\\.loads\\(']\n";
+
+    #[test]
+    fn all_of_requires_every_member() {
+        let content = "import pickle\ndata = pickle.loads(blob)\n";
+        assert_eq!(find(ALL_OF, content).as_deref(), Some("import pickle"));
+        assert_eq!(find(ALL_OF, "data = pickle.loads(blob)\n"), None);
+    }
+
+    #[test]
+    fn any_of_is_vetoed_by_none_of() {
+        let patterns = "  any_of: ['yaml\\.load\\(', 'yaml\\.unsafe_load\\(']\n  none_of: ['Loader=SafeLoader']\n";
+        assert_eq!(find(patterns, "yaml.unsafe_load(data)\n").as_deref(), Some("yaml.unsafe_load("));
+        assert_eq!(find(patterns, "yaml.load(data, Loader=SafeLoader)\n"), None);
+        assert_eq!(find(patterns, "json.loads(data)\n"), None);
+    }
+
+    #[test]
+    fn all_of_and_any_of_must_both_hold() {
+        let patterns = "  all_of: ['import subprocess']\n  any_of: ['shell=True']\n";
+        let content = "import subprocess\nsubprocess.run(cmd, shell=True)\n";
+        assert_eq!(find(patterns, content).as_deref(), Some("import subprocess"));
+        assert_eq!(find(patterns, "import subprocess\nsubprocess.run(cmd)\n"), None);
+        // 命中位置取最早的正向匹配
+        let content = "subprocess.run(cmd, shell=True)\nimport subprocess\n";
+        assert_eq!(find(patterns, content).as_deref(), Some("shell=True"));
+    }
+
+    #[test]
+    fn none_of_only_and_empty_sets_are_rejected() {
+        assert!(composite_rule("  none_of: ['safe']\n").is_err());
+        assert!(composite_rule("  {}\n").is_err());
+
+        let empty = CompositeMatcher {
+            all_of: Vec::new(),
+            any_of: Vec::new(),
+            none_of: Vec::new(),
+        };
+        assert!(empty.find("anything").is_none());
+    }
+
+    #[test]
+    fn unmatched_placeholders_warn_once_per_field() {
+        let rule: Rule = serde_yaml::from_str(
+            "id: templated\nname: Templated\ndescription: 'Call to {{func}}'\nseverity: high\nlanguage: python\npattern: eval\\(\nfix: '{{safe}}('\n",
+        )
+        .unwrap();
+        let scanner = RuleScanner::new(vec![rule.clone()]);
+        let values = HashMap::new();
+
+        assert_eq!(scanner.render_description(&rule, &values), "Call to ");
+        assert_eq!(scanner.render_fix(&rule, &values), None);
+        assert_eq!(scanner.render_fix(&rule, &values), None);
+
+        let warned = scanner.warned_rules.lock().unwrap();
+        assert_eq!(warned.len(), 2);
+        assert!(warned.contains(&("templated".to_string(), "description")));
+        assert!(warned.contains(&("templated".to_string(), "fix")));
+    }
+}
@@ -0,0 +1,152 @@
+// 发现置信度：以规则（或外部工具）声明的置信度为基础，按命中位置折减——注释、字符串字面量、测试文件中的命中更可能是误报
+
+use crate::diff::classify::{literal_spans, LiteralKind};
+use crate::rules::model::Severity;
+use std::path::Path;
+
+/// 未声明置信度的规则与扫描器使用的初始置信度，声明了置信度的按 `Confidence::score` 取值
+pub const DEFAULT_CONFIDENCE: f32 = 0.6;
+
+/// 命中位于注释中时的折减系数
+const COMMENT_FACTOR: f32 = 0.3;
+/// 命中起始于字符串字面量内部时的折减系数
+const STRING_FACTOR: f32 = 0.6;
+/// 命中位于测试文件或测试目录中时的折减系数
+const TEST_FILE_FACTOR: f32 = 0.5;
+
+/// 测试目录名
+const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "specs", "testdata", "fixtures"];
+
+/// 路径是否为测试文件：位于测试目录中，或文件名符合常见的测试命名
+/// （`test_*.py`、`*_test.py`、`*_test.go`、`*.test.js`、`*.spec.ts`、`*Test.java` 等）
+pub fn is_test_path(path: &Path) -> bool {
+    let in_test_dir = path.parent().is_some_and(|dir| {
+        dir.components()
+            .any(|c| TEST_DIRS.contains(&c.as_os_str().to_string_lossy().to_lowercase().as_str()))
+    });
+    let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+        return in_test_dir;
+    };
+    in_test_dir
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with(".test")
+        || stem.ends_with(".spec")
+        || (stem.len() > 4 && (stem.ends_with("Test") || stem.ends_with("Tests")))
+}
+
+/// 发现的排序优先级：置信度乘以严重程度权重，无法识别的严重程度按 info 计
+pub fn priority(severity: &str, confidence: f32) -> f32 {
+    confidence * Severity::parse(severity).unwrap_or(Severity::Info).weight()
+}
+
+/// 单个文件的置信度计算上下文，注释与字符串字面量的位置只计算一次
+pub(crate) struct ConfidenceContext<'a> {
+    content: &'a str,
+    spans: Vec<(usize, usize, LiteralKind)>,
+    test_file: bool,
+}
+
+impl<'a> ConfidenceContext<'a> {
+    /// 项目 `root` 之外的路径不参与测试文件判断，避免项目本身位于 `fixtures/` 等目录下时整体被折减
+    pub(crate) fn new(root: &Path, path: &Path, content: &'a str) -> Self {
+        Self {
+            content,
+            spans: literal_spans(path, content),
+            test_file: is_test_path(path.strip_prefix(root).unwrap_or(path)),
+        }
+    }
+
+    /// 起始于字节偏移 `offset` 的命中的置信度，由初始置信度 `base` 折减，保留两位小数
+    pub(crate) fn score(&self, base: f32, offset: usize) -> f32 {
+        let mut score = base;
+        // 最后一个起始位置不晚于 offset 的区间
+        let index = self.spans.partition_point(|&(start, _, _)| start <= offset);
+        let literal = index
+            .checked_sub(1)
+            .map(|i| self.spans[i])
+            .filter(|&(_, end, _)| offset < end);
+        match literal {
+            Some((_, _, LiteralKind::Comment)) => score *= COMMENT_FACTOR,
+            // 命中从引号开始时字符串本身就是问题所在（如硬编码的密钥），不折减
+            Some((start, _, LiteralKind::String)) if offset > start => score *= STRING_FACTOR,
+            _ => {}
+        }
+        if self.test_file {
+            score *= TEST_FILE_FACTOR;
+        }
+        (score * 100.0).round() / 100.0
+    }
+
+    /// 只知道行号（从 1 开始）的命中，以该行第一个非空白字符的位置计算
+    pub(crate) fn score_line(&self, base: f32, line: usize) -> f32 {
+        let line_offset: usize = self.content.split_inclusive('\n').take(line.saturating_sub(1)).map(str::len).sum();
+        let indent = self.content[line_offset..]
+            .find(|c: char| !c.is_whitespace() || c == '\n')
+            .unwrap_or(0);
+        self.score(base, line_offset + indent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(root: &str, path: &str, content: &'a str) -> ConfidenceContext<'a> {
+        ConfidenceContext::new(Path::new(root), Path::new(path), content)
+    }
+
+    #[test]
+    fn test_paths_are_recognised() {
+        for path in [
+            "tests/app.py",
+            "src/__tests__/app.js",
+            "pkg/testdata/input.go",
+            "test_app.py",
+            "app_test.go",
+            "app.test.js",
+            "app.spec.ts",
+            "src/AppTest.java",
+        ] {
+            assert!(is_test_path(Path::new(path)), "{path}");
+        }
+        for path in ["src/app.py", "src/attest.py", "src/Test.java", "latest/app.py"] {
+            assert!(!is_test_path(Path::new(path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn project_root_is_not_part_of_test_detection() {
+        let content = "password = \"hunter2\"\n";
+        let root = "/srv/fixtures/app";
+        assert_eq!(context(root, "/srv/fixtures/app/src/main.py", content).score(0.8, 0), 0.8);
+        assert_eq!(context(root, "/srv/fixtures/app/tests/test_main.py", content).score(0.8, 0), 0.4);
+        // 不在项目中的路径按原样判断
+        assert_eq!(context(root, "/srv/fixtures/other/main.py", content).score(0.8, 0), 0.4);
+    }
+
+    #[test]
+    fn score_discounts_comments_and_string_interiors() {
+        let content = "# eval(x)\nrun(\"eval(x)\")\neval(x)\n";
+        let context = context("", "app.py", content);
+        let comment = content.find("eval").unwrap();
+        let string_start = content.find('"').unwrap();
+        let code = content.rfind("eval").unwrap();
+
+        assert_eq!(context.score(0.6, comment), 0.18);
+        assert_eq!(context.score(0.6, string_start + 1), 0.36);
+        // 从引号开始的命中不折减
+        assert_eq!(context.score(0.6, string_start), 0.6);
+        assert_eq!(context.score(0.6, code), 0.6);
+        assert_eq!(context.score_line(0.6, 1), 0.18);
+        assert_eq!(context.score_line(0.6, 3), 0.6);
+    }
+
+    #[test]
+    fn factors_combine_in_test_files() {
+        let content = "// token = \"abc\"\n";
+        let context = context("/p", "/p/src/app.spec.ts", content);
+        assert_eq!(context.score(0.8, 3), 0.12);
+        assert_eq!(context.score(0.8, content.len()), 0.4);
+    }
+}
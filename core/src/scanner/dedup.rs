@@ -0,0 +1,129 @@
+// 发现去重：多个扫描器在同一位置报告同一类问题时合并为一条
+
+use super::external_scanner::cwe_id;
+use super::Finding;
+use crate::rules::model::Severity;
+
+/// 合并同一文件中行范围重叠、问题类型相同的发现
+///
+/// 问题类型优先按 CWE 编号比较，没有 CWE 时按 `vuln_type` 比较。合并后的发现以严重程度最高者为准，
+/// 行范围取并集，置信度取较高者，缺失的 CWE/OWASP/修复建议等字段由其他发现补齐，`detectors` 记录全部参与的扫描器。
+/// 结果保持各组首次出现的顺序
+pub fn merge_duplicate_findings(findings: Vec<Finding>) -> Vec<Finding> {
+    let mut merged: Vec<Finding> = Vec::with_capacity(findings.len());
+    for mut finding in findings {
+        if finding.detectors.is_empty() {
+            finding.detectors.push(finding.detector.clone());
+        }
+        match merged.iter_mut().find(|m| is_duplicate(m, &finding)) {
+            Some(existing) => merge_into(existing, finding),
+            None => merged.push(finding),
+        }
+    }
+    merged
+}
+
+fn issue_key(finding: &Finding) -> &str {
+    finding.cwe.as_deref().map(cwe_id).unwrap_or(&finding.vuln_type)
+}
+
+fn is_duplicate(a: &Finding, b: &Finding) -> bool {
+    a.file_path == b.file_path
+        && a.line_start <= b.line_end
+        && b.line_start <= a.line_end
+        && issue_key(a) == issue_key(b)
+}
+
+/// 严重程度排序，未知取值最低
+fn severity_rank(severity: &str) -> Option<u8> {
+    Severity::parse(severity).map(|s| s.rank())
+}
+
+fn merge_into(existing: &mut Finding, mut other: Finding) {
+    if severity_rank(&other.severity) > severity_rank(&existing.severity) {
+        std::mem::swap(existing, &mut other);
+        // 交换后 existing 携带的是新发现的 detectors，需并入之前累积的列表
+        std::mem::swap(&mut existing.detectors, &mut other.detectors);
+    }
+    for detector in other.detectors {
+        if !existing.detectors.contains(&detector) {
+            existing.detectors.push(detector);
+        }
+    }
+
+    // 多个扫描器命中同一位置不降低置信度
+    existing.confidence = existing.confidence.max(other.confidence);
+    existing.line_start = existing.line_start.min(other.line_start);
+    existing.line_end = existing.line_end.max(other.line_end);
+    existing.rule_id = existing.rule_id.take().or(other.rule_id);
+    existing.cwe = existing.cwe.take().or(other.cwe);
+    existing.owasp = existing.owasp.take().or(other.owasp);
+    existing.remediation = existing.remediation.take().or(other.remediation);
+    for reference in other.references {
+        if !existing.references.contains(&reference) {
+            existing.references.push(reference);
+        }
+    }
+    // 修复建议与命中文本成对使用
+    if existing.suggested_fix.is_none() && other.suggested_fix.is_some() {
+        existing.matched_text = other.matched_text;
+        existing.suggested_fix = other.suggested_fix;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::test_finding;
+
+    fn finding(detector: &str, lines: (usize, usize), severity: &str) -> Finding {
+        Finding {
+            detector: detector.to_string(),
+            line_start: lines.0,
+            line_end: lines.1,
+            severity: severity.to_string(),
+            ..test_finding("app.py", lines.0)
+        }
+    }
+
+    #[test]
+    fn overlapping_findings_from_two_scanners_merge() {
+        let regex = Finding {
+            cwe: Some("CWE-798".to_string()),
+            confidence: 0.9,
+            ..finding("RegexScanner", (4, 4), "medium")
+        };
+        let rule = Finding {
+            cwe: Some("CWE-798: Use of Hard-coded Credentials".to_string()),
+            rule_id: Some("no-hardcoded-passwords".to_string()),
+            references: vec!["https://cwe.mitre.org/data/definitions/798.html".to_string()],
+            ..finding("RegexRule", (3, 5), "high")
+        };
+
+        let merged = merge_duplicate_findings(vec![regex, rule]);
+        assert_eq!(merged.len(), 1);
+        let merged = &merged[0];
+        assert_eq!(merged.detectors, ["RegexScanner", "RegexRule"]);
+        assert_eq!(merged.detector, "RegexRule");
+        assert_eq!(merged.severity, "high");
+        assert_eq!((merged.line_start, merged.line_end), (3, 5));
+        assert_eq!(merged.confidence, 0.9);
+        assert_eq!(merged.rule_id.as_deref(), Some("no-hardcoded-passwords"));
+        assert_eq!(merged.references.len(), 1);
+    }
+
+    #[test]
+    fn distinct_issues_stay_separate() {
+        let other_type = Finding {
+            vuln_type: "SQL Injection".to_string(),
+            ..finding("B", (1, 1), "high")
+        };
+        let other_line = finding("C", (2, 2), "high");
+        let other_file = Finding {
+            file_path: "other.py".to_string(),
+            ..finding("D", (1, 1), "high")
+        };
+        let findings = vec![finding("A", (1, 1), "high"), other_type, other_line, other_file];
+        assert_eq!(merge_duplicate_findings(findings).len(), 4);
+    }
+}
@@ -0,0 +1,407 @@
+// 外部扫描器：调用 semgrep、bandit 等命令行工具扫描文件，并将其 JSON 输出转换为 Finding
+
+use super::confidence::{ConfidenceContext, DEFAULT_CONFIDENCE};
+use super::{Finding, Scanner};
+use crate::rules::model::Confidence;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// 外部工具的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExternalOutputFormat {
+    /// `semgrep --json`
+    Semgrep,
+    /// `bandit -f json`
+    Bandit,
+}
+
+/// 外部扫描器配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalScannerConfig {
+    /// 扫描器名称，同时作为发现的 detector（如 `semgrep`）
+    pub name: String,
+    /// 可执行文件名或路径
+    pub command: String,
+    /// 命令参数，`{path}` 替换为被扫描文件路径；不含占位符时将路径追加到末尾
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub format: ExternalOutputFormat,
+    /// 只扫描这些扩展名的文件（不含点），为空时扫描全部支持的文件
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// 单个文件的超时时间（秒）
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_timeout_secs() -> u64 {
+    60
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+pub struct ExternalScanner {
+    config: ExternalScannerConfig,
+    executable: PathBuf,
+}
+
+impl ExternalScanner {
+    /// 创建外部扫描器；配置被禁用或命令不存在时返回 None 并记录警告
+    pub fn new(config: ExternalScannerConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        match find_executable(&config.command) {
+            Some(executable) => Some(Self { config, executable }),
+            None => {
+                log::warn!(
+                    "External scanner '{}' skipped: command '{}' not found",
+                    config.name,
+                    config.command
+                );
+                None
+            }
+        }
+    }
+
+    fn applies_to(&self, path: &Path) -> bool {
+        if self.config.extensions.is_empty() {
+            return true;
+        }
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.config.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+
+    fn build_args(&self, path: &Path) -> Vec<String> {
+        let path = path.to_string_lossy();
+        let mut args: Vec<String> = self
+            .config
+            .args
+            .iter()
+            .map(|arg| arg.replace("{path}", &path))
+            .collect();
+        if !self.config.args.iter().any(|arg| arg.contains("{path}")) {
+            args.push(path.into_owned());
+        }
+        args
+    }
+}
+
+#[async_trait]
+impl Scanner for ExternalScanner {
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    async fn scan_file(&self, root: &Path, path: &Path, content: &str) -> Vec<Finding> {
+        if !self.applies_to(path) {
+            return Vec::new();
+        }
+
+        let mut command = tokio::process::Command::new(&self.executable);
+        command.args(self.build_args(path)).kill_on_drop(true);
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let output = match tokio::time::timeout(timeout, command.output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                log::warn!("External scanner '{}' failed to run: {}", self.config.name, e);
+                return Vec::new();
+            }
+            Err(_) => {
+                log::warn!(
+                    "External scanner '{}' timed out after {}s on {}",
+                    self.config.name,
+                    self.config.timeout_secs,
+                    path.display()
+                );
+                return Vec::new();
+            }
+        };
+
+        // semgrep / bandit 发现问题时可能以非零状态退出，以输出能否解析为准
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match parse_output(self.config.format, &stdout, &self.config.name, path) {
+            Ok(mut findings) => {
+                if !findings.is_empty() {
+                    let context = ConfidenceContext::new(root, path, content);
+                    for finding in &mut findings {
+                        finding.confidence = context.score_line(finding.confidence, finding.line_start);
+                    }
+                }
+                findings
+            }
+            Err(e) => {
+                log::warn!(
+                    "External scanner '{}' produced unreadable output for {} ({}): {}",
+                    self.config.name,
+                    path.display(),
+                    e,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// 按输出格式解析外部工具的 JSON 输出，发现的文件路径统一使用 `path`；置信度为工具声明的置信度，未按命中位置折减
+pub fn parse_output(
+    format: ExternalOutputFormat,
+    output: &str,
+    detector: &str,
+    path: &Path,
+) -> Result<Vec<Finding>, String> {
+    let json: Value = serde_json::from_str(output).map_err(|e| format!("invalid JSON: {}", e))?;
+    let results = json
+        .get("results")
+        .and_then(Value::as_array)
+        .ok_or("missing 'results' array")?;
+    Ok(results
+        .iter()
+        .map(|result| match format {
+            ExternalOutputFormat::Semgrep => semgrep_finding(result, detector, path),
+            ExternalOutputFormat::Bandit => bandit_finding(result, detector, path),
+        })
+        .collect())
+}
+
+fn semgrep_finding(result: &Value, detector: &str, path: &Path) -> Finding {
+    let check_id = str_field(result, &["check_id"]).unwrap_or("unknown");
+    let line_start = usize_field(result, &["start", "line"]).unwrap_or(1);
+    let metadata = result.pointer("/extra/metadata");
+    let cwe = metadata.and_then(|m| first_string(m.get("cwe")));
+    let owasp = metadata.and_then(|m| first_string(m.get("owasp")));
+    let confidence = metadata.and_then(|m| m.get("confidence")).and_then(Value::as_str);
+    let references = metadata
+        .and_then(|m| m.get("references"))
+        .and_then(Value::as_array)
+        .map(|refs| refs.iter().filter_map(|r| r.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+
+    Finding {
+        finding_id: Uuid::new_v4().to_string(),
+        file_path: path.to_string_lossy().to_string(),
+        line_start,
+        line_end: usize_field(result, &["end", "line"]).unwrap_or(line_start),
+        detector: detector.to_string(),
+        detectors: Vec::new(),
+        vuln_type: cwe.as_deref().map(cwe_id).unwrap_or(check_id).to_string(),
+        severity: map_severity(str_field(result, &["extra", "severity"]).unwrap_or("")),
+        confidence: declared_confidence(confidence),
+        description: str_field(result, &["extra", "message"])
+            .unwrap_or(check_id)
+            .to_string(),
+        rule_id: Some(check_id.to_string()),
+        cwe,
+        owasp,
+        remediation: None,
+        references,
+        matched_text: None,
+        suggested_fix: None,
+        fingerprint: String::new(),
+        analysis_trail: None,
+        llm_output: None,
+    }
+}
+
+fn bandit_finding(result: &Value, detector: &str, path: &Path) -> Finding {
+    let test_id = str_field(result, &["test_id"]).unwrap_or("unknown");
+    let line_start = usize_field(result, &["line_number"]).unwrap_or(1);
+    let line_end = result
+        .get("line_range")
+        .and_then(Value::as_array)
+        .and_then(|range| range.iter().filter_map(Value::as_u64).max())
+        .map(|line| line as usize)
+        .unwrap_or(line_start);
+    let cwe = result
+        .pointer("/issue_cwe/id")
+        .and_then(Value::as_u64)
+        .map(|id| format!("CWE-{}", id));
+
+    Finding {
+        finding_id: Uuid::new_v4().to_string(),
+        file_path: path.to_string_lossy().to_string(),
+        line_start,
+        line_end: line_end.max(line_start),
+        detector: detector.to_string(),
+        detectors: Vec::new(),
+        vuln_type: cwe
+            .clone()
+            .or_else(|| str_field(result, &["test_name"]).map(str::to_string))
+            .unwrap_or_else(|| test_id.to_string()),
+        severity: map_severity(str_field(result, &["issue_severity"]).unwrap_or("")),
+        confidence: declared_confidence(str_field(result, &["issue_confidence"])),
+        description: str_field(result, &["issue_text"]).unwrap_or(test_id).to_string(),
+        rule_id: Some(test_id.to_string()),
+        cwe,
+        owasp: None,
+        remediation: None,
+        references: str_field(result, &["more_info"])
+            .map(|url| vec![url.to_string()])
+            .unwrap_or_default(),
+        matched_text: None,
+        suggested_fix: None,
+        fingerprint: String::new(),
+        analysis_trail: None,
+        llm_output: None,
+    }
+}
+
+/// 将工具的严重程度映射为 critical/high/medium/low/info
+fn map_severity(severity: &str) -> String {
+    match severity.to_ascii_uppercase().as_str() {
+        "CRITICAL" => "critical",
+        "ERROR" | "HIGH" => "high",
+        "WARNING" | "MEDIUM" => "medium",
+        "LOW" => "low",
+        _ => "info",
+    }
+    .to_string()
+}
+
+/// 工具声明的置信度（HIGH/MEDIUM/LOW）对应的分值，未声明或无法识别时为默认值
+fn declared_confidence(confidence: Option<&str>) -> f32 {
+    confidence.and_then(Confidence::parse).map_or(DEFAULT_CONFIDENCE, Confidence::score)
+}
+
+/// 从 `CWE-798: Use of Hard-coded Credentials` 中取出 `CWE-798`
+pub(crate) fn cwe_id(cwe: &str) -> &str {
+    cwe.split(':').next().unwrap_or(cwe).trim()
+}
+
+fn str_field<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().try_fold(value, |v, key| v.get(key))?.as_str()
+}
+
+fn usize_field(value: &Value, keys: &[&str]) -> Option<usize> {
+    keys.iter()
+        .try_fold(value, |v, key| v.get(key))?
+        .as_u64()
+        .map(|n| n as usize)
+}
+
+/// 字段可能是字符串或字符串数组，取第一个
+fn first_string(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) => items.first()?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+/// 在 PATH 中查找可执行文件；包含路径分隔符的命令按路径检查
+fn find_executable(command: &str) -> Option<PathBuf> {
+    let candidate = Path::new(command);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then(|| candidate.to_path_buf());
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| {
+        let path = dir.join(command);
+        if path.is_file() {
+            return Some(path);
+        }
+        let with_suffix = dir.join(format!("{}{}", command, std::env::consts::EXE_SUFFIX));
+        with_suffix.is_file().then_some(with_suffix)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEMGREP_OUTPUT: &str = r#"{
+        "results": [
+            {
+                "check_id": "python.lang.security.audit.exec-detected",
+                "path": "/tmp/scan/app.py",
+                "start": {"line": 12, "col": 5},
+                "end": {"line": 14, "col": 20},
+                "extra": {
+                    "message": "Detected use of exec()",
+                    "severity": "ERROR",
+                    "metadata": {
+                        "cwe": ["CWE-95: Improper Neutralization of Directives in Dynamically Evaluated Code"],
+                        "owasp": ["A03:2021 - Injection"],
+                        "confidence": "HIGH",
+                        "references": ["https://docs.python.org/3/library/functions.html#exec"]
+                    }
+                }
+            },
+            {
+                "check_id": "generic.todo",
+                "start": {"line": 3},
+                "end": {"line": 3},
+                "extra": {"message": "TODO left in code", "severity": "INFO"}
+            }
+        ],
+        "errors": []
+    }"#;
+
+    #[test]
+    fn semgrep_results_become_findings() {
+        let path = Path::new("src/app.py");
+        let findings = parse_output(ExternalOutputFormat::Semgrep, SEMGREP_OUTPUT, "semgrep", path).unwrap();
+        assert_eq!(findings.len(), 2);
+
+        let exec = &findings[0];
+        assert_eq!(exec.file_path, "src/app.py");
+        assert_eq!(exec.detector, "semgrep");
+        assert_eq!((exec.line_start, exec.line_end), (12, 14));
+        assert_eq!(exec.vuln_type, "CWE-95");
+        assert_eq!(exec.severity, "high");
+        assert_eq!(exec.confidence, Confidence::High.score());
+        assert_eq!(exec.description, "Detected use of exec()");
+        assert_eq!(exec.rule_id.as_deref(), Some("python.lang.security.audit.exec-detected"));
+        assert!(exec.cwe.as_deref().unwrap().starts_with("CWE-95:"));
+        assert_eq!(exec.owasp.as_deref(), Some("A03:2021 - Injection"));
+        assert_eq!(exec.references.len(), 1);
+
+        // 没有 metadata 时以 check_id 作为漏洞类型，置信度取默认值
+        let todo = &findings[1];
+        assert_eq!(todo.vuln_type, "generic.todo");
+        assert_eq!(todo.severity, "info");
+        assert_eq!(todo.confidence, DEFAULT_CONFIDENCE);
+        assert_eq!(todo.cwe, None);
+        assert!(todo.references.is_empty());
+    }
+
+    #[test]
+    fn bandit_results_become_findings() {
+        let output = r#"{"results": [{
+            "test_id": "B105",
+            "test_name": "hardcoded_password_string",
+            "line_number": 7,
+            "line_range": [7, 8],
+            "issue_severity": "LOW",
+            "issue_confidence": "MEDIUM",
+            "issue_text": "Possible hardcoded password",
+            "issue_cwe": {"id": 259},
+            "more_info": "https://bandit.readthedocs.io/"
+        }]}"#;
+        let findings = parse_output(ExternalOutputFormat::Bandit, output, "bandit", Path::new("a.py")).unwrap();
+        assert_eq!(findings.len(), 1);
+        let finding = &findings[0];
+        assert_eq!((finding.line_start, finding.line_end), (7, 8));
+        assert_eq!(finding.vuln_type, "CWE-259");
+        assert_eq!(finding.severity, "low");
+        assert_eq!(finding.confidence, Confidence::Medium.score());
+        assert_eq!(finding.rule_id.as_deref(), Some("B105"));
+    }
+
+    #[test]
+    fn malformed_output_is_an_error() {
+        assert!(parse_output(ExternalOutputFormat::Semgrep, "not json", "semgrep", Path::new("a.py")).is_err());
+        assert!(parse_output(ExternalOutputFormat::Semgrep, "{}", "semgrep", Path::new("a.py")).is_err());
+    }
+}
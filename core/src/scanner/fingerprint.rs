@@ -0,0 +1,83 @@
+// 发现指纹：由规则与命中代码（空白归一化）计算，不含行号，无关的行移动后保持不变，用于基线比较
+
+use super::Finding;
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path};
+
+/// 发现的指纹：相对 `root` 的文件路径、规则（非规则扫描器为扫描器与问题类型）与命中各行代码的哈希
+///
+/// 路径分隔符统一为 `/`，项目目录移动后指纹不变；不在 `root` 下的路径按原样计算。
+/// 命中代码按行去除首尾空白并将连续空白合并为一个空格，缩进或空白调整不影响指纹；
+/// 同一文件中相同规则命中相同代码的发现指纹相同
+pub fn fingerprint(finding: &Finding, root: &Path, lines: &[&str]) -> String {
+    let path = Path::new(&finding.file_path);
+    let relative: Vec<_> = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_) | Component::ParentDir))
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    let relative = relative.join("/");
+    let rule = match &finding.rule_id {
+        Some(rule_id) => rule_id.clone(),
+        None => format!("{}:{}", finding.detector, finding.vuln_type),
+    };
+    let start = finding.line_start.saturating_sub(1);
+    let end = finding.line_end.max(finding.line_start).min(lines.len());
+    let code: Vec<String> = lines
+        .get(start..end)
+        .unwrap_or_default()
+        .iter()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+
+    let mut hasher = Sha256::new();
+    for part in [relative.as_str(), rule.as_str(), code.join("\n").as_str()] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// 为同一文件的发现计算指纹，路径相对项目根目录 `root`
+pub(crate) fn assign_fingerprints(findings: &mut [Finding], root: &Path, content: &str) {
+    if findings.is_empty() {
+        return;
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    for finding in findings {
+        finding.fingerprint = fingerprint(finding, root, &lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::test_finding;
+
+    fn fingerprint_at(root: &str, file_path: &str, line: usize, content: &str) -> String {
+        let mut finding = test_finding(file_path, line);
+        finding.rule_id = Some("no-hardcoded-passwords".to_string());
+        let lines: Vec<&str> = content.lines().collect();
+        fingerprint(&finding, Path::new(root), &lines)
+    }
+
+    #[test]
+    fn moved_project_keeps_fingerprint() {
+        let content = "import os\npassword = \"hunter2\"\n";
+        let before = fingerprint_at("/home/a/project", "/home/a/project/src/app.py", 2, content);
+        let after = fingerprint_at("/srv/checkout", "/srv/checkout/src/app.py", 2, content);
+        assert_eq!(before, after);
+        assert_eq!(before, fingerprint_at("", "src/app.py", 2, content));
+        assert_ne!(before, fingerprint_at("/srv/checkout", "/srv/checkout/lib/app.py", 2, content));
+    }
+
+    #[test]
+    fn inserted_lines_keep_fingerprint() {
+        let before = fingerprint_at("/p", "/p/app.py", 2, "import os\npassword = \"hunter2\"\n");
+        let after = fingerprint_at("/p", "/p/app.py", 4, "import os\nimport sys\n\n    password  =  \"hunter2\"\n");
+        assert_eq!(before, after);
+        assert_ne!(before, fingerprint_at("/p", "/p/app.py", 2, "import os\npassword = \"changed\"\n"));
+    }
+}
@@ -1,5 +1,13 @@
-use super::{Finding, Scanner};
-use std::path::PathBuf;
+use super::dedup::merge_duplicate_findings;
+use super::fingerprint::assign_fingerprints;
+use super::regex_scanner::RegexScanner;
+use super::suppression::drop_suppressed;
+use super::{is_supported_file, Finding, Scanner};
+use crate::rules::model::Rule;
+use crate::rules::scanner::RuleScanner;
+use ignore::gitignore::GitignoreBuilder;
+use ignore::Match;
+use std::path::Path;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -7,6 +15,12 @@ pub struct ScannerManager {
     scanners: Vec<Arc<dyn Scanner>>,
 }
 
+impl Default for ScannerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ScannerManager {
     pub fn new() -> Self {
         Self {
@@ -14,37 +28,139 @@ impl ScannerManager {
         }
     }
 
+    /// 使用内置正则扫描器和给定规则构建扫描管理器
+    pub fn with_rules(rules: Vec<Rule>) -> Self {
+        let mut manager = Self::new();
+        manager.register_scanner(RegexScanner::new());
+        manager.rebuild_rule_scanner(rules);
+        manager
+    }
+
     pub fn register_scanner<S: Scanner + 'static>(&mut self, scanner: S) {
         self.scanners.push(Arc::new(scanner));
     }
 
-    pub async fn scan_file(&self, path: &PathBuf, content: &str) -> Vec<Finding> {
+    /// 替换同名扫描器，不存在时追加注册
+    pub fn replace_scanner<S: Scanner + 'static>(&mut self, scanner: S) {
+        let name = scanner.name();
+        self.scanners.retain(|s| s.name() != name);
+        self.scanners.push(Arc::new(scanner));
+    }
+
+    /// 根据新的规则集重建规则扫描器
+    pub fn rebuild_rule_scanner(&mut self, rules: Vec<Rule>) {
+        if rules.is_empty() {
+            self.scanners.retain(|s| s.name() != RuleScanner::NAME);
+        } else {
+            self.replace_scanner(RuleScanner::new(rules));
+        }
+    }
+
+    /// 仅启用指定规则包中的规则，构建新的扫描管理器
+    pub fn with_rule_packs(&self, rules: &[Rule], packs: &[String]) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|r| packs.iter().any(|p| p == r.pack_name()))
+            .cloned()
+            .collect();
+        let mut manager = self.clone();
+        manager.rebuild_rule_scanner(rules);
+        manager
+    }
+
+    /// 已注册的扫描器名称
+    pub fn scanner_names(&self) -> Vec<String> {
+        self.scanners.iter().map(|s| s.name()).collect()
+    }
+
+    /// 依次运行全部扫描器，去除被行内抑制注释（`deepaudit: ignore`）抑制的发现，
+    /// 多个扫描器在同一位置报告的同类问题合并为一条
+    ///
+    /// 抑制在合并前按各扫描器的发现判断，限定了规则 id 或 CWE 的抑制注释不影响同一行的其他发现；
+    /// 合并后的发现附带指纹，指纹按 `path` 原样计算，扫描项目中的文件时使用 `scan_project_file`
+    pub async fn scan_file(&self, path: &Path, content: &str) -> Vec<Finding> {
+        self.scan_project_file(Path::new(""), path, content).await
+    }
+
+    /// 扫描项目 `root` 中的单个文件，与 `scan_file` 相同，但指纹按相对 `root` 的路径计算，
+    /// 项目目录移动后基线仍然有效
+    pub async fn scan_project_file(&self, root: &Path, path: &Path, content: &str) -> Vec<Finding> {
         let mut all_findings = Vec::new();
         for scanner in &self.scanners {
-            let findings = scanner.scan_file(path, content).await;
+            let findings = scanner.scan_file(root, path, content).await;
             all_findings.extend(findings);
         }
-        all_findings
+        let mut findings = merge_duplicate_findings(drop_suppressed(all_findings, content));
+        assign_fingerprints(&mut findings, root, content);
+        findings
     }
 
+    /// 判断 `root` 下的文件是否属于目录扫描的范围
+    ///
+    /// 与 `scan_directory` 的遍历规则一致：跳过隐藏路径，遵循沿途各级目录的
+    /// `.gitignore` / `.ignore`（越深的目录优先），且只接受支持的文件类型。
+    /// 文件可以已被删除，用于增量扫描时判断变更事件
+    pub fn is_scan_target(root: &Path, path: &Path) -> bool {
+        if !is_supported_file(path) {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        if relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return false;
+        }
+
+        // 由深到浅检查各级目录，第一个命中的规则决定结果
+        for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+            let mut builder = GitignoreBuilder::new(dir);
+            let mut has_rules = false;
+            for name in [".gitignore", ".ignore"] {
+                let file = dir.join(name);
+                if file.is_file() && builder.add(&file).is_none() {
+                    has_rules = true;
+                }
+            }
+            if !has_rules {
+                continue;
+            }
+            let Ok(gitignore) = builder.build() else {
+                continue;
+            };
+            match gitignore.matched_path_or_any_parents(path, false) {
+                Match::Ignore(_) => return false,
+                Match::Whitelist(_) => return true,
+                Match::None => {}
+            }
+        }
+        true
+    }
+
+    /// 并发扫描目录下的文件
+    ///
+    /// 只扫描支持的文件类型，与 `scanner::scan_directory` 保持一致：web 端改用
+    /// 共享的扫描管理器后，扫描范围不应扩大到锁文件、文档等其它文本文件
     pub async fn scan_directory(&self, root_path: &str) -> Vec<Finding> {
+        let root = Arc::new(Path::new(root_path).to_path_buf());
         let walker = ignore::WalkBuilder::new(root_path).build();
         let mut set = tokio::task::JoinSet::new();
 
-        for result in walker {
-            if let Ok(entry) = result {
-                if entry.file_type().map_or(false, |ft| ft.is_file()) {
-                    let path = entry.path().to_path_buf();
-                    let manager = self.clone();
-
-                    set.spawn(async move {
-                        if let Ok(content) = tokio::fs::read_to_string(&path).await {
-                            manager.scan_file(&path, &content).await
-                        } else {
-                            Vec::new()
-                        }
-                    });
-                }
+        for entry in walker.flatten() {
+            if entry.file_type().is_some_and(|ft| ft.is_file()) && is_supported_file(entry.path()) {
+                let path = entry.path().to_path_buf();
+                let manager = self.clone();
+                let root = root.clone();
+
+                set.spawn(async move {
+                    if let Ok(content) = tokio::fs::read_to_string(&path).await {
+                        manager.scan_project_file(&root, &path, &content).await
+                    } else {
+                        Vec::new()
+                    }
+                });
             }
         }
 
@@ -57,3 +173,74 @@ impl ScannerManager {
         all_findings
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::test_finding;
+    use async_trait::async_trait;
+
+    /// 在固定行报告固定 CWE 的扫描器
+    struct FixedScanner {
+        name: &'static str,
+        line: usize,
+    }
+
+    #[async_trait]
+    impl Scanner for FixedScanner {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        async fn scan_file(&self, _root: &Path, path: &Path, _content: &str) -> Vec<Finding> {
+            vec![Finding {
+                detector: self.name.to_string(),
+                cwe: Some("CWE-89".to_string()),
+                ..test_finding(&path.to_string_lossy(), self.line)
+            }]
+        }
+    }
+
+    #[tokio::test]
+    async fn overlapping_findings_from_two_scanners_merge_into_one() {
+        let mut manager = ScannerManager::new();
+        manager.register_scanner(FixedScanner { name: "first", line: 2 });
+        manager.register_scanner(FixedScanner { name: "second", line: 2 });
+
+        let findings = manager.scan_file(Path::new("app.py"), "a\nb\n").await;
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].detectors, ["first", "second"]);
+        assert!(!findings[0].fingerprint.is_empty());
+    }
+
+    #[tokio::test]
+    async fn scan_directory_skips_unsupported_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = "password = \"hunter2\"\n";
+        std::fs::write(dir.path().join("app.py"), line).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), line).unwrap();
+
+        let manager = ScannerManager::with_rules(Vec::new());
+        let findings = manager
+            .scan_directory(dir.path().to_str().unwrap())
+            .await;
+
+        assert!(!findings.is_empty());
+        assert!(findings.iter().all(|f| f.file_path.ends_with("app.py")));
+    }
+
+    #[tokio::test]
+    async fn fingerprints_survive_moving_the_project() {
+        let manager = ScannerManager::with_rules(Vec::new());
+        let mut fingerprints = Vec::new();
+        for prefix in ["", "import os\n\n"] {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir(dir.path().join("src")).unwrap();
+            std::fs::write(dir.path().join("src/app.py"), format!("{}password = \"hunter2\"\n", prefix)).unwrap();
+            let findings = manager.scan_directory(dir.path().to_str().unwrap()).await;
+            assert_eq!(findings.len(), 1);
+            fingerprints.push(findings[0].fingerprint.clone());
+        }
+        assert_eq!(fingerprints[0], fingerprints[1]);
+    }
+}
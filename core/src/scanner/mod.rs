@@ -1,12 +1,18 @@
 // Scanner module - 扫描器模块
 // 定义扫描器的核心接口和类型
 
+pub mod confidence;
+pub mod dedup;
+pub mod external_scanner;
+pub mod fingerprint;
 pub mod manager;
 pub mod regex_scanner;
+pub mod suppression;
 
+use crate::rules::model::Severity;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::Path;
 
 /// 漏洞发现结果
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,36 +22,65 @@ pub struct Finding {
     pub line_start: usize,
     pub line_end: usize,
     pub detector: String,
+    /// 合并后的发现中参与命中的全部扫描器，`detector` 为其中严重程度最高的一个
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub detectors: Vec<String>,
     pub vuln_type: String,
     pub severity: String,
+    /// 命中为真实问题的把握（0-1）：规则声明的置信度按命中位置（注释、字符串字面量、测试文件）折减
+    #[serde(default = "default_confidence")]
+    pub confidence: f32,
     pub description: String,
+    /// 产生该发现的规则 id，非规则扫描器为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwe: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owasp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    /// 规则命中的原始文本，仅在规则提供修复模板时记录，用于应用修复前校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_text: Option<String>,
+    /// 渲染后的修复建议，替换 `matched_text`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_fix: Option<String>,
+    /// 不含行号的指纹，无关的行移动后保持不变，用于与基线比较；由 `ScannerManager::scan_file` 填写
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub analysis_trail: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub llm_output: Option<String>,
 }
 
+fn default_confidence() -> f32 {
+    confidence::DEFAULT_CONFIDENCE
+}
+
 /// 扫描器 trait - 所有扫描器都需要实现此接口
 #[async_trait]
 pub trait Scanner: Send + Sync {
     /// 返回扫描器名称
     fn name(&self) -> String;
 
-    /// 扫描单个文件
-    async fn scan_file(&self, path: &PathBuf, content: &str) -> Vec<Finding>;
+    /// 扫描项目 `root` 中的单个文件，`root` 用于按相对路径判断测试文件，不属于项目时传空路径
+    async fn scan_file(&self, root: &Path, path: &Path, content: &str) -> Vec<Finding>;
 }
 
-/// 便捷的 scan_directory 函数（用于web-backend）
-pub async fn scan_directory(path: &str) -> Result<Vec<Finding>, String> {
+/// 便捷的 scan_directory 函数，规则从 `rules_dir` 加载
+pub async fn scan_directory(path: &str, rules_dir: &std::path::Path) -> Result<Vec<Finding>, String> {
     use ignore::Walk;
     use tokio::fs;
 
     let mut findings = Vec::new();
 
     // 加载规则
-    let rules_path = std::path::Path::new("rules");
-    let rules = if rules_path.exists() {
-        match crate::rules::loader::load_rules_from_dir(rules_path) {
+    let rules = if rules_dir.exists() {
+        match crate::rules::loader::load_rules_from_dir(rules_dir) {
             Ok(r) => r,
             Err(e) => {
                 eprintln!("Failed to load rules: {}, using only RegexScanner", e);
@@ -53,7 +88,7 @@ pub async fn scan_directory(path: &str) -> Result<Vec<Finding>, String> {
             }
         }
     } else {
-        eprintln!("Rules directory not found, using only RegexScanner");
+        eprintln!("Rules directory {} not found, using only RegexScanner", rules_dir.display());
         vec![]
     };
 
@@ -68,26 +103,23 @@ pub async fn scan_directory(path: &str) -> Result<Vec<Finding>, String> {
     let regex_scanner = regex_scanner::RegexScanner::new();
 
     // 使用 ignore 库遍历目录
-    for entry in Walk::new(path) {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-
-            // 只扫描支持的文件类型
-            if path.is_file() && is_supported_file(path) {
-                if let Ok(content) = fs::read_to_string(path).await {
-                    let path_buf = path.to_path_buf();
-
-                    // 使用 RegexScanner 进行简单扫描
-                    let mut file_findings = regex_scanner.scan_file(&path_buf, &content).await;
-
-                    // 如果有规则扫描器，也使用规则扫描
-                    if let Some(ref scanner) = rule_scanner {
-                        let mut rule_findings = scanner.scan_file(&path_buf, &content).await;
-                        findings.append(&mut rule_findings);
-                    }
-
-                    findings.append(&mut file_findings);
+    let root = std::path::Path::new(path);
+    for entry in Walk::new(path).flatten() {
+        let path = entry.path();
+
+        // 只扫描支持的文件类型
+        if path.is_file() && is_supported_file(path) {
+            if let Ok(content) = fs::read_to_string(path).await {
+                // 使用 RegexScanner 进行简单扫描
+                let mut file_findings = regex_scanner.scan_file(root, path, &content).await;
+
+                // 如果有规则扫描器，也使用规则扫描
+                if let Some(ref scanner) = rule_scanner {
+                    let mut rule_findings = scanner.scan_file(root, path, &content).await;
+                    findings.append(&mut rule_findings);
                 }
+
+                findings.append(&mut file_findings);
             }
         }
     }
@@ -95,7 +127,20 @@ pub async fn scan_directory(path: &str) -> Result<Vec<Finding>, String> {
     Ok(findings)
 }
 
-fn is_supported_file(path: &std::path::Path) -> bool {
+/// 丢弃严重程度低于 `min_severity` 的发现，返回保留的发现和被丢弃的数量
+///
+/// 无法识别严重程度的发现总是保留
+pub fn filter_by_severity(findings: Vec<Finding>, min_severity: &Severity) -> (Vec<Finding>, usize) {
+    let total = findings.len();
+    let kept: Vec<Finding> = findings
+        .into_iter()
+        .filter(|f| Severity::parse(&f.severity).is_none_or(|s| s.rank() >= min_severity.rank()))
+        .collect();
+    let suppressed = total - kept.len();
+    (kept, suppressed)
+}
+
+pub(crate) fn is_supported_file(path: &std::path::Path) -> bool {
     if let Some(ext) = path.extension() {
         let ext = ext.to_str().unwrap_or("");
         matches!(
@@ -108,3 +153,30 @@ fn is_supported_file(path: &std::path::Path) -> bool {
         false
     }
 }
+
+/// 测试用的发现，规则 id、CWE 等可选字段为空
+#[cfg(test)]
+pub(crate) fn test_finding(file_path: &str, line: usize) -> Finding {
+    Finding {
+        finding_id: uuid::Uuid::new_v4().to_string(),
+        file_path: file_path.to_string(),
+        line_start: line,
+        line_end: line,
+        detector: "TestScanner".to_string(),
+        detectors: Vec::new(),
+        vuln_type: "Test Finding".to_string(),
+        severity: "high".to_string(),
+        confidence: confidence::DEFAULT_CONFIDENCE,
+        description: String::new(),
+        rule_id: None,
+        cwe: None,
+        owasp: None,
+        remediation: None,
+        references: Vec::new(),
+        matched_text: None,
+        suggested_fix: None,
+        fingerprint: String::new(),
+        analysis_trail: None,
+        llm_output: None,
+    }
+}
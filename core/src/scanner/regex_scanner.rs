@@ -1,11 +1,19 @@
+use super::confidence::{ConfidenceContext, DEFAULT_CONFIDENCE};
 use super::{Finding, Scanner};
 use async_trait::async_trait;
 use regex::Regex;
-use std::path::PathBuf;
+use std::cell::OnceCell;
+use std::path::Path;
 use uuid::Uuid;
 
 pub struct RegexScanner {
-    patterns: Vec<(Regex, String, String)>, // Regex, VulnType, Severity
+    patterns: Vec<(Regex, String, String, Option<String>)>, // Regex, VulnType, Severity, CWE
+}
+
+impl Default for RegexScanner {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RegexScanner {
@@ -15,16 +23,19 @@ impl RegexScanner {
                 Regex::new(r#"(?i)password\s*=\s*['"][^'"]+['"]"#).unwrap(),
                 "Hardcoded Password".to_string(),
                 "high".to_string(),
+                Some("CWE-798".to_string()),
             ),
             (
                 Regex::new(r#"(?i)api_key\s*=\s*['"][^'"]+['"]"#).unwrap(),
                 "Hardcoded API Key".to_string(),
                 "high".to_string(),
+                Some("CWE-798".to_string()),
             ),
             (
                 Regex::new(r"(?i)TODO:").unwrap(),
                 "TODO Comment".to_string(),
                 "low".to_string(),
+                None,
             ),
         ];
         Self { patterns }
@@ -37,27 +48,42 @@ impl Scanner for RegexScanner {
         "RegexScanner".to_string()
     }
 
-    async fn scan_file(&self, path: &PathBuf, content: &str) -> Vec<Finding> {
+    async fn scan_file(&self, root: &Path, path: &Path, content: &str) -> Vec<Finding> {
         let mut findings = Vec::new();
-        let lines: Vec<&str> = content.lines().collect();
+        let context = OnceCell::new();
+        let mut line_offset = 0;
 
-        for (i, line) in lines.iter().enumerate() {
-            for (regex, vuln_type, severity) in &self.patterns {
-                if regex.is_match(line) {
+        for (i, line) in content.split_inclusive('\n').enumerate() {
+            for (regex, vuln_type, severity, cwe) in &self.patterns {
+                if let Some(m) = regex.find(line) {
+                    let confidence = context
+                        .get_or_init(|| ConfidenceContext::new(root, path, content))
+                        .score(DEFAULT_CONFIDENCE, line_offset + m.start());
                     findings.push(Finding {
                         finding_id: Uuid::new_v4().to_string(),
                         file_path: path.to_string_lossy().to_string(),
                         line_start: i + 1,
                         line_end: i + 1,
                         detector: self.name(),
+                        detectors: Vec::new(),
                         vuln_type: vuln_type.clone(),
                         severity: severity.clone(),
+                        confidence,
                         description: format!("Found potential {} at line {}", vuln_type, i + 1),
+                        rule_id: None,
+                        cwe: cwe.clone(),
+                        owasp: None,
+                        remediation: None,
+                        references: Vec::new(),
+                        matched_text: None,
+                        suggested_fix: None,
+                        fingerprint: String::new(),
                         analysis_trail: None,
                         llm_output: None,
                     });
                 }
             }
+            line_offset += line.len();
         }
         findings
     }
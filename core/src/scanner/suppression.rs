@@ -0,0 +1,151 @@
+// 行内抑制注释：发现所在行或上一行带有 `deepaudit: ignore` 时丢弃该发现，与 `# noqa`、`// nosec` 的用法一致
+
+use super::Finding;
+
+/// 抑制注释的标记，不区分大小写
+const MARKER: &str = "deepaudit:";
+
+/// 独占一行的注释的起始符号
+const COMMENT_STARTS: &[&str] = &["#", "//", "/*", "*", "--", ";", "<!--"];
+
+/// 标记之前是否只有注释符号，且以注释起始符号开头；
+/// 多行字符串中的 `deepaudit: ignore`（标记前只有缩进）不算注释
+fn is_comment_prefix(prefix: &str) -> bool {
+    let prefix = prefix.trim_start();
+    COMMENT_STARTS.iter().any(|start| prefix.starts_with(start))
+        && prefix.chars().all(|c| c.is_whitespace() || "#/*-;<!".contains(c))
+}
+
+/// 一行中的抑制注释：`None` 为没有抑制注释，`Some(vec![])` 抑制全部发现，
+/// 否则只抑制规则 id 或 CWE 在列表中的发现（`deepaudit: ignore[CWE-89, sql-injection]`）
+///
+/// `comment_only` 时只接受独占一行的注释（标记之前只有注释符号），用于抑制下一行的发现
+fn suppression(line: &str, comment_only: bool) -> Option<Vec<String>> {
+    let lower = line.to_lowercase();
+    let mut search = 0;
+    while let Some(index) = lower[search..].find(MARKER) {
+        if comment_only && !is_comment_prefix(&lower[..search + index]) {
+            return None;
+        }
+        let rest = lower[search + index + MARKER.len()..].trim_start();
+        search += index + MARKER.len();
+        let Some(qualifier) = rest.strip_prefix("ignore") else {
+            continue;
+        };
+        if let Some(list) = qualifier.strip_prefix('[') {
+            let Some(end) = list.find(']') else {
+                continue;
+            };
+            return Some(
+                list[..end]
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect(),
+            );
+        }
+        // `ignore` 之后须是单词边界，避免匹配 `ignored` 等
+        if qualifier.starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '-') {
+            continue;
+        }
+        return Some(Vec::new());
+    }
+    None
+}
+
+/// 发现是否被所在行（`line_start`）的抑制注释或上一行独占一行的抑制注释抑制
+pub fn is_suppressed(finding: &Finding, lines: &[&str]) -> bool {
+    let line = finding.line_start;
+    let candidates = [(line.checked_sub(1), false), (line.checked_sub(2), true)];
+    candidates
+        .into_iter()
+        .filter_map(|(index, comment_only)| lines.get(index?).and_then(|line| suppression(line, comment_only)))
+        .any(|qualifiers| {
+            qualifiers.is_empty()
+                || qualifiers.iter().any(|qualifier| {
+                    [&finding.rule_id, &finding.cwe]
+                        .into_iter()
+                        .flatten()
+                        .any(|id| id.eq_ignore_ascii_case(qualifier))
+                })
+        })
+}
+
+/// 去除文件内容中被抑制注释抑制的发现
+pub(crate) fn drop_suppressed(findings: Vec<Finding>, content: &str) -> Vec<Finding> {
+    if findings.is_empty() || !content.to_lowercase().contains(MARKER) {
+        return findings;
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let total = findings.len();
+    let kept: Vec<Finding> = findings.into_iter().filter(|finding| !is_suppressed(finding, &lines)).collect();
+    if kept.len() < total {
+        log::debug!("Suppressed {} findings by inline comments", total - kept.len());
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::test_finding;
+
+    fn suppressed(content: &str, line: usize, cwe: Option<&str>) -> bool {
+        let mut finding = test_finding("app.py", line);
+        finding.cwe = cwe.map(str::to_string);
+        finding.rule_id = Some("sql-injection".to_string());
+        let lines: Vec<&str> = content.lines().collect();
+        is_suppressed(&finding, &lines)
+    }
+
+    #[test]
+    fn marker_on_same_line() {
+        assert!(suppressed("a = 1\nquery(sql)  # deepaudit: ignore\n", 2, None));
+        assert!(suppressed("query(sql)  // DeepAudit: Ignore\n", 1, None));
+        assert!(!suppressed("query(sql)\n", 1, None));
+    }
+
+    #[test]
+    fn marker_on_preceding_comment_line() {
+        assert!(suppressed("# deepaudit: ignore\nquery(sql)\n", 2, None));
+        assert!(suppressed("    /* deepaudit: ignore */\n    query(sql)\n", 2, None));
+        // 上一行代码末尾的标记只作用于该行
+        assert!(!suppressed("other()  # deepaudit: ignore\nquery(sql)\n", 2, None));
+        // 相隔两行不生效
+        assert!(!suppressed("# deepaudit: ignore\n\nquery(sql)\n", 3, None));
+    }
+
+    #[test]
+    fn qualified_marker_matches_cwe_or_rule_id() {
+        let content = "query(sql)  # deepaudit: ignore[CWE-89]\n";
+        assert!(suppressed(content, 1, Some("CWE-89")));
+        assert!(suppressed(content, 1, Some("cwe-89")));
+        assert!(!suppressed(content, 1, Some("CWE-798")));
+        assert!(!suppressed(content, 1, None));
+        assert!(suppressed("query(sql)  # deepaudit: ignore[CWE-1, sql-injection]\n", 1, None));
+    }
+
+    #[test]
+    fn ignored_is_not_a_marker() {
+        assert!(!suppressed("query(sql)  # deepaudit: ignored\n", 1, None));
+        assert!(!suppressed("# deepaudit: ignore_this\nquery(sql)\n", 2, None));
+    }
+
+    #[test]
+    fn marker_inside_string_on_preceding_line() {
+        assert!(!suppressed("x = \"deepaudit: ignore\"\nquery(sql)\n", 2, None));
+        assert!(!suppressed("\"deepaudit: ignore\",\nquery(sql)\n", 2, None));
+        // 多行字符串中只有缩进的一行
+        assert!(!suppressed("sql = \"\"\"\n    deepaudit: ignore\n    SELECT * FROM t WHERE id = %s\n\"\"\"\n", 3, None));
+        assert!(!suppressed("    deepaudit: ignore\nquery(sql)\n", 2, None));
+    }
+
+    #[test]
+    fn drop_suppressed_keeps_other_findings() {
+        let content = "query(a)  # deepaudit: ignore\nquery(b)\n";
+        let findings = vec![test_finding("app.py", 1), test_finding("app.py", 2)];
+        let kept = drop_suppressed(findings, content);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].line_start, 2);
+    }
+}
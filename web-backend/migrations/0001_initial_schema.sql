@@ -0,0 +1,128 @@
+-- 初始表结构（引入迁移前 init_db 内联创建的表与索引）
+-- 保留 IF NOT EXISTS：迁移前创建的数据库已有这些表，补齐列后在此基线上继续迁移
+
+CREATE TABLE IF NOT EXISTS projects (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    uuid TEXT UNIQUE NOT NULL,
+    name TEXT NOT NULL,
+    path TEXT NOT NULL UNIQUE,
+    settings TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS findings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER,
+    finding_id TEXT UNIQUE,
+    file_path TEXT,
+    line_start INTEGER,
+    line_end INTEGER,
+    detector TEXT,
+    vuln_type TEXT,
+    severity TEXT,
+    description TEXT,
+    code_snippet TEXT,
+    rule_id TEXT,
+    cwe TEXT,
+    owasp TEXT,
+    remediation TEXT,
+    reference_links TEXT,
+    matched_text TEXT,
+    suggested_fix TEXT,
+    detectors TEXT,
+    status TEXT DEFAULT 'new',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(project_id) REFERENCES projects(id)
+);
+
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER,
+    status TEXT DEFAULT 'pending',
+    files_scanned INTEGER DEFAULT 0,
+    findings_found INTEGER DEFAULT 0,
+    findings_suppressed INTEGER DEFAULT 0,
+    started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    completed_at DATETIME,
+    FOREIGN KEY(project_id) REFERENCES projects(id)
+);
+
+-- AST 索引历史表
+CREATE TABLE IF NOT EXISTS ast_indices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER NOT NULL,
+    index_version TEXT NOT NULL,
+    total_symbols INTEGER DEFAULT 0,
+    total_files INTEGER DEFAULT 0,
+    index_data BLOB,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(project_id) REFERENCES projects(id)
+);
+
+-- 符号表（支持历史查询）
+CREATE TABLE IF NOT EXISTS symbols (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER NOT NULL,
+    ast_index_id INTEGER NOT NULL,
+    symbol_id TEXT NOT NULL,
+    symbol_name TEXT NOT NULL,
+    symbol_type TEXT NOT NULL,
+    file_path TEXT NOT NULL,
+    line_number INTEGER,
+    end_line INTEGER,
+    column_number INTEGER,
+    end_column INTEGER,
+    parent_name TEXT,
+    metadata TEXT,
+    language TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(project_id) REFERENCES projects(id),
+    FOREIGN KEY(ast_index_id) REFERENCES ast_indices(id)
+);
+
+-- 代码图谱表
+CREATE TABLE IF NOT EXISTS code_graphs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER NOT NULL,
+    graph_type TEXT NOT NULL,
+    entry_point TEXT,
+    graph_data TEXT NOT NULL,
+    node_count INTEGER DEFAULT 0,
+    edge_count INTEGER DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(project_id) REFERENCES projects(id)
+);
+
+-- 调用关系表
+CREATE TABLE IF NOT EXISTS call_relations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER NOT NULL,
+    graph_id INTEGER NOT NULL,
+    caller_function TEXT NOT NULL,
+    callee_function TEXT NOT NULL,
+    file_path TEXT NOT NULL,
+    line_number INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(project_id) REFERENCES projects(id),
+    FOREIGN KEY(graph_id) REFERENCES code_graphs(id)
+);
+
+-- 创建索引以提高查询性能
+CREATE INDEX IF NOT EXISTS idx_symbols_project ON symbols(project_id);
+CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(symbol_name);
+CREATE INDEX IF NOT EXISTS idx_symbols_type ON symbols(symbol_type);
+CREATE INDEX IF NOT EXISTS idx_graphs_project ON code_graphs(project_id);
+CREATE INDEX IF NOT EXISTS idx_graphs_type ON code_graphs(graph_type);
+CREATE INDEX IF NOT EXISTS idx_calls_project ON call_relations(project_id);
+CREATE INDEX IF NOT EXISTS idx_indices_project ON ast_indices(project_id);
+-- 复合索引的前缀同样用于只按 project_id 的查询
+CREATE INDEX IF NOT EXISTS idx_findings_project_severity ON findings(project_id, severity);
+CREATE INDEX IF NOT EXISTS idx_findings_status ON findings(status);
+CREATE INDEX IF NOT EXISTS idx_findings_file ON findings(file_path);
+CREATE INDEX IF NOT EXISTS idx_symbols_project_name ON symbols(project_id, symbol_name);
+CREATE INDEX IF NOT EXISTS idx_calls_project_callee ON call_relations(project_id, callee_function);
+CREATE INDEX IF NOT EXISTS idx_calls_project_caller ON call_relations(project_id, caller_function);
+
+-- 当前表结构版本，即最近一次成功应用的迁移
+CREATE VIEW IF NOT EXISTS schema_version AS
+    SELECT MAX(version) AS version FROM _sqlx_migrations WHERE success = 1;
@@ -0,0 +1,7 @@
+-- 发现所在行（line_start）的 git blame，由 POST /api/scanner/findings/{finding_id}/blame 按需填写
+-- 未提交的行或不在 git 仓库中的文件保持为空
+
+ALTER TABLE findings ADD COLUMN blame_commit TEXT;
+ALTER TABLE findings ADD COLUMN blame_author TEXT;
+ALTER TABLE findings ADD COLUMN blame_time INTEGER;
+ALTER TABLE findings ADD COLUMN blame_summary TEXT;
@@ -0,0 +1,4 @@
+-- 发现的置信度（0-1），由扫描器按规则声明的置信度与命中位置计算
+-- 此前保存的发现保持为空，读取时按默认置信度处理
+
+ALTER TABLE findings ADD COLUMN confidence REAL;
@@ -0,0 +1,6 @@
+-- 只扫描两个 git 引用之间变更行的扫描（POST /api/scanner/scan-range），记录比较的两个引用
+-- 完整扫描的 range_scan 为 0，base_ref / head_ref 为空
+
+ALTER TABLE scans ADD COLUMN range_scan INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE scans ADD COLUMN base_ref TEXT;
+ALTER TABLE scans ADD COLUMN head_ref TEXT;
@@ -0,0 +1,13 @@
+-- 发现基线：保存基线时项目全部发现的指纹，查询发现时可排除基线中已有的发现
+-- findings.fingerprint 由扫描器计算，不含行号；此前保存的发现保持为空，不会进入基线
+
+ALTER TABLE findings ADD COLUMN fingerprint TEXT;
+
+CREATE TABLE IF NOT EXISTS finding_baselines (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id INTEGER NOT NULL,
+    fingerprint TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(project_id, fingerprint),
+    FOREIGN KEY(project_id) REFERENCES projects(id)
+);
@@ -1,32 +1,53 @@
 use actix_web::{web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
+use crate::error::{AppError, ErrorCode};
 use crate::state::AppState;
+use deepaudit_core::{ASTEngine, SymbolKind};
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize)]
 pub struct BuildIndexRequest {
     pub project_path: String,
     pub project_id: Option<i64>,  // 新增：项目ID，用于保存到数据库
+    /// 相对项目根目录的排除 glob，另会读取项目根目录的 .deepauditignore
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    /// 非空时仅索引匹配的文件
+    #[serde(default)]
+    pub include_globs: Vec<String>,
 }
 
 #[derive(Serialize)]
 pub struct BuildIndexResponse {
     pub files_processed: usize,
+    /// 被排除的源码文件数，不含整体跳过的目录中的文件
+    pub files_excluded: usize,
+    /// 被整体跳过的目录数
+    pub dirs_excluded: usize,
     pub message: String,
     pub index_id: Option<i64>,  // 新增：返回数据库中的索引ID
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct SearchSymbolRequest {
-    pub symbol_name: String,
-}
-
 #[derive(Serialize, Deserialize)]
 pub struct GetCallGraphRequest {
     pub entry_function: String,
     pub max_depth: Option<usize>,
     pub project_id: Option<i64>,  // 新增：项目ID，用于保存图谱
     pub save_graph: Option<bool>,  // 新增：是否保存图谱到数据库
+    #[serde(default = "default_cross_file")]
+    pub cross_file: bool,  // 跨文件解析被调用者定义，默认开启
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetReverseCallGraphRequest {
+    pub function: String,
+    pub max_depth: Option<usize>,
+    pub project_id: Option<i64>,  // 提供时基于 call_relations 表构建
+    pub save_graph: Option<bool>,
+}
+
+fn default_cross_file() -> bool {
+    true
 }
 
 #[derive(Serialize)]
@@ -35,6 +56,28 @@ pub struct Symbol {
     pub kind: String,
     pub file_path: String,
     pub line: usize,
+    /// 起始列（从 1 开始），0 表示未知
+    pub column: usize,
+    pub end_column: usize,
+    /// 源码语言，如 "python"
+    pub language: String,
+}
+
+// 新增：分页获取符号请求
+#[derive(Serialize, Deserialize)]
+pub struct SymbolsPageQuery {
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+    pub kind: Option<String>,
+    pub language: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SymbolsPage {
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+    pub symbols: Vec<Symbol>,
 }
 
 // 新增：历史查询请求
@@ -42,6 +85,12 @@ pub struct Symbol {
 pub struct GetHistoryRequest {
     pub project_id: i64,
     pub limit: Option<usize>,
+    /// 仅列出指定类型的图谱，如 `knowledge_graph`
+    #[serde(default)]
+    pub graph_type: Option<String>,
+    /// 是否返回图谱内容
+    #[serde(default)]
+    pub include_graph: bool,
 }
 
 // 新增：历史记录响应
@@ -54,6 +103,21 @@ pub struct AstIndexHistory {
     pub created_at: String,
 }
 
+// 新增：历史清理请求
+#[derive(Serialize, Deserialize)]
+pub struct PruneHistoryRequest {
+    pub project_id: i64,
+    pub keep_last: usize,
+}
+
+#[derive(Serialize)]
+pub struct PruneHistoryResponse {
+    pub indices_deleted: u64,
+    pub symbols_deleted: u64,
+    pub graphs_deleted: u64,
+    pub call_relations_deleted: u64,
+}
+
 #[derive(Serialize)]
 pub struct CodeGraphHistory {
     pub id: i64,
@@ -62,6 +126,29 @@ pub struct CodeGraphHistory {
     pub node_count: i64,
     pub edge_count: i64,
     pub created_at: String,
+    /// 图谱内容，仅在请求 `include_graph` 时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphDiffRequest {
+    /// 旧版本图谱 ID
+    pub graph_id_a: i64,
+    /// 新版本图谱 ID
+    pub graph_id_b: i64,
+}
+
+/// 两个已保存图谱之间的差异，节点与边保持各自图谱中的原始内容
+#[derive(Serialize)]
+pub struct GraphDiffResponse {
+    pub graph_id_a: i64,
+    pub graph_id_b: i64,
+    pub graph_type: String,
+    pub added_nodes: Vec<serde_json::Value>,
+    pub removed_nodes: Vec<serde_json::Value>,
+    pub added_edges: Vec<serde_json::Value>,
+    pub removed_edges: Vec<serde_json::Value>,
 }
 
 // ==================== AST Context 相关 ====================
@@ -114,6 +201,60 @@ pub struct ContextSymbol {
     pub kind: String,
     pub line: usize,
     pub column: usize,
+    pub end_column: usize,
+}
+
+#[derive(Deserialize)]
+pub struct SymbolContextRequest {
+    pub symbol_name: String,
+    pub project_id: i64,
+    pub project_path: String,
+    #[serde(default = "default_true")]
+    pub include_callers: bool,
+    #[serde(default = "default_true")]
+    pub include_callees: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+pub struct SymbolContextResponse {
+    pub symbol_name: String,
+    /// 同名的每个定义各一项
+    pub matches: Vec<SymbolContextMatch>,
+}
+
+#[derive(Serialize)]
+pub struct SymbolContextMatch {
+    pub file_path: String,
+    pub kind: String,
+    pub line_range: Vec<usize>,
+    pub context: AstContextData,
+}
+
+// ==================== 未使用符号分析 ====================
+
+#[derive(Deserialize)]
+pub struct DeadCodeRequest {
+    pub project_id: i64,
+    /// 排除的文件 glob（相对项目根目录），如 `**/tests/**`，匹配文件中的候选不会返回
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    /// 是否保留公开/导出的符号
+    #[serde(default)]
+    pub include_exported: bool,
+}
+
+#[derive(Serialize)]
+pub struct DeadCodeResponse {
+    pub project_id: i64,
+    /// 参与分析的函数/方法总数
+    pub total_functions: usize,
+    pub candidates: Vec<deepaudit_core::ast::dead_code::DeadCodeCandidate>,
+    /// 分析方法的已知局限，结果需人工确认
+    pub limitations: Vec<&'static str>,
 }
 
 pub fn configure_ast_routes(cfg: &mut web::ServiceConfig) {
@@ -121,32 +262,41 @@ pub fn configure_ast_routes(cfg: &mut web::ServiceConfig) {
         .route("/build_index", web::post().to(build_index))
         .route("/search_symbol/{name}", web::get().to(search_symbol))
         .route("/get_call_graph", web::post().to(get_call_graph))
+        .route("/get_reverse_call_graph", web::post().to(get_reverse_call_graph))
         .route("/get_code_structure/{file_path}", web::get().to(get_code_structure))
+        .route("/symbols/{project_id}", web::get().to(get_symbols_page))
         .route("/get_knowledge_graph", web::post().to(get_knowledge_graph))
         .route("/context", web::post().to(get_ast_context))  // 新增：AST上下文端点
+        .route("/symbol_context", web::post().to(get_symbol_context))
+        .route("/dead_code", web::post().to(get_dead_code))
         // 新增：历史查询端点
         .route("/history/indices/{project_id}", web::get().to(get_index_history))
-        .route("/history/graphs/{project_id}", web::get().to(get_graph_history));
+        .route("/history/graphs/{project_id}", web::get().to(get_graph_history))
+        .route("/graphs/diff", web::post().to(diff_code_graphs))
+        .route("/history/prune", web::post().to(prune_ast_history));
 }
 
 pub async fn build_index(
     state: web::Data<AppState>,
     req: web::Json<BuildIndexRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     tracing::info!(
         "[AST:build_index] 开始构建索引 - project_path: {}, project_id: {:?}",
         req.project_path,
         req.project_id
     );
 
-    let start_time = std::time::Instant::now();
-    let mut engine = state.ast_engine.lock().await;
+    // 先校验 glob，避免加载索引后才发现参数错误
+    let path_filter = deepaudit_core::rules::model::PathFilter {
+        include: req.include_globs.clone(),
+        exclude: req.exclude_globs.clone(),
+    };
+    path_filter.compile().map_err(AppError::invalid_input)?;
 
-    // 设置仓库路径
-    engine.use_repository(&req.project_path);
-    tracing::debug!("[AST:build_index] 已设置仓库路径: {}", req.project_path);
+    let start_time = std::time::Instant::now();
 
     // 如果提供了 project_id，尝试从数据库加载之前的索引
+    let mut cached = None;
     if let Some(project_id) = req.project_id {
         tracing::info!("[AST:build_index] 尝试从数据库加载索引 - project_id: {}", project_id);
         match load_ast_index_from_db(&state, project_id, &req.project_path).await {
@@ -155,7 +305,7 @@ pub async fn build_index(
                     "[AST:build_index] 从数据库加载了 {} 个文件的 AST 索引",
                     cache_data.index.len()
                 );
-                engine.load_from_cache_data(cache_data);
+                cached = Some(cache_data);
             }
             Ok(None) => {
                 tracing::info!("[AST:build_index] 数据库中未找到之前的索引，从头开始");
@@ -167,34 +317,40 @@ pub async fn build_index(
     }
 
     // 扫描项目（如果有缓存，这将是增量更新）
-    let scan_start = std::time::Instant::now();
-    let files_processed = match engine.scan_project(&req.project_path) {
-        Ok(count) => count,
-        Err(e) => {
-            tracing::error!("[AST:build_index] 扫描项目失败: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to scan project: {}", e)
-            }));
-        }
+    let project_path = req.project_path.clone();
+    let options = deepaudit_core::ast::ScanOptions {
+        include_globs: req.include_globs.clone(),
+        exclude_globs: req.exclude_globs.clone(),
     };
-    let scan_duration = scan_start.elapsed();
+    let (summary, symbols, scan_duration) = with_engine_blocking(&state, move |engine| {
+        engine.use_repository(&project_path);
+        tracing::debug!("[AST:build_index] 已设置仓库路径: {}", project_path);
+        if let Some(cache_data) = cached {
+            engine.load_from_cache_data(cache_data);
+        }
+
+        let scan_start = std::time::Instant::now();
+        let summary = engine.scan_project_with_options(&project_path, &options)?;
+        let scan_duration = scan_start.elapsed();
+
+        // 获取所有符号用于存储
+        let symbols = engine.get_all_symbols().unwrap_or_else(|e| {
+            tracing::error!("[AST:build_index] 获取符号失败: {}", e);
+            Vec::new()
+        });
+        Ok::<_, String>((summary, symbols, scan_duration))
+    })
+    .await?
+    .map_err(|e| AppError::new(ErrorCode::FileNotFound, "Failed to scan project").with_detail(e))?;
+    let files_processed = summary.files_processed;
     tracing::info!(
-        "[AST:build_index] 扫描完成 - 文件数: {}, 耗时: {}ms",
+        "[AST:build_index] 扫描完成 - 文件数: {}, 排除文件数: {}, 排除目录数: {}, 耗时: {}ms",
         files_processed,
+        summary.files_excluded,
+        summary.dirs_excluded,
         scan_duration.as_millis()
     );
 
-    // 获取所有符号用于存储
-    let symbols = match engine.get_all_symbols() {
-        Ok(s) => s,
-        Err(e) => {
-            tracing::error!("[AST:build_index] 获取符号失败: {}", e);
-            Vec::new()
-        }
-    };
-
-    drop(engine);
-
     tracing::info!(
         "[AST:build_index] 索引构建完成 - 总耗时: {}ms, 符号数: {}",
         start_time.elapsed().as_millis(),
@@ -222,11 +378,26 @@ pub async fn build_index(
         cache_state.symbol_count = symbols.len();
     }
 
-    HttpResponse::Ok().json(BuildIndexResponse {
+    Ok(HttpResponse::Ok().json(BuildIndexResponse {
         files_processed,
+        files_excluded: summary.files_excluded,
+        dirs_excluded: summary.dirs_excluded,
         message: format!("Successfully indexed {} files", files_processed),
         index_id,
-    })
+    }))
+}
+
+/// 在阻塞线程池中使用 AST 引擎
+///
+/// 引擎方法内部加锁，这里只持有读锁：索引或加载缓存期间，其他查询请求仍可读取引擎
+async fn with_engine_blocking<T: Send + 'static>(
+    state: &AppState,
+    f: impl FnOnce(&ASTEngine) -> T + Send + 'static,
+) -> Result<T, AppError> {
+    let engine = state.ast_engine.clone().read_owned().await;
+    tokio::task::spawn_blocking(move || f(&engine))
+        .await
+        .map_err(|e| AppError::internal("AST engine task failed", e))
 }
 
 /// 从数据库加载 AST 索引
@@ -238,7 +409,7 @@ async fn load_ast_index_from_db(
     tracing::info!("Loading AST index from database for project {}", project_id);
 
     // 查询最近的索引
-    let row = match sqlx::query_as::<_, (i64, String, String)>(
+    let row = match sqlx::query_as::<_, (i64, String, Vec<u8>)>(
         "SELECT id, index_version, index_data
          FROM ast_indices
          WHERE project_id = ?
@@ -256,13 +427,19 @@ async fn load_ast_index_from_db(
         }
     };
 
-    let (id, version, index_data_json) = row;
+    let (id, version, index_data) = row;
     tracing::info!("Found AST index {} (version {}) in database", id, version);
 
-    // 从 JSON 反序列化符号
-    let symbols: Vec<deepaudit_core::Symbol> = match serde_json::from_str::<Vec<deepaudit_core::Symbol>>(&index_data_json) {
+    // 解码符号（兼容旧版 JSON 文本）
+    let decode_start = std::time::Instant::now();
+    let symbols = match crate::index_codec::decode_symbols(&index_data) {
         Ok(s) => {
-            tracing::info!("Deserialized {} symbols from database", s.len());
+            tracing::info!(
+                "Decoded {} symbols from {} bytes in {}ms",
+                s.len(),
+                index_data.len(),
+                decode_start.elapsed().as_millis()
+            );
             s
         }
         Err(e) => {
@@ -281,7 +458,13 @@ async fn load_ast_index_from_db(
 
     // 按文件路径分组符号
     let mut file_symbols: std::collections::HashMap<String, Vec<deepaudit_core::Symbol>> = std::collections::HashMap::new();
-    for symbol in symbols {
+    for mut symbol in symbols {
+        // 旧索引中的符号没有语言信息，按扩展名补全
+        if symbol.language.is_empty() {
+            symbol.language = deepaudit_core::ast::language_for_path(std::path::Path::new(&symbol.file_path))
+                .unwrap_or_default()
+                .to_string();
+        }
         let file_path = symbol.file_path.clone();
         file_symbols.entry(file_path).or_default().push(symbol);
     }
@@ -323,7 +506,7 @@ async fn load_ast_index_from_db(
 async fn save_ast_index_to_db(
     state: &AppState,
     project_id: i64,
-    project_path: &str,
+    _project_path: &str,
     files_processed: usize,
     symbols: &[deepaudit_core::Symbol],
 ) -> Result<i64, Box<dyn std::error::Error>> {
@@ -332,8 +515,9 @@ async fn save_ast_index_to_db(
     // 生成索引版本号（使用时间戳）
     let index_version = format!("{}-{}", chrono::Utc::now().to_rfc3339(), Uuid::new_v4());
 
-    // 序列化符号数据
-    let index_data = serde_json::to_string(symbols)?;
+    // 序列化符号数据（压缩二进制），完整符号用于恢复缓存，symbols 表仅用于查询
+    let index_data = crate::index_codec::encode_symbols(symbols)?;
+    tracing::info!("Encoded {} symbols into {} bytes", symbols.len(), index_data.len());
 
     // 1. 插入 ast_indices 记录
     let idx = sqlx::query_scalar::<_, i64>(
@@ -349,7 +533,14 @@ async fn save_ast_index_to_db(
     .fetch_one(&mut *tx)
     .await?;
 
-    // 2. 批量插入符号
+    // 2. 清理该项目旧索引的符号，symbols 表只保留最新索引
+    sqlx::query("DELETE FROM symbols WHERE project_id = ? AND ast_index_id != ?")
+        .bind(project_id)
+        .bind(idx)
+        .execute(&mut *tx)
+        .await?;
+
+    // 3. 批量插入符号
     for symbol in symbols {
         let metadata_json = serde_json::to_string(&symbol.metadata)?;
         let symbol_type = format!("{:?}", symbol.kind);
@@ -365,8 +556,8 @@ async fn save_ast_index_to_db(
         };
 
         sqlx::query(
-            "INSERT INTO symbols (project_id, ast_index_id, symbol_id, symbol_name, symbol_type, file_path, line_number, end_line, parent_name, metadata)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO symbols (project_id, ast_index_id, symbol_id, symbol_name, symbol_type, file_path, line_number, end_line, column_number, end_column, parent_name, metadata, language)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(project_id)
         .bind(idx)
@@ -376,8 +567,11 @@ async fn save_ast_index_to_db(
         .bind(&symbol.file_path)
         .bind(symbol.start_line as i64)
         .bind(symbol.end_line as i64)
+        .bind(symbol.column as i64)
+        .bind(symbol.end_column as i64)
         .bind(&parent_name)
         .bind(&metadata_json)
+        .bind(&symbol.language)
         .execute(&mut *tx)
         .await?;
     }
@@ -390,8 +584,9 @@ pub async fn search_symbol(
     state: web::Data<AppState>,
     path: web::Path<String>,
     query: web::Query<std::collections::HashMap<String, String>>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let name = path.into_inner();
+    let kind_filter = parse_kind_filter(query.get("kind")).map_err(AppError::invalid_input)?;
 
     tracing::info!(
         "[AST:search_symbol] 搜索符号 - name: {}, project_id: {:?}",
@@ -406,7 +601,7 @@ pub async fn search_symbol(
         }
     }
 
-    let mut engine = state.ast_engine.lock().await;
+    let engine = state.ast_engine.read().await;
 
     let results = match engine.search_symbols(&name) {
         Ok(results) => {
@@ -416,52 +611,210 @@ pub async fn search_symbol(
         Err(_) => {
             // 没有缓存，返回空结果
             tracing::warn!("[AST:search_symbol] 未加载 AST 缓存，返回空结果");
-            return HttpResponse::Ok().json(vec![] as Vec<Symbol>);
+            return Ok(HttpResponse::Ok().json(vec![] as Vec<Symbol>));
         }
     };
 
-    let symbols: Vec<Symbol> = results
+    let exact = query
+        .get("exact")
+        .is_some_and(|v| v == "true" || v == "1");
+    let path_prefix = query
+        .get("path_prefix")
+        .map(|p| normalize_path(p.trim()))
+        .filter(|p| !p.is_empty());
+    let project_root = query.get("project_path").map(|p| normalize_path(p));
+
+    let language = parse_language_filter(query.get("language"));
+
+    let mut ranked: Vec<(u8, &deepaudit_core::Symbol)> = results
         .iter()
-        .map(|s| Symbol {
+        .filter(|s| kind_filter.is_none_or(|kind| s.kind == kind))
+        .filter(|s| language.as_ref().is_none_or(|l| &s.language == l))
+        .filter(|s| {
+            path_prefix.as_ref().is_none_or(|prefix| {
+                matches_path_prefix(&s.file_path, prefix, project_root.as_deref())
+            })
+        })
+        .filter_map(|s| {
+            let rank = match_rank(&s.name, &name);
+            (!exact || s.name == name).then_some((rank, s))
+        })
+        .collect();
+
+    // 相关度排序：完全匹配 > 前缀匹配 > 子串匹配，同级按名称长度和位置
+    ranked.sort_by(|(ra, a), (rb, b)| {
+        ra.cmp(rb)
+            .then_with(|| a.name.len().cmp(&b.name.len()))
+            .then_with(|| a.file_path.cmp(&b.file_path))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+
+    let symbols: Vec<Symbol> = ranked
+        .into_iter()
+        .map(|(_, s)| Symbol {
             name: s.name.clone(),
             kind: format!("{:?}", s.kind),
             file_path: s.file_path.clone(),
             line: s.line as usize,
+            column: s.column as usize,
+            end_column: s.end_column as usize,
+            language: s.language.clone(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(symbols))
+}
+
+/// 符号名与查询的匹配等级：0 完全匹配，1 前缀匹配，2 子串匹配
+fn match_rank(symbol_name: &str, query: &str) -> u8 {
+    let symbol_name = symbol_name.to_lowercase();
+    let query = query.to_lowercase();
+    if symbol_name == query {
+        0
+    } else if symbol_name.starts_with(&query) {
+        1
+    } else {
+        2
+    }
+}
+
+/// 统一路径分隔符，便于前缀比较
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// 判断符号所在文件是否位于指定目录前缀下，前缀可以是绝对路径或相对项目根目录的路径
+fn matches_path_prefix(file_path: &str, prefix: &str, project_root: Option<&str>) -> bool {
+    let file_path = normalize_path(file_path);
+    if file_path.starts_with(prefix) {
+        return true;
+    }
+    project_root
+        .and_then(|root| file_path.strip_prefix(root.trim_end_matches('/')))
+        .map(|rel| rel.trim_start_matches('/'))
+        .is_some_and(|rel| rel.starts_with(prefix.trim_start_matches("./")))
+}
+
+/// 解析可选的语言过滤参数，统一为小写
+fn parse_language_filter(language: Option<&String>) -> Option<String> {
+    language
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+}
+
+/// 解析可选的符号类型过滤参数
+fn parse_kind_filter(kind: Option<&String>) -> Result<Option<SymbolKind>, String> {
+    match kind.map(|k| k.trim()).filter(|k| !k.is_empty()) {
+        Some(kind) => kind.parse::<SymbolKind>().map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 分页获取项目符号，直接查询 symbols 表，避免将全部符号加载到内存
+pub async fn get_symbols_page(
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<SymbolsPageQuery>,
+) -> Result<HttpResponse, AppError> {
+    let project_id = path.into_inner();
+    let offset = query.offset.unwrap_or(0).max(0);
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let kind_filter = parse_kind_filter(query.kind.as_ref())
+        .map_err(AppError::invalid_input)?
+        .map(|k| format!("{:?}", k));
+
+    let language = parse_language_filter(query.language.as_ref());
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM symbols
+         WHERE project_id = ? AND (? IS NULL OR symbol_type = ?) AND (? IS NULL OR language = ?)"
+    )
+    .bind(project_id)
+    .bind(&kind_filter)
+    .bind(&kind_filter)
+    .bind(&language)
+    .bind(&language)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to count symbols", e))?;
+
+    let rows = sqlx::query_as::<_, (String, String, String, Option<i64>, Option<i64>, Option<i64>, Option<String>)>(
+        "SELECT symbol_name, symbol_type, file_path, line_number, column_number, end_column, language
+         FROM symbols
+         WHERE project_id = ? AND (? IS NULL OR symbol_type = ?) AND (? IS NULL OR language = ?)
+         ORDER BY file_path, line_number, id
+         LIMIT ? OFFSET ?"
+    )
+    .bind(project_id)
+    .bind(&kind_filter)
+    .bind(&kind_filter)
+    .bind(&language)
+    .bind(&language)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to fetch symbols", e))?;
+
+    let symbols = rows
+        .into_iter()
+        .map(|(name, kind, file_path, line, column, end_column, language)| Symbol {
+            name,
+            kind,
+            file_path,
+            line: line.unwrap_or(0) as usize,
+            column: column.unwrap_or(0) as usize,
+            end_column: end_column.unwrap_or(0) as usize,
+            language: language.unwrap_or_default(),
         })
         .collect();
 
-    HttpResponse::Ok().json(symbols)
+    Ok(HttpResponse::Ok().json(SymbolsPage {
+        total,
+        offset,
+        limit,
+        symbols,
+    }))
 }
 
 pub async fn get_call_graph(
     state: web::Data<AppState>,
     req: web::Json<GetCallGraphRequest>,
-) -> impl Responder {
-    let mut engine = state.ast_engine.lock().await;
+) -> Result<HttpResponse, AppError> {
+    let engine = state.ast_engine.read().await;
 
     let max_depth = req.max_depth.unwrap_or(3);
-    let call_graph = match engine.get_call_graph(&req.entry_function, max_depth) {
-        Ok(graph) => graph,
-        Err(_) => {
+    let cached_graph = engine.get_call_graph(&req.entry_function, max_depth, req.cross_file);
+    drop(engine);
+
+    let call_graph = match (cached_graph, req.project_id) {
+        (Ok(graph), _) => graph,
+        // 缓存未加载时基于已保存的调用关系构建
+        (Err(_), Some(project_id)) => load_call_graph_from_db(&state, project_id, &req.entry_function, max_depth)
+            .await
+            .map_err(|e| AppError::database("Failed to build call graph", e))?,
+        (Err(_), None) => {
             // 没有缓存，返回空图
             tracing::info!("No AST cache loaded, returning empty call graph");
-            return HttpResponse::Ok().json(serde_json::json!({
+            return Ok(HttpResponse::Ok().json(serde_json::json!({
                 "nodes": [],
                 "edges": []
-            }));
+            })));
         }
     };
 
-    drop(engine);
-
     // 如果需要保存到数据库
-    let mut graph_id = None;
+    let mut saved_graph = None;
     if req.save_graph.unwrap_or(false) {
         if let Some(project_id) = req.project_id {
             match save_code_graph_to_db(&state, project_id, "call_graph", Some(&req.entry_function), &call_graph).await {
-                Ok(id) => {
-                    graph_id = Some(id);
-                    tracing::info!("Saved call graph to database: id={}", id);
+                Ok(saved) => {
+                    tracing::info!(
+                        "Saved call graph to database: id={}, relations={}",
+                        saved.id,
+                        saved.relations
+                    );
+                    saved_graph = Some(saved);
                 }
                 Err(e) => {
                     tracing::error!("Failed to save call graph: {}", e);
@@ -470,36 +823,163 @@ pub async fn get_call_graph(
         }
     }
 
-    // 添加 graph_id 到响应
+    // 添加 graph_id 与保存的调用关系数到响应
     let mut response = call_graph.clone();
-    if let Some(id) = graph_id {
+    if let Some(saved) = saved_graph {
         if let Some(obj) = response.as_object_mut() {
-            obj.insert("graph_id".to_string(), serde_json::json!(id));
+            obj.insert("graph_id".to_string(), serde_json::json!(saved.id));
+            obj.insert("relations_saved".to_string(), serde_json::json!(saved.relations));
         }
     }
 
-    match serde_json::to_value(response) {
-        Ok(value) => HttpResponse::Ok().json(value),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("Failed to serialize call graph: {}", e)
-        }))
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 从 call_relations 递归查询入口函数可达的调用关系并构建调用图
+async fn load_call_graph_from_db(
+    state: &AppState,
+    project_id: i64,
+    entry_function: &str,
+    max_depth: usize,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let entry = entry_function.trim();
+    // reach 为深度小于 max_depth、需要展开的函数
+    let rows = sqlx::query_as::<_, (String, String, String, Option<i64>)>(
+        "WITH RECURSIVE reach(name, depth) AS (
+             SELECT ?, 0 WHERE 0 < ?
+             UNION
+             SELECT cr.callee_function, reach.depth + 1
+             FROM call_relations cr
+             JOIN reach ON cr.caller_function = reach.name
+             WHERE cr.project_id = ? AND reach.depth + 1 < ?
+         )
+         SELECT DISTINCT caller_function, callee_function, file_path, line_number
+         FROM call_relations
+         WHERE project_id = ? AND caller_function IN (SELECT name FROM reach)
+         ORDER BY file_path, line_number"
+    )
+    .bind(entry)
+    .bind(max_depth as i64)
+    .bind(project_id)
+    .bind(max_depth as i64)
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    tracing::info!(
+        "Building call graph for {} from {} saved call relations (project {})",
+        entry,
+        rows.len(),
+        project_id
+    );
+
+    let relations = rows.into_iter().map(|(caller, callee, file, line)| {
+        deepaudit_core::ast::CallRelation {
+            caller,
+            callee,
+            file,
+            line: line.unwrap_or(0),
+        }
+    });
+    Ok(deepaudit_core::ast::build_call_graph(entry, max_depth, relations))
+}
+
+/// 获取反向调用图（谁调用了该函数）
+///
+/// 提供 project_id 时基于已保存的 call_relations 构建，否则使用当前加载的 AST 缓存
+pub async fn get_reverse_call_graph(
+    state: web::Data<AppState>,
+    req: web::Json<GetReverseCallGraphRequest>,
+) -> Result<HttpResponse, AppError> {
+    let max_depth = req.max_depth.unwrap_or(3);
+
+    let graph = match req.project_id {
+        Some(project_id) => {
+            let rows = sqlx::query_as::<_, (String, String, String, Option<i64>)>(
+                "SELECT DISTINCT caller_function, callee_function, file_path, line_number
+                 FROM call_relations
+                 WHERE project_id = ?"
+            )
+            .bind(project_id)
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| AppError::database("Failed to fetch call relations", e))?;
+
+            let relations = rows.into_iter().map(|(caller, callee, file, line)| {
+                deepaudit_core::ast::CallRelation {
+                    caller,
+                    callee,
+                    file,
+                    line: line.unwrap_or(0),
+                }
+            });
+            deepaudit_core::ast::build_reverse_call_graph(&req.function, max_depth, relations)
+        }
+        None => {
+            let engine = state.ast_engine.read().await;
+            match engine.get_reverse_call_graph(&req.function, max_depth) {
+                Ok(graph) => graph,
+                Err(_) => {
+                    tracing::info!("No AST cache loaded, returning empty reverse call graph");
+                    return Ok(HttpResponse::Ok().json(serde_json::json!({
+                        "nodes": [],
+                        "edges": []
+                    })));
+                }
+            }
+        }
+    };
+
+    // 如果需要保存到数据库
+    let mut response = graph;
+    if req.save_graph.unwrap_or(false) {
+        if let Some(project_id) = req.project_id {
+            match save_code_graph_to_db(&state, project_id, "reverse_call_graph", Some(&req.function), &response).await {
+                Ok(saved) => {
+                    tracing::info!("Saved reverse call graph to database: id={}", saved.id);
+                    if let Some(obj) = response.as_object_mut() {
+                        obj.insert("graph_id".to_string(), serde_json::json!(saved.id));
+                        obj.insert("relations_saved".to_string(), serde_json::json!(saved.relations));
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Failed to save reverse call graph: {}", e);
+                }
+            }
+        }
     }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 调用关系批量插入时每条语句的行数（每行 6 个参数）
+const CALL_RELATION_BATCH: usize = 500;
+
+/// 已保存的代码图谱
+struct SavedGraph {
+    id: i64,
+    /// 写入 call_relations 的调用关系数
+    relations: usize,
 }
 
 /// 保存代码图谱到数据库
+///
+/// 图谱记录与其调用关系在同一事务中写入，任一步失败都不会留下只有部分调用关系的图谱；图谱内容压缩保存
 async fn save_code_graph_to_db(
     state: &AppState,
     project_id: i64,
     graph_type: &str,
     entry_point: Option<&str>,
     graph_data: &serde_json::Value,
-) -> Result<i64, Box<dyn std::error::Error>> {
-    let graph_json = serde_json::to_string(graph_data)?;
+) -> Result<SavedGraph, Box<dyn std::error::Error>> {
+    let encoded = crate::index_codec::encode_graph(graph_data)?;
 
     // 计算 nodes 和 edges 数量
     let node_count = graph_data["nodes"].as_array().map(|v| v.len()).unwrap_or(0) as i64;
     let edge_count = graph_data["edges"].as_array().map(|v| v.len()).unwrap_or(0) as i64;
 
+    let mut tx = state.db.begin().await?;
+
     let graph_id = sqlx::query_scalar::<_, i64>(
         "INSERT INTO code_graphs (project_id, graph_type, entry_point, graph_data, node_count, edge_count)
          VALUES (?, ?, ?, ?, ?, ?)
@@ -508,13 +988,14 @@ async fn save_code_graph_to_db(
     .bind(project_id)
     .bind(graph_type)
     .bind(entry_point)
-    .bind(&graph_json)
+    .bind(&encoded)
     .bind(node_count)
     .bind(edge_count)
-    .fetch_one(&state.db)
+    .fetch_one(&mut *tx)
     .await?;
 
-    // 如果是调用关系图，保存调用关系到 call_relations 表
+    // 如果是调用关系图，批量保存调用关系到 call_relations 表
+    let mut relations = Vec::new();
     if graph_type == "call_graph" {
         if let Some(edges) = graph_data["edges"].as_array() {
             for edge in edges {
@@ -524,32 +1005,41 @@ async fn save_code_graph_to_db(
                 let line = edge["line"].as_i64().unwrap_or(0);
 
                 if !from.is_empty() && !to.is_empty() {
-                    sqlx::query(
-                        "INSERT INTO call_relations (project_id, graph_id, caller_function, callee_function, file_path, line_number)
-                         VALUES (?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(project_id)
-                    .bind(graph_id)
-                    .bind(from)
-                    .bind(to)
-                    .bind(file_path)
-                    .bind(line)
-                    .execute(&state.db)
-                    .await?;
+                    relations.push((from, to, file_path, line));
                 }
             }
         }
     }
 
-    Ok(graph_id)
+    for batch in relations.chunks(CALL_RELATION_BATCH) {
+        let mut query = sqlx::QueryBuilder::<sqlx::Sqlite>::new(
+            "INSERT INTO call_relations (project_id, graph_id, caller_function, callee_function, file_path, line_number) ",
+        );
+        query.push_values(batch, |mut row, &(from, to, file_path, line)| {
+            row.push_bind(project_id)
+                .push_bind(graph_id)
+                .push_bind(from)
+                .push_bind(to)
+                .push_bind(file_path)
+                .push_bind(line);
+        });
+        query.build().execute(&mut *tx).await?;
+    }
+
+    tx.commit().await?;
+    Ok(SavedGraph {
+        id: graph_id,
+        relations: relations.len(),
+    })
 }
 
 pub async fn get_code_structure(
     state: web::Data<AppState>,
     path: web::Path<String>,
     query: web::Query<std::collections::HashMap<String, String>>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let file_path = path.into_inner();
+    let kind_filter = parse_kind_filter(query.get("kind")).map_err(AppError::invalid_input)?;
 
     tracing::info!(
         "[AST:get_code_structure] 获取代码结构 - file_path: {}, project_id: {:?}",
@@ -564,7 +1054,7 @@ pub async fn get_code_structure(
         }
     }
 
-    let mut engine = state.ast_engine.lock().await;
+    let engine = state.ast_engine.read().await;
 
     let structure = match engine.get_file_structure(&file_path) {
         Ok(structure) => {
@@ -574,33 +1064,44 @@ pub async fn get_code_structure(
         Err(e) => {
             // 没有缓存，返回空结果
             tracing::warn!("[AST:get_code_structure] 未找到 AST 缓存: {}", e);
-            return HttpResponse::Ok().json(vec![] as Vec<Symbol>);
+            return Ok(HttpResponse::Ok().json(vec![] as Vec<Symbol>));
         }
     };
 
     let symbols: Vec<Symbol> = structure
         .iter()
+        .filter(|s| kind_filter.is_none_or(|kind| s.kind == kind))
         .map(|s| Symbol {
             name: s.name.clone(),
             kind: format!("{:?}", s.kind),
             file_path: s.file_path.clone(),
             line: s.line as usize,
+            column: s.column as usize,
+            end_column: s.end_column as usize,
+            language: s.language.clone(),
         })
         .collect();
 
-    HttpResponse::Ok().json(symbols)
+    Ok(HttpResponse::Ok().json(symbols))
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct KnowledgeGraphRequest {
     pub limit: Option<usize>,
+    /// 仅包含指定语言的符号
+    pub language: Option<String>,
     pub project_id: Option<i64>,
     pub project_path: Option<String>,
+    /// 是否保存图谱到数据库，需要提供 project_id
+    pub save_graph: Option<bool>,
 }
 
 #[derive(Serialize)]
 pub struct KnowledgeGraphResponse {
     pub graph: GraphData,
+    /// 保存到数据库时的图谱 ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph_id: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -625,6 +1126,37 @@ pub struct GraphEdge {
     pub label: Option<String>,
     #[serde(rename = "type")]
     pub edge_type: String,
+    /// 调用边的调用次数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<usize>,
+}
+
+/// 按 (source, target, type) 去重的边集合，重复的调用边累计为权重
+#[derive(Default)]
+struct EdgeSet {
+    edges: Vec<GraphEdge>,
+    index: std::collections::HashMap<(String, String, String), usize>,
+}
+
+impl EdgeSet {
+    fn add(&mut self, source: &str, target: &str, label: &str, edge_type: &str, count: usize) {
+        let key = (source.to_string(), target.to_string(), edge_type.to_string());
+        if let Some(&pos) = self.index.get(&key) {
+            if let Some(weight) = self.edges[pos].weight.as_mut() {
+                *weight += count;
+            }
+            return;
+        }
+        self.index.insert(key, self.edges.len());
+        self.edges.push(GraphEdge {
+            id: format!("edge_{}", self.edges.len()),
+            source: source.to_string(),
+            target: target.to_string(),
+            label: Some(label.to_string()),
+            edge_type: edge_type.to_string(),
+            weight: (edge_type == "call").then_some(count),
+        });
+    }
 }
 
 /// 确保AST引擎已加载指定项目的缓存
@@ -633,16 +1165,14 @@ async fn ensure_cache_loaded(
     project_id: i64,
     project_path: &str,
 ) -> Result<(), String> {
-    // 检查是否已经加载了同一个项目的缓存
-    {
-        let cache_state = state.ast_cache_state.lock().await;
-        if cache_state.current_project_id == Some(project_id)
-            && cache_state.symbol_count > 0 {
-            // 已经加载了同一个项目的有效缓存
-            tracing::info!("Using cached AST data for project {} ({} symbols)",
-                project_id, cache_state.symbol_count);
-            return Ok(());
-        }
+    // 检查是否已经加载了同一个项目的缓存；加载期间持有状态锁，避免并发请求重复从数据库加载
+    let mut cache_state = state.ast_cache_state.lock().await;
+    if cache_state.current_project_id == Some(project_id)
+        && cache_state.symbol_count > 0 {
+        // 已经加载了同一个项目的有效缓存
+        tracing::info!("Using cached AST data for project {} ({} symbols)",
+            project_id, cache_state.symbol_count);
+        return Ok(());
     }
 
     // 需要加载新项目的缓存
@@ -655,20 +1185,17 @@ async fn ensure_cache_loaded(
             tracing::info!("Loaded AST cache from database for project {} ({} files, {} symbols)",
                 project_id, cache_data.index.len(), symbol_count);
 
-            // 设置仓库路径
-            let engine = state.ast_engine.lock().await;
-            engine.use_repository(project_path);
-            drop(engine);
-
-            // 加载缓存数据
-            let engine = state.ast_engine.lock().await;
-            engine.load_from_cache_data(cache_data);
-
-            // 加载后保存到文件系统，以便下次使用
-            let _ = engine.save_cache();
+            // 设置仓库路径并加载缓存数据，加载后保存到文件系统，以便下次使用
+            let repo_path = project_path.to_string();
+            with_engine_blocking(state, move |engine| {
+                engine.use_repository(&repo_path);
+                engine.load_from_cache_data(cache_data);
+                let _ = engine.save_cache();
+            })
+            .await
+            .map_err(|e| e.to_string())?;
 
             // 更新缓存状态
-            let mut cache_state = state.ast_cache_state.lock().await;
             cache_state.current_project_id = Some(project_id);
             cache_state.current_project_path = Some(project_path.to_string());
             cache_state.symbol_count = symbol_count;
@@ -698,7 +1225,7 @@ pub async fn get_knowledge_graph(
         let _ = ensure_cache_loaded(&state, project_id, project_path).await;
     }
 
-    let mut engine = state.ast_engine.lock().await;
+    let engine = state.ast_engine.read().await;
 
     let limit = req.limit.unwrap_or(500);
 
@@ -713,38 +1240,45 @@ pub async fn get_knowledge_graph(
             tracing::info!("No AST cache loaded, returning empty graph: {}", e);
             return HttpResponse::Ok().json(KnowledgeGraphResponse {
                 graph: GraphData { nodes: vec![], edges: vec![] },
+                graph_id: None,
             });
         }
     };
 
-    // 限制节点数量
-    let symbols: Vec<_> = symbols.into_iter().take(limit).collect();
+    // 按语言过滤并限制节点数量
+    let language = parse_language_filter(req.language.as_ref());
+    let symbols: Vec<_> = symbols
+        .into_iter()
+        .filter(|s| language.as_ref().is_none_or(|l| &s.language == l))
+        .take(limit)
+        .collect();
 
     tracing::info!("get_knowledge_graph: using {} symbols (limited from {})", symbols.len(), limit);
 
-    // 创建节点 - 使用唯一 ID (文件路径:符号名:行号)
-    let nodes: Vec<GraphNode> = symbols
-        .iter()
-        .map(|s| {
-            let unique_id = format!("{}:{}:{}", s.file_path, s.name, s.line);
-            GraphNode {
-                id: unique_id.clone(),
-                label: s.name.clone(),
-                node_type: format!("{:?}", s.kind),
-            }
-        })
-        .collect();
-
-    // 构建符号名到节点ID的映射（支持同名符号）
+    // 创建节点 - 使用唯一 ID (文件路径:符号名:行号)，同一行的同名符号只保留第一个
+    // 同时构建符号名到节点ID的映射（支持同名符号）
+    let mut nodes: Vec<GraphNode> = Vec::new();
+    let mut seen_ids = std::collections::HashSet::new();
+    let mut call_site_ids = std::collections::HashSet::new();
     let mut name_to_ids: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
     for s in &symbols {
         let unique_id = format!("{}:{}:{}", s.file_path, s.name, s.line);
-        name_to_ids.entry(s.name.clone()).or_default().push(unique_id);
+        if !seen_ids.insert(unique_id.clone()) {
+            continue;
+        }
+        if s.kind == SymbolKind::MethodCall {
+            call_site_ids.insert(unique_id.clone());
+        }
+        name_to_ids.entry(s.name.clone()).or_default().push(unique_id.clone());
+        nodes.push(GraphNode {
+            id: unique_id,
+            label: s.name.clone(),
+            node_type: format!("{:?}", s.kind),
+        });
     }
 
     // 创建边（基于实际的代码关系）
-    let mut edges = Vec::new();
-    let mut edge_id = 0;
+    let mut edges = EdgeSet::default();
 
     // 按文件分组符号，用于建立包含关系
     let mut file_symbols: std::collections::HashMap<String, Vec<&deepaudit_core::Symbol>> = std::collections::HashMap::new();
@@ -762,41 +1296,32 @@ pub async fn get_knowledge_graph(
                 for parent_class in &symbol.parent_classes {
                     if let Some(parent_ids) = name_to_ids.get(parent_class) {
                         for parent_id in parent_ids {
-                            edges.push(GraphEdge {
-                                id: format!("edge_{}", edge_id),
-                                source: source_id.clone(),
-                                target: parent_id.clone(),
-                                label: Some("extends".to_string()),
-                                edge_type: "inheritance".to_string(),
-                            });
-                            edge_id += 1;
+                            edges.add(&source_id, parent_id, "extends", "inheritance", 1);
                         }
                     }
                 }
 
-                // 查找同一文件中属于这个类的方法
+                // 查找属于这个类的方法：优先使用所属类元数据，其次按源码范围包含关系
                 if let Some(file_syms) = file_symbols.get(&symbol.file_path) {
                     for other in file_syms {
-                        if other.line > symbol.line && other.line < symbol.line + 100 {
-                            match other.kind {
-                                deepaudit_core::SymbolKind::Method | deepaudit_core::SymbolKind::Function => {
-                                    // 检查是否可能是这个类的成员
-                                    let other_code_lower = other.code.to_lowercase();
-                                    let symbol_name_lower = symbol.name.to_lowercase();
-                                    if other_code_lower.contains(&symbol_name_lower) || other.package.contains(&symbol.name) {
-                                        let target_id = format!("{}:{}:{}", other.file_path, other.name, other.line);
-                                        edges.push(GraphEdge {
-                                            id: format!("edge_{}", edge_id),
-                                            source: source_id.clone(),
-                                            target: target_id,
-                                            label: Some("contains".to_string()),
-                                            edge_type: "containment".to_string(),
-                                        });
-                                        edge_id += 1;
-                                    }
-                                }
-                                _ => {}
-                            }
+                        if !matches!(
+                            other.kind,
+                            deepaudit_core::SymbolKind::Method | deepaudit_core::SymbolKind::Function
+                        ) {
+                            continue;
+                        }
+                        let owner = other.metadata.get("ownerClass")
+                            .or_else(|| other.metadata.get("callerClass"))
+                            .and_then(|v| v.as_str());
+                        let is_member = match owner {
+                            Some(owner) => owner == symbol.name,
+                            None => other.start_line >= symbol.start_line
+                                && other.end_line <= symbol.end_line
+                                && other.start_line > symbol.start_line,
+                        };
+                        if is_member {
+                            let target_id = format!("{}:{}:{}", other.file_path, other.name, other.line);
+                            edges.add(&source_id, &target_id, "contains", "containment", 1);
                         }
                     }
                 }
@@ -812,14 +1337,7 @@ pub async fn get_knowledge_graph(
                 if let Some(caller_name) = caller {
                     if let Some(caller_ids) = name_to_ids.get(caller_name) {
                         for caller_id in caller_ids {
-                            edges.push(GraphEdge {
-                                id: format!("edge_{}", edge_id),
-                                source: caller_id.clone(),
-                                target: source_id.clone(),
-                                label: Some("calls".to_string()),
-                                edge_type: "call".to_string(),
-                            });
-                            edge_id += 1;
+                            edges.add(caller_id, &source_id, "calls", "call", 1);
                         }
                     }
                 }
@@ -831,30 +1349,41 @@ pub async fn get_knowledge_graph(
                 for (other_name, other_ids) in &name_to_ids {
                     if other_name != &symbol.name {
                         // 检查代码中是否包含对这个函数/方法的引用
+                        // 调用点节点已通过调用者元数据连接，这里只连接定义，权重为调用次数
                         let pattern = format!("{}(", other_name);
-                        if symbol.code.contains(&pattern) {
-                            for target_id in other_ids {
-                                edges.push(GraphEdge {
-                                    id: format!("edge_{}", edge_id),
-                                    source: source_id.clone(),
-                                    target: target_id.clone(),
-                                    label: Some("calls".to_string()),
-                                    edge_type: "call".to_string(),
-                                });
-                                edge_id += 1;
+                        let count = symbol.code.matches(&pattern).count();
+                        if count > 0 {
+                            for target_id in other_ids.iter().filter(|id| !call_site_ids.contains(*id)) {
+                                edges.add(&source_id, target_id, "calls", "call", count);
                             }
                         }
                     }
                 }
             }
+        }
+    }
 
-            _ => {}
+    drop(engine);
+    let graph = GraphData { nodes, edges: edges.edges };
+
+    let mut graph_id = None;
+    if let (true, Some(project_id)) = (req.save_graph.unwrap_or(false), req.project_id) {
+        let saved = match serde_json::to_value(&graph) {
+            Ok(graph_data) => save_code_graph_to_db(&state, project_id, "knowledge_graph", None, &graph_data).await,
+            Err(e) => Err(e.into()),
+        };
+        match saved {
+            Ok(saved) => {
+                tracing::info!("Saved knowledge graph to database: id={}", saved.id);
+                graph_id = Some(saved.id);
+            }
+            Err(e) => {
+                tracing::error!("Failed to save knowledge graph: {}", e);
+            }
         }
     }
 
-    HttpResponse::Ok().json(KnowledgeGraphResponse {
-        graph: GraphData { nodes, edges },
-    })
+    HttpResponse::Ok().json(KnowledgeGraphResponse { graph, graph_id })
 }
 
 /// 获取项目的 AST 索引历史
@@ -862,11 +1391,11 @@ pub async fn get_index_history(
     state: web::Data<AppState>,
     path: web::Path<i64>,
     query: web::Query<GetHistoryRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let project_id = path.into_inner();
     let limit = query.limit.unwrap_or(20) as i64;
 
-    let indices = match sqlx::query_as::<_, (i64, String, i64, i64, String)>(
+    let indices = sqlx::query_as::<_, (i64, String, i64, i64, String)>(
         "SELECT id, index_version, total_symbols, total_files, datetime(created_at) as created_at
          FROM ast_indices
          WHERE project_id = ?
@@ -877,15 +1406,7 @@ pub async fn get_index_history(
     .bind(limit)
     .fetch_all(&state.db)
     .await
-    {
-        Ok(indices) => indices,
-        Err(e) => {
-            tracing::error!("Failed to fetch index history: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch index history: {}", e)
-            }));
-        }
-    };
+    .map_err(|e| AppError::database("Failed to fetch index history", e))?;
 
     let history: Vec<AstIndexHistory> = indices
         .into_iter()
@@ -898,7 +1419,7 @@ pub async fn get_index_history(
         })
         .collect();
 
-    HttpResponse::Ok().json(history)
+    Ok(HttpResponse::Ok().json(history))
 }
 
 /// 获取项目的代码图谱历史
@@ -906,51 +1427,243 @@ pub async fn get_graph_history(
     state: web::Data<AppState>,
     path: web::Path<i64>,
     query: web::Query<GetHistoryRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let project_id = path.into_inner();
     let limit = query.limit.unwrap_or(20) as i64;
 
-    let graphs = match sqlx::query_as::<_, (i64, String, Option<String>, i64, i64, String)>(
-        "SELECT id, graph_type, entry_point, node_count, edge_count, datetime(created_at) as created_at
+    let graphs = sqlx::query_as::<_, (i64, String, Option<String>, i64, i64, String, Option<Vec<u8>>)>(
+        "SELECT id, graph_type, entry_point, node_count, edge_count, datetime(created_at) as created_at,
+                CASE WHEN ? THEN graph_data END
          FROM code_graphs
-         WHERE project_id = ?
+         WHERE project_id = ? AND (? IS NULL OR graph_type = ?)
          ORDER BY created_at DESC
          LIMIT ?"
     )
+    .bind(query.include_graph)
     .bind(project_id)
+    .bind(&query.graph_type)
+    .bind(&query.graph_type)
     .bind(limit)
     .fetch_all(&state.db)
     .await
-    {
-        Ok(graphs) => graphs,
-        Err(e) => {
-            tracing::error!("Failed to fetch graph history: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch graph history: {}", e)
-            }));
-        }
-    };
+    .map_err(|e| AppError::database("Failed to fetch graph history", e))?;
 
-    let history: Vec<CodeGraphHistory> = graphs
+    let history = graphs
         .into_iter()
-        .map(|(id, graph_type, entry_point, node_count, edge_count, created_at)| CodeGraphHistory {
-            id,
-            graph_type,
-            entry_point,
-            node_count,
-            edge_count,
-            created_at,
+        .map(|(id, graph_type, entry_point, node_count, edge_count, created_at, graph_data)| {
+            let graph = graph_data
+                .map(|data| crate::index_codec::decode_graph(&data))
+                .transpose()
+                .map_err(|e| AppError::internal(format!("Failed to decode graph {}", id), e))?;
+            Ok(CodeGraphHistory {
+                id,
+                graph_type,
+                entry_point,
+                node_count,
+                edge_count,
+                created_at,
+                graph,
+            })
         })
-        .collect();
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(HttpResponse::Ok().json(history))
+}
 
-    HttpResponse::Ok().json(history)
+/// 比较两个已保存的同类型图谱，列出新增与删除的节点和边
+///
+/// 节点按 `id` 匹配；边按 (源, 目标, 类型) 匹配，调用图的 `from`/`to` 视为源和目标、类型为 `call`
+pub async fn diff_code_graphs(
+    state: web::Data<AppState>,
+    req: web::Json<GraphDiffRequest>,
+) -> Result<HttpResponse, AppError> {
+    let (type_a, graph_a) = load_code_graph(&state, req.graph_id_a).await?;
+    let (type_b, graph_b) = load_code_graph(&state, req.graph_id_b).await?;
+    if type_a != type_b {
+        return Err(AppError::invalid_input(format!(
+            "Cannot compare a {} with a {}",
+            type_a, type_b
+        )));
+    }
+
+    let node_key = |node: &serde_json::Value| node["id"].as_str().map(str::to_string);
+    let (added_nodes, removed_nodes) = diff_graph_items(&graph_a["nodes"], &graph_b["nodes"], node_key);
+    let (added_edges, removed_edges) = diff_graph_items(&graph_a["edges"], &graph_b["edges"], graph_edge_key);
+
+    tracing::info!(
+        "Graph diff {} -> {}: +{} -{} nodes, +{} -{} edges",
+        req.graph_id_a,
+        req.graph_id_b,
+        added_nodes.len(),
+        removed_nodes.len(),
+        added_edges.len(),
+        removed_edges.len()
+    );
+
+    Ok(HttpResponse::Ok().json(GraphDiffResponse {
+        graph_id_a: req.graph_id_a,
+        graph_id_b: req.graph_id_b,
+        graph_type: type_a,
+        added_nodes,
+        removed_nodes,
+        added_edges,
+        removed_edges,
+    }))
+}
+
+/// 读取已保存的图谱类型与内容
+async fn load_code_graph(state: &AppState, graph_id: i64) -> Result<(String, serde_json::Value), AppError> {
+    let (graph_type, data) = sqlx::query_as::<_, (String, Vec<u8>)>(
+        "SELECT graph_type, graph_data FROM code_graphs WHERE id = ?"
+    )
+    .bind(graph_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to load code graph", e))?
+    .ok_or_else(|| AppError::new(ErrorCode::GraphNotFound, format!("Code graph {} not found", graph_id)))?;
+    let graph = crate::index_codec::decode_graph(&data)
+        .map_err(|e| AppError::internal(format!("Failed to decode graph {}", graph_id), e))?;
+    Ok((graph_type, graph))
+}
+
+/// 边的匹配键：知识图谱为 (source, target, type)，调用图为 (from, to, "call")
+fn graph_edge_key(edge: &serde_json::Value) -> Option<String> {
+    let source = edge["source"].as_str().or_else(|| edge["from"].as_str())?;
+    let target = edge["target"].as_str().or_else(|| edge["to"].as_str())?;
+    let edge_type = edge["type"].as_str().unwrap_or("call");
+    Some(format!("{}\u{0}{}\u{0}{}", source, target, edge_type))
+}
+
+/// 按匹配键比较两组节点或边，返回 (仅在 b 中的项, 仅在 a 中的项)；同键的重复项只取第一个，无法取键的项忽略
+fn diff_graph_items(
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+    key: impl Fn(&serde_json::Value) -> Option<String>,
+) -> (Vec<serde_json::Value>, Vec<serde_json::Value>) {
+    fn keyed<'a>(
+        items: &'a serde_json::Value,
+        key: &impl Fn(&serde_json::Value) -> Option<String>,
+    ) -> Vec<(String, &'a serde_json::Value)> {
+        let mut seen = std::collections::HashSet::new();
+        items
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| key(item).map(|k| (k, item)))
+            .filter(|(k, _)| seen.insert(k.clone()))
+            .collect()
+    }
+    let (a, b) = (keyed(a, &key), keyed(b, &key));
+    let a_keys: std::collections::HashSet<&String> = a.iter().map(|(k, _)| k).collect();
+    let b_keys: std::collections::HashSet<&String> = b.iter().map(|(k, _)| k).collect();
+    let added = b.iter().filter(|(k, _)| !a_keys.contains(k)).map(|(_, item)| (*item).clone()).collect();
+    let removed = a.iter().filter(|(k, _)| !b_keys.contains(k)).map(|(_, item)| (*item).clone()).collect();
+    (added, removed)
+}
+
+/// 清理项目的历史索引和图谱，只保留最近的 keep_last 个版本
+pub async fn prune_ast_history(
+    state: web::Data<AppState>,
+    req: web::Json<PruneHistoryRequest>,
+) -> Result<HttpResponse, AppError> {
+    let result = prune_history_in_db(&state, req.project_id, req.keep_last)
+        .await
+        .map_err(|e| AppError::database("Failed to prune AST history", e))?;
+    tracing::info!(
+        "Pruned AST history for project {}: {} indices, {} graphs",
+        req.project_id,
+        result.indices_deleted,
+        result.graphs_deleted
+    );
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// 在事务中删除超出保留数量的索引、符号、图谱及调用关系
+async fn prune_history_in_db(
+    state: &AppState,
+    project_id: i64,
+    keep_last: usize,
+) -> Result<PruneHistoryResponse, sqlx::Error> {
+    let keep_last = keep_last as i64;
+    let mut tx = state.db.begin().await?;
+
+    // 先删除引用旧索引的符号，再删除索引本身
+    let symbols_deleted = sqlx::query(
+        "DELETE FROM symbols
+         WHERE project_id = ?
+           AND ast_index_id NOT IN (
+               SELECT id FROM ast_indices WHERE project_id = ?
+               ORDER BY created_at DESC, id DESC LIMIT ?
+           )"
+    )
+    .bind(project_id)
+    .bind(project_id)
+    .bind(keep_last)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let indices_deleted = sqlx::query(
+        "DELETE FROM ast_indices
+         WHERE project_id = ?
+           AND id NOT IN (
+               SELECT id FROM ast_indices WHERE project_id = ?
+               ORDER BY created_at DESC, id DESC LIMIT ?
+           )"
+    )
+    .bind(project_id)
+    .bind(project_id)
+    .bind(keep_last)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    // 先删除引用旧图谱的调用关系，再删除图谱本身
+    let call_relations_deleted = sqlx::query(
+        "DELETE FROM call_relations
+         WHERE project_id = ?
+           AND graph_id NOT IN (
+               SELECT id FROM code_graphs WHERE project_id = ?
+               ORDER BY created_at DESC, id DESC LIMIT ?
+           )"
+    )
+    .bind(project_id)
+    .bind(project_id)
+    .bind(keep_last)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let graphs_deleted = sqlx::query(
+        "DELETE FROM code_graphs
+         WHERE project_id = ?
+           AND id NOT IN (
+               SELECT id FROM code_graphs WHERE project_id = ?
+               ORDER BY created_at DESC, id DESC LIMIT ?
+           )"
+    )
+    .bind(project_id)
+    .bind(project_id)
+    .bind(keep_last)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+
+    Ok(PruneHistoryResponse {
+        indices_deleted,
+        symbols_deleted,
+        graphs_deleted,
+        call_relations_deleted,
+    })
 }
 
 /// 获取 AST 上下文
 pub async fn get_ast_context(
     state: web::Data<AppState>,
     req: web::Json<AstContextRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     tracing::info!(
         "[AST:get_ast_context] 获取AST上下文 - file_path: {}, line_range: {:?}",
         req.file_path,
@@ -958,99 +1671,199 @@ pub async fn get_ast_context(
     );
 
     // 读取文件内容
-    let code_snippet = match std::fs::read_to_string(&req.file_path) {
-        Ok(content) => {
-            // 提取指定行范围
-            let lines: Vec<&str> = content.lines().collect();
-            let start = if let Some(&s) = req.line_range.first() { s - 1 } else { 0 };
-            let end = if let Some(&e) = req.line_range.get(1) { e } else { lines.len() };
-
-            if start >= lines.len() {
-                return HttpResponse::BadRequest().json(serde_json::json!({
-                    "error": format!("Invalid line range: start {} exceeds file length {}", start + 1, lines.len())
-                }));
-            }
-
-            let actual_end = end.min(lines.len());
-            let range_lines = &lines[start..actual_end];
-            range_lines.join("\n")
+    let content = std::fs::read_to_string(&req.file_path)
+        .map_err(|e| AppError::new(ErrorCode::FileNotFound, "Failed to read file").with_detail(e))?;
+
+    // 提取指定行范围
+    let code_snippet = {
+        let lines: Vec<&str> = content.lines().collect();
+        let start = if let Some(&s) = req.line_range.first() { s - 1 } else { 0 };
+        let end = if let Some(&e) = req.line_range.get(1) { e } else { lines.len() };
+
+        if start >= lines.len() {
+            return Err(AppError::invalid_input(format!(
+                "Invalid line range: start {} exceeds file length {}",
+                start + 1,
+                lines.len()
+            )));
         }
-        Err(e) => {
-            tracing::error!("[AST:get_ast_context] 读取文件失败: {}", e);
-            return HttpResponse::NotFound().json(serde_json::json!({
-                "error": format!("Failed to read file: {}", e)
-            }));
-        }
-    };
 
-    // 获取AST引擎
-    let engine = state.ast_engine.lock().await;
+        let actual_end = end.min(lines.len());
+        lines[start..actual_end].join("\n")
+    };
 
-    // 尝试从数据库加载索引（如果提供了project_id或project_path）
-    if let Some(project_id) = req.project_id {
-        if let Some(project_path) = &req.project_path {
-            match load_ast_index_from_db(&state, project_id, project_path).await {
-                Ok(Some(cache_data)) => {
-                    tracing::info!("[AST:get_ast_context] 从数据库加载了索引");
-                    engine.load_from_cache_data(cache_data);
-                }
-                Ok(None) => {
-                    tracing::info!("[AST:get_ast_context] 数据库中未找到索引，使用现有缓存");
-                }
-                Err(e) => {
-                    tracing::warn!("[AST:get_ast_context] 加载索引失败: {}", e);
-                }
-            }
+    // 确保缓存已加载：同一项目复用内存中的索引，仅在切换项目或缓存缺失时从数据库加载
+    if let (Some(project_id), Some(project_path)) = (req.project_id, &req.project_path) {
+        if let Err(e) = ensure_cache_loaded(&state, project_id, project_path).await {
+            tracing::info!("[AST:get_ast_context] {}，使用现有缓存", e);
         }
     }
 
-    // 查找函数名 - 通过搜索符号来确定
+    // 获取AST引擎
+    let engine = state.ast_engine.read().await;
+
     let start_line = if let Some(&s) = req.line_range.first() { s } else { 1 };
-    let function_name: Option<String> = None;  // 简化实现，暂不查找函数名
+    let end_line = if let Some(&e) = req.line_range.get(1) { e } else { start_line };
+    let context = collect_ast_context(
+        &engine,
+        &req.file_path,
+        start_line,
+        end_line,
+        code_snippet,
+        req.include_callers,
+        req.include_callees,
+    );
+    drop(engine);
 
-    // 收集调用者 - 使用find_call_sites方法
+    let response = AstContextResponse {
+        file_path: req.file_path.clone(),
+        line_range: req.line_range.clone(),
+        context,
+    };
+
+    tracing::info!(
+        "[AST:get_ast_context] 返回上下文 - 函数: {:?}, 调用者: {}, 被调用者: {}, 符号: {}",
+        response.context.function_name,
+        response.context.callers.len(),
+        response.context.callees.len(),
+        response.context.symbols.len()
+    );
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 按符号名获取 AST 上下文，同名的定义全部返回
+pub async fn get_symbol_context(
+    state: web::Data<AppState>,
+    req: web::Json<SymbolContextRequest>,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    tracing::info!(
+        "[AST:get_symbol_context] 获取符号上下文 - symbol: {}, project_id: {}",
+        req.symbol_name,
+        req.project_id
+    );
+
+    if let Err(e) = ensure_cache_loaded(&state, req.project_id, &req.project_path).await {
+        tracing::info!("[AST:get_symbol_context] {}，使用现有缓存", e);
+    }
+
+    let engine = state.ast_engine.read().await;
+    // search_symbols 为模糊匹配，这里只保留同名定义，排除调用点
+    let mut definitions: Vec<_> = engine
+        .search_symbols(&req.symbol_name)
+        .map_err(|e| AppError::new(ErrorCode::IndexNotBuilt, "Build the AST index first").with_detail(e))?
+        .into_iter()
+        .filter(|s| s.name == req.symbol_name && !matches!(s.kind, SymbolKind::MethodCall))
+        .collect();
+    definitions.sort_by(|a, b| (&a.file_path, a.start_line).cmp(&(&b.file_path, b.start_line)));
+
+    let root = std::path::Path::new(&req.project_path);
+    let mut matches = Vec::with_capacity(definitions.len());
+    for symbol in definitions {
+        let start_line = (if symbol.start_line > 0 { symbol.start_line } else { symbol.line } as usize).max(1);
+        let end_line = (symbol.end_line as usize).max(start_line);
+
+        // 从源文件截取符号行范围，文件不可读时退回索引中保存的代码
+        let code_snippet = std::fs::read_to_string(root.join(&symbol.file_path))
+            .ok()
+            .map(|content| {
+                content
+                    .lines()
+                    .skip(start_line - 1)
+                    .take(end_line - start_line + 1)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .filter(|snippet| !snippet.is_empty())
+            .unwrap_or_else(|| symbol.code.clone());
+
+        let context = collect_ast_context(
+            &engine,
+            &symbol.file_path,
+            start_line,
+            end_line,
+            code_snippet,
+            req.include_callers,
+            req.include_callees,
+        );
+        matches.push(SymbolContextMatch {
+            file_path: symbol.file_path,
+            kind: format!("{:?}", symbol.kind),
+            line_range: vec![start_line, end_line],
+            context,
+        });
+    }
+    drop(engine);
+
+    tracing::info!(
+        "[AST:get_symbol_context] 返回上下文 - symbol: {}, 匹配: {}",
+        req.symbol_name,
+        matches.len()
+    );
+
+    Ok(HttpResponse::Ok().json(SymbolContextResponse {
+        symbol_name: req.symbol_name,
+        matches,
+    }))
+}
+
+/// 收集行范围 [start_line, end_line] 的 AST 上下文：所属函数、调用者、被调用者与范围内的符号
+fn collect_ast_context(
+    engine: &ASTEngine,
+    file_path: &str,
+    start_line: usize,
+    end_line: usize,
+    code_snippet: String,
+    include_callers: bool,
+    include_callees: bool,
+) -> AstContextData {
+    // 查找包含请求行范围的最内层函数/方法
+    let enclosing = engine
+        .find_enclosing_function(file_path, start_line as u32, end_line as u32)
+        .ok()
+        .flatten();
+    let function_name = enclosing.as_ref().map(|f| f.name.clone());
+
+    // 收集调用者：项目中调用该函数的位置
     let mut callers = Vec::new();
-    if req.include_callers {
-        // 由于没有具体的函数名，我们搜索文件中的所有函数符号
-        if let Ok(all_symbols) = engine.get_all_symbols() {
-            for symbol in all_symbols {
-                // 只查找同一文件中的函数符号
-                if symbol.file_path == req.file_path {
-                    // 使用matches!宏检查SymbolKind
-                    if matches!(symbol.kind, deepaudit_core::SymbolKind::Function) {
-                        // 尝试查找调用该函数的位置
-                        if let Ok(call_sites) = engine.find_call_sites(&symbol.name) {
-                            for site in call_sites {
-                                callers.push(CallerInfo {
-                                    file_path: site.file_path.clone(),
-                                    function_name: site.name.clone(),
-                                    line: site.line as usize,  // u32转usize
-                                });
-                            }
-                        }
-                    }
+    if include_callers {
+        if let Some(name) = &function_name {
+            if let Ok(call_sites) = engine.find_call_sites(name) {
+                for site in call_sites {
+                    let caller = site
+                        .metadata
+                        .get("callerMethod")
+                        .or_else(|| site.metadata.get("callerFunction"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("<module>")
+                        .to_string();
+                    callers.push(CallerInfo {
+                        file_path: site.file_path.clone(),
+                        function_name: caller,
+                        line: site.line as usize,
+                    });
                 }
             }
         }
     }
 
-    // 收集被调用者 - 由于没有具体的函数调用分析，简化为查找文件中的符号
+    // 收集被调用者：函数体（无所属函数时为请求范围）内的调用点
     let mut callees = Vec::new();
-    if req.include_callees {
-        // 查找文件中的函数符号作为潜在的被调用者
-        if let Ok(all_symbols) = engine.get_all_symbols() {
-            for symbol in all_symbols {
-                if symbol.file_path == req.file_path {
-                    if matches!(symbol.kind, deepaudit_core::SymbolKind::Function) {
-                        // 只添加在目标行之后的函数作为潜在的被调用者
-                        if symbol.line as usize >= start_line {
-                            callees.push(CalleeInfo {
-                                name: symbol.name.clone(),
-                                file_path: symbol.file_path.clone(),
-                                line: symbol.line as usize,
-                            });
-                        }
-                    }
+    if include_callees {
+        let (body_start, body_end) = match &enclosing {
+            Some(f) => (f.start_line, f.end_line),
+            None => (start_line as u32, end_line as u32),
+        };
+        if let Ok(calls) = engine.find_calls_in_range(file_path, body_start, body_end) {
+            let mut seen = std::collections::HashSet::new();
+            for call in calls {
+                if seen.insert((call.name.clone(), call.line)) {
+                    callees.push(CalleeInfo {
+                        name: call.name,
+                        file_path: call.file_path,
+                        line: call.line as usize,
+                    });
                 }
             }
         }
@@ -1058,45 +1871,301 @@ pub async fn get_ast_context(
 
     // 获取指定行范围内的符号
     let mut symbols = Vec::new();
-    let end_line = if let Some(&e) = req.line_range.get(1) { e } else { start_line };
 
     if let Ok(all_symbols) = engine.get_all_symbols() {
         for symbol in all_symbols {
-            if symbol.file_path == req.file_path {
+            if symbol.file_path == file_path {
                 let symbol_line = symbol.line as usize;
                 if symbol_line >= start_line && symbol_line <= end_line {
                     symbols.push(ContextSymbol {
                         name: symbol.name,
                         kind: format!("{:?}", symbol.kind),
                         line: symbol_line,
-                        column: 0,  // Symbol没有column字段，使用默认值0
+                        column: symbol.column as usize,
+                        end_column: symbol.end_column as usize,
                     });
                 }
             }
         }
     }
 
+    AstContextData {
+        code_snippet,
+        function_name,
+        callers,
+        callees,
+        symbols,
+    }
+}
+
+/// 列出从未被调用的函数/方法（排除入口点与公开/导出符号）
+///
+/// 被忽略文件中的调用仍计入调用关系，只是其中的候选不会返回
+pub async fn get_dead_code(
+    state: web::Data<AppState>,
+    req: web::Json<DeadCodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+
+    let project_path = sqlx::query_scalar::<_, String>("SELECT path FROM projects WHERE id = ?")
+        .bind(req.project_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::database("Failed to load project", e))?
+        .ok_or_else(|| {
+            AppError::new(ErrorCode::ProjectNotFound, format!("Project {} not found", req.project_id))
+        })?;
+
+    let ignore = deepaudit_core::rules::model::PathFilter {
+        include: Vec::new(),
+        exclude: req.ignore_patterns.clone(),
+    }
+    .compile()
+    .map_err(AppError::invalid_input)?;
+
+    ensure_cache_loaded(&state, req.project_id, &project_path)
+        .await
+        .map_err(|e| AppError::new(ErrorCode::IndexNotBuilt, "Build the AST index first").with_detail(e))?;
+
+    // 已保存的调用图中的被调用方，补充索引中的调用点
+    let called_names: std::collections::HashSet<String> = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT callee_function FROM call_relations WHERE project_id = ?"
+    )
+    .bind(req.project_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default()
+    .into_iter()
+    .collect();
+
+    let engine = state.ast_engine.read().await;
+    let symbols = engine
+        .get_all_symbols()
+        .map_err(|e| AppError::new(ErrorCode::IndexNotBuilt, "Failed to read symbols").with_detail(e))?;
     drop(engine);
 
-    let response = AstContextResponse {
-        file_path: req.file_path.clone(),
-        line_range: req.line_range.clone(),
-        context: AstContextData {
-            code_snippet,
-            function_name: function_name.clone(),
-            callers,
-            callees,
-            symbols,
-        },
-    };
+    let total_functions = symbols
+        .iter()
+        .filter(|s| matches!(s.kind, SymbolKind::Function | SymbolKind::Method))
+        .count();
 
-    tracing::info!(
-        "[AST:get_ast_context] 返回上下文 - 函数: {:?}, 调用者: {}, 被调用者: {}, 符号: {}",
-        function_name,
-        response.context.callers.len(),
-        response.context.callees.len(),
-        response.context.symbols.len()
-    );
+    let root = std::path::Path::new(&project_path);
+    let candidates = deepaudit_core::ast::dead_code::find_dead_code(&symbols, &called_names, req.include_exported)
+        .into_iter()
+        .filter(|s| {
+            let path = std::path::Path::new(&s.file_path);
+            ignore.is_match(path.strip_prefix(root).unwrap_or(path))
+        })
+        .map(Into::into)
+        .collect();
 
-    HttpResponse::Ok().json(response)
+    Ok(HttpResponse::Ok().json(DeadCodeResponse {
+        project_id: req.project_id,
+        total_functions,
+        candidates,
+        limitations: deepaudit_core::ast::dead_code::DEAD_CODE_LIMITATIONS.to_vec(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_state;
+
+    fn context_request(file: &std::path::Path, project_id: i64, project_path: &str) -> web::Json<AstContextRequest> {
+        web::Json(AstContextRequest {
+            file_path: file.to_str().unwrap().to_string(),
+            line_range: vec![1, 2],
+            include_callers: true,
+            include_callees: true,
+            project_id: Some(project_id),
+            project_path: Some(project_path.to_string()),
+        })
+    }
+
+    #[actix_web::test]
+    async fn consecutive_context_requests_load_index_once() {
+        let (dir, state) = test_state().await;
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        let file = project.join("app.py");
+        std::fs::write(&file, "def helper():\n    return 1\n\n\ndef main():\n    return helper()\n").unwrap();
+        let project_path = project.to_str().unwrap();
+
+        let engine = ASTEngine::new(dir.path().join("scan_cache").to_str().unwrap());
+        engine.use_repository(project_path);
+        engine.scan_project(project_path).unwrap();
+        let symbols = engine.get_all_symbols().unwrap();
+        assert!(symbols.len() > 1);
+
+        let project_id: i64 = sqlx::query_scalar("INSERT INTO projects (uuid, name, path) VALUES (?, ?, ?) RETURNING id")
+            .bind(Uuid::new_v4().to_string())
+            .bind("project")
+            .bind(project_path)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        save_ast_index_to_db(&state, project_id, project_path, 1, &symbols).await.unwrap();
+
+        get_ast_context(state.clone(), context_request(&file, project_id, project_path)).await.unwrap();
+        {
+            let cache_state = state.ast_cache_state.lock().await;
+            assert_eq!(cache_state.current_project_id, Some(project_id));
+            assert_eq!(cache_state.symbol_count, symbols.len());
+        }
+
+        // 替换数据库中的索引：第二次请求若再次从数据库加载，内存中的符号数会随之改变
+        let reduced = crate::index_codec::encode_symbols(&symbols[..1]).unwrap();
+        sqlx::query("UPDATE ast_indices SET index_data = ?, total_symbols = 1 WHERE project_id = ?")
+            .bind(reduced)
+            .bind(project_id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+
+        get_ast_context(state.clone(), context_request(&file, project_id, project_path)).await.unwrap();
+        assert_eq!(state.ast_cache_state.lock().await.symbol_count, symbols.len());
+        assert_eq!(state.ast_engine.read().await.get_all_symbols().unwrap().len(), symbols.len());
+    }
+
+    /// 调用处理函数并解析 JSON 响应体
+    async fn response_json(responder: impl Responder) -> serde_json::Value {
+        let request = actix_web::test::TestRequest::default().to_http_request();
+        let response = responder.respond_to(&request);
+        let Ok(body) = actix_web::body::to_bytes(response.into_body()).await else {
+            panic!("Failed to read response body");
+        };
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn repeated_calls_collapse_into_one_weighted_edge() {
+        let (dir, state) = test_state().await;
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(
+            project.join("app.py"),
+            "def b():\n    return 1\n\n\ndef a():\n    b()\n    b()\n    return b()\n",
+        )
+        .unwrap();
+        {
+            let engine = state.ast_engine.read().await;
+            engine.use_repository(project.to_str().unwrap());
+            engine.scan_project(project.to_str().unwrap()).unwrap();
+        }
+
+        let request = KnowledgeGraphRequest {
+            limit: None,
+            language: None,
+            project_id: None,
+            project_path: None,
+            save_graph: None,
+        };
+        let graph = response_json(get_knowledge_graph(state.clone(), web::Json(request)).await).await["graph"].clone();
+
+        let function_id = |name: &str| {
+            graph["nodes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|node| node["label"] == name && node["type"] == "Function")
+                .map(|node| node["id"].as_str().unwrap().to_string())
+                .unwrap()
+        };
+        let (a, b) = (function_id("a"), function_id("b"));
+        let edges: Vec<&serde_json::Value> = graph["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|edge| edge["source"] == a.as_str() && edge["target"] == b.as_str())
+            .collect();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["type"], "call");
+        assert_eq!(edges[0]["weight"], 3);
+
+        let mut keys: Vec<(&str, &str, &str)> = graph["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| (edge["source"].as_str().unwrap(), edge["target"].as_str().unwrap(), edge["type"].as_str().unwrap()))
+            .collect();
+        let total = keys.len();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), total);
+    }
+
+    fn graph_diff_request(graph_id_a: i64, graph_id_b: i64) -> web::Json<GraphDiffRequest> {
+        web::Json(GraphDiffRequest { graph_id_a, graph_id_b })
+    }
+
+    /// 返回 JSON 数组中各项的 id（节点）或 source/target/type（边）
+    fn item_keys(items: &serde_json::Value) -> Vec<String> {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| match item["id"].as_str() {
+                Some(id) => id.to_string(),
+                None => format!("{}->{}:{}", item["source"], item["target"], item["type"]).replace('"', ""),
+            })
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn graph_diff_reports_one_node_and_two_edges() {
+        let (_dir, state) = test_state().await;
+        let project_id: i64 = sqlx::query_scalar("INSERT INTO projects (uuid, name, path) VALUES (?, ?, ?) RETURNING id")
+            .bind(Uuid::new_v4().to_string())
+            .bind("project")
+            .bind("/tmp/project")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+
+        let old = serde_json::json!({
+            "nodes": [{"id": "n1", "label": "a"}, {"id": "n2", "label": "b"}, {"id": "n3", "label": "c"}],
+            "edges": [
+                {"source": "n1", "target": "n2", "type": "calls"},
+                {"source": "n2", "target": "n3", "type": "calls"}
+            ]
+        });
+        // 新增节点 n4，新增边 n3->n4，以及与已有边端点相同但类型不同的 n1->n2 imports
+        let new = serde_json::json!({
+            "nodes": [{"id": "n1", "label": "a"}, {"id": "n2", "label": "b"}, {"id": "n3", "label": "c"}, {"id": "n4", "label": "d"}],
+            "edges": [
+                {"source": "n1", "target": "n2", "type": "calls"},
+                {"source": "n2", "target": "n3", "type": "calls"},
+                {"source": "n3", "target": "n4", "type": "calls"},
+                {"source": "n1", "target": "n2", "type": "imports"}
+            ]
+        });
+        let old_id = save_code_graph_to_db(&state, project_id, "knowledge_graph", None, &old).await.unwrap().id;
+        let new_id = save_code_graph_to_db(&state, project_id, "knowledge_graph", None, &new).await.unwrap().id;
+
+        let diff = response_json(diff_code_graphs(state.clone(), graph_diff_request(old_id, new_id)).await.unwrap()).await;
+        assert_eq!(diff["graph_type"], "knowledge_graph");
+        assert_eq!(item_keys(&diff["added_nodes"]), ["n4"]);
+        assert_eq!(item_keys(&diff["added_edges"]), ["n3->n4:calls", "n1->n2:imports"]);
+        assert!(item_keys(&diff["removed_nodes"]).is_empty());
+        assert!(item_keys(&diff["removed_edges"]).is_empty());
+
+        // 交换顺序后结果对称
+        let diff = response_json(diff_code_graphs(state.clone(), graph_diff_request(new_id, old_id)).await.unwrap()).await;
+        assert_eq!(item_keys(&diff["removed_nodes"]), ["n4"]);
+        assert_eq!(item_keys(&diff["removed_edges"]), ["n3->n4:calls", "n1->n2:imports"]);
+        assert!(item_keys(&diff["added_nodes"]).is_empty());
+        assert!(item_keys(&diff["added_edges"]).is_empty());
+
+        let call_graph = serde_json::json!({"nodes": [], "edges": []});
+        let call_id = save_code_graph_to_db(&state, project_id, "call_graph", Some("main"), &call_graph).await.unwrap().id;
+        let Err(error) = diff_code_graphs(state.clone(), graph_diff_request(old_id, call_id)).await else {
+            panic!("graphs of different types were compared");
+        };
+        assert!(matches!(error.code, ErrorCode::InvalidInput));
+        let Err(error) = diff_code_graphs(state.clone(), graph_diff_request(old_id, new_id + 100)).await else {
+            panic!("missing graph was compared");
+        };
+        assert!(matches!(error.code, ErrorCode::GraphNotFound));
+    }
 }
@@ -0,0 +1,715 @@
+// 差异比较接口：比较默认同步返回结果，也可直接比较请求中的两段文本；大型目录比较在后台执行，通过事件推送进度与结果，可随时取消；
+// 大型比较可分阶段获取：先返回文件列表，再按文件获取差异行；比较结果可导出为补丁，补丁也可应用到目录；
+// 单个文件还可与共同的基准版本做三方比较；Git 仓库的提交可分页列出，用于选择比较的版本，单个文件的历史跟随重命名列出
+
+use actix_web::{web, HttpResponse, Responder};
+use deepaudit_core::diff::{
+    CommitComparisonRequest, CommitQuery, ComparisonCancelled, ComparisonConfig, ComparisonOverview, ComparisonRequest,
+    ComparisonResult, DiffEngine, DiffProgress, GitError, GitIntegration, PatchWriteFailed, ProgressCallback,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::api::{event_stream, ServerEvent};
+use crate::error::{AppError, ErrorCode};
+use crate::state::AppState;
+
+/// 进度事件的最小推送间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 未指定 `background` 时，两侧目录的文件总数或总大小超过任一阈值即在后台比较
+const BACKGROUND_FILE_THRESHOLD: usize = 2000;
+const BACKGROUND_SIZE_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// 比较或读取 Git 仓库失败时的错误：路径不在仓库中、引用无法解析时使用对应的 Git 错误码
+pub(crate) fn comparison_error(message: &str, error: anyhow::Error) -> AppError {
+    let code = match error.downcast_ref::<GitError>() {
+        Some(GitError::NotARepository(_)) => ErrorCode::GitNotARepo,
+        Some(GitError::UnknownRevision(_)) => ErrorCode::GitRefNotFound,
+        None => ErrorCode::ComparisonFailed,
+    };
+    AppError::new(code, message).with_detail(error)
+}
+
+pub fn configure_diff_routes(cfg: &mut web::ServiceConfig) {
+    cfg
+        .route("/compare", web::post().to(compare))
+        .route("/text", web::post().to(compare_text))
+        .route("/three-way", web::post().to(compare_three_way))
+        .route("/commit", web::post().to(compare_commit))
+        .route("/refs", web::get().to(get_refs))
+        .route("/commits", web::get().to(get_commits))
+        .route("/file-history", web::get().to(get_file_history))
+        .route("/blame", web::get().to(get_blame))
+        .route("/patch", web::post().to(export_patch))
+        .route("/html", web::post().to(export_diff_html))
+        .route("/apply-patch", web::post().to(apply_patch))
+        .route("/start", web::post().to(start_comparison))
+        .route("/events", web::get().to(diff_events))
+        .route("/cache", web::delete().to(clear_diff_cache))
+        .route("/{comparison_id}/cancel", web::post().to(cancel_comparison))
+        .route("/{comparison_id}/file", web::get().to(get_file_diff))
+        .route("/{comparison_id}", web::delete().to(close_comparison));
+}
+
+#[derive(Deserialize)]
+pub struct CompareRequest {
+    #[serde(flatten)]
+    pub request: ComparisonRequest,
+    /// 是否在后台执行；缺省时同步返回结果，仅两侧目录的文件数或总大小超过阈值时在后台执行
+    #[serde(default)]
+    pub background: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct CompareTextRequest {
+    pub text_a: String,
+    pub text_b: String,
+    /// 两侧的标识，缺省均为 `text`；不同时结果状态为重命名
+    #[serde(default = "default_text_label")]
+    pub label_a: String,
+    #[serde(default = "default_text_label")]
+    pub label_b: String,
+    #[serde(default)]
+    pub config: ComparisonConfig,
+}
+
+#[derive(Deserialize)]
+pub struct ThreeWayRequest {
+    /// 共同的基准版本
+    pub base_path: String,
+    /// 我方版本
+    pub a_path: String,
+    /// 对方版本
+    pub b_path: String,
+    #[serde(default)]
+    pub config: ComparisonConfig,
+}
+
+fn default_text_label() -> String {
+    "text".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct FileDiffQuery {
+    /// 文件在比较结果中的路径（`FileDiffEntry.path`）
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct RefsQuery {
+    pub repository_path: String,
+}
+
+#[derive(Deserialize)]
+pub struct FileHistoryQuery {
+    pub repository_path: String,
+    /// 相对仓库根目录的路径，或仓库内的绝对路径
+    pub file_path: String,
+    #[serde(default = "default_history_limit")]
+    pub limit: usize,
+}
+
+fn default_history_limit() -> usize {
+    50
+}
+
+#[derive(Deserialize)]
+pub struct BlameQuery {
+    pub repository_path: String,
+    /// 相对仓库根目录的路径，或仓库内的绝对路径
+    pub file_path: String,
+    pub line_start: u32,
+    /// 缺省时只查询 `line_start` 一行
+    #[serde(default)]
+    pub line_end: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct PatchRequest {
+    #[serde(flatten)]
+    pub request: ComparisonRequest,
+    /// 补丁写入路径；缺省时直接在响应中返回补丁内容
+    #[serde(default)]
+    pub output_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct HtmlReportRequest {
+    #[serde(flatten)]
+    pub request: ComparisonRequest,
+    /// 报告写入路径；缺省时直接在响应中返回 HTML
+    #[serde(default)]
+    pub output_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ApplyPatchRequest {
+    pub patch_path: String,
+    pub target_dir: String,
+    /// 只预览，不写入文件
+    pub dry_run: bool,
+    /// 有冲突时仍写入没有冲突的文件
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// 后台比较推送的事件：`diff-progress` / `diff-complete` / `diff-cancelled` / `diff-failed`
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum DiffEvent {
+    Progress {
+        comparison_id: String,
+        #[serde(flatten)]
+        progress: DiffProgress,
+    },
+    Complete {
+        comparison_id: String,
+        result: Box<ComparisonResult>,
+    },
+    Cancelled {
+        comparison_id: String,
+    },
+    Failed {
+        comparison_id: String,
+        error: String,
+    },
+}
+
+impl ServerEvent for DiffEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            DiffEvent::Progress { .. } => "diff-progress",
+            DiffEvent::Complete { .. } => "diff-complete",
+            DiffEvent::Cancelled { .. } => "diff-cancelled",
+            DiffEvent::Failed { .. } => "diff-failed",
+        }
+    }
+}
+
+/// 目录中的文件总数或总大小是否超过后台比较的阈值，超过后即停止遍历；不跟随符号链接
+fn exceeds_background_threshold(dirs: &[PathBuf]) -> bool {
+    let (mut files, mut bytes) = (0usize, 0u64);
+    let mut pending = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files += 1;
+                bytes += entry.metadata().map_or(0, |metadata| metadata.len());
+                if files > BACKGROUND_FILE_THRESHOLD || bytes > BACKGROUND_SIZE_THRESHOLD {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// 比较两个文件或目录
+///
+/// 同步模式直接返回 `ComparisonResult`；后台模式立即返回 `202 { comparison_id }`，
+/// 之后通过 `/api/diff/events` 推送进度和结果。未指定模式时，只有超过阈值的目录比较在后台执行
+pub async fn compare(
+    state: web::Data<AppState>,
+    req: web::Json<CompareRequest>,
+) -> Result<HttpResponse, AppError> {
+    let CompareRequest { request, background } = req.into_inner();
+    let background = match background {
+        Some(background) => background,
+        None if !request.is_git_comparison => {
+            let (dir_a, dir_b) = (PathBuf::from(&request.source_a), PathBuf::from(&request.source_b));
+            web::block(move || dir_a.is_dir() && dir_b.is_dir() && exceeds_background_threshold(&[dir_a, dir_b]))
+                .await
+                .map_err(|e| AppError::internal("Comparison task failed", e))?
+        }
+        None => false,
+    };
+
+    if !background {
+        let engine = DiffEngine::new(request.config.clone()).with_cache(Arc::clone(&state.diff_cache));
+        let result = web::block(move || engine.compare(request))
+            .await
+            .map_err(|e| AppError::internal("Comparison task failed", e))?
+            .map_err(|e| comparison_error("Comparison failed", e))?;
+        return Ok(HttpResponse::Ok().json(result));
+    }
+
+    let comparison_id = Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    state
+        .comparisons
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(comparison_id.clone(), Arc::clone(&cancel));
+
+    let engine = DiffEngine::new(request.config.clone())
+        .with_cache(Arc::clone(&state.diff_cache))
+        .with_cancel_flag(cancel)
+        .with_progress(progress_reporter(state.diff_events.clone(), comparison_id.clone()));
+    let task_state = state.clone();
+    let task_id = comparison_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let event = match engine.compare(request) {
+            Ok(result) => DiffEvent::Complete {
+                comparison_id: task_id.clone(),
+                result: Box::new(result),
+            },
+            Err(e) if e.is::<ComparisonCancelled>() => {
+                tracing::info!("Comparison {} cancelled", task_id);
+                DiffEvent::Cancelled {
+                    comparison_id: task_id.clone(),
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Comparison {} failed: {}", task_id, e);
+                DiffEvent::Failed {
+                    comparison_id: task_id.clone(),
+                    error: e.to_string(),
+                }
+            }
+        };
+        task_state
+            .comparisons
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&task_id);
+        let _ = task_state.diff_events.send(event);
+    });
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "comparison_id": comparison_id
+    })))
+}
+
+/// 比较两段文本，不访问文件系统，返回单个 `FileDiff`
+pub async fn compare_text(req: web::Json<CompareTextRequest>) -> Result<HttpResponse, AppError> {
+    let CompareTextRequest {
+        text_a,
+        text_b,
+        label_a,
+        label_b,
+        config,
+    } = req.into_inner();
+    let engine = DiffEngine::new(config);
+    let diff = web::block(move || engine.compare_contents(&text_a, &text_b, &label_a, &label_b))
+        .await
+        .map_err(|e| AppError::internal("Comparison task failed", e))?;
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+/// 三方比较单个文件，返回 `ThreeWayFileDiff`：各行在基准、我方与对方中的内容，两侧改法不同的区域标为冲突
+pub async fn compare_three_way(req: web::Json<ThreeWayRequest>) -> Result<HttpResponse, AppError> {
+    let ThreeWayRequest {
+        base_path,
+        a_path,
+        b_path,
+        config,
+    } = req.into_inner();
+    let engine = DiffEngine::new(config);
+    let diff = web::block(move || engine.compare_three_way(Path::new(&base_path), Path::new(&a_path), Path::new(&b_path)))
+        .await
+        .map_err(|e| AppError::internal("Comparison task failed", e))?
+        .map_err(|e| comparison_error("Three-way comparison failed", e))?;
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+/// 查看单个提交的改动：与其父提交比较（根提交与空树比较），返回 `CommitComparisonResult`，
+/// 其中 `parent_hash` 为所用的父提交；合并提交未指定 `parent_index` 时与第一个父提交比较并在 `note` 中说明
+pub async fn compare_commit(
+    state: web::Data<AppState>,
+    req: web::Json<CommitComparisonRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request = req.into_inner();
+    let engine = DiffEngine::new(request.config.clone()).with_cache(Arc::clone(&state.diff_cache));
+    let result = web::block(move || engine.compare_commit(request))
+        .await
+        .map_err(|e| AppError::internal("Comparison task failed", e))?
+        .map_err(|e| comparison_error("Commit comparison failed", e))?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// 列出仓库的分支与标签（含当前分支标记与所指提交），最近提交的在前
+pub async fn get_refs(query: web::Query<RefsQuery>) -> Result<HttpResponse, AppError> {
+    let RefsQuery { repository_path } = query.into_inner();
+    let refs = web::block(move || GitIntegration::new().get_refs(&repository_path))
+        .await
+        .map_err(|e| AppError::internal("Reference listing task failed", e))?
+        .map_err(|e| comparison_error("Failed to list references", e))?;
+    Ok(HttpResponse::Ok().json(refs))
+}
+
+/// 分页列出仓库的提交（`CommitQuery`），可按路径筛选出改动了某个文件的提交
+pub async fn get_commits(query: web::Query<CommitQuery>) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let commits = web::block(move || GitIntegration::new().get_commits(&query))
+        .await
+        .map_err(|e| AppError::internal("Commit listing task failed", e))?
+        .map_err(|e| comparison_error("Failed to list commits", e))?;
+    Ok(HttpResponse::Ok().json(commits))
+}
+
+/// 列出改动过某个文件的提交及文件在各提交中的路径（跟随重命名），任意两个提交可作为比较的左右两侧
+pub async fn get_file_history(query: web::Query<FileHistoryQuery>) -> Result<HttpResponse, AppError> {
+    let FileHistoryQuery {
+        repository_path,
+        file_path,
+        limit,
+    } = query.into_inner();
+    let history = web::block(move || GitIntegration::new().get_file_history(&repository_path, &file_path, limit))
+        .await
+        .map_err(|e| AppError::internal("File history task failed", e))?
+        .map_err(|e| comparison_error("Failed to read file history", e))?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+/// 工作目录中文件各行的来源提交，供文件查看器显示；未提交的行 `committed` 为 false
+pub async fn get_blame(query: web::Query<BlameQuery>) -> Result<HttpResponse, AppError> {
+    let BlameQuery {
+        repository_path,
+        file_path,
+        line_start,
+        line_end,
+    } = query.into_inner();
+    let line_end = line_end.unwrap_or(line_start);
+    let blame = web::block(move || GitIntegration::new().get_blame(&repository_path, &file_path, line_start, line_end))
+        .await
+        .map_err(|e| AppError::internal("Blame task failed", e))?
+        .map_err(|e| comparison_error("Failed to blame file", e))?;
+    Ok(HttpResponse::Ok().json(blame))
+}
+
+/// 分阶段比较：执行比较并缓存结果，只返回 `{ comparison_id, ...ComparisonOverview }`
+///
+/// 各文件的差异行通过 `/api/diff/{comparison_id}/file` 获取，用完后调用关闭接口释放缓存
+pub async fn start_comparison(
+    state: web::Data<AppState>,
+    req: web::Json<ComparisonRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request = req.into_inner();
+    let engine = DiffEngine::new(request.config.clone()).with_cache(Arc::clone(&state.diff_cache));
+    let result = web::block(move || engine.compare(request))
+        .await
+        .map_err(|e| AppError::internal("Comparison task failed", e))?
+        .map_err(|e| comparison_error("Comparison failed", e))?;
+
+    let comparison_id = Uuid::new_v4().to_string();
+    let overview = result.overview();
+    state
+        .comparison_results
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(comparison_id.clone(), Arc::new(result));
+    tracing::info!("Comparison {} cached with {} files", comparison_id, overview.files.len());
+
+    Ok(HttpResponse::Ok().json(StartedComparison {
+        comparison_id,
+        overview,
+    }))
+}
+
+#[derive(Serialize)]
+struct StartedComparison {
+    comparison_id: String,
+    #[serde(flatten)]
+    overview: ComparisonOverview,
+}
+
+/// 获取分阶段比较中单个文件的差异（差异行/差异块及文件内容）
+pub async fn get_file_diff(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<FileDiffQuery>,
+) -> Result<HttpResponse, AppError> {
+    let comparison_id = path.into_inner();
+    let result = cached_comparison(&state, &comparison_id)?;
+    let diff = result.file_diff(&query.path).ok_or_else(|| {
+        AppError::new(
+            ErrorCode::FileNotFound,
+            format!("File {} not found in comparison {}", query.path, comparison_id),
+        )
+    })?;
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+/// 关闭分阶段比较，释放缓存的结果
+pub async fn close_comparison(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let comparison_id = path.into_inner();
+    state
+        .comparison_results
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&comparison_id)
+        .ok_or_else(|| comparison_not_cached(&comparison_id))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "comparison_id": comparison_id,
+        "closed": true
+    })))
+}
+
+fn cached_comparison(state: &AppState, comparison_id: &str) -> Result<Arc<ComparisonResult>, AppError> {
+    state
+        .comparison_results
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(comparison_id)
+        .cloned()
+        .ok_or_else(|| comparison_not_cached(comparison_id))
+}
+
+fn comparison_not_cached(comparison_id: &str) -> AppError {
+    AppError::new(
+        ErrorCode::ComparisonNotFound,
+        format!("Comparison {} not found or already closed", comparison_id),
+    )
+}
+
+/// 清空单文件差异缓存，返回 `{ cleared }`（清除的条目数）
+pub async fn clear_diff_cache(state: web::Data<AppState>) -> impl Responder {
+    let cleared = state.diff_cache.len();
+    state.diff_cache.clear();
+    HttpResponse::Ok().json(serde_json::json!({ "cleared": cleared }))
+}
+
+/// 将比较结果导出为 unified diff 补丁
+///
+/// 指定 `output_path` 时写入文件并返回 `{ output_path, summary }`，否则以 `text/x-diff` 返回补丁内容
+pub async fn export_patch(req: web::Json<PatchRequest>) -> Result<HttpResponse, AppError> {
+    let PatchRequest { request, output_path } = req.into_inner();
+    let engine = DiffEngine::new(request.config.clone());
+    let (patch, summary) = web::block(move || engine.generate_patch(request))
+        .await
+        .map_err(|e| AppError::internal("Patch export task failed", e))?
+        .map_err(|e| comparison_error("Comparison failed", e))?;
+
+    match output_path {
+        Some(output_path) => {
+            tokio::fs::write(&output_path, patch)
+                .await
+                .map_err(|e| AppError::io(format!("Failed to write patch to {}", output_path), e))?;
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "output_path": output_path,
+                "summary": summary
+            })))
+        }
+        None => Ok(HttpResponse::Ok().content_type("text/x-diff; charset=utf-8").body(patch)),
+    }
+}
+
+/// 将比较结果导出为独立的 HTML 差异报告
+///
+/// 指定 `output_path` 时写入文件并返回 `{ output_path, summary }`，否则以 `text/html` 返回报告内容
+pub async fn export_diff_html(req: web::Json<HtmlReportRequest>) -> Result<HttpResponse, AppError> {
+    let HtmlReportRequest { request, output_path } = req.into_inner();
+    let engine = DiffEngine::new(request.config.clone());
+    let (report, summary) = web::block(move || engine.generate_html_report(request))
+        .await
+        .map_err(|e| AppError::internal("Report export task failed", e))?
+        .map_err(|e| comparison_error("Comparison failed", e))?;
+
+    match output_path {
+        Some(output_path) => {
+            tokio::fs::write(&output_path, report)
+                .await
+                .map_err(|e| AppError::io(format!("Failed to write report to {}", output_path), e))?;
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "output_path": output_path,
+                "summary": summary
+            })))
+        }
+        None => Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(report)),
+    }
+}
+
+/// 将补丁应用到目录，返回预览与冲突；冲突不视为请求错误，由 `applied` 表示是否已写入。
+/// 补丁无法读取或解析时返回 `PATCH_INVALID`，写入目标目录失败时返回 `IO_ERROR`
+pub async fn apply_patch(req: web::Json<ApplyPatchRequest>) -> Result<HttpResponse, AppError> {
+    let ApplyPatchRequest {
+        patch_path,
+        target_dir,
+        dry_run,
+        partial,
+    } = req.into_inner();
+    if !Path::new(&target_dir).is_dir() {
+        return Err(AppError::new(
+            ErrorCode::FileNotFound,
+            format!("Target directory {} not found", target_dir),
+        ));
+    }
+
+    let result = web::block(move || {
+        deepaudit_core::diff::apply_patch(Path::new(&patch_path), Path::new(&target_dir), dry_run, partial)
+    })
+    .await
+    .map_err(|e| AppError::internal("Patch apply task failed", e))?
+    .map_err(|e| match e.downcast_ref::<PatchWriteFailed>() {
+        Some(_) => AppError::io("Failed to write patched files", e),
+        None => AppError::new(ErrorCode::PatchInvalid, "Patch could not be applied").with_detail(e),
+    })?;
+
+    if !result.conflicts.is_empty() {
+        tracing::info!(
+            "Patch has {} conflicts (dry_run: {}, applied: {})",
+            result.conflicts.len(),
+            dry_run,
+            result.applied
+        );
+    }
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// 按最小间隔推送进度，最后一个文件完成时总会推送
+fn progress_reporter(events: broadcast::Sender<DiffEvent>, comparison_id: String) -> ProgressCallback {
+    let last_sent: Mutex<Option<Instant>> = Mutex::new(None);
+    Arc::new(move |progress: &DiffProgress| {
+        let mut last_sent = last_sent.lock().unwrap_or_else(|e| e.into_inner());
+        let finished = progress.files_processed == progress.files_paired;
+        if !finished && last_sent.is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        *last_sent = Some(Instant::now());
+        let _ = events.send(DiffEvent::Progress {
+            comparison_id: comparison_id.clone(),
+            progress: progress.clone(),
+        });
+    })
+}
+
+/// 取消后台比较，已在进行的文件比较完成后停止
+pub async fn cancel_comparison(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let comparison_id = path.into_inner();
+    let cancel = state
+        .comparisons
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&comparison_id)
+        .cloned()
+        .ok_or_else(|| {
+            AppError::new(
+                ErrorCode::ComparisonNotFound,
+                format!("Comparison {} not found or already finished", comparison_id),
+            )
+        })?;
+    cancel.store(true, Ordering::Relaxed);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "comparison_id": comparison_id,
+        "cancelled": true
+    })))
+}
+
+/// 以 Server-Sent Events 推送后台比较事件
+pub async fn diff_events(state: web::Data<AppState>) -> impl Responder {
+    event_stream(state.diff_events.subscribe())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn git_errors_use_git_error_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = RefsQuery {
+            repository_path: dir.path().to_str().unwrap().to_string(),
+        };
+        let err = get_refs(web::Query(query)).await.err().unwrap();
+        assert_eq!(err.code, ErrorCode::GitNotARepo);
+        assert_eq!(err.code.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let err = comparison_error("Failed", GitError::UnknownRevision("feature".to_string()).into());
+        assert_eq!(err.code, ErrorCode::GitRefNotFound);
+        assert_eq!(err.code.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        let err = comparison_error("Failed", anyhow::anyhow!("Path type mismatch"));
+        assert_eq!(err.code, ErrorCode::ComparisonFailed);
+    }
+
+    #[actix_web::test]
+    async fn patch_write_failure_is_an_io_error() {
+        let target = tempfile::tempdir().unwrap();
+        std::fs::write(target.path().join("blocker"), "file\n").unwrap();
+        let patch = target.path().join("change.patch");
+        let request = |patch_text: &str| {
+            std::fs::write(&patch, patch_text).unwrap();
+            web::Json(ApplyPatchRequest {
+                patch_path: patch.to_str().unwrap().to_string(),
+                target_dir: target.path().to_str().unwrap().to_string(),
+                dry_run: false,
+                partial: false,
+            })
+        };
+
+        let err = apply_patch(request("--- /dev/null\n+++ b/blocker/new.txt\n@@ -0,0 +1 @@\n+new\n"))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code, ErrorCode::IoError);
+
+        let err = apply_patch(request("not a patch\n")).await.err().unwrap();
+        assert_eq!(err.code, ErrorCode::PatchInvalid);
+    }
+
+    fn directory_request(dir_a: &Path, dir_b: &Path, background: Option<bool>) -> CompareRequest {
+        CompareRequest {
+            request: ComparisonRequest {
+                source_a: dir_a.to_str().unwrap().to_string(),
+                source_b: dir_b.to_str().unwrap().to_string(),
+                config: ComparisonConfig::default(),
+                is_git_comparison: false,
+                git_params: None,
+                is_working_tree_comparison: false,
+            },
+            background,
+        }
+    }
+
+    #[actix_web::test]
+    async fn small_directory_comparison_is_synchronous_by_default() {
+        let (_dir, state) = crate::state::test_state().await;
+        let (left, right) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        std::fs::write(left.path().join("a.txt"), "one\n").unwrap();
+        std::fs::write(right.path().join("a.txt"), "two\n").unwrap();
+
+        let response = compare(state.clone(), web::Json(directory_request(left.path(), right.path(), None)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), actix_web::http::StatusCode::OK);
+
+        let response = compare(state, web::Json(directory_request(left.path(), right.path(), Some(true))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), actix_web::http::StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    fn background_threshold_counts_files_on_both_sides() {
+        let (left, right) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let dirs = [left.path().to_path_buf(), right.path().to_path_buf()];
+        std::fs::create_dir(right.path().join("nested")).unwrap();
+        for i in 0..BACKGROUND_FILE_THRESHOLD / 2 {
+            std::fs::write(left.path().join(format!("{}.txt", i)), "x").unwrap();
+            std::fs::write(right.path().join("nested").join(format!("{}.txt", i)), "x").unwrap();
+        }
+        assert!(!exceeds_background_threshold(&dirs));
+        std::fs::write(right.path().join("extra.txt"), "x").unwrap();
+        assert!(exceeds_background_threshold(&dirs));
+    }
+}
@@ -1,7 +1,9 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
 use std::path::{Path as StdPath, PathBuf};
 
+use crate::error::{AppError, ErrorCode};
+
 #[derive(Serialize, Deserialize)]
 pub struct ReadFileRequest {
     pub path: String,
@@ -33,41 +35,33 @@ pub fn configure_files_routes(cfg: &mut web::ServiceConfig) {
         .route("/search", web::get().to(search_files));
 }
 
-pub async fn read_file(query: web::Query<ReadFileRequest>) -> impl Responder {
+pub async fn read_file(query: web::Query<ReadFileRequest>) -> Result<HttpResponse, AppError> {
     let path = PathBuf::from(&query.path);
 
     if !path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": format!("文件不存在: {}", query.path)
-        }));
+        return Err(AppError::new(ErrorCode::FileNotFound, format!("文件不存在: {}", query.path)));
     }
 
-    match tokio::fs::read_to_string(&path).await {
-        Ok(content) => HttpResponse::Ok().json(content),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("读取文件失败: {}", e)
-        }))
-    }
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| AppError::io("读取文件失败", e))?;
+    Ok(HttpResponse::Ok().json(content))
 }
 
-pub async fn list_files(query: web::Query<ListFilesRequest>) -> impl Responder {
+pub async fn list_files(query: web::Query<ListFilesRequest>) -> Result<HttpResponse, AppError> {
     let path = PathBuf::from(&query.directory);
 
     if !path.exists() {
-        return HttpResponse::Ok().json(vec![] as Vec<String>);
+        return Ok(HttpResponse::Ok().json(vec![] as Vec<String>));
     }
 
     // 默认递归列出所有文件
     let mut entries = vec![];
-    match _list_files_recursive(&path, &mut entries).await {
-        Ok(_) => {
-            entries.sort();
-            HttpResponse::Ok().json(entries)
-        }
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("读取目录失败: {}", e)
-        }))
-    }
+    _list_files_recursive(&path, &mut entries)
+        .await
+        .map_err(|e| AppError::io("读取目录失败", e))?;
+    entries.sort();
+    Ok(HttpResponse::Ok().json(entries))
 }
 
 // 递归列出所有文件
@@ -101,20 +95,18 @@ async fn _list_files_recursive(dir: &StdPath, entries: &mut Vec<String>) -> Resu
     Ok(())
 }
 
-pub async fn search_files(query: web::Query<SearchFilesRequest>) -> impl Responder {
+pub async fn search_files(query: web::Query<SearchFilesRequest>) -> Result<HttpResponse, AppError> {
     let path = PathBuf::from(&query.path);
     let query_str = &query.query;
 
     if !path.exists() {
-        return HttpResponse::Ok().json(vec![] as Vec<FileInfo>);
+        return Ok(HttpResponse::Ok().json(vec![] as Vec<FileInfo>));
     }
 
-    match _search_files_recursive(&path, query_str).await {
-        Ok(results) => HttpResponse::Ok().json(results),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("搜索文件失败: {}", e)
-        }))
-    }
+    let results = _search_files_recursive(&path, query_str)
+        .await
+        .map_err(|e| AppError::io("搜索文件失败", e))?;
+    Ok(HttpResponse::Ok().json(results))
 }
 
 async fn _search_files_recursive(
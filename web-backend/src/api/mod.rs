@@ -1,10 +1,13 @@
-use actix_web::{web, Scope};
+use actix_web::{web, HttpResponse, Scope};
+use serde::Serialize;
+use tokio::sync::broadcast;
 
 pub mod ast;
 pub mod project;
 pub mod scanner;
 pub mod files;
 pub mod rules;
+pub mod diff;
 
 pub fn create_api_router() -> Scope {
     web::scope("/api")
@@ -13,6 +16,36 @@ pub fn create_api_router() -> Scope {
         .service(scanner_routes())
         .service(files_routes())
         .service(rules_routes())
+        .service(diff_routes())
+}
+
+/// 通过 Server-Sent Events 推送的事件
+pub trait ServerEvent: Serialize + Clone + Send + 'static {
+    /// SSE 事件名
+    fn name(&self) -> &'static str;
+}
+
+/// 将广播通道中的事件以 Server-Sent Events 流式返回，落后过多时丢弃旧事件
+pub(crate) fn event_stream<T: ServerEvent>(rx: broadcast::Receiver<T>) -> HttpResponse {
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    let frame = format!("event: {}\ndata: {}\n\n", event.name(), data);
+                    return Some((Ok::<_, actix_web::Error>(web::Bytes::from(frame)), rx));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Event subscriber lagged, {} events dropped", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
 }
 
 fn project_routes() -> Scope {
@@ -39,3 +72,8 @@ fn rules_routes() -> Scope {
     web::scope("/rules")
         .configure(rules::configure_rules_routes)
 }
+
+fn diff_routes() -> Scope {
+    web::scope("/diff")
+        .configure(diff::configure_diff_routes)
+}
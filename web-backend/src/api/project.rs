@@ -1,10 +1,12 @@
 use actix_multipart::Multipart;
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 use futures_util::TryStreamExt;
+use deepaudit_core::rules::model::Severity;
 
+use crate::error::{AppError, ErrorCode};
 use crate::state::AppState;
 
 #[derive(Serialize, Deserialize, FromRow)]
@@ -16,15 +18,21 @@ pub struct Project {
     pub created_at: String,
 }
 
-#[derive(Deserialize)]
-pub struct CreateProjectRequest {
-    pub name: String,
-    pub path: String,
+/// 项目设置，以 JSON 保存在 projects.settings 列
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ProjectSettings {
+    /// 启用的规则包，为空时启用全部规则包
+    #[serde(default)]
+    pub enabled_packs: Option<Vec<String>>,
+    /// 最低保存的严重程度，低于此级别的发现只计数不保存；未设置时保存全部
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<Severity>,
 }
 
 #[derive(Deserialize)]
-pub struct UploadProjectRequest {
+pub struct CreateProjectRequest {
     pub name: String,
+    pub path: String,
 }
 
 pub fn configure_project_routes(cfg: &mut web::ServiceConfig) {
@@ -34,54 +42,57 @@ pub fn configure_project_routes(cfg: &mut web::ServiceConfig) {
         .route("/upload", web::post().to(upload_project))    // POST /api/projects/upload
         .route("", web::get().to(list_projects))             // GET /api/projects
         .route("/{uuid}", web::get().to(get_project))        // GET /api/projects/{uuid}
+        .route("/{uuid}/settings", web::get().to(get_project_settings))
+        .route("/{uuid}/settings", web::put().to(update_project_settings))
+        .route("/{uuid}/watch", web::post().to(watch_project))     // 开始监听项目目录
+        .route("/{uuid}/watch", web::delete().to(unwatch_project)) // 停止监听
         .route("/{uuid}", web::delete().to(delete_project)); // DELETE /api/projects/{uuid}
 }
 
 async fn create_project(
     state: web::Data<AppState>,
     req: web::Json<CreateProjectRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
+    validate_project_dir(&req.path)?;
     let uuid = Uuid::new_v4().to_string();
     let result = sqlx::query("INSERT INTO projects (uuid, name, path) VALUES (?, ?, ?)")
         .bind(&uuid)
         .bind(&req.name)
         .bind(&req.path)
         .execute(&state.db)
-        .await;
-
-    match result {
-        Ok(result) => {
-            let id = result.last_insert_rowid();
-            match sqlx::query_as::<_, Project>(
-                "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects WHERE id = ?"
-            )
-            .bind(id)
-            .fetch_one(&state.db)
-            .await
-            {
-                Ok(project) => HttpResponse::Ok().json(project),
-                Err(e) => {
-                    tracing::error!("Failed to fetch project: {}", e);
-                    HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": format!("Failed to fetch project: {}", e)
-                    }))
-                }
-            }
-        }
-        Err(e) => {
-            tracing::error!("Failed to create project: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create project: {}", e)
-            }))
-        }
+        .await
+        .map_err(|e| AppError::database("Failed to create project", e))?;
+
+    let project = fetch_project_by_id(&state, result.last_insert_rowid()).await?;
+    Ok(HttpResponse::Ok().json(project))
+}
+
+/// 检查项目路径是已存在的目录，便于脚本直接按路径创建项目或扫描
+pub(crate) fn validate_project_dir(path: &str) -> Result<(), AppError> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| AppError::new(ErrorCode::FileNotFound, format!("项目目录不存在: {}", path)).with_detail(e))?;
+    if !metadata.is_dir() {
+        return Err(AppError::invalid_input(format!("项目路径不是目录: {}", path)));
     }
+    Ok(())
+}
+
+/// 按自增 id 读取项目
+async fn fetch_project_by_id(state: &AppState, id: i64) -> Result<Project, AppError> {
+    sqlx::query_as::<_, Project>(
+        "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects WHERE id = ?"
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to fetch project", e))
 }
 
 async fn upload_project(
     state: web::Data<AppState>,
     mut payload: Multipart,
     _req: HttpRequest,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     tracing::info!("Starting project upload...");
 
     let mut name = String::new();
@@ -89,193 +100,110 @@ async fn upload_project(
     let mut filename = String::new();
 
     // 解析 multipart 表单 - 使用循环处理所有字段
-    loop {
-        match payload.try_next().await {
-            Ok(Some(mut field)) => {
-                let field_name = field.name().unwrap_or("").to_string();
-                tracing::debug!("Processing field: {}", field_name);
-
-                if field_name == "name" {
-                    // bytes 方法需要 limit 参数
-                    let limit = 1024 * 1024; // 1MB limit for name
-                    match field.bytes(limit).await {
-                        Ok(Ok(data)) => {
-                            name = String::from_utf8(Vec::from(data.as_ref())).unwrap_or_default();
-                            tracing::info!("Project name: {}", name);
-                        }
-                        Ok(Err(e)) => {
-                            tracing::error!("Failed to read name field: {}", e);
-                            return HttpResponse::InternalServerError().json(serde_json::json!({
-                                "error": format!("Failed to read name: {}", e)
-                            }));
-                        }
-                        Err(_) => {
-                            return HttpResponse::InternalServerError().json(serde_json::json!({
-                                "error": "Limit exceeded for name field"
-                            }));
-                        }
-                    }
-                } else if field_name == "file" {
-                    let content_type = field.content_type()
-                        .map(|m| m.to_string())
-                        .unwrap_or_else(|| "application/octet-stream".to_string());
-
-                    // 获取文件名 - 尝试从 content_disposition 获取
-                    let file_name = field.content_disposition()
-                        .and_then(|cd| cd.get_filename())
-                        .unwrap_or("unknown.zip")
-                        .to_string();
-                    filename = file_name.clone();
-
-                    tracing::info!("Receiving file: {} (content-type: {})", filename, content_type);
-
-                    // 验证是 ZIP 文件
-                    if !file_name.ends_with(".zip") {
-                        tracing::error!("Invalid file format: {}", file_name);
-                        return HttpResponse::BadRequest().json(serde_json::json!({
-                            "error": "Only ZIP files are allowed"
-                        }));
-                    }
-
-                    // 读取文件数据
-                    let limit = 1024 * 1024 * 1024; // 1GB limit for file
-                    match field.bytes(limit).await {
-                        Ok(Ok(data)) => {
-                            file_data = Some(Vec::from(data.as_ref()));
-                            tracing::info!("File data received: {} bytes", file_data.as_ref().map(|d| d.len()).unwrap_or(0));
-                        }
-                        Ok(Err(e)) => {
-                            tracing::error!("Failed to read file data: {}", e);
-                            return HttpResponse::InternalServerError().json(serde_json::json!({
-                                "error": format!("Failed to read file: {}", e)
-                            }));
-                        }
-                        Err(_) => {
-                            return HttpResponse::InternalServerError().json(serde_json::json!({
-                                "error": "File size limit exceeded"
-                            }));
-                        }
-                    }
-                }
-                // 继续处理下一个字段
-            }
-            Ok(None) => {
-                // 没有更多字段了，退出循环
-                tracing::info!("All fields processed");
-                break;
-            }
-            Err(e) => {
-                tracing::error!("Failed to read multipart field: {}", e);
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to read multipart: {}", e)
-                }));
+    while let Some(mut field) = payload
+        .try_next()
+        .await
+        .map_err(|e| AppError::new(ErrorCode::UploadFailed, "Failed to read multipart").with_detail(e))?
+    {
+        let field_name = field.name().unwrap_or("").to_string();
+        tracing::debug!("Processing field: {}", field_name);
+
+        if field_name == "name" {
+            // bytes 方法需要 limit 参数
+            let limit = 1024 * 1024; // 1MB limit for name
+            let data = field
+                .bytes(limit)
+                .await
+                .map_err(|_| AppError::new(ErrorCode::UploadTooLarge, "Limit exceeded for name field"))?
+                .map_err(|e| AppError::new(ErrorCode::UploadFailed, "Failed to read name").with_detail(e))?;
+            name = String::from_utf8(Vec::from(data.as_ref())).unwrap_or_default();
+            tracing::info!("Project name: {}", name);
+        } else if field_name == "file" {
+            let content_type = field.content_type()
+                .map(|m| m.to_string())
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+
+            // 获取文件名 - 尝试从 content_disposition 获取
+            let file_name = field.content_disposition()
+                .and_then(|cd| cd.get_filename())
+                .unwrap_or("unknown.zip")
+                .to_string();
+            filename = file_name.clone();
+
+            tracing::info!("Receiving file: {} (content-type: {})", filename, content_type);
+
+            // 验证是 ZIP 文件
+            if !file_name.ends_with(".zip") {
+                tracing::error!("Invalid file format: {}", file_name);
+                return Err(AppError::invalid_input("Only ZIP files are allowed"));
             }
-        }
+
+            // 读取文件数据
+            let limit = 1024 * 1024 * 1024; // 1GB limit for file
+            let data = field
+                .bytes(limit)
+                .await
+                .map_err(|_| AppError::new(ErrorCode::UploadTooLarge, "File size limit exceeded"))?
+                .map_err(|e| AppError::new(ErrorCode::UploadFailed, "Failed to read file").with_detail(e))?;
+            file_data = Some(Vec::from(data.as_ref()));
+            tracing::info!("File data received: {} bytes", file_data.as_ref().map(|d| d.len()).unwrap_or(0));
+        }
+        // 继续处理下一个字段
     }
+    tracing::info!("All fields processed");
 
     if name.is_empty() {
         tracing::error!("Project name is empty");
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Project name is required"
-        }));
+        return Err(AppError::invalid_input("Project name is required"));
     }
 
-    let file_data = match file_data {
-        Some(data) => data,
-        None => {
-            tracing::error!("No file data received");
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "No file uploaded"
-            }));
-        }
+    let Some(file_data) = file_data else {
+        tracing::error!("No file data received");
+        return Err(AppError::invalid_input("No file uploaded"));
     };
 
     tracing::info!("Uploading project: {} from file: {}", name, filename);
 
     // 创建项目目录
-    let projects_dir = std::path::PathBuf::from("./data/projects");
-    if let Err(e) = std::fs::create_dir_all(&projects_dir) {
-        tracing::error!("Failed to create projects directory: {}", e);
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("Failed to create projects directory: {}", e)
-        }));
-    }
+    let projects_dir = state.data_dir.join("projects");
+    std::fs::create_dir_all(&projects_dir)
+        .map_err(|e| AppError::io("Failed to create projects directory", e))?;
 
     let project_id = Uuid::new_v4();
     let project_dir = projects_dir.join(format!("{}_{}", name.replace(" ", "_"), project_id));
-    if let Err(e) = std::fs::create_dir_all(&project_dir) {
-        tracing::error!("Failed to create project directory: {}", e);
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("Failed to create project directory: {}", e)
-        }));
-    }
+    std::fs::create_dir_all(&project_dir)
+        .map_err(|e| AppError::io("Failed to create project directory", e))?;
 
     tracing::info!("Created project directory: {:?}", project_dir);
 
     // 保存上传的 ZIP 文件
     let zip_path = project_dir.join("upload.zip");
-    match std::fs::File::create(&zip_path) {
-        Ok(mut file) => {
-            if let Err(e) = std::io::Write::write_all(&mut file, &file_data) {
-                tracing::error!("Failed to write zip file: {}", e);
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to write zip file: {}", e)
-                }));
-            }
-        }
-        Err(e) => {
-            tracing::error!("Failed to create zip file: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create zip file: {}", e)
-            }));
-        }
-    }
+    let mut zip_out = std::fs::File::create(&zip_path)
+        .map_err(|e| AppError::io("Failed to create zip file", e))?;
+    std::io::Write::write_all(&mut zip_out, &file_data)
+        .map_err(|e| AppError::io("Failed to write zip file", e))?;
 
     tracing::info!("Saved ZIP file: {}, size: {} bytes", zip_path.display(), file_data.len());
 
     // 解压 ZIP 文件
     let extract_dir = project_dir.join("code");
-    if let Err(e) = std::fs::create_dir_all(&extract_dir) {
-        tracing::error!("Failed to create extract directory: {}", e);
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("Failed to create extract directory: {}", e)
-        }));
-    }
+    std::fs::create_dir_all(&extract_dir)
+        .map_err(|e| AppError::io("Failed to create extract directory", e))?;
 
     // 使用 zip 解压
-    let zip_file = match std::fs::File::open(&zip_path) {
-        Ok(file) => file,
-        Err(e) => {
-            tracing::error!("Failed to open zip file: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to open zip: {}", e)
-            }));
-        }
-    };
+    let zip_file = std::fs::File::open(&zip_path)
+        .map_err(|e| AppError::io("Failed to open zip", e))?;
 
-    let mut archive = match zip::ZipArchive::new(zip_file) {
-        Ok(archive) => archive,
-        Err(e) => {
-            tracing::error!("Failed to create zip archive: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create zip archive: {}", e)
-            }));
-        }
-    };
+    let mut archive = zip::ZipArchive::new(zip_file)
+        .map_err(|e| AppError::new(ErrorCode::ArchiveInvalid, "Failed to create zip archive").with_detail(e))?;
 
     tracing::info!("Extracting ZIP archive with {} files...", archive.len());
 
     // 手动解压每个文件（zip 2.x 兼容方式）
     for i in 0..archive.len() {
-        let mut file = match archive.by_index(i) {
-            Ok(file) => file,
-            Err(e) => {
-                tracing::error!("Failed to get file at index {}: {}", i, e);
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to get file at index {}: {}", i, e)
-                }));
-            }
-        };
+        let mut file = archive.by_index(i).map_err(|e| {
+            AppError::new(ErrorCode::ArchiveInvalid, format!("Failed to get file at index {}", i)).with_detail(e)
+        })?;
 
         let enclosed_name = file.enclosed_name()
             .map(|p| p.to_path_buf())
@@ -285,39 +213,19 @@ async fn upload_project(
 
         // 创建目录
         if let Some(parent) = file_path.parent() {
-            if let Err(e) = std::fs::create_dir_all(parent) {
-                tracing::error!("Failed to create directory {:?}: {}", parent, e);
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to create directory {:?}: {}", parent, e)
-                }));
-            }
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AppError::io(format!("Failed to create directory {:?}", parent), e))?;
         }
 
         if file.is_dir() {
-            if let Err(e) = std::fs::create_dir_all(&file_path) {
-                tracing::error!("Failed to create directory {:?}: {}", file_path, e);
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to create directory {:?}: {}", file_path, e)
-                }));
-            }
+            std::fs::create_dir_all(&file_path)
+                .map_err(|e| AppError::io(format!("Failed to create directory {:?}", file_path), e))?;
             tracing::debug!("Created directory: {:?}", file_path);
         } else {
-            let mut outfile = match std::fs::File::create(&file_path) {
-                Ok(file) => file,
-                Err(e) => {
-                    tracing::error!("Failed to create file {:?}: {}", file_path, e);
-                    return HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": format!("Failed to create file {:?}: {}", file_path, e)
-                    }));
-                }
-            };
-
-            if let Err(e) = std::io::copy(&mut file, &mut outfile) {
-                tracing::error!("Failed to write file {:?}: {}", file_path, e);
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": format!("Failed to write file {:?}: {}", file_path, e)
-                }));
-            }
+            let mut outfile = std::fs::File::create(&file_path)
+                .map_err(|e| AppError::io(format!("Failed to create file {:?}", file_path), e))?;
+            std::io::copy(&mut file, &mut outfile)
+                .map_err(|e| AppError::io(format!("Failed to write file {:?}", file_path), e))?;
 
             tracing::debug!("Extracted file: {:?}", file_path);
         }
@@ -331,252 +239,213 @@ async fn upload_project(
 
     tracing::info!("Saving project to database: {} at {}", name, project_path_str);
 
-    let result = match sqlx::query("INSERT INTO projects (uuid, name, path) VALUES (?, ?, ?)")
+    let result = sqlx::query("INSERT INTO projects (uuid, name, path) VALUES (?, ?, ?)")
         .bind(&project_uuid)
         .bind(&name)
         .bind(&project_path_str)
         .execute(&state.db)
         .await
-    {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to insert project into database: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create project: {}", e)
-            }));
-        }
-    };
+        .map_err(|e| AppError::database("Failed to create project", e))?;
 
     let id = result.last_insert_rowid();
     tracing::info!("Project inserted with ID: {}, UUID: {}", id, project_uuid);
 
-    let project = match sqlx::query_as::<_, Project>(
-        "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects WHERE id = ?"
-    )
-    .bind(id)
-    .fetch_one(&state.db)
-    .await
-    {
-        Ok(project) => project,
-        Err(e) => {
-            tracing::error!("Failed to fetch project from database: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch project: {}", e)
-            }));
-        }
-    };
+    let project = fetch_project_by_id(&state, id).await?;
 
     tracing::info!("Project created successfully: {}", project.name);
 
-    HttpResponse::Ok().json(project)
+    Ok(HttpResponse::Ok().json(project))
 }
 
-async fn list_projects(state: web::Data<AppState>) -> impl Responder {
-    match sqlx::query_as::<_, Project>(
+async fn list_projects(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let projects = sqlx::query_as::<_, Project>(
         "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects ORDER BY created_at DESC"
     )
     .fetch_all(&state.db)
     .await
-    {
-        Ok(projects) => HttpResponse::Ok().json(projects),
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to list projects: {}", e)
-            }))
-        }
-    }
+    .map_err(|e| AppError::database("Failed to list projects", e))?;
+    Ok(HttpResponse::Ok().json(projects))
 }
 
 async fn get_project(
     state: web::Data<AppState>,
     path: web::Path<String>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let uuid = path.into_inner();
-    match sqlx::query_as::<_, Project>(
-        "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects WHERE uuid = ?"
-    )
-    .bind(&uuid)
-    .fetch_one(&state.db)
-    .await
-    {
-        Ok(project) => HttpResponse::Ok().json(project),
-        Err(e) => {
-            tracing::error!("Failed to fetch project: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Project not found: {}", e)
-            }))
-        }
-    }
+    let project = fetch_project_by_uuid(&state, &uuid).await?;
+    Ok(HttpResponse::Ok().json(project))
 }
 
-async fn delete_project(
+/// 监听项目目录，文件变更后自动重新扫描受影响的文件
+async fn watch_project(
     state: web::Data<AppState>,
     path: web::Path<String>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let uuid = path.into_inner();
+    let project = fetch_project_by_uuid(&state, &uuid).await?;
+    crate::watcher::watch_project(&state, project.id, &project.path)
+        .await
+        .map_err(|e| AppError::io("Failed to watch project directory", e))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "project_id": project.id,
+        "watching": true
+    })))
+}
 
-    // 首先获取项目信息（需要 project_id 用于级联删除）
-    let project = match sqlx::query_as::<_, (i64, String, String, String, String)>(
+async fn unwatch_project(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let uuid = path.into_inner();
+    let project = fetch_project_by_uuid(&state, &uuid).await?;
+    let was_watching = crate::watcher::unwatch_project(&state, project.id).await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "project_id": project.id,
+        "watching": false,
+        "was_watching": was_watching
+    })))
+}
+
+async fn fetch_project_by_uuid(state: &AppState, uuid: &str) -> Result<Project, AppError> {
+    sqlx::query_as::<_, Project>(
         "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects WHERE uuid = ?"
     )
-    .bind(&uuid)
+    .bind(uuid)
     .fetch_optional(&state.db)
     .await
-    {
-        Ok(Some(proj)) => proj,
-        Ok(None) => {
-            tracing::warn!("Project {} not found, nothing to delete", uuid);
-            return HttpResponse::Ok().json(serde_json::json!({
-                "message": "Project not found"
-            }));
-        }
-        Err(e) => {
-            tracing::error!("Failed to fetch project: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch project: {}", e)
-            }));
-        }
-    };
-
-    let (project_id, _project_uuid, _project_name, project_path, _created_at) = project;
-
-    tracing::info!("Deleting project {} (ID: {}), cleanup scheduled for: {}", uuid, project_id, project_path);
+    .map_err(|e| AppError::database("Failed to fetch project", e))?
+    .ok_or_else(|| project_not_found(uuid))
+}
 
-    // 使用事务删除所有关联数据
-    let mut tx = match state.db.begin().await {
-        Ok(tx) => tx,
-        Err(e) => {
-            tracing::error!("Failed to begin transaction: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to begin transaction: {}", e)
-            }));
-        }
-    };
+fn project_not_found(uuid: &str) -> AppError {
+    AppError::new(ErrorCode::ProjectNotFound, format!("Project {} not found", uuid))
+}
 
-    // 1. 删除 findings 表中的关联记录
-    match sqlx::query("DELETE FROM findings WHERE project_id = ?")
+/// 读取项目设置，项目不存在时返回 None
+pub async fn load_project_settings(
+    db: &sqlx::Pool<sqlx::Sqlite>,
+    project_id: i64,
+) -> anyhow::Result<Option<ProjectSettings>> {
+    let row = sqlx::query_scalar::<_, Option<String>>("SELECT settings FROM projects WHERE id = ?")
         .bind(project_id)
-        .execute(&mut *tx)
-        .await
-    {
-        Ok(result) => {
-            tracing::info!("Deleted {} findings for project {}", result.rows_affected(), project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete findings: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete findings: {}", e)
-            }));
-        }
-    }
+        .fetch_optional(db)
+        .await?;
+    Ok(match row {
+        Some(Some(json)) => Some(serde_json::from_str(&json)?),
+        Some(None) => Some(ProjectSettings::default()),
+        None => None,
+    })
+}
 
-    // 2. 删除 scans 表中的关联记录
-    match sqlx::query("DELETE FROM scans WHERE project_id = ?")
-        .bind(project_id)
-        .execute(&mut *tx)
+/// 获取项目设置
+async fn get_project_settings(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let uuid = path.into_inner();
+    let settings = sqlx::query_scalar::<_, Option<String>>("SELECT settings FROM projects WHERE uuid = ?")
+        .bind(&uuid)
+        .fetch_optional(&state.db)
         .await
-    {
-        Ok(result) => {
-            tracing::info!("Deleted {} scan records for project {}", result.rows_affected(), project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete scans: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete scans: {}", e)
-            }));
-        }
-    }
+        .map_err(|e| AppError::database("Failed to load project settings", e))?
+        .ok_or_else(|| project_not_found(&uuid))?;
 
-    // 3. 删除 AST 相关数据（依赖关系：call_relations -> code_graphs -> symbols -> ast_indices）
-    match sqlx::query("DELETE FROM call_relations WHERE project_id = ?")
-        .bind(project_id)
-        .execute(&mut *tx)
-        .await
-    {
-        Ok(result) => {
-            tracing::info!("Deleted {} call relations for project {}", result.rows_affected(), project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete call relations: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete call relations: {}", e)
-            }));
-        }
-    }
+    let settings: ProjectSettings = settings
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok().json(settings))
+}
 
-    match sqlx::query("DELETE FROM code_graphs WHERE project_id = ?")
-        .bind(project_id)
-        .execute(&mut *tx)
-        .await
-    {
-        Ok(result) => {
-            tracing::info!("Deleted {} code graphs for project {}", result.rows_affected(), project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete code graphs: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete code graphs: {}", e)
-            }));
-        }
-    }
+/// 更新项目设置，启用的规则包必须是已加载的规则包
+async fn update_project_settings(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<ProjectSettings>,
+) -> Result<HttpResponse, AppError> {
+    let uuid = path.into_inner();
+    let settings = req.into_inner();
 
-    match sqlx::query("DELETE FROM symbols WHERE project_id = ?")
-        .bind(project_id)
-        .execute(&mut *tx)
-        .await
-    {
-        Ok(result) => {
-            tracing::info!("Deleted {} symbols for project {}", result.rows_affected(), project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete symbols: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete symbols: {}", e)
-            }));
+    if let Some(packs) = &settings.enabled_packs {
+        let snapshot = state.rules_snapshot();
+        let unknown: Vec<&String> = packs
+            .iter()
+            .filter(|p| !snapshot.rules.iter().any(|r| r.pack_name() == p.as_str()))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(AppError::invalid_input(format!("Unknown rule packs: {:?}", unknown)));
         }
     }
 
-    match sqlx::query("DELETE FROM ast_indices WHERE project_id = ?")
-        .bind(project_id)
-        .execute(&mut *tx)
+    let json = serde_json::to_string(&settings)
+        .map_err(|e| AppError::internal("Failed to serialize settings", e))?;
+    let result = sqlx::query("UPDATE projects SET settings = ? WHERE uuid = ?")
+        .bind(&json)
+        .bind(&uuid)
+        .execute(&state.db)
         .await
-    {
-        Ok(result) => {
-            tracing::info!("Deleted {} AST indices for project {}", result.rows_affected(), project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete AST indices: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete AST indices: {}", e)
-            }));
-        }
+        .map_err(|e| AppError::database("Failed to update project settings", e))?;
+    if result.rows_affected() == 0 {
+        return Err(project_not_found(&uuid));
     }
+    Ok(HttpResponse::Ok().json(settings))
+}
 
-    // 4. 删除项目记录
-    match sqlx::query("DELETE FROM projects WHERE id = ?")
-        .bind(project_id)
-        .execute(&mut *tx)
+async fn delete_project(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let uuid = path.into_inner();
+
+    // 首先获取项目信息（需要 project_id 用于级联删除）
+    let project = sqlx::query_as::<_, (i64, String, String, String, String)>(
+        "SELECT id, uuid, name, path, datetime(created_at) as created_at FROM projects WHERE uuid = ?"
+    )
+    .bind(&uuid)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to fetch project", e))?;
+
+    let Some((project_id, _project_uuid, _project_name, project_path, _created_at)) = project else {
+        tracing::warn!("Project {} not found, nothing to delete", uuid);
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "message": "Project not found"
+        })));
+    };
+
+    tracing::info!("Deleting project {} (ID: {}), cleanup scheduled for: {}", uuid, project_id, project_path);
+    crate::watcher::unwatch_project(&state, project_id).await;
+
+    // 使用事务删除所有关联数据
+    let mut tx = state
+        .db
+        .begin()
         .await
-    {
-        Ok(_) => {
-            tracing::info!("Deleted project {}", project_id);
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete project: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete project: {}", e)
-            }));
-        }
+        .map_err(|e| AppError::database("Failed to begin transaction", e))?;
+
+    // 依次删除关联数据，AST 相关数据按依赖顺序：call_relations -> code_graphs -> symbols -> ast_indices，最后删除项目记录
+    let deletions = [
+        ("findings", "DELETE FROM findings WHERE project_id = ?"),
+        ("finding baseline", "DELETE FROM finding_baselines WHERE project_id = ?"),
+        ("scan records", "DELETE FROM scans WHERE project_id = ?"),
+        ("call relations", "DELETE FROM call_relations WHERE project_id = ?"),
+        ("code graphs", "DELETE FROM code_graphs WHERE project_id = ?"),
+        ("symbols", "DELETE FROM symbols WHERE project_id = ?"),
+        ("AST indices", "DELETE FROM ast_indices WHERE project_id = ?"),
+        ("project", "DELETE FROM projects WHERE id = ?"),
+    ];
+    for (what, sql) in deletions {
+        let result = sqlx::query(sql)
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to delete {}", what), e))?;
+        tracing::info!("Deleted {} {} for project {}", result.rows_affected(), what, project_id);
     }
 
     // 提交事务
-    if let Err(e) = tx.commit().await {
-        tracing::error!("Failed to commit transaction: {}", e);
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": format!("Failed to commit transaction: {}", e)
-        }));
-    }
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database("Failed to commit transaction", e))?;
 
     // 异步清理文件系统
     let project_path_clone = project_path.clone();
@@ -588,7 +457,7 @@ async fn delete_project(
         }
     });
 
-    HttpResponse::Ok().json(serde_json::json!({
+    Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Project deleted successfully"
-    }))
+    })))
 }
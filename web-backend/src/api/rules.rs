@@ -2,7 +2,14 @@ use actix_web::{web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use std::io::Write;
 use std::fs;
+use std::path::PathBuf;
 
+use deepaudit_core::rules::model::{Confidence, PathFilter, PatternSet, Severity, DEFAULT_PACK};
+use deepaudit_core::rules::scanner::CompiledRule;
+use deepaudit_core::{Rule, RuleScanner, Scanner};
+
+use crate::error::{AppError, ErrorCode};
+use crate::rule_store::{validate_rule_id, RuleOverride, RuleOverrides, RulePaths};
 use crate::state::AppState;
 
 /// 规则响应结构（与前端保持一致）
@@ -15,16 +22,104 @@ pub struct RuleResponse {
     pub language: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pattern: Option<String>,
+    /// 正则是否跨行匹配（`.` 匹配换行）
+    #[serde(default)]
+    pub multiline: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patterns: Option<PatternSet>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// 所属规则包，未归类的规则为 "default"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cwe: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owasp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paths: Option<PathFilter>,
+    /// 是否为内置规则，保存时总是写入用户目录
+    #[serde(default)]
+    pub builtin: bool,
+    /// 生效的启用状态（含覆盖配置）
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 严重级别覆盖，存在时 severity 即为覆盖后的值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity_override: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 规则列表过滤条件，各条件同时生效
+#[derive(Deserialize, Default)]
+pub struct RuleFilter {
+    pub category: Option<String>,
+    pub severity: Option<Severity>,
+    pub language: Option<String>,
+    /// 规则需包含该标签
+    pub tag: Option<String>,
+    pub pack: Option<String>,
+    pub confidence: Option<Confidence>,
+    pub enabled: Option<bool>,
+}
+
+impl RuleFilter {
+    /// 按规则的生效属性（含覆盖配置）匹配
+    fn matches(&self, rule: &RuleResponse) -> bool {
+        let eq = |filter: &Option<String>, value: Option<&str>| {
+            filter
+                .as_deref()
+                .is_none_or(|f| value.is_some_and(|v| v.eq_ignore_ascii_case(f)))
+        };
+        eq(&self.category, rule.category.as_deref())
+            && self
+                .severity
+                .as_ref()
+                .is_none_or(|s| format!("{:?}", s).eq_ignore_ascii_case(&rule.severity))
+            && eq(&self.language, Some(&rule.language))
+            && eq(&self.pack, rule.pack.as_deref())
+            && self
+                .tag
+                .as_deref()
+                .is_none_or(|tag| rule.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            && self.confidence.is_none_or(|c| rule.confidence == Some(c))
+            && self.enabled.is_none_or(|enabled| rule.enabled == enabled)
+    }
+}
+
+/// 未设置类别的规则在分组中使用的类别名
+const UNCATEGORIZED: &str = "uncategorized";
+
+/// 设置规则启用状态请求
+#[derive(Deserialize)]
+pub struct SetRuleEnabledRequest {
+    pub enabled: bool,
+}
+
+/// 设置严重级别覆盖请求，severity 为空时清除覆盖
+#[derive(Deserialize)]
+pub struct SetSeverityOverrideRequest {
+    pub severity: Option<Severity>,
 }
 
 impl From<deepaudit_core::rules::model::Rule> for RuleResponse {
     fn from(rule: deepaudit_core::rules::model::Rule) -> Self {
+        let pack = Some(rule.pack_name().to_string());
         RuleResponse {
             id: rule.id,
             name: rule.name,
@@ -32,13 +127,96 @@ impl From<deepaudit_core::rules::model::Rule> for RuleResponse {
             severity: format!("{:?}", rule.severity).to_lowercase(),
             language: rule.language,
             pattern: rule.pattern,
+            multiline: rule.multiline,
+            patterns: rule.patterns,
             query: rule.query,
             category: rule.category,
+            tags: rule.tags,
+            confidence: rule.confidence,
+            pack,
             cwe: rule.cwe,
+            owasp: rule.owasp,
+            remediation: rule.remediation,
+            references: rule.references,
+            fix: rule.fix,
+            paths: rule.paths,
+            builtin: rule.builtin,
+            enabled: rule.enabled,
+            severity_override: None,
         }
     }
 }
 
+/// 将生效规则转换为响应，并附带严重级别覆盖信息
+fn to_rule_response(rule: &Rule, overrides: &RuleOverrides) -> RuleResponse {
+    let mut response = RuleResponse::from(rule.clone());
+    response.severity_override = overrides
+        .rules
+        .get(&rule.id)
+        .and_then(|o| o.severity.as_ref())
+        .map(|s| format!("{:?}", s).to_lowercase());
+    response
+}
+
+/// 规则试运行请求
+#[derive(Deserialize)]
+pub struct TestRuleRequest {
+    pub rule: RuleResponse,
+    /// 用于语言匹配的文件名，如 `app.py`
+    pub file_name: String,
+    pub content: String,
+}
+
+/// 规则包信息
+#[derive(Serialize)]
+pub struct RulePackInfo {
+    pub name: String,
+    pub rule_count: usize,
+    /// 启用的规则数量（含覆盖配置）
+    pub enabled_count: usize,
+}
+
+/// 规则检查请求：提供 rule 时检查该规则（无需保存），否则检查已加载的规则
+#[derive(Deserialize, Default)]
+pub struct VerifyRulesRequest {
+    #[serde(default)]
+    pub rule: Option<RuleResponse>,
+    /// 只检查这些已加载的规则，为空时检查全部
+    #[serde(default)]
+    pub rule_ids: Vec<String>,
+}
+
+/// 远程规则包同步请求
+#[derive(Deserialize)]
+pub struct SyncRulesRequest {
+    /// 规则包索引地址（JSON，列出 YAML 文件及其 sha256）
+    pub url: String,
+}
+
+/// 规则重叠分析请求，语料来自项目目录和/或内联样本
+#[derive(Deserialize)]
+pub struct AnalyzeOverlapRequest {
+    pub project_path: Option<String>,
+    #[serde(default)]
+    pub samples: Vec<RuleSample>,
+}
+
+/// 内联样本文件
+#[derive(Deserialize)]
+pub struct RuleSample {
+    /// 用于语言匹配的文件名，如 `app.py`
+    pub file_name: String,
+    pub content: String,
+}
+
+/// 创建/更新规则的响应：保存后的规则及其文件的绝对路径
+#[derive(Serialize)]
+pub struct SavedRule {
+    #[serde(flatten)]
+    pub rule: RuleResponse,
+    pub saved_path: String,
+}
+
 /// 规则统计信息
 #[derive(Serialize)]
 pub struct RuleStats {
@@ -53,305 +231,636 @@ pub fn configure_rules_routes(cfg: &mut web::ServiceConfig) {
         .route("", web::get().to(get_rules))
         .route("", web::post().to(create_rule))
         .route("/stats", web::get().to(get_rule_stats))
+        .route("/by_category", web::get().to(get_rules_by_category))
+        .route("/reload", web::post().to(reload_rules))
+        .route("/paths", web::get().to(get_rules_paths))
+        .route("/packs", web::get().to(list_rule_packs))
+        .route("/test", web::post().to(test_rule))
+        .route("/shadowed", web::get().to(get_shadowed_rules))
+        .route("/load_errors", web::get().to(get_rule_load_errors))
+        .route("/verify", web::post().to(verify_rules))
+        .route("/sync", web::get().to(get_installed_bundle))
+        .route("/sync", web::post().to(sync_rules))
+        .route("/analyze_overlap", web::post().to(analyze_rule_overlap))
         .route("/{rule_id}", web::get().to(get_rule_by_id))
         .route("/{rule_id}", web::put().to(update_rule))
-        .route("/{rule_id}", web::delete().to(delete_rule));
+        .route("/{rule_id}", web::delete().to(delete_rule))
+        .route("/{rule_id}/enabled", web::put().to(set_rule_enabled))
+        .route("/{rule_id}/severity", web::put().to(set_rule_severity_override));
 }
 
-/// 获取所有规则列表
+/// 获取规则列表，可按类别、严重级别、语言、标签等过滤
 pub async fn get_rules(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
+    filter: web::Query<RuleFilter>,
 ) -> impl Responder {
-    // 从项目根目录的 rules 目录加载规则（web-backend 在项目根目录下）
-    let rules_path = std::path::Path::new("../rules");
+    HttpResponse::Ok().json(filtered_rules(&state, &filter))
+}
 
-    if !rules_path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Rules directory not found"
-        }));
+/// 按类别分组返回规则，组内按严重级别从高到低排列；未设置类别的规则归入 `uncategorized`
+pub async fn get_rules_by_category(
+    state: web::Data<AppState>,
+    filter: web::Query<RuleFilter>,
+) -> impl Responder {
+    let mut groups: std::collections::BTreeMap<String, Vec<RuleResponse>> = std::collections::BTreeMap::new();
+    for rule in filtered_rules(&state, &filter) {
+        let category = rule.category.clone().unwrap_or_else(|| UNCATEGORIZED.to_string());
+        groups.entry(category).or_default().push(rule);
     }
-
-    match deepaudit_core::rules::loader::load_rules_from_dir(rules_path) {
-        Ok(core_rules) => {
-            let rules: Vec<RuleResponse> = core_rules
-                .into_iter()
-                .map(|r| RuleResponse::from(r))
-                .collect();
-            HttpResponse::Ok().json(rules)
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to load rules: {}", e)
-            }))
-        }
+    for rules in groups.values_mut() {
+        rules.sort_by_key(|r| {
+            let rank = Severity::parse(&r.severity).map_or(0, |s| s.rank());
+            (std::cmp::Reverse(rank), r.id.clone())
+        });
     }
+    HttpResponse::Ok().json(groups)
+}
+
+/// 当前生效的规则中满足过滤条件的规则
+fn filtered_rules(state: &AppState, filter: &RuleFilter) -> Vec<RuleResponse> {
+    let snapshot = state.rules_snapshot();
+    let overrides = state.rule_overrides.read().unwrap_or_else(|e| e.into_inner());
+    snapshot
+        .rules
+        .iter()
+        .map(|r| to_rule_response(r, &overrides))
+        .filter(|r| filter.matches(r))
+        .collect()
 }
 
 /// 根据ID获取单个规则详情
 pub async fn get_rule_by_id(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
     path: web::Path<String>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let rule_id = path.into_inner();
 
-    let rules_path = std::path::Path::new("../rules");
+    let overrides = state.rule_overrides.read().unwrap_or_else(|e| e.into_inner());
+    let rule = state
+        .rules_snapshot()
+        .rules
+        .iter()
+        .find(|r| r.id == rule_id)
+        .map(|r| to_rule_response(r, &overrides))
+        .ok_or_else(|| rule_not_found(&rule_id))?;
 
-    if !rules_path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Rules directory not found"
-        }));
-    }
+    Ok(HttpResponse::Ok().json(rule))
+}
 
-    match deepaudit_core::rules::loader::load_rules_from_dir(rules_path) {
-        Ok(core_rules) => {
-            let rule = core_rules
-                .into_iter()
-                .find(|r| r.id == rule_id)
-                .map(|r| RuleResponse::from(r));
-
-            match rule {
-                Some(rule) => HttpResponse::Ok().json(rule),
-                None => HttpResponse::NotFound().json(serde_json::json!({
-                    "error": format!("Rule '{}' not found", rule_id)
-                })),
-            }
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to load rules: {}", e)
-            }))
-        }
-    }
+fn rule_not_found(rule_id: &str) -> AppError {
+    AppError::new(ErrorCode::RuleNotFound, format!("Rule '{}' not found", rule_id))
 }
 
 /// 获取规则统计信息
 pub async fn get_rule_stats(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
 ) -> impl Responder {
-    let rules_path = std::path::Path::new("../rules");
+    let snapshot = state.rules_snapshot();
+    let core_rules = &snapshot.rules;
+    let total = core_rules.len();
+
+    // 按严重级别统计
+    let mut by_severity = serde_json::Map::new();
+    for rule in core_rules {
+        let severity = format!("{:?}", rule.severity).to_lowercase();
+        let count = by_severity.entry(severity).or_insert(serde_json::json!(0));
+        if let Some(n) = count.as_i64() {
+            *count = serde_json::json!(n + 1);
+        }
+    }
 
-    if !rules_path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Rules directory not found"
-        }));
+    // 按语言统计
+    let mut by_language = serde_json::Map::new();
+    for rule in core_rules {
+        let count = by_language.entry(rule.language.clone()).or_insert(serde_json::json!(0));
+        if let Some(n) = count.as_i64() {
+            *count = serde_json::json!(n + 1);
+        }
     }
 
-    match deepaudit_core::rules::loader::load_rules_from_dir(rules_path) {
-        Ok(core_rules) => {
-            let total = core_rules.len();
-
-            // 按严重级别统计
-            let mut by_severity = serde_json::Map::new();
-            for rule in &core_rules {
-                let severity = format!("{:?}", rule.severity).to_lowercase();
-                let count = by_severity.entry(severity).or_insert(serde_json::json!(0));
-                if let Some(n) = count.as_i64() {
-                    *count = serde_json::json!(n + 1);
-                }
+    // 按类别统计
+    let mut by_category = serde_json::Map::new();
+    for rule in core_rules {
+        if let Some(category) = &rule.category {
+            let count = by_category.entry(category.clone()).or_insert(serde_json::json!(0));
+            if let Some(n) = count.as_i64() {
+                *count = serde_json::json!(n + 1);
             }
+        }
+    }
 
-            // 按语言统计
-            let mut by_language = serde_json::Map::new();
-            for rule in &core_rules {
-                let count = by_language.entry(rule.language.clone()).or_insert(serde_json::json!(0));
-                if let Some(n) = count.as_i64() {
-                    *count = serde_json::json!(n + 1);
-                }
-            }
+    let stats = RuleStats {
+        total,
+        by_severity: serde_json::to_value(by_severity).unwrap_or_default(),
+        by_language: serde_json::to_value(by_language).unwrap_or_default(),
+        by_category: serde_json::to_value(by_category).unwrap_or_default(),
+    };
 
-            // 按类别统计
-            let mut by_category = serde_json::Map::new();
-            for rule in &core_rules {
-                if let Some(category) = &rule.category {
-                    let count = by_category.entry(category.clone()).or_insert(serde_json::json!(0));
-                    if let Some(n) = count.as_i64() {
-                        *count = serde_json::json!(n + 1);
-                    }
-                }
-            }
+    HttpResponse::Ok().json(stats)
+}
 
-            let stats = RuleStats {
-                total,
-                by_severity: serde_json::to_value(by_severity).unwrap_or_default(),
-                by_language: serde_json::to_value(by_language).unwrap_or_default(),
-                by_category: serde_json::to_value(by_category).unwrap_or_default(),
-            };
+/// 重新加载规则目录并重建规则扫描器
+pub async fn reload_rules(
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let count = state
+        .reload_rules()
+        .map_err(|e| AppError::internal("Failed to reload rules", e))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "rules_loaded": count
+    })))
+}
 
-            HttpResponse::Ok().json(stats)
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to load rules: {}", e)
-            }))
+/// 启用或禁用规则（持久化到覆盖配置，不修改规则文件）
+pub async fn set_rule_enabled(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<SetRuleEnabledRequest>,
+) -> Result<HttpResponse, AppError> {
+    let rule_id = path.into_inner();
+
+    if !state.rules_snapshot().rules.iter().any(|r| r.id == rule_id) {
+        return Err(rule_not_found(&rule_id));
+    }
+
+    let enabled = req.enabled;
+    state
+        .update_rule_override(&rule_id, |o| {
+            // 启用是默认状态，无需记录
+            o.enabled = (!enabled).then_some(false);
+        })
+        .map_err(|e| AppError::io("Failed to update rule override", e))?;
+
+    tracing::info!("Set rule {} enabled={}", rule_id, enabled);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "rule_id": rule_id,
+        "enabled": enabled
+    })))
+}
+
+/// 设置或清除规则的严重级别覆盖
+pub async fn set_rule_severity_override(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<SetSeverityOverrideRequest>,
+) -> Result<HttpResponse, AppError> {
+    let rule_id = path.into_inner();
+
+    if !state.rules_snapshot().rules.iter().any(|r| r.id == rule_id) {
+        return Err(rule_not_found(&rule_id));
+    }
+
+    let severity = req.into_inner().severity;
+    state
+        .update_rule_override(&rule_id, |o| o.severity = severity.clone())
+        .map_err(|e| AppError::io("Failed to update rule override", e))?;
+
+    tracing::info!("Set rule {} severity override={:?}", rule_id, severity);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "rule_id": rule_id,
+        "severity_override": severity
+    })))
+}
+
+/// 在给定代码片段上试运行规则，不保存规则
+pub async fn test_rule(
+    req: web::Json<TestRuleRequest>,
+) -> Result<HttpResponse, AppError> {
+    let rule = validate_rule(&req.rule).map_err(invalid_rule)?;
+
+    let scanner = RuleScanner::new(vec![rule]);
+    let findings = scanner
+        .scan_file(std::path::Path::new(""), std::path::Path::new(&req.file_name), &req.content)
+        .await;
+
+    Ok(HttpResponse::Ok().json(findings))
+}
+
+fn invalid_rule(e: String) -> AppError {
+    AppError::invalid_input("Invalid rule").with_detail(e)
+}
+
+/// 列出已加载的规则包及其规则数量
+pub async fn list_rule_packs(
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let mut packs: std::collections::BTreeMap<&str, RulePackInfo> = std::collections::BTreeMap::new();
+    let snapshot = state.rules_snapshot();
+    for rule in &snapshot.rules {
+        let info = packs.entry(rule.pack_name()).or_insert_with(|| RulePackInfo {
+            name: rule.pack_name().to_string(),
+            rule_count: 0,
+            enabled_count: 0,
+        });
+        info.rule_count += 1;
+        if rule.enabled {
+            info.enabled_count += 1;
         }
     }
+    HttpResponse::Ok().json(packs.into_values().collect::<Vec<_>>())
 }
 
-/// 将 RuleResponse 转换为 YAML 格式
-fn rule_to_yaml(rule: &RuleResponse) -> String {
-    let mut yaml = String::new();
-    yaml.push_str(&format!("id: {}\n", rule.id));
-    yaml.push_str(&format!("name: {}\n", rule.name));
-    yaml.push_str(&format!("description: {}\n", rule.description));
-    yaml.push_str(&format!("severity: {}\n", rule.severity));
-    yaml.push_str(&format!("language: {}\n", rule.language));
-    if let Some(category) = &rule.category {
-        yaml.push_str(&format!("category: {}\n", category));
+/// 获取规则目录位置
+pub async fn get_rules_paths(
+    state: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(&state.rule_paths)
+}
+
+/// 获取加载时因 id 重复被遮蔽的规则
+pub async fn get_shadowed_rules(
+    state: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(&state.rules_snapshot().shadowed)
+}
+
+/// 获取加载规则时发现的编译错误与正则静态检查警告
+pub async fn get_rule_load_errors(
+    state: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(&state.rules_snapshot().diagnostics)
+}
+
+/// 检查规则：编译错误、可疑正则写法，并用对抗性输入测量匹配耗时
+pub async fn verify_rules(
+    state: web::Data<AppState>,
+    req: Option<web::Json<VerifyRulesRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let req = req.map(|r| r.into_inner()).unwrap_or_default();
+    let rules = match &req.rule {
+        Some(rule) => vec![to_core_rule(rule).map_err(invalid_rule)?],
+        None => state
+            .rules_snapshot()
+            .rules
+            .iter()
+            .filter(|r| req.rule_ids.is_empty() || req.rule_ids.contains(&r.id))
+            .cloned()
+            .collect(),
+    };
+
+    let rules_checked = rules.len();
+    let diagnostics = web::block(move || deepaudit_core::rules::lint::verify_rules(&rules, true))
+        .await
+        .map_err(|e| AppError::internal("Failed to verify rules", e))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "rules_checked": rules_checked,
+        "diagnostics": diagnostics
+    })))
+}
+
+/// 从远程索引同步规则包到用户规则目录的 remote/ 子目录，成功后重新加载规则
+pub async fn sync_rules(
+    state: web::Data<AppState>,
+    req: web::Json<SyncRulesRequest>,
+) -> Result<HttpResponse, AppError> {
+    let outcome = crate::rule_sync::sync_rules(&state.rule_paths, &state.data_dir, &req.url)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Rule sync from {} failed: {}", req.url, e);
+            AppError::new(ErrorCode::RuleSyncFailed, "Failed to sync rules").with_detail(e)
+        })?;
+
+    if !outcome.up_to_date {
+        reload_after_change(&state);
     }
-    if let Some(cwe) = &rule.cwe {
-        yaml.push_str(&format!("cwe: {}\n", cwe));
+    tracing::info!(
+        "Synced rule bundle {} version {} ({} downloaded, {} removed)",
+        outcome.source,
+        outcome.version,
+        outcome.downloaded.len(),
+        outcome.removed.len()
+    );
+    Ok(HttpResponse::Ok().json(outcome))
+}
+
+/// 获取已安装的远程规则包信息
+pub async fn get_installed_bundle(
+    state: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(crate::rule_sync::installed_bundle(&state.rule_paths))
+}
+
+/// 在样本语料上运行所有启用的规则，报告总是同时命中的规则对
+pub async fn analyze_rule_overlap(
+    state: web::Data<AppState>,
+    req: web::Json<AnalyzeOverlapRequest>,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    if req.project_path.is_none() && req.samples.is_empty() {
+        return Err(AppError::invalid_input("Either project_path or samples is required"));
+    }
+
+    let mut corpus = Vec::new();
+    if let Some(project_path) = &req.project_path {
+        let root = std::path::PathBuf::from(project_path);
+        if !root.is_dir() {
+            return Err(AppError::new(
+                ErrorCode::FileNotFound,
+                format!("Directory not found: {}", project_path),
+            ));
+        }
+        corpus = web::block(move || deepaudit_core::rules::overlap::load_corpus(&root))
+            .await
+            .unwrap_or_default();
+    }
+    corpus.extend(
+        req.samples
+            .into_iter()
+            .map(|s| (std::path::PathBuf::from(s.file_name), s.content)),
+    );
+
+    let rules = state.rules_snapshot().rules.clone();
+    let report = deepaudit_core::rules::overlap::analyze_rule_overlap(rules, &corpus).await;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// 规则文件变更后刷新规则快照
+fn reload_after_change(state: &AppState) {
+    if let Err(e) = state.reload_rules() {
+        tracing::error!("Failed to reload rules after change: {}", e);
     }
-    if let Some(pattern) = &rule.pattern {
-        yaml.push_str(&format!("pattern: {}\n", pattern));
+}
+
+/// 将 RuleResponse 转换为核心规则结构，同时校验严重级别
+fn to_core_rule(rule: &RuleResponse) -> Result<Rule, String> {
+    let severity = serde_json::from_value::<Severity>(serde_json::Value::String(
+        rule.severity.to_lowercase(),
+    ))
+    .map_err(|_| format!("Invalid severity '{}'", rule.severity))?;
+
+    Ok(Rule {
+        id: rule.id.clone(),
+        name: rule.name.clone(),
+        description: rule.description.clone(),
+        severity,
+        language: rule.language.clone(),
+        pattern: rule.pattern.clone(),
+        multiline: rule.multiline,
+        patterns: rule.patterns.clone(),
+        query: rule.query.clone(),
+        category: rule.category.clone(),
+        tags: rule.tags.clone(),
+        confidence: rule.confidence,
+        pack: rule.pack.clone().filter(|p| p != DEFAULT_PACK),
+        cwe: rule.cwe.clone(),
+        owasp: rule.owasp.clone(),
+        remediation: rule.remediation.clone(),
+        references: rule.references.clone(),
+        fix: rule.fix.clone(),
+        paths: rule.paths.clone(),
+        builtin: false,
+        enabled: rule.enabled,
+    })
+}
+
+/// 去掉请求体中来自覆盖配置的值
+///
+/// 规则接口返回的是生效值，客户端原样提交时严重级别或启用状态可能等于覆盖值。
+/// 与覆盖值相同的字段还原为规则文件中的原值，避免覆盖被写入 YAML 后无法清除；
+/// 与覆盖值不同的字段视为对规则本身的修改
+fn strip_overrides(rule: &mut RuleResponse, base: &Rule, overrides: &RuleOverride) {
+    if let Some(severity) = &overrides.severity {
+        if rule.severity.eq_ignore_ascii_case(&format!("{:?}", severity)) {
+            rule.severity = format!("{:?}", base.severity).to_lowercase();
+        }
     }
-    if let Some(query) = &rule.query {
-        yaml.push_str(&format!("query: {}\n", query));
+    if overrides.enabled == Some(rule.enabled) {
+        rule.enabled = base.enabled;
     }
-    yaml
+    rule.severity_override = None;
+}
+
+/// 校验规则能否被扫描器编译
+fn validate_rule(rule: &RuleResponse) -> Result<Rule, String> {
+    validate_rule_id(&rule.id)?;
+    let core_rule = to_core_rule(rule)?;
+    CompiledRule::compile(&core_rule)?;
+    Ok(core_rule)
 }
 
-/// 保存规则到文件
-fn save_rule_to_file(rule: &RuleResponse, rules_path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
-    let file_name = format!("{}.yaml", rule.id);
-    let file_path = rules_path.join(&file_name);
+/// 将 RuleResponse 转换为 YAML 格式
+fn rule_to_yaml(rule: &RuleResponse) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_yaml::to_string(&to_core_rule(rule)?)?)
+}
+
+/// 将规则写入用户规则目录，返回写入的文件路径
+fn save_rule_to_file(rule: &RuleResponse, rule_paths: &RulePaths) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    rule_paths.ensure_user_dir()?;
+    let file_path = rule_paths.user_rule_file(&rule.id)?;
 
-    let yaml_content = rule_to_yaml(rule);
+    let yaml_content = rule_to_yaml(rule)?;
 
     let mut file = fs::File::create(&file_path)?;
     file.write_all(yaml_content.as_bytes())?;
 
-    Ok(())
+    Ok(file_path)
 }
 
 /// 创建新规则
 pub async fn create_rule(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
     rule: web::Json<RuleResponse>,
-) -> impl Responder {
-    let rules_path = std::path::Path::new("../rules");
-
-    // 确保规则目录存在
-    if !rules_path.exists() {
-        if let Err(e) = fs::create_dir_all(rules_path) {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create rules directory: {}", e)
-            }));
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let mut rule = rule.into_inner();
+    rule.builtin = false;
 
     // 检查规则ID是否已存在
-    let existing_rules = match deepaudit_core::rules::loader::load_rules_from_dir(rules_path) {
-        Ok(rules) => rules,
-        Err(_) => vec![],
-    };
-
-    if existing_rules.iter().any(|r| r.id == rule.id) {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": format!("Rule with ID '{}' already exists", rule.id)
-        }));
+    if state.rules_snapshot().rules.iter().any(|r| r.id == rule.id) {
+        return Err(AppError::new(
+            ErrorCode::RuleAlreadyExists,
+            format!("Rule with ID '{}' already exists", rule.id),
+        ));
     }
 
+    // 校验规则
+    validate_rule(&rule).map_err(invalid_rule)?;
+
     // 保存规则到文件
-    match save_rule_to_file(&rule, rules_path) {
-        Ok(_) => {
-            tracing::info!("Created new rule: {}", rule.id);
-            HttpResponse::Ok().json(rule.into_inner())
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to save rule: {}", e)
-            }))
-        }
-    }
+    let saved_path =
+        save_rule_to_file(&rule, &state.rule_paths).map_err(|e| AppError::io("Failed to save rule", e))?;
+    tracing::info!("Created new rule {} at {}", rule.id, saved_path.display());
+    reload_after_change(&state);
+    Ok(HttpResponse::Ok().json(SavedRule {
+        rule,
+        saved_path: saved_path.display().to_string(),
+    }))
 }
 
 /// 更新规则
 pub async fn update_rule(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
     path: web::Path<String>,
     rule: web::Json<RuleResponse>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let rule_id = path.into_inner();
-    let rules_path = std::path::Path::new("../rules");
 
-    if !rules_path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Rules directory not found"
-        }));
+    // 检查规则是否存在
+    if !state.rules_snapshot().rules.iter().any(|r| r.id == rule_id) {
+        return Err(rule_not_found(&rule_id));
     }
 
-    // 检查规则是否存在
-    let existing_rules = match deepaudit_core::rules::loader::load_rules_from_dir(rules_path) {
-        Ok(rules) => rules,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to load rules: {}", e)
-            }));
+    let mut rule_data = rule.into_inner();
+    rule_data.builtin = false;
+
+    let overrides = state
+        .rule_overrides
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .rules
+        .get(&rule_id)
+        .cloned();
+    if let Some(overrides) = overrides {
+        let loaded = state
+            .rule_paths
+            .load_rules()
+            .map_err(|e| AppError::internal("Failed to load rules", e))?;
+        if let Some(base) = loaded.rules.iter().find(|r| r.id == rule_id) {
+            strip_overrides(&mut rule_data, base, &overrides);
         }
-    };
-
-    if !existing_rules.iter().any(|r| r.id == rule_id) {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": format!("Rule '{}' not found", rule_id)
-        }));
     }
 
-    // 如果ID发生变化，需要删除旧文件
-    let rule_data = rule.into_inner();
-    if rule_data.id != rule_id {
-        let old_file = rules_path.join(format!("{}.yaml", rule_id));
-        let _ = fs::remove_file(&old_file);
-    }
+    // 校验规则
+    validate_rule(&rule_data).map_err(invalid_rule)?;
 
-    // 保存更新后的规则
-    match save_rule_to_file(&rule_data, rules_path) {
-        Ok(_) => {
-            tracing::info!("Updated rule: {}", rule_data.id);
-            HttpResponse::Ok().json(rule_data)
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to save rule: {}", e)
-            }))
+    // 如果ID发生变化，需要删除用户目录中的旧文件
+    if rule_data.id != rule_id {
+        if let Ok(old_file) = state.rule_paths.user_rule_file(&rule_id) {
+            let _ = fs::remove_file(&old_file);
         }
     }
+
+    // 保存更新后的规则（内置规则会在用户目录生成覆盖副本）
+    let saved_path =
+        save_rule_to_file(&rule_data, &state.rule_paths).map_err(|e| AppError::io("Failed to save rule", e))?;
+    tracing::info!("Updated rule {} at {}", rule_data.id, saved_path.display());
+    reload_after_change(&state);
+    Ok(HttpResponse::Ok().json(SavedRule {
+        rule: rule_data,
+        saved_path: saved_path.display().to_string(),
+    }))
 }
 
 /// 删除规则
 pub async fn delete_rule(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
     path: web::Path<String>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let rule_id = path.into_inner();
-    let rules_path = std::path::Path::new("../rules");
+    validate_rule_id(&rule_id).map_err(|e| AppError::invalid_input("Invalid rule id").with_detail(e))?;
+    let file_path = state
+        .rule_paths
+        .user_rule_file(&rule_id)
+        .map_err(|e| AppError::io("Failed to resolve rule file", e))?;
 
-    if !rules_path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Rules directory not found"
-        }));
+    if !file_path.exists() {
+        // 内置规则不在用户目录中，无法删除
+        if state.rules_snapshot().rules.iter().any(|r| r.id == rule_id) {
+            return Err(AppError::new(
+                ErrorCode::RuleReadOnly,
+                format!("Rule '{}' is a bundled rule and cannot be deleted", rule_id),
+            ));
+        }
+        return Err(rule_not_found(&rule_id));
     }
 
-    let file_name = format!("{}.yaml", rule_id);
-    let file_path = rules_path.join(&file_name);
+    fs::remove_file(&file_path).map_err(|e| AppError::io("Failed to delete rule", e))?;
+    tracing::info!("Deleted rule: {}", rule_id);
+    reload_after_change(&state);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("Rule '{}' deleted successfully", rule_id)
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !file_path.exists() {
-        return HttpResponse::NotFound().json(serde_json::json!({
-            "error": format!("Rule '{}' not found", rule_id)
-        }));
+    fn base_rule() -> Rule {
+        serde_yaml::from_str(
+            "id: sample-rule\nname: Sample\ndescription: Sample rule\nseverity: low\nlanguage: python\npattern: eval\\(\n",
+        )
+        .unwrap()
     }
 
-    match fs::remove_file(&file_path) {
-        Ok(_) => {
-            tracing::info!("Deleted rule: {}", rule_id);
-            HttpResponse::Ok().json(serde_json::json!({
-                "success": true,
-                "message": format!("Rule '{}' deleted successfully", rule_id)
-            }))
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to delete rule: {}", e)
-            }))
+    #[test]
+    fn echoed_override_is_not_written_to_yaml() {
+        let base = base_rule();
+        let overrides = RuleOverride {
+            enabled: Some(false),
+            severity: Some(Severity::Critical),
+        };
+        let mut effective = base.clone();
+        effective.enabled = false;
+        effective.severity = Severity::Critical;
+
+        let mut body = RuleResponse::from(effective);
+        body.severity_override = Some("critical".to_string());
+        strip_overrides(&mut body, &base, &overrides);
+
+        let core = to_core_rule(&body).unwrap();
+        assert_eq!(core.severity, Severity::Low);
+        assert!(core.enabled);
+    }
+
+    #[test]
+    fn edited_severity_is_kept() {
+        let base = base_rule();
+        let overrides = RuleOverride {
+            enabled: None,
+            severity: Some(Severity::Critical),
+        };
+        let mut body = RuleResponse::from(base.clone());
+        body.severity = "medium".to_string();
+        strip_overrides(&mut body, &base, &overrides);
+
+        assert_eq!(to_core_rule(&body).unwrap().severity, Severity::Medium);
+    }
+
+    #[test]
+    fn disabled_rule_stays_disabled() {
+        let mut rule = base_rule();
+        rule.enabled = false;
+        let yaml = rule_to_yaml(&RuleResponse::from(rule)).unwrap();
+        assert!(yaml.contains("enabled: false"));
+    }
+
+    #[test]
+    fn save_rule_rejects_traversal_ids() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let rule_paths = RulePaths {
+            bundled_dir: None,
+            user_dir: data_dir.path().join("rules"),
+        };
+        for id in ["../evil", "../../etc/cron.d/evil", "a/b", "x.y", "..", ""] {
+            let mut rule = RuleResponse::from(base_rule());
+            rule.id = id.to_string();
+            assert!(validate_rule(&rule).is_err(), "{}", id);
+            assert!(save_rule_to_file(&rule, &rule_paths).is_err(), "{}", id);
         }
+        // 用户目录之外没有写入任何文件
+        let entries: Vec<_> = fs::read_dir(data_dir.path()).unwrap().flatten().map(|e| e.file_name()).collect();
+        assert_eq!(entries, ["rules"]);
+        assert_eq!(fs::read_dir(&rule_paths.user_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn save_rule_accepts_valid_id() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let rule_paths = RulePaths {
+            bundled_dir: None,
+            user_dir: data_dir.path().join("rules"),
+        };
+        let mut rule = RuleResponse::from(base_rule());
+        rule.id = "ok_id-1".to_string();
+        assert!(validate_rule(&rule).is_ok());
+
+        let file_path = save_rule_to_file(&rule, &rule_paths).unwrap();
+        assert_eq!(file_path, rule_paths.user_dir.join("ok_id-1.yaml"));
+        let saved: Rule = serde_yaml::from_str(&fs::read_to_string(file_path).unwrap()).unwrap();
+        assert_eq!(saved.id, "ok_id-1");
     }
 }
@@ -4,9 +4,19 @@ use serde::{Deserialize, Serialize};
 use std::io::Write;
 use tempfile::tempdir;
 use futures_util::TryStreamExt;
-use uuid::Uuid;
 
-use crate::state::AppState;
+use crate::api::diff::comparison_error;
+use crate::api::event_stream;
+use crate::api::project::ProjectSettings;
+use crate::error::{AppError, ErrorCode};
+use crate::state::{AppState, RuleSnapshot};
+use crate::watcher::ScanEvent;
+use deepaudit_core::rules::model::Severity;
+use deepaudit_core::scanner::confidence::{priority, DEFAULT_CONFIDENCE};
+use deepaudit_core::scanner::filter_by_severity;
+use deepaudit_core::ScannerManager;
+use deepaudit_core::diff::{ComparisonConfig, DiffEngine, DiffLine, GitIntegration, LineBlame};
+use sqlx::FromRow;
 
 #[derive(Serialize, Deserialize)]
 pub struct ScanRequest {
@@ -14,36 +24,112 @@ pub struct ScanRequest {
     #[serde(default)]
     pub project_id: Option<i64>,
     pub rules: Option<Vec<String>>,
+    /// 覆盖项目设置中的最低保存严重程度
+    #[serde(default)]
+    pub min_severity: Option<Severity>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Finding {
     pub id: String,
     pub file_path: String,
     pub line_start: usize,
     pub line_end: usize,
     pub detector: String,
+    /// 合并重复发现时参与命中的全部扫描器
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub detectors: Vec<String>,
     pub vuln_type: String,
     pub severity: String,
+    /// 命中为真实问题的把握（0-1）
+    pub confidence: f32,
     pub description: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code_snippet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwe: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owasp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    /// 修复建议，应用后替换命中的原始文本
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_fix: Option<String>,
+    /// 命中的原始文本，仅用于应用修复前的校验
+    #[serde(skip_serializing)]
+    pub matched_text: Option<String>,
+    /// 引入 `line_start` 所在行的提交，通过 `blame_finding` 按需填写
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blame: Option<LineBlame>,
+    /// 不含行号的指纹，用于与基线比较；引入指纹前保存的发现为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+impl From<deepaudit_core::Finding> for Finding {
+    fn from(f: deepaudit_core::Finding) -> Self {
+        Finding {
+            id: f.finding_id,
+            file_path: f.file_path,
+            line_start: f.line_start,
+            line_end: f.line_end,
+            detector: f.detector,
+            detectors: f.detectors,
+            vuln_type: f.vuln_type,
+            severity: f.severity,
+            confidence: f.confidence,
+            description: f.description,
+            code_snippet: None,
+            rule_id: f.rule_id,
+            cwe: f.cwe,
+            owasp: f.owasp,
+            remediation: f.remediation,
+            references: f.references,
+            suggested_fix: f.suggested_fix,
+            matched_text: f.matched_text,
+            blame: None,
+            fingerprint: Some(f.fingerprint).filter(|fingerprint| !fingerprint.is_empty()),
+        }
+    }
 }
 
 #[derive(Serialize)]
 pub struct ScanResult {
     pub findings: Vec<Finding>,
     pub files_scanned: usize,
+    /// 低于最低严重程度而未保存的发现数
+    pub findings_suppressed: usize,
     pub scan_time: String,
     pub scan_id: Option<i64>,
+    /// 保存到数据库失败的发现数（保存在单个事务中，失败时全部未保存）
+    pub findings_failed: usize,
+    /// 保存失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store_error: Option<String>,
 }
 
 pub fn configure_scanner_routes(cfg: &mut web::ServiceConfig) {
     cfg
         .route("/scan", web::post().to(run_scan))
+        .route("/scan-range", web::post().to(scan_git_range))
         .route("/upload", web::post().to(upload_and_scan))
         .route("/findings/{project_id}", web::get().to(get_findings))
-        .route("/scans/{project_id}", web::get().to(get_scans));  // 新增：获取扫描历史
+        .route("/findings/{finding_id}/preview_fix", web::post().to(preview_fix))
+        .route("/findings/{finding_id}/apply_fix", web::post().to(apply_fix))
+        .route("/findings/{finding_id}/blame", web::get().to(finding_blame))
+        .route("/findings/{finding_id}/blame", web::post().to(blame_finding))
+        .route("/baseline/{project_id}", web::post().to(save_baseline))
+        .route("/scans/{project_id}", web::get().to(get_scans))  // 新增：获取扫描历史
+        .route("/events", web::get().to(scan_events));           // 增量扫描事件（SSE）
+}
+
+/// 以 Server-Sent Events 推送监听项目的增量扫描事件（`scan-finding` / `finding-removed`）
+pub async fn scan_events(state: web::Data<AppState>) -> impl Responder {
+    event_stream(state.scan_events.subscribe())
 }
 
 #[derive(Serialize)]
@@ -52,24 +138,32 @@ pub struct ScanRecord {
     pub status: String,
     pub files_scanned: i64,
     pub findings_found: i64,
+    pub findings_suppressed: i64,
     pub started_at: String,
     pub completed_at: Option<String>,
+    /// 只扫描了 `base_ref` 与 `head_ref` 之间变更行的扫描
+    pub range_scan: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_ref: Option<String>,
 }
 
 /// 获取项目的扫描历史
 pub async fn get_scans(
     state: web::Data<AppState>,
     path: web::Path<i64>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let project_id = path.into_inner();
 
-    let scans = match sqlx::query_as::<_, (i64, String, i64, i64, String, Option<String>)>(
-        "SELECT id, status, files_scanned, findings_found,
+    let scans = sqlx::query_as::<_, (i64, String, i64, i64, i64, String, Option<String>, bool, Option<String>, Option<String>)>(
+        "SELECT id, status, files_scanned, findings_found, COALESCE(findings_suppressed, 0),
                 datetime(started_at) as started_at,
                 CASE WHEN completed_at IS NOT NULL
                      THEN datetime(completed_at)
                      ELSE NULL
-                END as completed_at
+                END as completed_at,
+                range_scan, base_ref, head_ref
          FROM scans
          WHERE project_id = ?
          ORDER BY started_at DESC"
@@ -77,77 +171,93 @@ pub async fn get_scans(
     .bind(project_id)
     .fetch_all(&state.db)
     .await
-    {
-        Ok(scans) => scans,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch scans: {}", e)
-            }));
-        }
-    };
+    .map_err(|e| AppError::database("Failed to fetch scans", e))?;
 
     let scans: Vec<ScanRecord> = scans
         .into_iter()
-        .map(|(id, status, files_scanned, findings_found, started_at, completed_at)| ScanRecord {
-            id,
-            status,
-            files_scanned,
-            findings_found,
-            started_at,
-            completed_at,
-        })
+        .map(
+            |(id, status, files_scanned, findings_found, findings_suppressed, started_at, completed_at, range_scan, base_ref, head_ref)| {
+                ScanRecord {
+                    id,
+                    status,
+                    files_scanned,
+                    findings_found,
+                    findings_suppressed,
+                    started_at,
+                    completed_at,
+                    range_scan,
+                    base_ref,
+                    head_ref,
+                }
+            },
+        )
         .collect();
 
-    HttpResponse::Ok().json(scans)
+    Ok(HttpResponse::Ok().json(scans))
 }
 
-/// 将扫描结果存储到数据库
+/// 插入一条漏洞发现，finding_id 已存在时跳过，返回是否插入
+pub(crate) async fn insert_finding(
+    conn: &mut sqlx::SqliteConnection,
+    project_id: i64,
+    finding: &Finding,
+) -> anyhow::Result<bool> {
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO findings (project_id, finding_id, file_path, line_start, line_end, detector, vuln_type, severity, description,
+                                         rule_id, cwe, owasp, remediation, reference_links, matched_text, suggested_fix, detectors, confidence, fingerprint)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+    .bind(project_id)
+    .bind(&finding.id)
+    .bind(&finding.file_path)
+    .bind(finding.line_start as i64)
+    .bind(finding.line_end as i64)
+    .bind(&finding.detector)
+    .bind(&finding.vuln_type)
+    .bind(&finding.severity)
+    .bind(&finding.description)
+    .bind(&finding.rule_id)
+    .bind(&finding.cwe)
+    .bind(&finding.owasp)
+    .bind(&finding.remediation)
+    .bind(serde_json::to_string(&finding.references)?)
+    .bind(&finding.matched_text)
+    .bind(&finding.suggested_fix)
+    .bind(serde_json::to_string(&finding.detectors)?)
+    .bind(finding.confidence)
+    .bind(&finding.fingerprint)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 将扫描结果存储到数据库；`range` 为变更行扫描比较的 `(base_ref, head_ref)`
 async fn store_scan_results(
     state: &AppState,
     project_id: i64,
     findings: &[Finding],
     files_scanned: usize,
+    findings_suppressed: usize,
+    range: Option<(&str, &str)>,
 ) -> Result<i64, Box<dyn std::error::Error>> {
     // 开始事务
     let mut tx = state.db.begin().await?;
 
     // 1. 创建扫描记录
     let scan_id = sqlx::query_scalar::<_, i64>(
-        "INSERT INTO scans (project_id, status, files_scanned, findings_found)
-         VALUES (?, 'running', 0, 0)
+        "INSERT INTO scans (project_id, status, files_scanned, findings_found, range_scan, base_ref, head_ref)
+         VALUES (?, 'running', 0, 0, ?, ?, ?)
          RETURNING id"
     )
     .bind(project_id)
+    .bind(range.is_some())
+    .bind(range.map(|(base_ref, _)| base_ref))
+    .bind(range.map(|(_, head_ref)| head_ref))
     .fetch_one(&mut *tx)
     .await?;
 
     // 2. 批量插入漏洞发现
     for finding in findings {
-        // 检查是否已存在（基于 finding_id）
-        let exists = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM findings WHERE finding_id = ?"
-        )
-        .bind(&finding.id)
-        .fetch_one(&mut *tx)
-        .await?;
-
-        if exists == 0 {
-            // 插入新记录
-            sqlx::query(
-                "INSERT INTO findings (project_id, finding_id, file_path, line_start, line_end, detector, vuln_type, severity, description)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
-            .bind(project_id)
-            .bind(&finding.id)
-            .bind(&finding.file_path)
-            .bind(finding.line_start as i64)
-            .bind(finding.line_end as i64)
-            .bind(&finding.detector)
-            .bind(&finding.vuln_type)
-            .bind(&finding.severity)
-            .bind(&finding.description)
-            .execute(&mut *tx)
-            .await?;
-        }
+        insert_finding(&mut tx, project_id, finding).await?;
     }
 
     // 3. 更新扫描记录状态
@@ -157,11 +267,13 @@ async fn store_scan_results(
          SET status = 'completed',
              files_scanned = ?,
              findings_found = ?,
+             findings_suppressed = ?,
              completed_at = ?
          WHERE id = ?"
     )
     .bind(files_scanned as i64)
     .bind(findings.len() as i64)
+    .bind(findings_suppressed as i64)
     .bind(&now)
     .bind(scan_id)
     .execute(&mut *tx)
@@ -176,78 +288,216 @@ async fn store_scan_results(
 pub async fn run_scan(
     state: web::Data<AppState>,
     req: web::Json<ScanRequest>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
+    crate::api::project::validate_project_dir(&req.project_path)?;
+
     // 运行扫描
     let start = std::time::Instant::now();
 
-    // 调用 core 库的扫描函数
-    let core_findings = match deepaudit_core::scan_directory(&req.project_path).await {
-        Ok(findings) => findings,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Scan failed: {}", e)
-            }));
-        }
-    };
+    // 使用当前规则快照扫描，期间重新加载规则不影响本次扫描
+    let snapshot = state.rules_snapshot();
+    let ProjectScanner { scanner, min_severity } =
+        scanner_for_project(&state, &snapshot, req.project_id).await;
+    let min_severity = req.min_severity.clone().unwrap_or(min_severity);
+    let (core_findings, findings_suppressed) =
+        filter_by_severity(scanner.scan_directory(&req.project_path).await, &min_severity);
+    if findings_suppressed > 0 {
+        tracing::info!("Suppressed {} findings below {:?}", findings_suppressed, min_severity);
+    }
 
     let scan_time = format!("{:?}", start.elapsed());
 
     // 转换结果格式
     let findings: Vec<Finding> = core_findings
         .into_iter()
-        .map(|f| Finding {
-            id: f.finding_id,
-            file_path: f.file_path,
-            line_start: f.line_start,
-            line_end: f.line_end,
-            detector: f.detector,
-            vuln_type: f.vuln_type,
-            severity: f.severity,
-            description: f.description,
-            code_snippet: None,
-        })
+        .map(Finding::from)
         .collect();
 
     let files_scanned = findings.len();
-    let mut scan_id = None;
 
     // 如果提供了 project_id，将结果存入数据库
-    if let Some(project_id) = req.project_id {
-        match store_scan_results(&state, project_id, &findings, files_scanned).await {
-            Ok(id) => {
-                scan_id = Some(id);
-                tracing::info!("Stored {} findings for project {}", findings.len(), project_id);
-            }
-            Err(e) => {
-                tracing::error!("Failed to store scan results: {}", e);
-                // 继续返回结果，即使存储失败
-            }
+    let (scan_id, findings_failed, store_error) = match req.project_id {
+        Some(project_id) => save_scan(&state, project_id, &findings, files_scanned, findings_suppressed, None).await,
+        None => {
+            tracing::warn!("No project_id provided, scan results not stored to database");
+            (None, 0, None)
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(ScanResult {
+        findings,
+        files_scanned,
+        findings_suppressed,
+        scan_time,
+        scan_id,
+        findings_failed,
+        store_error,
+    }))
+}
+
+/// 保存扫描结果，返回扫描记录 id、保存失败的发现数与失败原因
+///
+/// 保存失败时仍返回扫描结果，通过事件和响应告知前端
+async fn save_scan(
+    state: &AppState,
+    project_id: i64,
+    findings: &[Finding],
+    files_scanned: usize,
+    findings_suppressed: usize,
+    range: Option<(&str, &str)>,
+) -> (Option<i64>, usize, Option<String>) {
+    match store_scan_results(state, project_id, findings, files_scanned, findings_suppressed, range).await {
+        Ok(id) => {
+            tracing::info!("Stored {} findings for project {}", findings.len(), project_id);
+            (Some(id), 0, None)
+        }
+        Err(e) => {
+            tracing::error!("Failed to store {} findings for project {}: {}", findings.len(), project_id, e);
+            let findings_failed = findings.len();
+            let _ = state.scan_events.send(ScanEvent::Error {
+                project_id,
+                file_path: None,
+                error: e.to_string(),
+                findings_failed,
+            });
+            (None, findings_failed, Some(e.to_string()))
         }
-    } else {
-        tracing::warn!("No project_id provided, scan results not stored to database");
     }
+}
 
-    HttpResponse::Ok().json(ScanResult {
+#[derive(Deserialize)]
+pub struct RangeScanRequest {
+    pub project_id: i64,
+    /// 仓库路径，可以是仓库内的任意目录
+    pub repository_path: String,
+    /// 比较的基准引用（如目标分支），其中已有的代码不扫描
+    pub base_ref: String,
+    /// 要检查的引用（如待合并的分支），也可以是 `INDEX` 或 `WORKTREE`
+    pub head_ref: String,
+    /// 覆盖项目设置中的最低保存严重程度
+    #[serde(default)]
+    pub min_severity: Option<Severity>,
+}
+
+/// 只扫描 `head_ref` 相对 `base_ref` 新增或修改的代码：扫描 `head_ref` 一侧变更文件的内容，
+/// 只保留所在行与新增、修改行相交的发现；重命名的文件按新路径报告，扫描记录标记为变更行扫描
+pub async fn scan_git_range(
+    state: web::Data<AppState>,
+    req: web::Json<RangeScanRequest>,
+) -> Result<HttpResponse, AppError> {
+    let RangeScanRequest {
+        project_id,
+        repository_path,
+        base_ref,
+        head_ref,
+        min_severity,
+    } = req.into_inner();
+    crate::api::project::validate_project_dir(&repository_path)?;
+
+    let start = std::time::Instant::now();
+    let (repo_root, changed_files) = {
+        let (base_ref, head_ref) = (base_ref.clone(), head_ref.clone());
+        web::block(move || GitIntegration::new().changed_line_ranges(&repository_path, &base_ref, &head_ref))
+            .await
+            .map_err(|e| AppError::internal("Git comparison task failed", e))?
+            .map_err(|e| comparison_error("Failed to list changed lines", e))?
+    };
+
+    let snapshot = state.rules_snapshot();
+    let ProjectScanner { scanner, min_severity: project_min_severity } =
+        scanner_for_project(&state, &snapshot, Some(project_id)).await;
+    let min_severity = min_severity.unwrap_or(project_min_severity);
+
+    // 指纹按相对项目目录的路径计算，与完整扫描一致；不在项目目录中的文件相对仓库根目录
+    let project_path = sqlx::query_scalar::<_, String>("SELECT path FROM projects WHERE id = ?")
+        .bind(project_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::database("Failed to load project", e))?
+        .map(std::path::PathBuf::from);
+
+    // 与目录扫描一致地跳过隐藏、忽略与不支持的文件
+    let mut files_scanned = 0;
+    let mut core_findings = Vec::new();
+    for file in &changed_files {
+        let path = repo_root.join(&file.path);
+        if !ScannerManager::is_scan_target(&repo_root, &path) {
+            continue;
+        }
+        files_scanned += 1;
+        let fingerprint_root = project_path.as_deref().filter(|root| path.starts_with(root)).unwrap_or(&repo_root);
+        core_findings.extend(
+            scanner
+                .scan_project_file(fingerprint_root, &path, &file.content)
+                .await
+                .into_iter()
+                .filter(|finding| file.intersects(finding.line_start as u32, finding.line_end as u32)),
+        );
+    }
+    let (core_findings, findings_suppressed) = filter_by_severity(core_findings, &min_severity);
+    if findings_suppressed > 0 {
+        tracing::info!("Suppressed {} findings below {:?}", findings_suppressed, min_severity);
+    }
+    let scan_time = format!("{:?}", start.elapsed());
+
+    let findings: Vec<Finding> = core_findings.into_iter().map(Finding::from).collect();
+    let range = Some((base_ref.as_str(), head_ref.as_str()));
+    let (scan_id, findings_failed, store_error) =
+        save_scan(&state, project_id, &findings, files_scanned, findings_suppressed, range).await;
+
+    Ok(HttpResponse::Ok().json(ScanResult {
         findings,
         files_scanned,
+        findings_suppressed,
         scan_time,
         scan_id,
-    })
+        findings_failed,
+        store_error,
+    }))
+}
+
+/// 按项目设置构建的扫描器及保存发现的最低严重程度
+pub(crate) struct ProjectScanner {
+    pub scanner: ScannerManager,
+    pub min_severity: Severity,
+}
+
+/// 按项目设置中启用的规则包构建扫描器，未设置时使用完整规则快照；最低严重程度默认为 Info（保存全部）
+pub(crate) async fn scanner_for_project(
+    state: &AppState,
+    snapshot: &RuleSnapshot,
+    project_id: Option<i64>,
+) -> ProjectScanner {
+    let settings = match project_id {
+        Some(project_id) => crate::api::project::load_project_settings(&state.db, project_id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to load settings for project {}: {}", project_id, e);
+                None
+            })
+            .unwrap_or_default(),
+        None => ProjectSettings::default(),
+    };
+
+    let scanner = match &settings.enabled_packs {
+        Some(packs) => {
+            tracing::info!("Scanning project {:?} with rule packs {:?}", project_id, packs);
+            snapshot.scanner.with_rule_packs(&snapshot.rules, packs)
+        }
+        None => snapshot.scanner.clone(),
+    };
+    ProjectScanner {
+        scanner,
+        min_severity: settings.min_severity.unwrap_or(Severity::Info),
+    }
 }
 
 pub async fn upload_and_scan(
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
     mut payload: Multipart,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     // 创建临时目录
-    let temp_dir_obj = match tempdir() {
-        Ok(dir) => dir,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to create temp dir: {}", e)
-            }));
-        }
-    };
+    let temp_dir_obj = tempdir().map_err(|e| AppError::io("Failed to create temp dir", e))?;
     let project_path = temp_dir_obj.path().to_string_lossy().to_string();
 
     // 处理上传的文件
@@ -261,36 +511,18 @@ pub async fn upload_and_scan(
                     .to_string();
 
                 let limit = 1024 * 1024 * 1024; // 1GB limit
-                let data = match field.bytes(limit).await {
-                    Ok(Ok(bytes)) => Vec::from(bytes.as_ref()),
-                    Ok(Err(e)) => {
-                        return HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": format!("Failed to read field: {}", e)
-                        }));
-                    }
-                    Err(_) => {
-                        return HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": "File size limit exceeded"
-                        }));
-                    }
-                };
+                let data = field
+                    .bytes(limit)
+                    .await
+                    .map_err(|_| AppError::new(ErrorCode::UploadTooLarge, "File size limit exceeded"))?
+                    .map_err(|e| AppError::new(ErrorCode::UploadFailed, "Failed to read field").with_detail(e))?;
 
                 // 保存文件
                 let file_path = std::path::PathBuf::from(&project_path).join(&filename);
-                match std::fs::File::create(&file_path) {
-                    Ok(mut file) => {
-                        if let Err(e) = file.write_all(&data) {
-                            return HttpResponse::InternalServerError().json(serde_json::json!({
-                                "error": format!("Failed to write file: {}", e)
-                            }));
-                        }
-                    }
-                    Err(e) => {
-                        return HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": format!("Failed to create file: {}", e)
-                        }));
-                    }
-                }
+                let mut file = std::fs::File::create(&file_path)
+                    .map_err(|e| AppError::io("Failed to create file", e))?;
+                file.write_all(&data)
+                    .map_err(|e| AppError::io("Failed to write file", e))?;
             }
             Ok(None) => {
                 // 没有更多字段了，退出循环
@@ -303,78 +535,533 @@ pub async fn upload_and_scan(
     }
 
     // 运行扫描
-    let findings = match deepaudit_core::scan_directory(&project_path).await {
-        Ok(findings) => findings,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Scan failed: {}", e)
-            }));
-        }
-    };
+    let snapshot = state.rules_snapshot();
+    let findings = snapshot.scanner.scan_directory(&project_path).await;
 
     let findings: Vec<Finding> = findings
         .into_iter()
-        .map(|f| Finding {
-            id: f.finding_id,
-            file_path: f.file_path,
-            line_start: f.line_start,
-            line_end: f.line_end,
-            detector: f.detector,
-            vuln_type: f.vuln_type,
-            severity: f.severity,
-            description: f.description,
-            code_snippet: None,
-        })
+        .map(Finding::from)
         .collect();
 
     let files_scanned = findings.len();
 
-    HttpResponse::Ok().json(ScanResult {
+    Ok(HttpResponse::Ok().json(ScanResult {
         findings,
         files_scanned,
+        findings_suppressed: 0,
         scan_time: "upload scan".to_string(),
         scan_id: None,
-    })
+        findings_failed: 0,
+        store_error: None,
+    }))
+}
+
+/// `findings` 表中 `get_findings` 返回的列
+#[derive(FromRow)]
+struct FindingRow {
+    finding_id: String,
+    file_path: String,
+    line_start: i64,
+    line_end: i64,
+    detector: String,
+    vuln_type: String,
+    severity: String,
+    description: String,
+    code_snippet: Option<String>,
+    rule_id: Option<String>,
+    cwe: Option<String>,
+    owasp: Option<String>,
+    remediation: Option<String>,
+    reference_links: Option<String>,
+    suggested_fix: Option<String>,
+    detectors: Option<String>,
+    confidence: Option<f64>,
+    blame_commit: Option<String>,
+    blame_author: Option<String>,
+    blame_time: Option<i64>,
+    blame_summary: Option<String>,
+    fingerprint: Option<String>,
+}
+
+impl From<FindingRow> for Finding {
+    fn from(row: FindingRow) -> Self {
+        let blame = row.blame_commit.map(|commit| LineBlame {
+            commit,
+            author: row.blame_author.unwrap_or_default(),
+            author_time: row.blame_time.unwrap_or_default(),
+            summary: row.blame_summary.unwrap_or_default(),
+        });
+        Finding {
+            id: row.finding_id,
+            file_path: row.file_path,
+            line_start: row.line_start as usize,
+            line_end: row.line_end as usize,
+            detector: row.detector,
+            detectors: row
+                .detectors
+                .and_then(|d| serde_json::from_str(&d).ok())
+                .unwrap_or_default(),
+            vuln_type: row.vuln_type,
+            severity: row.severity,
+            confidence: row.confidence.map_or(DEFAULT_CONFIDENCE, |confidence| confidence as f32),
+            description: row.description,
+            code_snippet: row.code_snippet,
+            rule_id: row.rule_id,
+            cwe: row.cwe,
+            owasp: row.owasp,
+            remediation: row.remediation,
+            references: row
+                .reference_links
+                .and_then(|r| serde_json::from_str(&r).ok())
+                .unwrap_or_default(),
+            suggested_fix: row.suggested_fix,
+            matched_text: None,
+            blame,
+            fingerprint: row.fingerprint,
+        }
+    }
+}
+
+/// 发现列表的排序方式
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingSort {
+    /// 最新保存的在前
+    #[default]
+    Newest,
+    /// 按置信度乘以严重程度权重从高到低，最值得优先审查的在前；相同时最新的在前
+    Priority,
+}
+
+#[derive(Deserialize)]
+pub struct FindingsQuery {
+    #[serde(default)]
+    pub sort: FindingSort,
+    /// 排除指纹在项目基线中的发现，只返回保存基线之后新出现的
+    #[serde(default)]
+    pub exclude_baseline: bool,
 }
 
 pub async fn get_findings(
     state: web::Data<AppState>,
     path: web::Path<i64>,
-) -> impl Responder {
+    query: web::Query<FindingsQuery>,
+) -> Result<HttpResponse, AppError> {
     let project_id = path.into_inner();
 
-    let findings = match sqlx::query_as::<_, (String, String, i64, i64, String, String, String, String, Option<String>)>(
-        "SELECT finding_id, file_path, line_start, line_end, detector, vuln_type, severity, description, code_snippet
+    let findings = sqlx::query_as::<_, FindingRow>(
+        "SELECT finding_id, file_path, line_start, line_end, detector, vuln_type, severity, description, code_snippet,
+                rule_id, cwe, owasp, remediation, reference_links, suggested_fix, detectors, confidence,
+                blame_commit, blame_author, blame_time, blame_summary, fingerprint
          FROM findings
          WHERE project_id = ?
+           AND (? = 0 OR fingerprint IS NULL
+                OR fingerprint NOT IN (SELECT fingerprint FROM finding_baselines WHERE project_id = findings.project_id))
          ORDER BY created_at DESC"
     )
     .bind(project_id)
+    .bind(query.exclude_baseline)
     .fetch_all(&state.db)
     .await
-    {
-        Ok(findings) => findings,
+    .map_err(|e| AppError::database("Failed to fetch findings", e))?;
+
+    let mut findings: Vec<Finding> = findings.into_iter().map(Finding::from).collect();
+    if query.sort == FindingSort::Priority {
+        findings.sort_by(|a, b| priority(&b.severity, b.confidence).total_cmp(&priority(&a.severity, a.confidence)));
+    }
+
+    Ok(HttpResponse::Ok().json(findings))
+}
+
+/// 以项目当前全部发现的指纹替换项目基线，返回基线中的指纹数
+///
+/// 之后查询发现时传入 `exclude_baseline=true` 只返回基线之外的发现；没有指纹的旧发现不进入基线
+pub async fn save_baseline(
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let project_id = path.into_inner();
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::database("Failed to begin transaction", e))?;
+    sqlx::query("DELETE FROM finding_baselines WHERE project_id = ?")
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::database("Failed to clear baseline", e))?;
+    let fingerprints = sqlx::query(
+        "INSERT INTO finding_baselines (project_id, fingerprint)
+         SELECT DISTINCT project_id, fingerprint FROM findings
+         WHERE project_id = ? AND fingerprint IS NOT NULL"
+    )
+    .bind(project_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::database("Failed to save baseline", e))?
+    .rows_affected();
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database("Failed to commit baseline", e))?;
+
+    tracing::info!("Saved baseline of {} fingerprints for project {}", fingerprints, project_id);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "project_id": project_id,
+        "fingerprints": fingerprints
+    })))
+}
+
+/// 修复预览响应
+#[derive(Serialize)]
+pub struct FixPreview {
+    pub finding_id: String,
+    pub file_path: String,
+    pub lines: Vec<DiffLine>,
+}
+
+/// 待应用的修复：文件当前内容与替换后的内容
+struct FixPlan {
+    file_path: String,
+    original: String,
+    fixed: String,
+}
+
+/// 根据发现记录生成修复方案
+///
+/// 重新读取文件，确认命中的原始文本仍位于记录的行范围内，文件已变更时拒绝修复
+async fn plan_fix(state: &AppState, finding_id: &str) -> Result<FixPlan, AppError> {
+    let row = sqlx::query_as::<_, (String, i64, i64, Option<String>, Option<String>)>(
+        "SELECT file_path, line_start, line_end, matched_text, suggested_fix
+         FROM findings
+         WHERE finding_id = ?"
+    )
+    .bind(finding_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to fetch finding", e))?;
+
+    let Some((file_path, line_start, line_end, matched_text, suggested_fix)) = row else {
+        return Err(AppError::new(ErrorCode::FindingNotFound, format!("Finding not found: {}", finding_id)));
+    };
+    let (Some(matched_text), Some(suggested_fix)) = (matched_text, suggested_fix) else {
+        return Err(AppError::new(ErrorCode::FixUnavailable, "Finding has no suggested fix"));
+    };
+
+    let original = tokio::fs::read_to_string(&file_path)
+        .await
+        .map_err(|e| AppError::new(ErrorCode::FileNotFound, "Failed to read file").with_detail(e))?;
+
+    // 记录的行范围对应的字节区间（保留换行符）
+    let mut offset = 0;
+    let mut range = None;
+    for (idx, line) in original.split_inclusive('\n').enumerate() {
+        let line_no = idx as i64 + 1;
+        if line_no == line_start {
+            range = Some((offset, offset + line.len()));
+        } else if line_no > line_start && line_no <= line_end {
+            range = range.map(|(start, _)| (start, offset + line.len()));
+        }
+        offset += line.len();
+    }
+
+    let fixed = range
+        .and_then(|(start, end)| {
+            let pos = original[start..end].find(&matched_text)? + start;
+            Some(format!(
+                "{}{}{}",
+                &original[..pos],
+                suggested_fix,
+                &original[pos + matched_text.len()..]
+            ))
+        })
+        .ok_or_else(|| {
+            AppError::new(
+                ErrorCode::FixConflict,
+                "File has changed since the scan, the matched code was not found at the recorded location",
+            )
+        })?;
+
+    Ok(FixPlan { file_path, original, fixed })
+}
+
+/// 预览修复，返回差异而不写入文件
+pub async fn preview_fix(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let finding_id = path.into_inner();
+    let plan = plan_fix(&state, &finding_id).await?;
+
+    let to_lines = |content: &str| content.lines().map(String::from).collect::<Vec<_>>();
+    let lines = DiffEngine::new(ComparisonConfig::default())
+        .compute_line_diff(&to_lines(&plan.original), &to_lines(&plan.fixed))
+        .lines;
+
+    Ok(HttpResponse::Ok().json(FixPreview {
+        finding_id,
+        file_path: plan.file_path,
+        lines,
+    }))
+}
+
+/// 应用修复：写回文件并将发现标记为已修复
+pub async fn apply_fix(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let finding_id = path.into_inner();
+    let plan = plan_fix(&state, &finding_id).await?;
+
+    tokio::fs::write(&plan.file_path, &plan.fixed)
+        .await
+        .map_err(|e| AppError::io("Failed to write file", e))?;
+
+    sqlx::query("UPDATE findings SET status = 'fixed' WHERE finding_id = ?")
+        .bind(&finding_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| AppError::database("Fix applied but failed to update finding status", e))?;
+
+    tracing::info!("Applied fix for finding {} in {}", finding_id, plan.file_path);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "finding_id": finding_id,
+        "file_path": plan.file_path,
+        "status": "fixed"
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct FindingBlameQuery {
+    pub left_ref: String,
+    pub right_ref: String,
+}
+
+/// 对比发现所在行在两个 git 版本之间的变化（新增、修改或未变）及引入该行的提交，
+/// 用于区分新引入与早已存在的漏洞；行号按 `right_ref` 版本计
+pub async fn finding_blame(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<FindingBlameQuery>,
+) -> Result<HttpResponse, AppError> {
+    let finding_id = path.into_inner();
+    let FindingBlameQuery { left_ref, right_ref } = query.into_inner();
+
+    let row = sqlx::query_as::<_, (String, i64)>("SELECT file_path, line_start FROM findings WHERE finding_id = ?")
+        .bind(&finding_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::database("Failed to fetch finding", e))?;
+    let Some((file_path, line_start)) = row else {
+        return Err(AppError::new(ErrorCode::FindingNotFound, format!("Finding not found: {}", finding_id)));
+    };
+    let line = u32::try_from(line_start)
+        .map_err(|_| AppError::new(ErrorCode::InvalidInput, format!("Invalid line number: {}", line_start)))?;
+
+    let history = web::block(move || {
+        GitIntegration::new().line_history(std::path::Path::new(&file_path), line, &left_ref, &right_ref)
+    })
+    .await
+    .map_err(|e| AppError::internal("Blame task failed", e))?
+    .map_err(|e| comparison_error("Failed to compare finding across refs", e))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "finding_id": finding_id,
+        "history": history,
+    })))
+}
+
+/// 查询并保存发现 `line_start` 所在行的 git blame；已保存时直接返回
+///
+/// 行尚未提交时不保存，之后再次调用会重新查询；文件不在 git 仓库中时返回 `unavailable`
+pub async fn blame_finding(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let finding_id = path.into_inner();
+
+    let row = sqlx::query_as::<_, FindingRow>(
+        "SELECT finding_id, file_path, line_start, line_end, detector, vuln_type, severity, description, code_snippet,
+                rule_id, cwe, owasp, remediation, reference_links, suggested_fix, detectors, confidence,
+                blame_commit, blame_author, blame_time, blame_summary, fingerprint
+         FROM findings
+         WHERE finding_id = ?"
+    )
+    .bind(&finding_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to fetch finding", e))?;
+    let Some(finding) = row.map(Finding::from) else {
+        return Err(AppError::new(ErrorCode::FindingNotFound, format!("Finding not found: {}", finding_id)));
+    };
+    if let Some(blame) = finding.blame {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "finding_id": finding_id,
+            "status": "committed",
+            "blame": blame,
+        })));
+    }
+    let line = u32::try_from(finding.line_start)
+        .map_err(|_| AppError::new(ErrorCode::InvalidInput, format!("Invalid line number: {}", finding.line_start)))?;
+
+    // 从文件所在目录查找仓库
+    let file_path = finding.file_path;
+    let result = web::block(move || {
+        let dir = std::path::Path::new(&file_path).parent().unwrap_or(std::path::Path::new("."));
+        GitIntegration::new().get_blame(&dir.to_string_lossy(), &file_path, line, line)
+    })
+    .await
+    .map_err(|e| AppError::internal("Blame task failed", e))?;
+
+    let blame_line = match result {
+        Ok(lines) => lines.into_iter().next(),
         Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch findings: {}", e)
-            }));
+            tracing::info!("Blame unavailable for finding {}: {:#}", finding_id, e);
+            return Ok(HttpResponse::Ok().json(serde_json::json!({
+                "finding_id": finding_id,
+                "status": "unavailable",
+                "reason": e.to_string(),
+            })));
         }
     };
+    let Some(blame) = blame_line.and_then(|l| l.blame) else {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "finding_id": finding_id,
+            "status": "not_committed",
+        })));
+    };
 
-    let findings: Vec<Finding> = findings
-        .into_iter()
-        .map(|(id, file_path, line_start, line_end, detector, vuln_type, severity, description, code_snippet)| Finding {
-            id,
-            file_path,
-            line_start: line_start as usize,
-            line_end: line_end as usize,
-            detector,
-            vuln_type,
-            severity,
-            description,
-            code_snippet,
-        })
-        .collect();
+    sqlx::query(
+        "UPDATE findings SET blame_commit = ?, blame_author = ?, blame_time = ?, blame_summary = ? WHERE finding_id = ?"
+    )
+    .bind(&blame.commit)
+    .bind(&blame.author)
+    .bind(blame.author_time)
+    .bind(&blame.summary)
+    .bind(&finding_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError::database("Failed to save finding blame", e))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "finding_id": finding_id,
+        "status": "committed",
+        "blame": blame,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_state;
+
+    /// 带修复建议的发现：将第 `line` 行的 `eval(` 替换为 `safe_eval(`
+    fn fixable_finding(id: &str, file_path: &str, line: usize) -> Finding {
+        Finding {
+            id: id.to_string(),
+            file_path: file_path.to_string(),
+            line_start: line,
+            line_end: line,
+            detector: "RuleBasedScanner".to_string(),
+            detectors: Vec::new(),
+            vuln_type: "Code Injection".to_string(),
+            severity: "high".to_string(),
+            confidence: 0.9,
+            description: "Use of eval".to_string(),
+            code_snippet: None,
+            rule_id: Some("python-eval".to_string()),
+            cwe: Some("CWE-95".to_string()),
+            owasp: None,
+            remediation: None,
+            references: vec!["https://cwe.mitre.org/data/definitions/95.html".to_string()],
+            suggested_fix: Some("safe_eval(".to_string()),
+            matched_text: Some("eval(".to_string()),
+            blame: None,
+            fingerprint: Some(format!("fp-{}", id)),
+        }
+    }
+
+    async fn insert_project(state: &AppState, path: &str) -> i64 {
+        sqlx::query_scalar("INSERT INTO projects (uuid, name, path) VALUES (?, ?, ?) RETURNING id")
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind("sample")
+            .bind(path)
+            .fetch_one(&state.db)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn plan_fix_refuses_file_changed_after_scan() {
+        let (dir, state) = test_state().await;
+        let file = dir.path().join("app.py");
+        std::fs::write(&file, "import os\nresult = eval(data)\n").unwrap();
+        let project_id = insert_project(&state, dir.path().to_str().unwrap()).await;
+        let mut conn = state.db.acquire().await.unwrap();
+        insert_finding(&mut conn, project_id, &fixable_finding("f1", file.to_str().unwrap(), 2))
+            .await
+            .unwrap();
+        drop(conn);
+
+        let plan = plan_fix(&state, "f1").await.unwrap();
+        assert_eq!(plan.fixed, "import os\nresult = safe_eval(data)\n");
+
+        // 命中的代码被移到了其他行
+        std::fs::write(&file, "import os\n\nresult = eval(data)\n").unwrap();
+        let err = plan_fix(&state, "f1").await.err().unwrap();
+        assert_eq!(err.code, ErrorCode::FixConflict);
+
+        // 命中的代码已被删除
+        std::fs::write(&file, "import os\nresult = 1\n").unwrap();
+        let err = plan_fix(&state, "f1").await.err().unwrap();
+        assert_eq!(err.code, ErrorCode::FixConflict);
+    }
+
+    async fn findings_json(state: web::Data<AppState>, project_id: i64, query: &str) -> serde_json::Value {
+        let query = web::Query::<FindingsQuery>::from_query(query).unwrap();
+        let response = get_findings(state, web::Path::from(project_id), query).await.unwrap();
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stored_findings_are_returned_by_the_findings_query() {
+        let (_dir, state) = test_state().await;
+        let project_id = insert_project(&state, "/srv/app").await;
+        let mut low = fixable_finding("low", "/srv/app/util.py", 4);
+        low.severity = "low".to_string();
+        low.confidence = 0.5;
+        low.detectors = vec!["RuleBasedScanner".to_string(), "Semgrep".to_string()];
+        let high = fixable_finding("high", "/srv/app/app.py", 2);
+        let scan_id = store_scan_results(&state, project_id, &[low, high], 7, 1, None).await.unwrap();
 
-    HttpResponse::Ok().json(findings)
+        let findings = findings_json(state.clone(), project_id, "sort=priority").await;
+        let findings = findings.as_array().unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0]["id"], "high");
+        assert_eq!(findings[0]["file_path"], "/srv/app/app.py");
+        assert_eq!(findings[0]["line_start"], 2);
+        assert_eq!(findings[0]["cwe"], "CWE-95");
+        assert_eq!(findings[0]["suggested_fix"], "safe_eval(");
+        assert_eq!(findings[0]["references"][0], "https://cwe.mitre.org/data/definitions/95.html");
+        assert_eq!(findings[1]["id"], "low");
+        assert_eq!(findings[1]["confidence"], 0.5);
+        assert_eq!(findings[1]["detectors"], serde_json::json!(["RuleBasedScanner", "Semgrep"]));
+        // 仅用于校验的命中文本不返回
+        assert!(findings[1].get("matched_text").is_none());
+
+        let (files_scanned, findings_found, suppressed): (i64, i64, i64) =
+            sqlx::query_as("SELECT files_scanned, findings_found, findings_suppressed FROM scans WHERE id = ?")
+                .bind(scan_id)
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert_eq!((files_scanned, findings_found, suppressed), (7, 2, 1));
+
+        // 其他项目的发现不返回
+        let other = insert_project(&state, "/srv/other").await;
+        assert_eq!(findings_json(state, other, "").await, serde_json::json!([]));
+    }
 }
@@ -0,0 +1,142 @@
+// 统一的 API 错误：以稳定的错误码区分错误类型，前端据此显示本地化提示或决定是否重试
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+/// 错误码，序列化为 `PROJECT_NOT_FOUND` 形式的字符串
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// 请求参数无效
+    InvalidInput,
+    ProjectNotFound,
+    FileNotFound,
+    RuleNotFound,
+    FindingNotFound,
+    /// 已保存的代码图谱不存在
+    GraphNotFound,
+    /// 项目尚未构建 AST 索引
+    IndexNotBuilt,
+    RuleAlreadyExists,
+    /// 内置规则只读，不能删除
+    RuleReadOnly,
+    /// 发现记录没有可应用的修复
+    FixUnavailable,
+    /// 文件在扫描后已变更，修复无法定位原始代码
+    FixConflict,
+    /// 上传内容读取失败
+    UploadFailed,
+    UploadTooLarge,
+    /// 上传的压缩包无法解析
+    ArchiveInvalid,
+    /// 远程规则包同步失败
+    RuleSyncFailed,
+    /// 差异比较失败（路径不存在、类型不一致等）
+    ComparisonFailed,
+    /// 后台比较不存在或已结束，或分阶段比较的结果已关闭
+    ComparisonNotFound,
+    /// 补丁文件无法读取或解析
+    PatchInvalid,
+    /// 路径不在 Git 仓库中
+    GitNotARepo,
+    /// 分支、标签或提交无法解析
+    GitRefNotFound,
+    DatabaseError,
+    IoError,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidInput
+            | ErrorCode::FixUnavailable
+            | ErrorCode::UploadFailed
+            | ErrorCode::ArchiveInvalid
+            | ErrorCode::ComparisonFailed
+            | ErrorCode::PatchInvalid
+            | ErrorCode::GitNotARepo => StatusCode::BAD_REQUEST,
+            ErrorCode::ProjectNotFound
+            | ErrorCode::FileNotFound
+            | ErrorCode::RuleNotFound
+            | ErrorCode::FindingNotFound
+            | ErrorCode::GraphNotFound
+            | ErrorCode::IndexNotBuilt
+            | ErrorCode::ComparisonNotFound
+            | ErrorCode::GitRefNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::RuleAlreadyExists | ErrorCode::FixConflict => StatusCode::CONFLICT,
+            ErrorCode::RuleReadOnly => StatusCode::FORBIDDEN,
+            ErrorCode::UploadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::RuleSyncFailed => StatusCode::BAD_GATEWAY,
+            ErrorCode::DatabaseError | ErrorCode::IoError | ErrorCode::InternalError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+/// API 错误响应：`{ "code": "...", "message": "...", "detail": "..." }`
+#[derive(Debug, Clone, Serialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    /// 面向用户的说明
+    pub message: String,
+    /// 底层错误信息，便于排查
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            detail: None,
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl fmt::Display) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn database(message: impl Into<String>, detail: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::DatabaseError, message).with_detail(detail)
+    }
+
+    pub fn io(message: impl Into<String>, detail: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::IoError, message).with_detail(detail)
+    }
+
+    pub fn internal(message: impl Into<String>, detail: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::InternalError, message).with_detail(detail)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {}", self.message, detail),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.code.status().is_server_error() {
+            tracing::error!("{:?}: {}", self.code, self);
+        }
+        HttpResponse::build(self.status_code()).json(self)
+    }
+}
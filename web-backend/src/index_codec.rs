@@ -0,0 +1,51 @@
+// AST 索引与代码图谱编解码：带版本号的 zstd 压缩二进制格式，兼容旧版 JSON 文本
+
+use deepaudit_core::Symbol;
+
+/// 二进制格式版本号，写在数据开头
+const FORMAT_MSGPACK_ZSTD: u8 = 1;
+
+/// 代码图谱的格式版本号：zstd 压缩的 JSON
+const FORMAT_JSON_ZSTD: u8 = 2;
+
+/// zstd 压缩级别
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// 将符号列表编码为带版本号的压缩二进制数据
+pub fn encode_symbols(symbols: &[Symbol]) -> anyhow::Result<Vec<u8>> {
+    let packed = rmp_serde::to_vec_named(symbols)?;
+    let mut data = vec![FORMAT_MSGPACK_ZSTD];
+    data.extend(zstd::encode_all(packed.as_slice(), COMPRESSION_LEVEL)?);
+    Ok(data)
+}
+
+/// 解码索引数据，未带版本号的数据按旧版 JSON 文本处理
+pub fn decode_symbols(data: &[u8]) -> anyhow::Result<Vec<Symbol>> {
+    match data.first() {
+        Some(&FORMAT_MSGPACK_ZSTD) => {
+            let packed = zstd::decode_all(&data[1..])?;
+            Ok(rmp_serde::from_slice(&packed)?)
+        }
+        Some(b'[') => Ok(serde_json::from_slice(data)?),
+        Some(version) => Err(anyhow::anyhow!("Unknown AST index format: {}", version)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// 将代码图谱编码为带版本号的压缩数据
+pub fn encode_graph(graph: &serde_json::Value) -> anyhow::Result<Vec<u8>> {
+    let json = serde_json::to_vec(graph)?;
+    let mut data = vec![FORMAT_JSON_ZSTD];
+    data.extend(zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL)?);
+    Ok(data)
+}
+
+/// 解码代码图谱，未带版本号的数据按旧版 JSON 文本处理
+pub fn decode_graph(data: &[u8]) -> anyhow::Result<serde_json::Value> {
+    match data.first() {
+        Some(&FORMAT_JSON_ZSTD) => Ok(serde_json::from_slice(&zstd::decode_all(&data[1..])?)?),
+        Some(b'{') => Ok(serde_json::from_slice(data)?),
+        Some(version) => Err(anyhow::anyhow!("Unknown code graph format: {}", version)),
+        None => Ok(serde_json::Value::Null),
+    }
+}
@@ -2,13 +2,15 @@ use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use actix_cors::Cors;
 use actix_files::Files;
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod api;
+mod error;
+mod index_codec;
+mod rule_store;
+mod rule_sync;
 mod state;
+mod watcher;
 
 use api::create_api_router;
 use state::AppState;
@@ -0,0 +1,400 @@
+// 规则存储：解析内置规则目录与用户规则目录，合并加载并负责规则文件读写
+
+use deepaudit_core::rules::model::Severity;
+use deepaudit_core::Rule;
+use include_dir::{include_dir, Dir};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 编译时嵌入的默认规则包
+static EMBEDDED_RULES: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/../rules");
+
+/// 旧版本按当前工作目录猜测的规则目录
+const LEGACY_RULE_DIRS: &[&str] = &["../rules", "rules", "../../rules"];
+
+/// 迁移完成标记文件
+const MIGRATION_MARKER: &str = ".legacy_migrated";
+
+/// 远程规则包在用户规则目录下的子目录名
+const REMOTE_DIR: &str = "remote";
+
+/// 规则覆盖配置文件名
+const OVERRIDES_FILE: &str = "rule_overrides.json";
+
+/// 检查规则 id：只允许字母、数字、`-` 和 `_`，规则 id 同时用作用户目录中的文件名
+pub fn validate_rule_id(rule_id: &str) -> Result<(), String> {
+    if rule_id.is_empty() {
+        return Err("Rule id must not be empty".to_string());
+    }
+    if let Some(c) = rule_id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Err(format!(
+            "Rule id '{}' contains invalid character '{}'; only letters, digits, '-' and '_' are allowed",
+            rule_id, c
+        ));
+    }
+    Ok(())
+}
+
+/// 单条规则的覆盖配置，独立于规则 YAML 持久化
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RuleOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+}
+
+/// 所有规则的覆盖配置（规则 id -> 覆盖项）
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RuleOverrides {
+    #[serde(default)]
+    pub rules: HashMap<String, RuleOverride>,
+}
+
+impl RuleOverrides {
+    /// 从数据目录加载覆盖配置，文件不存在时返回空配置
+    pub fn load(data_dir: &Path) -> anyhow::Result<Self> {
+        let path = data_dir.join(OVERRIDES_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// 保存覆盖配置到数据目录
+    pub fn save(&self, data_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(data_dir)?;
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(data_dir.join(OVERRIDES_FILE), content)?;
+        Ok(())
+    }
+
+    /// 将覆盖配置应用到规则上，得到生效的规则
+    pub fn apply(&self, rules: &mut [Rule]) {
+        for rule in rules {
+            if let Some(o) = self.rules.get(&rule.id) {
+                if let Some(enabled) = o.enabled {
+                    rule.enabled = enabled;
+                }
+                if let Some(severity) = &o.severity {
+                    rule.severity = severity.clone();
+                }
+            }
+        }
+    }
+
+    /// 获取可修改的覆盖项，必要时创建
+    pub fn entry(&mut self, rule_id: &str) -> &mut RuleOverride {
+        self.rules.entry(rule_id.to_string()).or_default()
+    }
+
+    /// 移除已为空的覆盖项
+    pub fn compact(&mut self) {
+        self.rules
+            .retain(|_, o| o.enabled.is_some() || o.severity.is_some());
+    }
+}
+
+/// 规则来源层级
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSource {
+    /// 编译时嵌入的内置规则
+    Builtin,
+    /// 外部规则目录
+    Bundled,
+    /// 从远程规则包同步的规则
+    Remote,
+    /// 用户规则目录
+    User,
+}
+
+/// 因 id 重复而被遮蔽的规则
+#[derive(Clone, Debug, Serialize)]
+pub struct ShadowedRule {
+    pub id: String,
+    /// 生效规则的来源
+    pub active_source: RuleSource,
+    /// 被遮蔽规则的来源
+    pub shadowed_source: RuleSource,
+}
+
+/// 合并后的规则及加载过程中发现的重复 id
+pub struct LoadedRules {
+    pub rules: Vec<Rule>,
+    pub shadowed: Vec<ShadowedRule>,
+}
+
+/// 规则目录位置
+#[derive(Clone, Debug, Serialize)]
+pub struct RulePaths {
+    /// 随程序分发的外部规则目录（只读，可选），叠加在内置规则之上
+    pub bundled_dir: Option<PathBuf>,
+    /// 用户规则目录，保存的规则都写入这里，并按 id 覆盖内置规则
+    pub user_dir: PathBuf,
+}
+
+impl RulePaths {
+    /// 解析规则目录，不依赖当前工作目录
+    ///
+    /// 外部规则目录优先级：`DEEPAUDIT_RULES_DIR` 环境变量 > 可执行文件旁的 `rules`
+    pub fn resolve(data_dir: &Path) -> Self {
+        let bundled_dir = std::env::var_os("DEEPAUDIT_RULES_DIR")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::current_exe()
+                    .ok()
+                    .and_then(|exe| exe.parent().map(|dir| dir.join("rules")))
+                    .filter(|dir| dir.is_dir())
+            })
+            .map(|dir| dir.canonicalize().unwrap_or(dir));
+
+        Self {
+            bundled_dir,
+            user_dir: data_dir.join("rules"),
+        }
+    }
+
+    /// 确保用户规则目录存在
+    pub fn ensure_user_dir(&self) -> std::io::Result<()> {
+        fs::create_dir_all(&self.user_dir)
+    }
+
+    /// 远程规则包目录，位于用户规则目录下，由同步流程整体替换
+    pub fn remote_dir(&self) -> PathBuf {
+        self.user_dir.join(REMOTE_DIR)
+    }
+
+    /// 用户目录中某条规则对应的文件路径
+    ///
+    /// 规则 id 不合法，或规范化后的路径不在用户规则目录内时返回 `InvalidInput` 错误
+    pub fn user_rule_file(&self, rule_id: &str) -> std::io::Result<PathBuf> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+        validate_rule_id(rule_id).map_err(invalid)?;
+
+        let file_path = self.user_dir.join(format!("{}.yaml", rule_id));
+        let user_dir = self.user_dir.canonicalize()?;
+        let parent = file_path
+            .parent()
+            .map(Path::canonicalize)
+            .transpose()?
+            .unwrap_or_default();
+        if parent != user_dir {
+            return Err(invalid(format!(
+                "Rule file {} is outside the rules directory",
+                file_path.display()
+            )));
+        }
+        Ok(file_path)
+    }
+
+    /// 加载并合并规则：内置规则 < 外部规则目录 < 远程规则包 < 用户规则，后者按 id 覆盖前者
+    ///
+    /// 同一 id 出现多次时记录被遮蔽的规则来源
+    pub fn load_rules(&self) -> anyhow::Result<LoadedRules> {
+        let mut rules: Vec<Rule> = Vec::new();
+        let mut sources: Vec<RuleSource> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut shadowed = Vec::new();
+
+        let mut layers = vec![(RuleSource::Builtin, embedded_rules())];
+        if let Some(dir) = self.bundled_dir.as_ref().filter(|d| d.is_dir()) {
+            layers.push((RuleSource::Bundled, deepaudit_core::load_rules_from_dir(dir)?));
+        }
+        let remote_dir = self.remote_dir();
+        if remote_dir.is_dir() {
+            layers.push((RuleSource::Remote, deepaudit_core::load_rules_from_dir(&remote_dir)?));
+        }
+        if self.user_dir.is_dir() {
+            layers.push((
+                RuleSource::User,
+                deepaudit_core::rules::loader::load_rules_from_dir_excluding(&self.user_dir, &[remote_dir])?,
+            ));
+        }
+
+        for (source, layer) in layers {
+            for rule in layer {
+                match positions.get(&rule.id) {
+                    Some(&pos) => {
+                        shadowed.push(ShadowedRule {
+                            id: rule.id.clone(),
+                            active_source: source,
+                            shadowed_source: sources[pos],
+                        });
+                        rules[pos] = rule;
+                        sources[pos] = source;
+                    }
+                    None => {
+                        positions.insert(rule.id.clone(), rules.len());
+                        rules.push(rule);
+                        sources.push(source);
+                    }
+                }
+            }
+        }
+
+        for s in &shadowed {
+            tracing::info!(
+                "Rule '{}' from {:?} rules shadows the one from {:?} rules",
+                s.id,
+                s.active_source,
+                s.shadowed_source
+            );
+        }
+
+        Ok(LoadedRules { rules, shadowed })
+    }
+
+    /// 首次运行时将旧的相对路径规则目录中的规则文件迁移到用户目录
+    ///
+    /// 与内置目录相同的位置会被跳过；用户目录中已存在的文件不会被覆盖。返回迁移的文件数量
+    pub fn migrate_legacy_rules(&self) -> std::io::Result<usize> {
+        self.ensure_user_dir()?;
+
+        let marker = self.user_dir.join(MIGRATION_MARKER);
+        if marker.exists() {
+            return Ok(0);
+        }
+
+        let bundled = self.bundled_dir.as_ref().and_then(|d| d.canonicalize().ok());
+        let user = self.user_dir.canonicalize().ok();
+        let embedded_source = Path::new(env!("CARGO_MANIFEST_DIR")).join("../rules").canonicalize().ok();
+        let mut total = 0;
+
+        for legacy in LEGACY_RULE_DIRS {
+            let Ok(legacy_dir) = Path::new(legacy).canonicalize() else {
+                continue;
+            };
+            if [&bundled, &user, &embedded_source].iter().any(|d| d.as_ref() == Some(&legacy_dir)) {
+                continue;
+            }
+
+            let mut migrated = 0;
+            for entry in list_yaml_files(&legacy_dir) {
+                let Some(file_name) = entry.file_name() else {
+                    continue;
+                };
+                let target = self.user_dir.join(file_name);
+                if target.exists() {
+                    continue;
+                }
+                fs::copy(&entry, &target)?;
+                migrated += 1;
+            }
+
+            if migrated > 0 {
+                tracing::info!("Migrated {} legacy rule files from {}", migrated, legacy_dir.display());
+            }
+            total += migrated;
+        }
+
+        fs::write(&marker, b"")?;
+        Ok(total)
+    }
+}
+
+/// 解析嵌入的默认规则，并标记为内置
+fn embedded_rules() -> Vec<Rule> {
+    let mut rules = Vec::new();
+    collect_embedded_rules(&EMBEDDED_RULES, &mut rules);
+    for rule in &mut rules {
+        rule.builtin = true;
+    }
+    rules
+}
+
+/// 递归解析嵌入目录中的规则，子目录中的规则以目录名作为默认规则包；与规则目录一样跳过校验失败的规则
+fn collect_embedded_rules(dir: &Dir<'_>, rules: &mut Vec<Rule>) {
+    for sub_dir in dir.dirs() {
+        collect_embedded_rules(sub_dir, rules);
+    }
+    for file in dir.files() {
+        let is_yaml = file
+            .path()
+            .extension()
+            .is_some_and(|ext| ext == "yaml" || ext == "yml");
+        if !is_yaml {
+            continue;
+        }
+        let parsed = file
+            .contents_utf8()
+            .and_then(deepaudit_core::rules::loader::parse_rules);
+        match parsed {
+            Some(parsed) => {
+                let pack = deepaudit_core::rules::loader::pack_from_path(file.path());
+                for mut rule in parsed {
+                    if rule.pack.is_none() {
+                        rule.pack = pack.clone();
+                    }
+                    match rule.validate() {
+                        Ok(()) => rules.push(rule),
+                        Err(e) => tracing::warn!("Invalid embedded rule in {}: {}", file.path().display(), e),
+                    }
+                }
+            }
+            None => tracing::warn!("Failed to parse embedded rule file: {}", file.path().display()),
+        }
+    }
+}
+
+/// 列出目录（不递归）中的 YAML 规则文件
+fn list_yaml_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension().is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use include_dir::{DirEntry, File};
+
+    const RULES: &str = "\
+name: Python
+version: \"1.0\"
+rules:
+  - id: valid-rule
+    name: Valid
+    description: Valid rule
+    severity: low
+    language: python
+    pattern: eval\\(
+    paths:
+      include: [\"src/**\"]
+  - id: bad-glob
+    name: Bad glob
+    description: Rule with an invalid glob
+    severity: low
+    language: python
+    pattern: exec\\(
+    paths:
+      include: [\"src/[unclosed\"]
+";
+
+    #[test]
+    fn embedded_rules_with_invalid_globs_are_skipped() {
+        let files = [DirEntry::File(File::new("python/rules.yaml", RULES.as_bytes()))];
+        let packs = [DirEntry::Dir(Dir::new("python", &files))];
+        let root = Dir::new("", &packs);
+
+        let mut rules = Vec::new();
+        collect_embedded_rules(&root, &mut rules);
+        let ids: Vec<&str> = rules.iter().map(|rule| rule.id.as_str()).collect();
+        assert_eq!(ids, ["valid-rule"]);
+        assert_eq!(rules[0].pack.as_deref(), Some("python"));
+    }
+}
@@ -0,0 +1,282 @@
+// 远程规则包同步：下载 JSON 索引列出的规则文件，校验摘要后整体替换用户规则目录下的 remote/ 子目录
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::rule_store::RulePaths;
+
+/// 已安装规则包的清单文件名，保存在 remote/ 目录中
+const MANIFEST_FILE: &str = ".bundle.json";
+
+/// 同步过程中的暂存目录，位于数据目录下，避免半成品被规则加载器读到
+const STAGING_DIR: &str = ".remote-staging";
+
+/// 替换时临时存放旧规则包的目录
+const PREVIOUS_DIR: &str = ".remote-old";
+
+/// 网络请求超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 同一时间只允许一个同步任务操作 remote/ 目录
+static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// 远程规则包索引
+#[derive(Debug, Deserialize)]
+struct BundleIndex {
+    version: String,
+    files: Vec<IndexEntry>,
+}
+
+/// 索引中的单个规则文件
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    /// 相对 remote/ 目录的文件路径
+    path: String,
+    sha256: String,
+    /// 下载地址，缺省时相对索引地址解析 `path`
+    #[serde(default)]
+    url: Option<String>,
+}
+
+/// 已安装的规则包信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledBundle {
+    pub source: String,
+    pub version: String,
+    /// 文件路径 -> sha256
+    pub files: BTreeMap<String, String>,
+    pub synced_at: String,
+}
+
+/// 同步结果
+#[derive(Debug, Serialize)]
+pub struct SyncOutcome {
+    pub source: String,
+    pub version: String,
+    pub previous_version: Option<String>,
+    /// 已是最新版本，未做任何修改
+    pub up_to_date: bool,
+    pub downloaded: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// 读取已安装的规则包信息
+pub fn installed_bundle(paths: &RulePaths) -> Option<InstalledBundle> {
+    let content = fs::read_to_string(paths.remote_dir().join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// 从索引地址同步远程规则包
+///
+/// 所有文件下载并校验通过后才会替换 remote/ 目录，任一步骤失败时保留原有规则
+pub async fn sync_rules(paths: &RulePaths, data_dir: &Path, url: &str) -> anyhow::Result<SyncOutcome> {
+    let _guard = SYNC_LOCK.lock().await;
+
+    let base = reqwest::Url::parse(url).map_err(|e| anyhow::anyhow!("Invalid URL '{}': {}", url, e))?;
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+
+    let mut index: BundleIndex = client
+        .get(base.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .map_err(|e| anyhow::anyhow!("Invalid bundle index: {}", e))?;
+    validate_index(&mut index)?;
+
+    let remote_dir = paths.remote_dir();
+    let installed = installed_bundle(paths);
+    let previous_version = installed.as_ref().map(|b| b.version.clone());
+    let installed_files = installed.map(|b| b.files).unwrap_or_default();
+
+    // 本地文件内容与索引一致的条目无需重新下载
+    let is_current = |entry: &IndexEntry| {
+        installed_files.get(&entry.path) == Some(&entry.sha256)
+            && fs::read(remote_dir.join(&entry.path)).is_ok_and(|data| sha256_hex(&data) == entry.sha256)
+    };
+
+    let removed: Vec<String> = installed_files
+        .keys()
+        .filter(|path| !index.files.iter().any(|e| &e.path == *path))
+        .cloned()
+        .collect();
+
+    if previous_version.as_deref() == Some(index.version.as_str())
+        && removed.is_empty()
+        && index.files.iter().all(is_current)
+    {
+        return Ok(SyncOutcome {
+            source: url.to_string(),
+            version: index.version,
+            previous_version,
+            up_to_date: true,
+            downloaded: Vec::new(),
+            unchanged: index.files.into_iter().map(|e| e.path).collect(),
+            removed: Vec::new(),
+        });
+    }
+
+    let staging = data_dir.join(STAGING_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let mut downloaded = Vec::new();
+    let mut unchanged = Vec::new();
+    let staged: anyhow::Result<()> = async {
+        for entry in &index.files {
+            let target = staging.join(&entry.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            if is_current(entry) {
+                fs::copy(remote_dir.join(&entry.path), &target)?;
+                unchanged.push(entry.path.clone());
+                continue;
+            }
+
+            let file_url = match &entry.url {
+                Some(u) => base.join(u)?,
+                None => base.join(&entry.path)?,
+            };
+            let data = client
+                .get(file_url)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+
+            let digest = sha256_hex(&data);
+            if digest != entry.sha256 {
+                anyhow::bail!(
+                    "Digest mismatch for {}: expected {}, got {}",
+                    entry.path,
+                    entry.sha256,
+                    digest
+                );
+            }
+            let content = std::str::from_utf8(&data)
+                .map_err(|_| anyhow::anyhow!("{} is not valid UTF-8", entry.path))?;
+            if deepaudit_core::rules::loader::parse_rules(content).is_none() {
+                anyhow::bail!("{} is not a valid rule file", entry.path);
+            }
+
+            fs::write(&target, &data)?;
+            downloaded.push(entry.path.clone());
+        }
+
+        let manifest = InstalledBundle {
+            source: url.to_string(),
+            version: index.version.clone(),
+            files: index
+                .files
+                .iter()
+                .map(|e| (e.path.clone(), e.sha256.clone()))
+                .collect(),
+            synced_at: chrono::Utc::now().to_rfc3339(),
+        };
+        fs::write(staging.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = staged {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    swap_into_place(&staging, &remote_dir, &data_dir.join(PREVIOUS_DIR))?;
+
+    Ok(SyncOutcome {
+        source: url.to_string(),
+        version: index.version,
+        previous_version,
+        up_to_date: false,
+        downloaded,
+        unchanged,
+        removed,
+    })
+}
+
+/// 校验索引中的文件路径，只允许 remote/ 目录内的 YAML 文件；
+/// sha256 统一转为小写，与 `sha256_hex` 的输出直接比较
+fn validate_index(index: &mut BundleIndex) -> anyhow::Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for entry in &mut index.files {
+        let path = Path::new(&entry.path);
+        let is_yaml = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        let is_contained = path.components().all(|c| matches!(c, Component::Normal(_)));
+        if !is_yaml || !is_contained {
+            anyhow::bail!("Invalid file path in bundle index: {}", entry.path);
+        }
+        if !seen.insert(entry.path.as_str()) {
+            anyhow::bail!("Duplicate file path in bundle index: {}", entry.path);
+        }
+        if entry.sha256.len() != 64 || !entry.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("Invalid sha256 for {}: {}", entry.path, entry.sha256);
+        }
+        entry.sha256.make_ascii_lowercase();
+    }
+    Ok(())
+}
+
+/// 用暂存目录替换 remote/ 目录，替换失败时恢复旧目录
+fn swap_into_place(staging: &Path, remote_dir: &Path, previous: &Path) -> anyhow::Result<()> {
+    if previous.exists() {
+        fs::remove_dir_all(previous)?;
+    }
+    if let Some(parent) = remote_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let had_previous = remote_dir.exists();
+    if had_previous {
+        fs::rename(remote_dir, previous)?;
+    }
+    if let Err(e) = fs::rename(staging, remote_dir) {
+        if had_previous {
+            let _ = fs::rename(previous, remote_dir);
+        }
+        let _ = fs::remove_dir_all(staging);
+        return Err(e.into());
+    }
+    if had_previous {
+        let _ = fs::remove_dir_all(previous);
+    }
+    Ok(())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uppercase_digest_matches_computed_digest() {
+        let data = b"id: remote-rule\n";
+        let mut index: BundleIndex = serde_json::from_value(serde_json::json!({
+            "version": "1",
+            "files": [{ "path": "pack/rule.yaml", "sha256": sha256_hex(data).to_uppercase() }]
+        }))
+        .unwrap();
+
+        validate_index(&mut index).unwrap();
+        assert_eq!(index.files[0].sha256, sha256_hex(data));
+    }
+}
@@ -1,9 +1,23 @@
-use deepaudit_core::ASTEngine;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use deepaudit_core::diff::{ComparisonResult, DiffCache};
+use deepaudit_core::rules::lint::{self, RuleDiagnostic};
+use deepaudit_core::{ASTEngine, ExternalScanner, ExternalScannerConfig, Rule, ScannerManager};
+use serde::Deserialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
 use sqlx::{Pool, Sqlite};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, Mutex};
+use tokio::sync::RwLock as AsyncRwLock;
+
+use crate::api::diff::DiffEvent;
+use crate::rule_store::{LoadedRules, RuleOverrides, RulePaths, ShadowedRule};
+use crate::watcher::{ProjectWatcher, ScanEvent};
+
+/// 差异缓存最多保留的文件差异数
+const DIFF_CACHE_CAPACITY: usize = 10_000;
 
 /// AST缓存状态跟踪
 #[derive(Default)]
@@ -13,59 +27,325 @@ pub struct AstCacheState {
     pub symbol_count: usize,
 }
 
+/// 规则快照：规则列表及据此构建的扫描管理器
+///
+/// 扫描开始时持有一份快照，重新加载规则不会影响进行中的扫描
+pub struct RuleSnapshot {
+    pub rules: Vec<Rule>,
+    pub scanner: ScannerManager,
+    /// 加载时因 id 重复被遮蔽的规则
+    pub shadowed: Vec<ShadowedRule>,
+    /// 加载时发现的规则编译错误与正则静态检查警告
+    pub diagnostics: Vec<RuleDiagnostic>,
+}
+
 #[derive(Clone)]
 pub struct AppState {
-    pub ast_engine: Arc<Mutex<ASTEngine>>,
+    /// 引擎方法内部加锁，请求只取读锁；索引与加载缓存在阻塞线程池中进行，不阻塞其他查询
+    pub ast_engine: Arc<AsyncRwLock<ASTEngine>>,
     pub db: Pool<Sqlite>,
     pub ast_cache_state: Arc<Mutex<AstCacheState>>,
+    pub data_dir: PathBuf,
+    pub rule_paths: RulePaths,
+    pub rules: Arc<RwLock<Arc<RuleSnapshot>>>,
+    pub rule_overrides: Arc<RwLock<RuleOverrides>>,
+    /// 正在监听的项目目录，按 project_id 索引
+    pub watchers: Arc<Mutex<HashMap<i64, ProjectWatcher>>>,
+    /// 增量扫描事件，通过 /api/scanner/events 推送
+    pub scan_events: broadcast::Sender<ScanEvent>,
+    /// 进行中的后台比较的取消标记，按 comparison_id 索引
+    pub comparisons: Arc<std::sync::Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    /// 后台比较事件，通过 /api/diff/events 推送
+    pub diff_events: broadcast::Sender<DiffEvent>,
+    /// 分阶段比较的结果缓存，按 comparison_id 索引，关闭比较时释放
+    pub comparison_results: Arc<std::sync::Mutex<HashMap<String, Arc<ComparisonResult>>>>,
+    /// 文件系统比较的单文件差异缓存，重复比较相同目录时复用
+    pub diff_cache: Arc<DiffCache>,
 }
 
 impl AppState {
     pub async fn new() -> anyhow::Result<Self> {
-        // 初始化 AST 引擎
-        let ast_engine = ASTEngine::new(".deepaudit_cache");
-        let ast_engine = Arc::new(Mutex::new(ast_engine));
-
         // 初始化数据库
         let db = init_db().await?;
+        Self::with_storage(db, resolve_data_dir(), ".deepaudit_cache")
+    }
+
+    /// 使用给定的数据库、数据目录与 AST 缓存目录创建状态
+    pub(crate) fn with_storage(db: Pool<Sqlite>, data_dir: PathBuf, ast_cache_dir: &str) -> anyhow::Result<Self> {
+        // 初始化 AST 引擎
+        let ast_engine = ASTEngine::new(ast_cache_dir);
+        let ast_engine = Arc::new(AsyncRwLock::new(ast_engine));
+
+        // 解析数据目录与规则目录，迁移旧位置的规则后加载
+        let rule_paths = RulePaths::resolve(&data_dir);
+        if let Err(e) = rule_paths.migrate_legacy_rules() {
+            tracing::warn!("Failed to migrate legacy rules: {}", e);
+        }
+        let rule_overrides = RuleOverrides::load(&data_dir).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load rule overrides: {}", e);
+            RuleOverrides::default()
+        });
+        let LoadedRules { mut rules, shadowed } = rule_paths.load_rules()?;
+        rule_overrides.apply(&mut rules);
+        tracing::info!(
+            "Loaded {} rules (bundled: {:?}, user: {})",
+            rules.len(),
+            rule_paths.bundled_dir,
+            rule_paths.user_dir.display()
+        );
+        let mut scanner = ScannerManager::with_rules(rules.clone());
+        register_external_scanners(&mut scanner, &data_dir);
+        let snapshot = RuleSnapshot {
+            scanner,
+            diagnostics: lint::verify_rules(&rules, false),
+            rules,
+            shadowed,
+        };
 
         Ok(Self {
             ast_engine,
             db,
             ast_cache_state: Arc::new(Mutex::new(AstCacheState::default())),
+            data_dir,
+            rule_paths,
+            rules: Arc::new(RwLock::new(Arc::new(snapshot))),
+            rule_overrides: Arc::new(RwLock::new(rule_overrides)),
+            watchers: Arc::new(Mutex::new(HashMap::new())),
+            scan_events: broadcast::channel(256).0,
+            comparisons: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            diff_events: broadcast::channel(256).0,
+            comparison_results: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            diff_cache: Arc::new(DiffCache::new(DIFF_CACHE_CAPACITY)),
         })
     }
+
+    /// 获取当前规则快照
+    pub fn rules_snapshot(&self) -> Arc<RuleSnapshot> {
+        self.rules
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 重新加载规则目录并重建规则扫描器，返回加载的规则数量
+    pub fn reload_rules(&self) -> anyhow::Result<usize> {
+        let LoadedRules { mut rules, shadowed } = self.rule_paths.load_rules()?;
+        self.rule_overrides
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .apply(&mut rules);
+        let count = rules.len();
+
+        let mut scanner = self.rules_snapshot().scanner.clone();
+        scanner.rebuild_rule_scanner(rules.clone());
+
+        let diagnostics = lint::verify_rules(&rules, false);
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) =
+            Arc::new(RuleSnapshot { rules, scanner, shadowed, diagnostics });
+
+        tracing::info!("Reloaded {} rules", count);
+        Ok(count)
+    }
+
+    /// 修改规则覆盖配置并持久化，随后重新加载规则
+    pub fn update_rule_override<F>(&self, rule_id: &str, update: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut crate::rule_store::RuleOverride),
+    {
+        {
+            let mut overrides = self.rule_overrides.write().unwrap_or_else(|e| e.into_inner());
+            update(overrides.entry(rule_id));
+            overrides.compact();
+            overrides.save(&self.data_dir)?;
+        }
+        self.reload_rules()?;
+        Ok(())
+    }
 }
 
+/// 外部扫描器配置文件名，位于数据目录
+const EXTERNAL_SCANNERS_FILE: &str = "external_scanners.json";
+
+/// 外部扫描器配置文件：`{ "scanners": [{ "name", "command", "args", "format", ... }] }`
+#[derive(Default, Deserialize)]
+struct ExternalScannersFile {
+    #[serde(default)]
+    scanners: Vec<ExternalScannerConfig>,
+}
+
+/// 按数据目录中的配置注册外部扫描器，命令未安装的扫描器跳过
+fn register_external_scanners(scanner: &mut ScannerManager, data_dir: &Path) {
+    let path = data_dir.join(EXTERNAL_SCANNERS_FILE);
+    if !path.exists() {
+        return;
+    }
+    let file: ExternalScannersFile = match std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_str(&content)?))
+    {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::warn!("Failed to load {}: {}", path.display(), e);
+            return;
+        }
+    };
+    for config in file.scanners {
+        let (name, command, enabled) = (config.name.clone(), config.command.clone(), config.enabled);
+        match ExternalScanner::new(config) {
+            Some(external) => {
+                tracing::info!("Registered external scanner '{}' ({})", name, command);
+                scanner.replace_scanner(external);
+            }
+            None if enabled => {
+                tracing::warn!("External scanner '{}' skipped: command '{}' not found", name, command)
+            }
+            None => {}
+        }
+    }
+}
+
+/// 数据目录，可通过 `DEEPAUDIT_DATA_DIR` 环境变量覆盖；启动时解析为绝对路径，之后不受工作目录影响
+fn resolve_data_dir() -> PathBuf {
+    let data_dir = std::env::var_os("DEEPAUDIT_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("./data"));
+    std::path::absolute(&data_dir).unwrap_or(data_dir)
+}
+
+/// 打开数据库，可通过 `DEEPAUDIT_DATABASE_URL` 环境变量指定连接地址（如 `sqlite::memory:`），
+/// 缺省为工作目录下的 deepaudit_web.db
 async fn init_db() -> anyhow::Result<Pool<Sqlite>> {
-    // 获取当前工作目录
-    let current_dir = std::env::current_dir()?;
-    let db_path = current_dir.join("deepaudit_web.db");
+    let db_url = match std::env::var("DEEPAUDIT_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            let db_path = std::env::current_dir()?.join("deepaudit_web.db");
+            format!("sqlite://{}", db_path.display())
+        }
+    };
+    init_db_with_url(&db_url).await
+}
 
-    println!("Database path: {}", db_path.display());
+/// 按连接地址打开数据库并执行迁移
+///
+/// 内存数据库（`sqlite::memory:`）在所有连接关闭后即被释放，且共享缓存下并发写入会直接报锁冲突，
+/// 因此只使用一个常驻连接
+pub async fn init_db_with_url(db_url: &str) -> anyhow::Result<Pool<Sqlite>> {
+    println!("Database: {}", db_url);
 
-    // 使用 SqliteConnectOptions 来确保数据库文件可以被创建
-    let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", db_path.display()))?
-        .create_if_missing(true);
+    // 使用 SqliteConnectOptions 来确保数据库文件可以被创建；
+    // WAL 模式下扫描写入发现时仍可并发读取，写锁冲突时等待而不是立即失败
+    let options = SqliteConnectOptions::from_str(db_url)?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(std::time::Duration::from_secs(5));
+    let in_memory = db_url.contains(":memory:") || db_url.contains("mode=memory");
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
+    let pool_options = if in_memory {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        SqlitePoolOptions::new().max_connections(5)
+    };
+    let pool = pool_options
         .connect_with(options)
         .await
         .map_err(|e| anyhow::anyhow!("Failed to connect to database: {}", e))?;
 
-    // 创建表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS projects (
+    upgrade_legacy_schema(&pool).await?;
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to run database migrations: {}", e))?;
+
+    println!("Database initialized successfully");
+
+    Ok(pool)
+}
+
+/// 为引入迁移前创建的数据库补充后来新增的列，使其与初始迁移的表结构一致
+///
+/// 已由迁移管理（存在 `_sqlx_migrations`）或尚未建表的数据库直接跳过
+async fn upgrade_legacy_schema(pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name IN ('findings', '_sqlx_migrations')",
+    )
+    .fetch_all(pool)
+    .await?;
+    if tables.iter().any(|t| t == "_sqlx_migrations") || !tables.iter().any(|t| t == "findings") {
+        return Ok(());
+    }
+
+    for (column, definition) in [
+        ("rule_id", "TEXT"),
+        ("cwe", "TEXT"),
+        ("owasp", "TEXT"),
+        ("remediation", "TEXT"),
+        ("reference_links", "TEXT"),
+        ("matched_text", "TEXT"),
+        ("suggested_fix", "TEXT"),
+        ("detectors", "TEXT"),
+    ] {
+        ensure_column(pool, "findings", column, definition).await?;
+    }
+    ensure_column(pool, "projects", "settings", "TEXT").await?;
+    ensure_column(pool, "scans", "findings_suppressed", "INTEGER DEFAULT 0").await?;
+    for (column, definition) in [
+        ("language", "TEXT"),
+        ("column_number", "INTEGER"),
+        ("end_column", "INTEGER"),
+    ] {
+        ensure_column(pool, "symbols", column, definition).await?;
+    }
+    Ok(())
+}
+
+/// 列不存在时通过 ALTER TABLE 添加
+async fn ensure_column(
+    pool: &Pool<Sqlite>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> anyhow::Result<()> {
+    let columns: Vec<String> =
+        sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .fetch_all(pool)
+            .await?;
+    if !columns.iter().any(|c| c == column) {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to add column {}.{}: {}", table, column, e))?;
+    }
+    Ok(())
+}
+
+/// 测试用的状态：内存数据库，数据目录与 AST 缓存目录位于返回的临时目录中
+#[cfg(test)]
+pub(crate) async fn test_state() -> (tempfile::TempDir, actix_web::web::Data<AppState>) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = init_db_with_url("sqlite::memory:").await.unwrap();
+    let cache_dir = dir.path().join("ast_cache");
+    let state = AppState::with_storage(db, dir.path().join("data"), cache_dir.to_str().unwrap()).unwrap();
+    (dir, actix_web::web::Data::new(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::{Connection, SqliteConnection};
+
+    /// 引入迁移前 init_db 创建的表结构（节选有数据的表）
+    const LEGACY_SCHEMA: &str = r#"
+        CREATE TABLE projects (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             uuid TEXT UNIQUE NOT NULL,
             name TEXT NOT NULL,
             path TEXT NOT NULL UNIQUE,
             created_at DATETIME DEFAULT CURRENT_TIMESTAMP
         );
-
-        CREATE TABLE IF NOT EXISTS findings (
+        CREATE TABLE findings (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             project_id INTEGER,
             finding_id TEXT UNIQUE,
@@ -81,8 +361,7 @@ async fn init_db() -> anyhow::Result<Pool<Sqlite>> {
             created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
             FOREIGN KEY(project_id) REFERENCES projects(id)
         );
-
-        CREATE TABLE IF NOT EXISTS scans (
+        CREATE TABLE scans (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             project_id INTEGER,
             status TEXT DEFAULT 'pending',
@@ -92,21 +371,7 @@ async fn init_db() -> anyhow::Result<Pool<Sqlite>> {
             completed_at DATETIME,
             FOREIGN KEY(project_id) REFERENCES projects(id)
         );
-
-        -- AST 索引历史表
-        CREATE TABLE IF NOT EXISTS ast_indices (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            project_id INTEGER NOT NULL,
-            index_version TEXT NOT NULL,
-            total_symbols INTEGER DEFAULT 0,
-            total_files INTEGER DEFAULT 0,
-            index_data TEXT,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(project_id) REFERENCES projects(id)
-        );
-
-        -- 符号表（支持历史查询）
-        CREATE TABLE IF NOT EXISTS symbols (
+        CREATE TABLE symbols (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             project_id INTEGER NOT NULL,
             ast_index_id INTEGER NOT NULL,
@@ -118,53 +383,65 @@ async fn init_db() -> anyhow::Result<Pool<Sqlite>> {
             end_line INTEGER,
             parent_name TEXT,
             metadata TEXT,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(project_id) REFERENCES projects(id),
-            FOREIGN KEY(ast_index_id) REFERENCES ast_indices(id)
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
         );
+        INSERT INTO projects (uuid, name, path) VALUES ('legacy-uuid', 'legacy', '/srv/legacy');
+        INSERT INTO findings (project_id, finding_id, file_path, line_start, line_end, detector, vuln_type, severity, description)
+            VALUES (1, 'legacy-finding', 'app.py', 3, 3, 'RuleBasedScanner', 'Code Injection', 'high', 'Use of eval');
+        INSERT INTO scans (project_id, status, files_scanned, findings_found) VALUES (1, 'completed', 10, 1);
+    "#;
 
-        -- 代码图谱表
-        CREATE TABLE IF NOT EXISTS code_graphs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            project_id INTEGER NOT NULL,
-            graph_type TEXT NOT NULL,
-            entry_point TEXT,
-            graph_data TEXT NOT NULL,
-            node_count INTEGER DEFAULT 0,
-            edge_count INTEGER DEFAULT 0,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(project_id) REFERENCES projects(id)
-        );
+    async fn columns(pool: &Pool<Sqlite>, table: &str) -> Vec<String> {
+        sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
 
-        -- 调用关系表
-        CREATE TABLE IF NOT EXISTS call_relations (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            project_id INTEGER NOT NULL,
-            graph_id INTEGER NOT NULL,
-            caller_function TEXT NOT NULL,
-            callee_function TEXT NOT NULL,
-            file_path TEXT NOT NULL,
-            line_number INTEGER,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(project_id) REFERENCES projects(id),
-            FOREIGN KEY(graph_id) REFERENCES code_graphs(id)
-        );
+    #[actix_web::test]
+    async fn legacy_schema_upgrades_and_keeps_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_url = format!("sqlite://{}", dir.path().join("legacy.db").display());
+        let mut conn = SqliteConnection::connect_with(&SqliteConnectOptions::from_str(&db_url).unwrap().create_if_missing(true))
+            .await
+            .unwrap();
+        sqlx::raw_sql(LEGACY_SCHEMA).execute(&mut conn).await.unwrap();
+        conn.close().await.unwrap();
 
-        -- 创建索引以提高查询性能
-        CREATE INDEX IF NOT EXISTS idx_symbols_project ON symbols(project_id);
-        CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(symbol_name);
-        CREATE INDEX IF NOT EXISTS idx_symbols_type ON symbols(symbol_type);
-        CREATE INDEX IF NOT EXISTS idx_graphs_project ON code_graphs(project_id);
-        CREATE INDEX IF NOT EXISTS idx_graphs_type ON code_graphs(graph_type);
-        CREATE INDEX IF NOT EXISTS idx_calls_project ON call_relations(project_id);
-        CREATE INDEX IF NOT EXISTS idx_indices_project ON ast_indices(project_id);
-        "#,
-    )
-    .execute(&pool)
-    .await
-    .map_err(|e| anyhow::anyhow!("Failed to create tables: {}", e))?;
+        let pool = init_db_with_url(&db_url).await.unwrap();
 
-    println!("Database initialized successfully");
+        let finding: (String, String, String, i64, Option<String>, Option<f64>) = sqlx::query_as(
+            "SELECT finding_id, file_path, severity, line_start, rule_id, confidence FROM findings",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(finding.0, "legacy-finding");
+        assert_eq!((finding.1.as_str(), finding.2.as_str(), finding.3), ("app.py", "high", 3));
+        assert_eq!((finding.4, finding.5), (None, None));
+        let project: (String, Option<String>) = sqlx::query_as("SELECT path, settings FROM projects")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(project, ("/srv/legacy".to_string(), None));
+        let scan: (i64, i64, bool) = sqlx::query_as("SELECT files_scanned, findings_suppressed, range_scan FROM scans")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(scan, (10, 0, false));
 
-    Ok(pool)
+        let findings = columns(&pool, "findings").await;
+        for column in ["rule_id", "matched_text", "suggested_fix", "detectors", "blame_commit", "confidence", "fingerprint"] {
+            assert!(findings.iter().any(|c| c == column), "findings.{} missing: {:?}", column, findings);
+        }
+        let symbols = columns(&pool, "symbols").await;
+        assert!(symbols.iter().any(|c| c == "language"), "{:?}", symbols);
+        assert!(!columns(&pool, "finding_baselines").await.is_empty());
+
+        // 再次打开时迁移已记录，不会重复执行
+        pool.close().await;
+        let pool = init_db_with_url(&db_url).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM findings").fetch_one(&pool).await.unwrap();
+        assert_eq!(count, 1);
+    }
 }
@@ -0,0 +1,239 @@
+// 项目目录监听：文件变更后只重新扫描受影响的文件，更新数据库中的发现并推送扫描事件
+
+use deepaudit_core::scanner::filter_by_severity;
+use deepaudit_core::ScannerManager;
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::api::scanner::{insert_finding, scanner_for_project, Finding, ProjectScanner};
+use crate::api::ServerEvent;
+use crate::state::AppState;
+
+/// 变更事件合并窗口
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 推送给前端的扫描事件，事件名为 `scan-finding` / `finding-removed` / `scan-error`
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum ScanEvent {
+    /// 增量扫描新发现的问题
+    Finding { project_id: i64, finding: Box<Finding> },
+    /// 文件修改或删除后不再存在的问题
+    Removed {
+        project_id: i64,
+        finding_id: String,
+        file_path: String,
+    },
+    /// 扫描结果保存失败，`findings_failed` 为未能保存的发现数
+    Error {
+        project_id: i64,
+        /// 增量扫描的文件或目录，完整扫描时为空
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file_path: Option<String>,
+        error: String,
+        findings_failed: usize,
+    },
+}
+
+impl ServerEvent for ScanEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ScanEvent::Finding { .. } => "scan-finding",
+            ScanEvent::Removed { .. } => "finding-removed",
+            ScanEvent::Error { .. } => "scan-error",
+        }
+    }
+}
+
+/// 正在监听的项目，释放时停止监听
+pub struct ProjectWatcher {
+    pub project_path: String,
+    _debouncer: Debouncer<RecommendedWatcher>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for ProjectWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 开始监听项目目录，已在监听时替换原有监听
+pub async fn watch_project(state: &AppState, project_id: i64, project_path: &str) -> anyhow::Result<()> {
+    let root = PathBuf::from(project_path);
+    // 监听事件给出的是规范化的绝对路径，需映射回扫描时使用的路径形式
+    let canonical_root = root.canonicalize()?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| {
+        let _ = tx.send(result);
+    })?;
+    debouncer
+        .watcher()
+        .watch(&canonical_root, RecursiveMode::Recursive)?;
+
+    let task_state = state.clone();
+    let task = tokio::spawn(async move {
+        while let Some(result) = rx.recv().await {
+            match result {
+                Ok(events) => {
+                    let paths: HashSet<PathBuf> = events
+                        .into_iter()
+                        .filter_map(|e| e.path.strip_prefix(&canonical_root).ok().map(|p| root.join(p)))
+                        .collect();
+                    rescan_paths(&task_state, project_id, &root, paths).await;
+                }
+                Err(e) => tracing::warn!("File watcher error for project {}: {}", project_id, e),
+            }
+        }
+    });
+
+    tracing::info!("Watching project {} at {}", project_id, project_path);
+    state.watchers.lock().await.insert(
+        project_id,
+        ProjectWatcher {
+            project_path: project_path.to_string(),
+            _debouncer: debouncer,
+            task,
+        },
+    );
+    Ok(())
+}
+
+/// 停止监听项目目录，返回此前是否在监听
+pub async fn unwatch_project(state: &AppState, project_id: i64) -> bool {
+    let removed = state.watchers.lock().await.remove(&project_id);
+    if let Some(watcher) = &removed {
+        tracing::info!("Stopped watching project {} at {}", project_id, watcher.project_path);
+    }
+    removed.is_some()
+}
+
+/// 重新扫描变更的路径；路径不存在时移除其下（文件或目录）的全部发现
+async fn rescan_paths(state: &AppState, project_id: i64, root: &Path, paths: HashSet<PathBuf>) {
+    let snapshot = state.rules_snapshot();
+    let project_scanner = scanner_for_project(state, &snapshot, Some(project_id)).await;
+
+    let mut findings_failed = 0;
+    for path in paths {
+        let findings = if path.is_file() {
+            if !ScannerManager::is_scan_target(root, &path) {
+                continue;
+            }
+            scan_file(&project_scanner, root, &path).await
+        } else if path.exists() {
+            // 目录本身的变更，其中文件的变更会单独产生事件
+            continue;
+        } else {
+            Vec::new()
+        };
+        let count = findings.len();
+        if let Err(e) = sync_findings(state, project_id, &path, findings).await {
+            tracing::error!("Failed to rescan {} for project {}: {}", path.display(), project_id, e);
+            findings_failed += count;
+            let _ = state.scan_events.send(ScanEvent::Error {
+                project_id,
+                file_path: Some(path.display().to_string()),
+                error: e.to_string(),
+                findings_failed: count,
+            });
+        }
+    }
+    if findings_failed > 0 {
+        tracing::warn!("{} findings could not be saved for project {}", findings_failed, project_id);
+    }
+}
+
+async fn scan_file(project_scanner: &ProjectScanner, root: &Path, path: &Path) -> Vec<Finding> {
+    // 与目录扫描一致，无法按文本读取的文件视为没有发现；低于最低严重程度的发现不保存
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => {
+            let findings = project_scanner.scanner.scan_project_file(root, path, &content).await;
+            filter_by_severity(findings, &project_scanner.min_severity)
+                .0
+                .into_iter()
+                .map(Finding::from)
+                .collect()
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// 判断两次扫描中是否为同一问题的键（finding_id 每次扫描都会重新生成）
+type FindingKey = (String, Option<String>, String, i64, i64, Option<String>);
+
+fn finding_key(finding: &Finding) -> FindingKey {
+    (
+        finding.detector.clone(),
+        finding.rule_id.clone(),
+        finding.vuln_type.clone(),
+        finding.line_start as i64,
+        finding.line_end as i64,
+        finding.matched_text.clone(),
+    )
+}
+
+/// 用 `path` 下的最新扫描结果更新数据库：保留仍存在的发现，插入新发现，删除已消失的发现，并推送对应事件
+async fn sync_findings(
+    state: &AppState,
+    project_id: i64,
+    path: &Path,
+    findings: Vec<Finding>,
+) -> anyhow::Result<()> {
+    let rows = sqlx::query_as::<_, (String, String, String, Option<String>, String, i64, i64, Option<String>)>(
+        "SELECT finding_id, file_path, detector, rule_id, vuln_type, line_start, line_end, matched_text
+         FROM findings WHERE project_id = ?",
+    )
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut existing: HashMap<FindingKey, (String, String)> = rows
+        .into_iter()
+        .filter(|row| Path::new(&row.1).starts_with(path))
+        .map(|(finding_id, file_path, detector, rule_id, vuln_type, line_start, line_end, matched_text)| {
+            (
+                (detector, rule_id, vuln_type, line_start, line_end, matched_text),
+                (finding_id, file_path),
+            )
+        })
+        .collect();
+
+    let mut events = Vec::new();
+    let mut tx = state.db.begin().await?;
+    for finding in findings {
+        if existing.remove(&finding_key(&finding)).is_some() {
+            continue;
+        }
+        if insert_finding(&mut tx, project_id, &finding).await? {
+            events.push(ScanEvent::Finding { project_id, finding: Box::new(finding) });
+        }
+    }
+    for (finding_id, file_path) in existing.into_values() {
+        sqlx::query("DELETE FROM findings WHERE finding_id = ?")
+            .bind(&finding_id)
+            .execute(&mut *tx)
+            .await?;
+        events.push(ScanEvent::Removed {
+            project_id,
+            finding_id,
+            file_path,
+        });
+    }
+    tx.commit().await?;
+
+    if !events.is_empty() {
+        tracing::info!("Incremental scan of {} produced {} finding events", path.display(), events.len());
+    }
+    for event in events {
+        // 没有订阅者时发送失败，可以忽略
+        let _ = state.scan_events.send(event);
+    }
+    Ok(())
+}